@@ -0,0 +1,25 @@
+#version 450
+
+layout(push_constant) uniform BackgroundConstants {
+    mat4 inverseViewProjection;
+    // Unit direction towards the sun, with the cosine of its angular radius in w.
+    vec4 sun;
+    vec4 groundColor;
+    float intensity;
+    // Depth of the far plane, and of the near one.
+    float farDepth;
+    float nearDepth;
+} constants;
+
+layout(location = 0) out vec3 ray;
+
+// One triangle covering the viewport at the far plane. The view ray runs between two
+// unprojected depths in front of it, since an infinite far plane unprojects to nothing.
+void main()
+{
+    vec2 ndc = vec2(gl_VertexIndex & 2, (gl_VertexIndex << 1) & 2) * 2.0 - 1.0;
+    vec4 near = constants.inverseViewProjection * vec4(ndc, constants.nearDepth, 1.0);
+    vec4 middle = constants.inverseViewProjection * vec4(ndc, 0.5, 1.0);
+    ray = middle.xyz / middle.w - near.xyz / near.w;
+    gl_Position = vec4(ndc, constants.farDepth, 1.0);
+}
@@ -0,0 +1,43 @@
+#version 450
+
+layout(binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform PostProcessConstants {
+    float exposure;
+    uint tonemapper;
+    float bloom_intensity;
+    float bloom_threshold;
+    vec2 texel_size;
+    uint flags;
+    float lut_size;
+} constants;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 color;
+
+const uint PREFILTER = 4u;
+
+// Keeps what is brighter than the threshold, with a soft knee below it.
+vec3 prefilter(vec3 c){
+    float brightness = max(c.r, max(c.g, c.b));
+    float knee = constants.bloom_threshold * 0.5;
+    float soft = clamp(brightness - constants.bloom_threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-5);
+    return c * max(soft, brightness - constants.bloom_threshold) / max(brightness, 1e-5);
+}
+
+// Dual filter downsample: the center and four diagonal bilinear taps of the source,
+// whose texel size is `constants.texel_size`.
+void main(){
+    vec2 h = constants.texel_size * 0.5;
+    vec3 sum = texture(source, uv).rgb * 4.0;
+    sum += texture(source, uv - h).rgb;
+    sum += texture(source, uv + h).rgb;
+    sum += texture(source, uv + vec2(h.x, -h.y)).rgb;
+    sum += texture(source, uv - vec2(h.x, -h.y)).rgb;
+    vec3 c = sum / 8.0;
+    if ((constants.flags & PREFILTER) != 0u) {
+        c = prefilter(c * constants.exposure);
+    }
+    color = vec4(c, 1.0);
+}
@@ -0,0 +1,32 @@
+#version 450
+
+layout(binding = 0) uniform sampler2D level;
+layout(binding = 1) uniform sampler2D lower;
+
+layout(push_constant) uniform PostProcessConstants {
+    float exposure;
+    uint tonemapper;
+    float bloom_intensity;
+    float bloom_threshold;
+    vec2 texel_size;
+    uint flags;
+    float lut_size;
+} constants;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 color;
+
+// Dual filter upsample of the lower level, whose texel size is `constants.texel_size`,
+// added to the downsampled level of the same size.
+void main(){
+    vec2 h = constants.texel_size * 0.5;
+    vec3 sum = texture(lower, uv + vec2(-h.x * 2.0, 0.0)).rgb;
+    sum += texture(lower, uv + vec2(h.x * 2.0, 0.0)).rgb;
+    sum += texture(lower, uv + vec2(0.0, -h.y * 2.0)).rgb;
+    sum += texture(lower, uv + vec2(0.0, h.y * 2.0)).rgb;
+    sum += texture(lower, uv + vec2(-h.x, h.y)).rgb * 2.0;
+    sum += texture(lower, uv + vec2(h.x, h.y)).rgb * 2.0;
+    sum += texture(lower, uv + vec2(h.x, -h.y)).rgb * 2.0;
+    sum += texture(lower, uv + vec2(-h.x, -h.y)).rgb * 2.0;
+    color = vec4(texture(level, uv).rgb + sum / 12.0, 1.0);
+}
@@ -0,0 +1,55 @@
+#version 450
+
+const float PI = 3.14159265359;
+
+layout(push_constant) uniform BakeConstants {
+    uint face;
+    float roughness;
+    float sourceSize;
+    uint sampleCount;
+} constants;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec2 scaleBias;
+
+vec2 hammersley(uint i, uint count)
+{
+    uint bits = bitfieldReverse(i);
+    return vec2(float(i) / float(count), float(bits) * 2.3283064365386963e-10);
+}
+
+float geometrySchlick(float nDotX, float k)
+{
+    return nDotX / (nDotX * (1.0 - k) + k);
+}
+
+// Scale and bias applied to F0 by the GGX specular lobe, integrated over the
+// hemisphere for the view angle's cosine along u and the roughness along v.
+void main()
+{
+    float nDotV = max(uv.x, 1e-3);
+    float roughness = uv.y;
+    float alpha = roughness * roughness;
+    vec3 view = vec3(sqrt(1.0 - nDotV * nDotV), 0.0, nDotV);
+    // Geometry term remapped for image-based lighting.
+    float k = alpha / 2.0;
+
+    vec2 total = vec2(0.0);
+    for (uint i = 0u; i < constants.sampleCount; i++) {
+        vec2 xi = hammersley(i, constants.sampleCount);
+        float phi = 2.0 * PI * xi.x;
+        float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+        float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+        vec3 halfway = vec3(cos(phi) * sinTheta, sin(phi) * sinTheta, cosTheta);
+        vec3 lightDirection = reflect(-view, halfway);
+        float nDotL = max(lightDirection.z, 0.0);
+        float nDotH = max(halfway.z, 0.0);
+        float vDotH = max(dot(view, halfway), 0.0);
+        if (nDotL > 0.0) {
+            float visibility = geometrySchlick(nDotV, k) * geometrySchlick(nDotL, k) * vDotH / (nDotH * nDotV);
+            float fresnel = pow(1.0 - vDotH, 5.0);
+            total += vec2((1.0 - fresnel) * visibility, fresnel * visibility);
+        }
+    }
+    scaleBias = total / float(constants.sampleCount);
+}
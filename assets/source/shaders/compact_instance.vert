@@ -0,0 +1,47 @@
+#version 450
+
+layout(binding = 0) uniform CameraMatrix {
+    mat4 view;
+    mat4 proj;
+} cam;
+
+layout(location = 0) in vec3 position;
+
+layout(location = 1) in vec3 inTranslation;
+layout(location = 2) in vec3 inScale;
+layout(location = 3) in vec4 inRotation;
+layout(location = 4) in vec4 inColor;
+layout(location = 6) in vec3 normal;
+layout(location = 7) in vec2 uv;
+layout(location = 8) in vec4 tangent;
+layout(location = 9) in uint inIndex;
+
+layout(location = 0) out vec4 fragColor;
+layout(location = 1) out vec3 worldPos;
+layout(location = 2) out vec3 worldNormal;
+layout(location = 3) out vec2 fragUv;
+layout(location = 4) out vec4 worldTangent;
+// For the view modes: the instance's index in its group, and a corner of the
+// triangle when drawn without indices.
+layout(location = 5) flat out uint instanceIndex;
+layout(location = 6) noperspective out vec3 barycentric;
+
+vec3 rotate(vec4 q, vec3 v)
+{
+return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+
+void main()
+{
+vec4 rotation = normalize(inRotation);
+vec3 world = rotate(rotation, position * inScale) + inTranslation;
+fragColor = inColor;
+worldPos = world;
+worldNormal = rotate(rotation, normal / inScale);
+fragUv = uv;
+instanceIndex = inIndex;
+uint corner = gl_VertexIndex % 3;
+barycentric = vec3(corner == 0, corner == 1, corner == 2);
+worldTangent = vec4(rotate(rotation, tangent.xyz * inScale), tangent.w);
+gl_Position = cam.proj * cam.view * vec4(world, 1.0);
+}
@@ -0,0 +1,17 @@
+#version 450
+
+layout(binding = 0) uniform CameraMatrix {
+    mat4 view;
+    mat4 proj;
+} cam;
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 inColor;
+
+layout(location = 0) out vec3 color;
+
+void main()
+{
+    color = inColor;
+    gl_Position = cam.proj * cam.view * vec4(position, 1.0);
+}
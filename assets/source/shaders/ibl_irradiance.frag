@@ -0,0 +1,74 @@
+#version 450
+
+const float PI = 3.14159265359;
+
+layout(binding = 0) uniform sampler2D environment;
+
+layout(push_constant) uniform BakeConstants {
+    uint face;
+    float roughness;
+    // Width of the panorama's top level in texels.
+    float sourceSize;
+    uint sampleCount;
+} constants;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 color;
+
+// Direction through the texel at uv of a cube map face, in the order +X, -X, +Y, -Y, +Z, -Z.
+vec3 faceDirection(uint face, vec2 uv)
+{
+    vec2 st = uv * 2.0 - 1.0;
+    vec3 directions[6] = vec3[](
+        vec3(1.0, -st.y, -st.x),
+        vec3(-1.0, -st.y, st.x),
+        vec3(st.x, 1.0, st.y),
+        vec3(st.x, -1.0, -st.y),
+        vec3(st.x, -st.y, 1.0),
+        vec3(-st.x, -st.y, -1.0)
+    );
+    return normalize(directions[face]);
+}
+
+// Panorama coordinates of a direction, with +Y along the top edge.
+vec2 equirectangular(vec3 direction)
+{
+    return vec2(atan(direction.z, direction.x) / (2.0 * PI) + 0.5, acos(clamp(direction.y, -1.0, 1.0)) / PI);
+}
+
+vec2 hammersley(uint i, uint count)
+{
+    uint bits = bitfieldReverse(i);
+    return vec2(float(i) / float(count), float(bits) * 2.3283064365386963e-10);
+}
+
+// Panorama level whose texels cover about the solid angle of one of the samples,
+// drawn with probability density pdf, so few samples still see all the light.
+float sampleLevel(float pdf)
+{
+    float sampleAngle = 1.0 / (float(constants.sampleCount) * pdf + 1e-6);
+    float texelAngle = 4.0 * PI / (0.5 * constants.sourceSize * constants.sourceSize);
+    return max(0.5 * log2(sampleAngle / texelAngle) + 1.0, 0.0);
+}
+
+// Cosine-weighted average of the light arriving over the hemisphere around each
+// direction, so diffuse lighting is this times the albedo.
+void main()
+{
+    vec3 normal = faceDirection(constants.face, uv);
+    vec3 up = abs(normal.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 tangent = normalize(cross(up, normal));
+    vec3 bitangent = cross(normal, tangent);
+
+    vec3 irradiance = vec3(0.0);
+    for (uint i = 0u; i < constants.sampleCount; i++) {
+        vec2 xi = hammersley(i, constants.sampleCount);
+        float phi = 2.0 * PI * xi.x;
+        float cosTheta = sqrt(1.0 - xi.y);
+        float sinTheta = sqrt(xi.y);
+        vec3 direction = tangent * (cos(phi) * sinTheta) + bitangent * (sin(phi) * sinTheta) + normal * cosTheta;
+        float level = sampleLevel(cosTheta / PI);
+        irradiance += textureLod(environment, equirectangular(direction), level).rgb;
+    }
+    color = vec4(irradiance / float(constants.sampleCount), 1.0);
+}
@@ -0,0 +1,93 @@
+#version 450
+
+const float PI = 3.14159265359;
+
+layout(binding = 0) uniform sampler2D environment;
+
+layout(push_constant) uniform BakeConstants {
+    uint face;
+    float roughness;
+    // Width of the panorama's top level in texels.
+    float sourceSize;
+    uint sampleCount;
+} constants;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 color;
+
+// Direction through the texel at uv of a cube map face, in the order +X, -X, +Y, -Y, +Z, -Z.
+vec3 faceDirection(uint face, vec2 uv)
+{
+    vec2 st = uv * 2.0 - 1.0;
+    vec3 directions[6] = vec3[](
+        vec3(1.0, -st.y, -st.x),
+        vec3(-1.0, -st.y, st.x),
+        vec3(st.x, 1.0, st.y),
+        vec3(st.x, -1.0, -st.y),
+        vec3(st.x, -st.y, 1.0),
+        vec3(-st.x, -st.y, -1.0)
+    );
+    return normalize(directions[face]);
+}
+
+// Panorama coordinates of a direction, with +Y along the top edge.
+vec2 equirectangular(vec3 direction)
+{
+    return vec2(atan(direction.z, direction.x) / (2.0 * PI) + 0.5, acos(clamp(direction.y, -1.0, 1.0)) / PI);
+}
+
+vec2 hammersley(uint i, uint count)
+{
+    uint bits = bitfieldReverse(i);
+    return vec2(float(i) / float(count), float(bits) * 2.3283064365386963e-10);
+}
+
+// Panorama level whose texels cover about the solid angle of one of the samples,
+// drawn with probability density pdf, so few samples still see all the light.
+float sampleLevel(float pdf)
+{
+    float sampleAngle = 1.0 / (float(constants.sampleCount) * pdf + 1e-6);
+    float texelAngle = 4.0 * PI / (0.5 * constants.sourceSize * constants.sourceSize);
+    return max(0.5 * log2(sampleAngle / texelAngle) + 1.0, 0.0);
+}
+
+float distributionGGX(float nDotH, float alpha)
+{
+    float alpha2 = alpha * alpha;
+    float denominator = nDotH * nDotH * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * denominator * denominator);
+}
+
+// Light reflected towards the normal by a GGX lobe of the given roughness, assuming
+// the view direction equals the normal.
+void main()
+{
+    vec3 normal = faceDirection(constants.face, uv);
+    if (constants.roughness == 0.0) {
+        color = vec4(textureLod(environment, equirectangular(normal), 0.0).rgb, 1.0);
+        return;
+    }
+    vec3 up = abs(normal.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+    vec3 tangent = normalize(cross(up, normal));
+    vec3 bitangent = cross(normal, tangent);
+    float alpha = constants.roughness * constants.roughness;
+
+    vec3 total = vec3(0.0);
+    float weight = 0.0;
+    for (uint i = 0u; i < constants.sampleCount; i++) {
+        vec2 xi = hammersley(i, constants.sampleCount);
+        float phi = 2.0 * PI * xi.x;
+        float cosTheta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+        float sinTheta = sqrt(1.0 - cosTheta * cosTheta);
+        vec3 halfway = tangent * (cos(phi) * sinTheta) + bitangent * (sin(phi) * sinTheta) + normal * cosTheta;
+        vec3 lightDirection = reflect(-normal, halfway);
+        float nDotL = dot(normal, lightDirection);
+        if (nDotL > 0.0) {
+            // With the view along the normal, the pdf of the reflected direction is D / 4.
+            float level = sampleLevel(distributionGGX(cosTheta, alpha) / 4.0);
+            total += textureLod(environment, equirectangular(lightDirection), level).rgb * nDotL;
+            weight += nDotL;
+        }
+    }
+    color = vec4(total / max(weight, 1e-4), 1.0);
+}
@@ -0,0 +1,12 @@
+#version 450
+
+layout(push_constant) uniform ObjectId {
+    uint group;
+} objectId;
+
+layout(location = 0) flat in uint instanceSlot;
+layout(location = 0) out uvec2 id;
+
+void main(){
+    id = uvec2(objectId.group, instanceSlot);
+}
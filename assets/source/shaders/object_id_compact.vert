@@ -0,0 +1,28 @@
+#version 450
+
+layout(binding = 0) uniform CameraMatrix {
+    mat4 view;
+    mat4 proj;
+} cam;
+
+layout(location = 0) in vec3 position;
+
+layout(location = 1) in vec3 inTranslation;
+layout(location = 2) in vec3 inScale;
+layout(location = 3) in vec4 inRotation;
+layout(location = 4) in vec4 inColor;
+
+layout(location = 0) flat out uint instanceSlot;
+
+vec3 rotate(vec4 q, vec3 v)
+{
+return v + 2.0 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+
+void main()
+{
+vec4 rotation = normalize(inRotation);
+vec3 world = rotate(rotation, position * inScale) + inTranslation;
+instanceSlot = gl_InstanceIndex;
+gl_Position = cam.proj * cam.view * vec4(world, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(binding = 0) uniform sampler2D accumulation;
+layout(binding = 1) uniform sampler2D revealage;
+
+layout(location = 0) out vec4 color;
+
+// Average color of the order-independent fragments of each pixel, blended over what is
+// behind them by how much of it they let through.
+void main()
+{
+    ivec2 pixel = ivec2(gl_FragCoord.xy);
+    float revealed = texelFetch(revealage, pixel, 0).r;
+    if (revealed >= 1.0) {
+        discard;
+    }
+    vec4 sum = texelFetch(accumulation, pixel, 0);
+    color = vec4(sum.rgb / max(sum.a, 1e-5), 1.0 - revealed);
+}
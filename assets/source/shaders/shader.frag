@@ -1,8 +1,254 @@
 #version 450
 
-layout( location=0) in vec4 fragColor;
-layout (location=0) out vec4 color;
+const int MAX_CASCADES = 4;
+const uvec3 CLUSTER_GRID = uvec3(16, 9, 24);
+const float SPOT_LIGHT = 1.0;
+const float PI = 3.14159265359;
+// Levels of the specular environment map, the last one for roughness 1.
+const float SPECULAR_MIP_LEVELS = 6.0;
+
+layout(set = 0, binding = 0) uniform CameraMatrix {
+    mat4 view;
+    mat4 proj;
+} cam;
+
+layout(push_constant) uniform Material {
+    vec4 baseColor;
+    // rgb: emissive color, a: normal scale.
+    vec4 emissive;
+    // x: metallic, y: roughness, z: occlusion strength, w: alpha cutoff of masked
+    // materials, or 0.
+    vec4 params;
+    // Position of the view among the frame's views, selecting its clusters.
+    uint viewIndex;
+    // Non-zero to write weighted blended transparency instead of the color.
+    uint orderIndependent;
+} material;
+
+layout(set = 1, binding = 0) uniform Lighting {
+    // Direction the light travels in.
+    vec4 direction;
+    // Color times intensity, with the ambient fraction in w.
+    vec4 color;
+    mat4 cascades[MAX_CASCADES];
+    // World-space size of a shadow map texel per cascade.
+    vec4 texelSizes;
+    // Cascade count, normal bias in texels, texel size in texture coordinates.
+    vec4 shadow;
+    // x: intensity of the environment, or 0 to use the ambient fraction.
+    vec4 environment;
+} light;
+layout(set = 1, binding = 1) uniform sampler2DArrayShadow shadowMap;
+
+struct LightData {
+    // xyz: position, w: range.
+    vec4 position;
+    // rgb: color times intensity.
+    vec4 color;
+    // xyz: cone axis, w: cosine of the outer angle.
+    vec4 direction;
+    // x: cosine of the inner angle, y: kind of light.
+    vec4 cone;
+};
+
+struct ClusterGrid {
+    // Scale and bias from view depth, or its logarithm if z is set, to slice.
+    vec4 depth;
+    // x: offset of the grid's cells in the cluster items.
+    uvec4 firstCell;
+};
+
+layout(std430, set = 1, binding = 2) readonly buffer Lights {
+    LightData lights[];
+};
+layout(std430, set = 1, binding = 3) readonly buffer ClusterGrids {
+    ClusterGrid grids[];
+};
+// Each view's cells as a first item and a count, then the light indices they point to.
+layout(std430, set = 1, binding = 4) readonly buffer ClusterItems {
+    uint items[];
+};
+// Cosine-weighted light arriving around each direction.
+layout(set = 1, binding = 5) uniform samplerCube irradianceMap;
+// Reflections for increasing roughness down the mip levels.
+layout(set = 1, binding = 6) uniform samplerCube specularMap;
+// Scale and bias of F0 by view angle and roughness.
+layout(set = 1, binding = 7) uniform sampler2D brdfLut;
+
+layout(set = 2, binding = 0) uniform sampler2D baseColorMap;
+// g: roughness, b: metallic.
+layout(set = 2, binding = 1) uniform sampler2D metallicRoughnessMap;
+layout(set = 2, binding = 2) uniform sampler2D normalMap;
+// r: occlusion.
+layout(set = 2, binding = 3) uniform sampler2D occlusionMap;
+layout(set = 2, binding = 4) uniform sampler2D emissiveMap;
+
+layout(location = 0) in vec4 fragColor;
+layout(location = 1) in vec3 worldPos;
+layout(location = 2) in vec3 worldNormal;
+layout(location = 3) in vec2 fragUv;
+layout(location = 4) in vec4 worldTangent;
+layout(location = 0) out vec4 color;
+// Only attached when drawing order-independent transparency.
+layout(location = 1) out float revealage;
+
+struct Surface {
+    vec3 normal;
+    vec3 view;
+    vec3 albedo;
+    float metallic;
+    float roughness;
+    // Reflectance at normal incidence.
+    vec3 f0;
+};
+
+// Cook-Torrance GGX specular and Lambert diffuse reflection of light arriving from
+// direction, times the cosine term. Scaled by PI so a white diffuse surface facing a
+// light reflects the light's color.
+vec3 brdf(Surface surface, vec3 direction)
+{
+    float nDotL = max(dot(surface.normal, direction), 0.0);
+    if (nDotL <= 0.0) {
+        return vec3(0.0);
+    }
+    vec3 halfway = normalize(surface.view + direction);
+    float nDotV = max(dot(surface.normal, surface.view), 1e-4);
+    float nDotH = max(dot(surface.normal, halfway), 0.0);
+    float vDotH = max(dot(surface.view, halfway), 0.0);
+
+    float alpha = surface.roughness * surface.roughness;
+    float alpha2 = alpha * alpha;
+    float denominator = nDotH * nDotH * (alpha2 - 1.0) + 1.0;
+    float distribution = alpha2 / (PI * denominator * denominator);
+    float k = (surface.roughness + 1.0) * (surface.roughness + 1.0) / 8.0;
+    float geometry = nDotV / (nDotV * (1.0 - k) + k) * nDotL / (nDotL * (1.0 - k) + k);
+    vec3 fresnel = surface.f0 + (1.0 - surface.f0) * pow(1.0 - vDotH, 5.0);
+
+    vec3 specular = distribution * geometry * fresnel / (4.0 * nDotV * nDotL);
+    vec3 diffuse = (1.0 - fresnel) * (1.0 - surface.metallic) * surface.albedo / PI;
+    return (diffuse + specular) * nDotL * PI;
+}
+
+// Indirect light from the environment, by the split-sum approximation.
+vec3 environmentLight(Surface surface)
+{
+    float nDotV = max(dot(surface.normal, surface.view), 1e-4);
+    vec3 fresnel = surface.f0 + (max(vec3(1.0 - surface.roughness), surface.f0) - surface.f0) * pow(1.0 - nDotV, 5.0);
+    vec2 scaleBias = texture(brdfLut, vec2(nDotV, surface.roughness)).rg;
+    vec3 reflection = reflect(-surface.view, surface.normal);
+    vec3 specular = textureLod(specularMap, reflection, surface.roughness * (SPECULAR_MIP_LEVELS - 1.0)).rgb;
+    vec3 diffuse = texture(irradianceMap, surface.normal).rgb * surface.albedo * (1.0 - fresnel) * (1.0 - surface.metallic);
+    return (diffuse + specular * (surface.f0 * scaleBias.x + scaleBias.y)) * light.environment.x;
+}
+
+// Fraction of the light reaching the fragment, from the first cascade covering it,
+// averaged over 3x3 shadow map texels.
+float shadowFactor(vec3 normal)
+{
+    int cascadeCount = int(light.shadow.x);
+    for (int i = 0; i < cascadeCount; i++) {
+        vec3 position = worldPos + normal * light.shadow.y * light.texelSizes[i];
+        vec4 clip = light.cascades[i] * vec4(position, 1.0);
+        vec3 coords = clip.xyz / clip.w;
+        // Leave a margin so the filter stays inside the cascade.
+        if (any(greaterThan(abs(coords.xy), vec2(1.0 - 4.0 * light.shadow.z))) || coords.z < 0.0 || coords.z > 1.0) {
+            continue;
+        }
+        vec2 uv = coords.xy * 0.5 + 0.5;
+        float lit = 0.0;
+        for (int x = -1; x <= 1; x++) {
+            for (int y = -1; y <= 1; y++) {
+                vec2 offset = vec2(x, y) * light.shadow.z;
+                // Explicit gradients, since the loop isn't uniform control flow.
+                lit += textureGrad(shadowMap, vec4(uv + offset, float(i), coords.z), vec2(0.0), vec2(0.0));
+            }
+        }
+        return lit / 9.0;
+    }
+    return 1.0;
+}
+
+// Light reflected from the point and spot lights of the fragment's cluster.
+vec3 clusteredLights(Surface surface)
+{
+    vec4 viewPos = cam.view * vec4(worldPos, 1.0);
+    vec4 clip = cam.proj * viewPos;
+    vec2 ndc = clip.xy / clip.w;
+    uvec2 tile = uvec2(clamp((ndc * 0.5 + 0.5) * vec2(CLUSTER_GRID.xy), vec2(0.0), vec2(CLUSTER_GRID.xy - 1u)));
+
+    ClusterGrid grid = grids[material.viewIndex];
+    float depth = -viewPos.z;
+    float scaled = grid.depth.z > 0.5 ? log(max(depth, 1e-6)) : depth;
+    uint slice = uint(clamp(floor(scaled * grid.depth.x + grid.depth.y), 0.0, float(CLUSTER_GRID.z - 1u)));
+    uint cell = grid.firstCell.x + 2u * (tile.x + CLUSTER_GRID.x * (tile.y + CLUSTER_GRID.y * slice));
+
+    vec3 total = vec3(0.0);
+    uint first = items[cell];
+    uint count = items[cell + 1u];
+    for (uint i = 0u; i < count; i++) {
+        LightData lightData = lights[items[first + i]];
+        vec3 toLight = lightData.position.xyz - worldPos;
+        float dist = length(toLight);
+        vec3 direction = toLight / max(dist, 1e-4);
+        // Inverse square falloff, windowed to reach zero at the light's range.
+        float window = clamp(1.0 - pow(dist / lightData.position.w, 4.0), 0.0, 1.0);
+        float attenuation = window * window / (dist * dist + 1.0);
+        if (lightData.cone.y == SPOT_LIGHT) {
+            attenuation *= smoothstep(lightData.direction.w, lightData.cone.x, dot(-direction, lightData.direction.xyz));
+        }
+        total += lightData.color.rgb * attenuation * brdf(surface, direction);
+    }
+    return total;
+}
 
 void main(){
-    color= fragColor;
-}
\ No newline at end of file
+    vec4 baseColor = fragColor * material.baseColor * texture(baseColorMap, fragUv);
+    if (material.params.w > 0.0) {
+        if (baseColor.a < material.params.w) {
+            discard;
+        }
+        baseColor.a = 1.0;
+    }
+    vec4 metallicRoughness = texture(metallicRoughnessMap, fragUv);
+
+    vec3 geometryNormal = normalize(worldNormal);
+    vec3 tangent = worldTangent.xyz - geometryNormal * dot(geometryNormal, worldTangent.xyz);
+    vec3 normal = geometryNormal;
+    if (dot(tangent, tangent) > 1e-8) {
+        tangent = normalize(tangent);
+        vec3 bitangent = cross(geometryNormal, tangent) * worldTangent.w;
+        vec3 tangentNormal = texture(normalMap, fragUv).xyz * 2.0 - 1.0;
+        tangentNormal.xy *= material.emissive.a;
+        normal = normalize(mat3(tangent, bitangent, geometryNormal) * tangentNormal);
+    }
+
+    Surface surface;
+    surface.normal = normal;
+    // Towards the eye, or against the view direction for orthographic projections.
+    vec3 toEye = cam.proj[3][3] == 1.0 ? vec3(0.0, 0.0, 1.0) : -(cam.view * vec4(worldPos, 1.0)).xyz;
+    surface.view = normalize(transpose(mat3(cam.view)) * toEye);
+    surface.albedo = baseColor.rgb;
+    surface.metallic = clamp(material.params.x * metallicRoughness.b, 0.0, 1.0);
+    surface.roughness = clamp(material.params.y * metallicRoughness.g, 0.03, 1.0);
+    surface.f0 = mix(vec3(0.04), surface.albedo, surface.metallic);
+
+    vec3 direction = -normalize(light.direction.xyz);
+    vec3 direct = brdf(surface, direction);
+    float shadow = any(greaterThan(direct, vec3(0.0))) ? shadowFactor(geometryNormal) : 1.0;
+    float occlusion = 1.0 + material.params.z * (texture(occlusionMap, fragUv).r - 1.0);
+    vec3 indirect = light.environment.x > 0.0 ? environmentLight(surface) : surface.albedo * light.color.a;
+    vec3 emissive = material.emissive.rgb * texture(emissiveMap, fragUv).rgb;
+    vec3 lighting = light.color.rgb * direct * shadow + clusteredLights(surface) + indirect * occlusion + emissive;
+    if (material.orderIndependent != 0u) {
+        // Weighted blended order-independent transparency, favoring near and opaque
+        // fragments by view depth.
+        float alpha = baseColor.a;
+        float depth = -(cam.view * vec4(worldPos, 1.0)).z;
+        float weight = alpha * clamp(10.0 / (1e-5 + pow(depth / 5.0, 2.0) + pow(depth / 200.0, 6.0)), 1e-2, 3e3);
+        color = vec4(lighting * alpha, alpha) * weight;
+        revealage = alpha;
+    } else {
+        color = vec4(lighting, baseColor.a);
+        revealage = 0.0;
+    }
+}
@@ -12,12 +12,33 @@ layout(location = 2) in vec4 inModelCol1;
 layout(location = 3) in vec4 inModelCol2;
 layout(location = 4) in vec4 inModelCol3;
 layout(location = 5) in vec3 inColor;
+layout(location = 6) in vec3 normal;
+layout(location = 7) in vec2 uv;
+layout(location = 8) in vec4 tangent;
+layout(location = 9) in uint inIndex;
 
 layout(location = 0) out vec4 fragColor;
+layout(location = 1) out vec3 worldPos;
+layout(location = 2) out vec3 worldNormal;
+layout(location = 3) out vec2 fragUv;
+layout(location = 4) out vec4 worldTangent;
+// For the view modes: the instance's index in its group, and a corner of the
+// triangle when drawn without indices.
+layout(location = 5) flat out uint instanceIndex;
+layout(location = 6) noperspective out vec3 barycentric;
 
 void main()
 {
 mat4 model = mat4(inModelCol0, inModelCol1, inModelCol2, inModelCol3);
+vec4 world = model * vec4(position, 1.0);
 fragColor = vec4(inColor, 1.0);
-gl_Position = cam.proj * cam.view * model * vec4(position, 1.0);
+worldPos = world.xyz;
+mat3 normalMatrix = transpose(inverse(mat3(model)));
+worldNormal = normalMatrix * normal;
+fragUv = uv;
+instanceIndex = inIndex;
+uint corner = gl_VertexIndex % 3;
+barycentric = vec3(corner == 0, corner == 1, corner == 2);
+worldTangent = vec4(mat3(model) * tangent.xyz, tangent.w);
+gl_Position = cam.proj * cam.view * world;
 }
\ No newline at end of file
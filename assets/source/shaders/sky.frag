@@ -0,0 +1,112 @@
+#version 450
+
+const float PI = 3.14159265359;
+
+// Earth-like single scattering, in meters.
+const float PLANET_RADIUS = 6371e3;
+const float ATMOSPHERE_RADIUS = 6471e3;
+const float VIEWER_HEIGHT = 1e3;
+const vec3 RAYLEIGH_COEFFICIENT = vec3(5.5e-6, 13.0e-6, 22.4e-6);
+const float MIE_COEFFICIENT = 21e-6;
+const float RAYLEIGH_SCALE_HEIGHT = 8e3;
+const float MIE_SCALE_HEIGHT = 1.2e3;
+const float MIE_ANISOTROPY = 0.758;
+const int VIEW_STEPS = 16;
+const int SUN_STEPS = 8;
+// Radiance of the sun's disk relative to the sky's intensity.
+const float SUN_DISK_SCALE = 100.0;
+
+layout(push_constant) uniform BackgroundConstants {
+    mat4 inverseViewProjection;
+    // Unit direction towards the sun, with the cosine of its angular radius in w.
+    vec4 sun;
+    vec4 groundColor;
+    float intensity;
+    float farDepth;
+    float nearDepth;
+} constants;
+
+layout(location = 0) in vec3 ray;
+layout(location = 0) out vec4 color;
+
+// Distances along a unit direction to where it enters and leaves a sphere around the
+// planet's center, or an empty range when it misses.
+vec2 raySphere(vec3 origin, vec3 direction, float radius)
+{
+    float b = dot(origin, direction);
+    float c = dot(origin, origin) - radius * radius;
+    float discriminant = b * b - c;
+    if (discriminant < 0.0) {
+        return vec2(1e9, -1e9);
+    }
+    float root = sqrt(discriminant);
+    return vec2(-b - root, -b + root);
+}
+
+// Optical depth of Rayleigh and Mie particles from a point towards the sun, or a
+// negative value when the planet is in the way.
+vec2 sunOpticalDepth(vec3 position, vec3 sun)
+{
+    if (raySphere(position, sun, PLANET_RADIUS).x > 0.0) {
+        return vec2(-1.0);
+    }
+    float stepLength = raySphere(position, sun, ATMOSPHERE_RADIUS).y / float(SUN_STEPS);
+    vec2 depth = vec2(0.0);
+    for (int i = 0; i < SUN_STEPS; i++) {
+        float height = length(position + sun * (stepLength * (float(i) + 0.5))) - PLANET_RADIUS;
+        depth += exp(-height / vec2(RAYLEIGH_SCALE_HEIGHT, MIE_SCALE_HEIGHT)) * stepLength;
+    }
+    return depth;
+}
+
+vec3 extinction(vec2 depth)
+{
+    return exp(-(RAYLEIGH_COEFFICIENT * depth.x + MIE_COEFFICIENT * 1.1 * depth.y));
+}
+
+void main()
+{
+    vec3 direction = normalize(ray);
+    vec3 sun = constants.sun.xyz;
+    vec3 origin = vec3(0.0, PLANET_RADIUS + VIEWER_HEIGHT, 0.0);
+    float end = raySphere(origin, direction, ATMOSPHERE_RADIUS).y;
+    float ground = raySphere(origin, direction, PLANET_RADIUS).x;
+    bool hitsGround = ground > 0.0;
+    if (hitsGround) {
+        end = ground;
+    }
+
+    float stepLength = end / float(VIEW_STEPS);
+    vec2 viewDepth = vec2(0.0);
+    vec3 rayleigh = vec3(0.0);
+    vec3 mie = vec3(0.0);
+    for (int i = 0; i < VIEW_STEPS; i++) {
+        vec3 position = origin + direction * (stepLength * (float(i) + 0.5));
+        float height = length(position) - PLANET_RADIUS;
+        vec2 stepDepth = exp(-height / vec2(RAYLEIGH_SCALE_HEIGHT, MIE_SCALE_HEIGHT)) * stepLength;
+        viewDepth += stepDepth;
+        vec2 sunDepth = sunOpticalDepth(position, sun);
+        if (sunDepth.x < 0.0) {
+            continue;
+        }
+        vec3 attenuation = extinction(viewDepth + sunDepth);
+        rayleigh += stepDepth.x * attenuation;
+        mie += stepDepth.y * attenuation;
+    }
+
+    float mu = dot(direction, sun);
+    float g = MIE_ANISOTROPY;
+    float rayleighPhase = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+    float miePhase = 3.0 / (8.0 * PI) * ((1.0 - g * g) * (1.0 + mu * mu)) / (pow(1.0 + g * g - 2.0 * mu * g, 1.5) * (2.0 + g * g));
+    vec3 radiance = constants.intensity * (rayleighPhase * RAYLEIGH_COEFFICIENT * rayleigh + miePhase * MIE_COEFFICIENT * mie);
+
+    vec3 transmittance = extinction(viewDepth);
+    if (hitsGround) {
+        // Lambertian ground lit by the sun, seen through the air in front of it.
+        radiance += constants.groundColor.rgb / PI * constants.intensity * max(sun.y, 0.0) * transmittance;
+    } else if (constants.sun.w < 1.0) {
+        float edge = mix(constants.sun.w, 1.0, 0.05);
+        radiance += smoothstep(constants.sun.w, edge, mu) * SUN_DISK_SCALE * constants.intensity * transmittance;
+    }
+    color = vec4(radiance, 1.0);
+}
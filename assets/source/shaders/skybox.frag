@@ -0,0 +1,20 @@
+#version 450
+
+layout(binding = 0) uniform samplerCube skybox;
+
+layout(push_constant) uniform BackgroundConstants {
+    mat4 inverseViewProjection;
+    vec4 sun;
+    vec4 groundColor;
+    float intensity;
+    float farDepth;
+    float nearDepth;
+} constants;
+
+layout(location = 0) in vec3 ray;
+layout(location = 0) out vec4 color;
+
+void main()
+{
+    color = vec4(texture(skybox, normalize(ray)).rgb * constants.intensity, 1.0);
+}
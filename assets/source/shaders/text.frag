@@ -0,0 +1,19 @@
+#version 450
+
+// Signed distance to the glyph outlines, 0.5 on the edge.
+layout(binding = 0) uniform sampler2D atlas;
+
+layout(location = 0) in vec2 fragUv;
+layout(location = 1) in vec4 fragColor;
+layout(location = 0) out vec4 color;
+
+void main(){
+    float distance = texture(atlas, fragUv).r;
+    // Half a pixel either side of the edge, whatever the scale.
+    float width = max(fwidth(distance) * 0.5, 1e-4);
+    float coverage = smoothstep(0.5 - width, 0.5 + width, distance);
+    if (coverage <= 0.0) {
+        discard;
+    }
+    color = vec4(fragColor.rgb, fragColor.a * coverage);
+}
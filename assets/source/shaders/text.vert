@@ -0,0 +1,25 @@
+#version 450
+
+layout(push_constant) uniform Placement {
+    mat4 transform;
+    // Directions of the layout's x and y axes: the camera's right and down for
+    // billboards, pixels for screen text.
+    vec4 right;
+    vec4 down;
+} placement;
+
+layout(location = 0) in vec3 anchor;
+layout(location = 1) in vec2 offset;
+layout(location = 2) in vec2 uv;
+layout(location = 3) in vec4 inColor;
+
+layout(location = 0) out vec2 fragUv;
+layout(location = 1) out vec4 fragColor;
+
+void main()
+{
+vec3 position = anchor + placement.right.xyz * offset.x + placement.down.xyz * offset.y;
+fragUv = uv;
+fragColor = inColor;
+gl_Position = placement.transform * vec4(position, 1.0);
+}
@@ -0,0 +1,94 @@
+#version 450
+
+layout(binding = 0) uniform sampler2D hdr;
+layout(binding = 1) uniform sampler2D bloom;
+layout(binding = 2) uniform sampler2D lut;
+
+layout(push_constant) uniform PostProcessConstants {
+    float exposure;
+    uint tonemapper;
+    float bloom_intensity;
+    float bloom_threshold;
+    vec2 texel_size;
+    uint flags;
+    float lut_size;
+} constants;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 color;
+
+const uint TONEMAP_NONE = 0u;
+const uint TONEMAP_REINHARD = 1u;
+const uint TONEMAP_ACES = 2u;
+const uint TONEMAP_AGX = 3u;
+
+const uint BLOOM = 1u;
+const uint COLOR_GRADING = 2u;
+
+vec3 reinhard(vec3 c){
+    return c / (1.0 + c);
+}
+
+// Narkowicz's fit of the ACES filmic curve.
+vec3 aces(vec3 c){
+    return clamp((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14), 0.0, 1.0);
+}
+
+// Minimal AgX with the default look, returning linear values.
+vec3 agx(vec3 c){
+    const mat3 inset = mat3(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104);
+    const mat3 outset = mat3(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116);
+    const float min_ev = -12.47393;
+    const float max_ev = 4.026069;
+
+    c = inset * c;
+    c = clamp(log2(max(c, 1e-10)), min_ev, max_ev);
+    c = (c - min_ev) / (max_ev - min_ev);
+    vec3 c2 = c * c;
+    vec3 c4 = c2 * c2;
+    c = 15.5 * c4 * c2 - 40.14 * c4 * c + 31.96 * c4 - 6.868 * c2 * c + 0.4298 * c2 + 0.1191 * c - 0.00232;
+    c = outset * c;
+    return pow(max(c, 0.0), vec3(2.2));
+}
+
+// Looks the color up in a strip of `lut_size` slices, red across each slice, green
+// down and blue selecting the slice. The table is indexed with gamma-encoded values
+// and stored as sRGB, so the result is linear again.
+vec3 grade(vec3 c){
+    float n = constants.lut_size;
+    vec3 encoded = clamp(pow(c, vec3(1.0 / 2.2)), 0.0, 1.0) * (n - 1.0);
+    float slice = floor(encoded.b);
+    float next = min(slice + 1.0, n - 1.0);
+    vec2 inner = (encoded.rg + 0.5) / vec2(n * n, n);
+    vec3 a = texture(lut, vec2(inner.x + slice / n, inner.y)).rgb;
+    vec3 b = texture(lut, vec2(inner.x + next / n, inner.y)).rgb;
+    return mix(a, b, encoded.b - slice);
+}
+
+void main(){
+    vec3 c = texture(hdr, uv).rgb * constants.exposure;
+    if ((constants.flags & BLOOM) != 0u) {
+        c += texture(bloom, uv).rgb * constants.bloom_intensity;
+    }
+
+    if (constants.tonemapper == TONEMAP_REINHARD) {
+        c = reinhard(c);
+    } else if (constants.tonemapper == TONEMAP_ACES) {
+        c = aces(c);
+    } else if (constants.tonemapper == TONEMAP_AGX) {
+        c = agx(c);
+    } else {
+        c = clamp(c, 0.0, 1.0);
+    }
+
+    if ((constants.flags & COLOR_GRADING) != 0u) {
+        c = grade(c);
+    }
+    color = vec4(c, 1.0);
+}
@@ -0,0 +1,74 @@
+#version 450
+
+// Values of ViewMode, with the wireframe drawn from barycentrics on devices that
+// cannot rasterize lines.
+const uint WIREFRAME = 1;
+const uint NORMALS = 2;
+const uint DEPTH = 3;
+const uint UV_CHECKER = 4;
+const uint INSTANCE_ID = 5;
+const uint OVERDRAW = 6;
+const uint BARYCENTRIC_WIREFRAME = 7;
+// View depth shown black.
+const float MAX_DEPTH = 1000.0;
+const float CHECKER_CELLS = 8.0;
+// Added by each fragment, so red saturates first, then green, then blue.
+const vec3 OVERDRAW_STEP = vec3(0.1, 0.03, 0.01);
+
+layout(set = 0, binding = 0) uniform CameraMatrix {
+    mat4 view;
+    mat4 proj;
+} cam;
+
+layout(push_constant) uniform Material {
+    vec4 baseColor;
+    vec4 emissive;
+    vec4 params;
+    uint viewIndex;
+    uint orderIndependent;
+    uint group;
+    uint viewMode;
+} material;
+
+layout(location = 0) in vec4 fragColor;
+layout(location = 1) in vec3 worldPos;
+layout(location = 2) in vec3 worldNormal;
+layout(location = 3) in vec2 fragUv;
+layout(location = 5) flat in uint instanceIndex;
+layout(location = 6) noperspective in vec3 barycentric;
+layout(location = 0) out vec4 color;
+
+uint hash(uint x){
+    x ^= x >> 16;
+    x *= 0x7feb352du;
+    x ^= x >> 15;
+    x *= 0x846ca68bu;
+    x ^= x >> 16;
+    return x;
+}
+
+void main(){
+    vec3 result = fragColor.rgb * material.baseColor.rgb;
+    if (material.viewMode == BARYCENTRIC_WIREFRAME) {
+        // Distance to the nearest edge in pixels.
+        vec3 edges = barycentric / fwidth(barycentric);
+        if (min(edges.x, min(edges.y, edges.z)) > 1.0) {
+            discard;
+        }
+    } else if (material.viewMode == NORMALS) {
+        result = normalize(worldNormal) * 0.5 + 0.5;
+    } else if (material.viewMode == DEPTH) {
+        float depth = max(-(cam.view * vec4(worldPos, 1.0)).z, 0.0);
+        result = vec3(1.0 - clamp(log2(1.0 + depth) / log2(1.0 + MAX_DEPTH), 0.0, 1.0));
+    } else if (material.viewMode == UV_CHECKER) {
+        ivec2 cell = ivec2(floor(fragUv * CHECKER_CELLS));
+        float checker = float((cell.x + cell.y) & 1);
+        result = mix(vec3(0.2), vec3(0.9), checker) * vec3(fract(fragUv), 1.0);
+    } else if (material.viewMode == INSTANCE_ID) {
+        uint id = hash(instanceIndex ^ hash(material.group));
+        result = vec3(id & 255u, (id >> 8) & 255u, (id >> 16) & 255u) / 255.0;
+    } else if (material.viewMode == OVERDRAW) {
+        result = OVERDRAW_STEP;
+    }
+    color = vec4(result, 1.0);
+}
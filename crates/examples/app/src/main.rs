@@ -1,6 +1,6 @@
-use glam::{vec3, Mat4};
+use glam::{vec3, Mat3, Mat4, Quat, Vec3};
 use turtle::*;
-use winit::event_loop::{ControlFlow, EventLoop};
+use winit::{event::MouseButton, event_loop::{ControlFlow, EventLoop}, keyboard::KeyCode};
 
 fn main() -> AnyResult<()> {
     pretty_env_logger::init();
@@ -10,6 +10,8 @@ fn main() -> AnyResult<()> {
     let mut app = TurtleApp::default();
     
     app.set_init_function(init);
+    let mut state = State::default();
+    app.set_update_function(move |app_data| update(app_data, &mut state));
 
     event_loop.run_app(&mut app).unwrap();
     Ok(())
@@ -17,8 +19,12 @@ fn main() -> AnyResult<()> {
 
 fn init(app_data: &mut AppData) {
         let mesh = app_data.renderer.context.create_mesh_from_cube().unwrap();
-        let mut instance_group = InstanceGroup::from(mesh);
+        let material = Material::default().with_metallic_roughness(0.2, 0.35);
+        let mut instance_group = InstanceGroup::from(mesh).with_format(InstanceFormat::Compact).with_material(material);
         instance_group.create_instance_buffer(&app_data.renderer.context).unwrap();
+        app_data.instance_groups.push(instance_group);
+        let group = app_data.instance_groups.len() - 1;
+        let sphere = app_data.scene.add_node("sphere", None, Transform::default());
         let count = 10000;        // how many cubes you want
         let radius = 50.0;      // radius of sphere
         let spacing = 1.0;      // optional multiplier for cube separation
@@ -41,25 +47,296 @@ fn init(app_data: &mut AppData) {
             let right = up.cross(forward).normalize();
             let adjusted_up = forward.cross(right);
 
-            let rotation = Mat4::from_cols(
-                right.extend(0.0),
-                adjusted_up.extend(0.0),
-                forward.extend(0.0),
-                glam::Vec4::W,
+            let rotation = Quat::from_mat3(&Mat3::from_cols(right, adjusted_up, forward));
+
+            let transform = Transform {
+                translation: position,
+                rotation,
+                ..Default::default()
+            };
+
+            app_data.scene.spawn_instance(
+                &mut app_data.instance_groups,
+                &format!("cube_{}", i),
+                Some(sphere),
+                transform,
+                group,
+                vec3(x, y, z),
             );
+        }
+
+        // A ring of tinted glass cubes around the center.
+        let glass = Material::default()
+            .with_base_color(glam::vec4(0.6, 0.8, 1.0, 0.35))
+            .with_metallic_roughness(0.0, 0.1)
+            .with_alpha_mode(AlphaMode::Blend);
+        let glass_mesh = app_data.renderer.context.create_mesh_from_cube().unwrap();
+        let mut glass_group = InstanceGroup::from(glass_mesh).with_material(glass);
+        glass_group.create_instance_buffer(&app_data.renderer.context).unwrap();
+        app_data.instance_groups.push(glass_group);
+        let glass_group = app_data.instance_groups.len() - 1;
+        let ring = app_data.scene.add_node("glass_ring", None, Transform::default());
+        for i in 0..48 {
+            let angle = std::f32::consts::TAU * i as f32 / 48.0;
+            let transform = Transform {
+                translation: vec3(angle.cos(), 0.0, angle.sin()) * 20.0,
+                rotation: Quat::from_rotation_y(-angle),
+                scale: Vec3::splat(3.0),
+            };
+            app_data.scene.spawn_instance(&mut app_data.instance_groups, &format!("glass_{}", i), Some(ring), transform, glass_group, Vec3::ONE);
+        }
+
+        // Small colored lights just inside the shell, and a spot light from the center.
+        let light_count = 256;
+        for i in 0..light_count {
+            let t = i as f32 / light_count as f32;
+            let y = 1.0 - t * 2.0;
+            let theta = std::f32::consts::TAU * 0.618034 * i as f32 * 7.0;
+            let position = vec3(theta.cos() * (1.0 - y * y).sqrt(), y, theta.sin() * (1.0 - y * y).sqrt()) * (radius - 2.0);
+            let color = vec3(t, 1.0 - t, (theta * 0.5).sin() * 0.5 + 0.5);
+            app_data.renderer.lights.push(PointLight { intensity: 4.0, ..PointLight::new(position, color, 8.0) }.into());
+        }
+        app_data.renderer.lights.push(SpotLight {
+            intensity: 400.0,
+            ..SpotLight::new(Vec3::ZERO, Vec3::NEG_Z, Vec3::ONE, 60.0)
+        }.into());
+
+        // Image-based lighting, when an HDR panorama has been put in place.
+        let environment = std::path::Path::new("assets/environment.hdr");
+        if environment.exists() {
+            app_data.renderer.load_environment(environment).unwrap();
+        }
+}
+
+#[derive(Default)]
+struct State {
+    selection: Option<Selection>,
+    /// Target the security camera draws into, created the first time it is shown.
+    monitor: Option<RenderTargetId>,
+    /// Cube map of the environment panorama, loaded the first time it is shown.
+    skybox: Option<std::sync::Arc<Skybox>>,
+    /// Whether a ground grid and the world axes are drawn.
+    debug_grid: bool,
+    /// Font of the labels and the frame rate, loaded on the first update when there is one.
+    font: Option<Option<FontId>>,
+    /// Frames per second, smoothed over the last few dozen frames.
+    fps: f32,
+}
+
+/// Instance highlighted by right-clicking it, with the color to restore.
+struct Selection {
+    group: usize,
+    instance: usize,
+    color: Vec3,
+}
 
-            let transform = Mat4::from_translation(position) * rotation;
+fn update(app_data: &mut AppData, state: &mut State) {
+    let keyboard = app_data.input_manager.keyboard();
+    if keyboard.just_pressed(KeyCode::KeyP) {
+        app_data.time.toggle_pause();
+    }
 
-            let instance_data = tvk::InstanceData {
-                model: transform,
-                color: vec3(x, y, z)
+    if keyboard.just_pressed(KeyCode::KeyO) {
+        app_data.camera.projection = match app_data.camera.projection {
+            Projection::Perspective { .. } => Projection::orthographic(120.0),
+            _ => Projection::default(),
+        };
+    }
+
+    // M toggles a top-down minimap in the bottom right corner.
+    if keyboard.just_pressed(KeyCode::KeyM) {
+        if !app_data.views.iter().any(|view| view.target.is_none()) {
+            let camera = Camera {
+                position: vec3(0.0, 200.0, 0.0),
+                projection: Projection::orthographic(120.0),
+                pitch: -90.0,
+                up: Vec3::NEG_Z,
+                ..Default::default()
             };
+            let viewport = Viewport::new(0.7, 0.7, 0.28, 0.28);
+            app_data.views.push(View::new(camera, viewport).with_clear_color([0.05, 0.05, 0.08, 1.0]));
+        } else {
+            app_data.views.retain(|view| view.target.is_some());
+        }
+    }
 
-            instance_group.add_instance(
-                instance_data,
-                true,
-            );
+    // C toggles a security camera drawn into a render target and shown as an overlay.
+    if keyboard.just_pressed(KeyCode::KeyC) {
+        if app_data.overlays.is_empty() {
+            let target = match state.monitor {
+                Some(target) => target,
+                None => *state.monitor.insert(app_data.renderer.create_render_target(512, 512, RenderTargetFormat::default()).unwrap()),
+            };
+            let mut camera = Camera {
+                position: vec3(90.0, 40.0, 90.0),
+                ..Default::default()
+            };
+            camera.look_at(Vec3::ZERO);
+            app_data.views.push(View::new(camera, Viewport::FULL).with_target(target).with_clear_color([0.1, 0.1, 0.1, 1.0]));
+            app_data.overlays.push(Overlay::new(target, Viewport::new(0.02, 0.7, 0.28, 0.28)));
+        } else {
+            app_data.overlays.clear();
+            app_data.views.retain(|view| view.target.is_none());
         }
-        instance_group.update_gpu_buffer().unwrap();
-        app_data.instance_groups.push(instance_group);
+    }
+
+    // N doubles the MSAA sample count, wrapping to 1 past the highest the device supports.
+    if keyboard.just_pressed(KeyCode::KeyN) {
+        let samples = app_data.renderer.msaa_samples();
+        app_data.renderer.set_msaa_samples(samples * 2).unwrap();
+        if app_data.renderer.msaa_samples() == samples {
+            app_data.renderer.set_msaa_samples(1).unwrap();
+        }
+    }
+
+    // T cycles the tonemapper, B toggles bloom and [ and ] change the exposure.
+    let post_process = &mut app_data.renderer.post_process;
+    if keyboard.just_pressed(KeyCode::KeyT) {
+        post_process.tonemapper = match post_process.tonemapper {
+            Tonemapper::None => Tonemapper::Reinhard,
+            Tonemapper::Reinhard => Tonemapper::Aces,
+            Tonemapper::Aces => Tonemapper::AgX,
+            Tonemapper::AgX => Tonemapper::None,
+        };
+    }
+    if keyboard.just_pressed(KeyCode::KeyB) {
+        post_process.bloom = match post_process.bloom {
+            Some(_) => None,
+            None => Some(Bloom::default()),
+        };
+    }
+    if keyboard.just_pressed(KeyCode::BracketLeft) {
+        post_process.exposure /= 1.25;
+    } else if keyboard.just_pressed(KeyCode::BracketRight) {
+        post_process.exposure *= 1.25;
+    }
+
+    // L toggles shadows.
+    if keyboard.just_pressed(KeyCode::KeyL) {
+        let renderer = &mut app_data.renderer;
+        renderer.shadows = match renderer.shadows {
+            Some(_) => None,
+            None => Some(Shadows::default()),
+        };
+    }
+
+    // X switches the glass between sorted and order-independent transparency.
+    if keyboard.just_pressed(KeyCode::KeyX) {
+        for instance_group in app_data.instance_groups.iter_mut() {
+            instance_group.material.alpha_mode = match instance_group.material.alpha_mode {
+                AlphaMode::Blend => AlphaMode::OrderIndependent,
+                AlphaMode::OrderIndependent => AlphaMode::Blend,
+                other => other,
+            };
+        }
+    }
+
+    // K cycles the background: clear color, procedural sky, then the environment
+    // panorama when there is one.
+    if keyboard.just_pressed(KeyCode::KeyK) {
+        let panorama = std::path::Path::new("assets/environment.hdr");
+        let renderer = &mut app_data.renderer;
+        renderer.background = match renderer.background {
+            Background::ClearColor => Background::Sky(ProceduralSky::default()),
+            Background::Sky(_) if panorama.exists() => {
+                let skybox = match &state.skybox {
+                    Some(skybox) => skybox.clone(),
+                    None => state.skybox.insert(renderer.load_skybox(panorama).unwrap()).clone(),
+                };
+                Background::Skybox { skybox, intensity: 1.0 }
+            }
+            _ => Background::ClearColor,
+        };
+    }
+
+    // G toggles a debug grid with the world axes.
+    if keyboard.just_pressed(KeyCode::KeyG) {
+        state.debug_grid = !state.debug_grid;
+    }
+    if state.debug_grid {
+        let debug_draw = &mut app_data.renderer.debug_draw;
+        debug_draw.grid(Vec3::ZERO, 200.0, 20, Vec3::splat(0.3));
+        debug_draw.axes(Mat4::IDENTITY, 20.0).depth_test(false);
+    }
+
+    // 1-4 switch between the camera controllers.
+    if keyboard.just_pressed(KeyCode::Digit1) {
+        app_data.set_camera_controller(FreeFlyController::default());
+    } else if keyboard.just_pressed(KeyCode::Digit2) {
+        app_data.set_camera_controller(OrbitController::new(Vec3::ZERO, 120.0));
+    } else if keyboard.just_pressed(KeyCode::Digit3) {
+        app_data.set_camera_controller(ArcballController::new(Vec3::ZERO, 120.0));
+    } else if keyboard.just_pressed(KeyCode::Digit4)
+        && let Some(cube) = app_data.scene.find("cube_0") {
+        app_data.set_camera_controller(FollowController::new(cube));
+    }
+
+    // Right click selects with a CPU raycast, middle click with the GPU id pass.
+    if app_data.input_manager.mouse().just_pressed(MouseButton::Right) {
+        let hit = app_data.pick(PickMode::Triangles).map(|hit| (hit.group, hit.instance));
+        if let Some(ray) = app_data.cursor_ray() {
+            app_data.renderer.debug_draw.ray(&ray, 500.0, vec3(1.0, 0.2, 0.2)).duration(2.0);
+        }
+        select(app_data, &mut state.selection, hit);
+    }
+    if app_data.input_manager.mouse().just_pressed(MouseButton::Middle) {
+        app_data.request_pick().unwrap();
+    }
+    if let Some(readback) = app_data.renderer.take_object_id() {
+        select(app_data, &mut state.selection, readback.object.map(|id| (id.group, id.instance)));
+    }
+
+    // Outline the selection's bounds through whatever is in front of it, and label it.
+    let font = *state.font.get_or_insert_with(|| {
+        let path = std::path::Path::new("crates/libs/turtle/tests/data/DejaVuSansMono.ttf");
+        app_data.renderer.load_font(path)
+            .inspect_err(|error| eprintln!("no labels, cannot load {}: {}", path.display(), error))
+            .ok()
+    });
+    if let Some(selection) = &state.selection {
+        let group = &app_data.instance_groups[selection.group];
+        if let Some(mesh) = group.mesh.get() {
+            let bounds = mesh.bounds().transform(group.instances()[selection.instance].model);
+            app_data.renderer.debug_draw.aabb(&bounds, vec3(1.0, 0.9, 0.0)).depth_test(false);
+            if let Some(font) = font {
+                let label = Text::new(format!("instance {}", selection.instance), font, 1.0).with_align(TextAlign::Center);
+                app_data.renderer.text.world(label, vec3(bounds.center().x, bounds.max.y + 1.5, bounds.center().z));
+            }
+        }
+    }
+
+    // Frame rate and view mode in the top left corner, and a label over the sphere.
+    let delta = app_data.time.unscaled_delta().as_secs_f32();
+    if delta > 0.0 {
+        state.fps += (1.0 / delta - state.fps) * 0.05;
+    }
+    if let Some(font) = font {
+        let status = format!("{:.0} fps\n{:?}", state.fps, app_data.renderer.view_mode);
+        app_data.renderer.text.screen(Text::new(status, font, 20.0), glam::vec2(16.0, 16.0));
+        let label = Text::new("10000 cubes", font, 6.0).with_color(glam::vec4(1.0, 0.8, 0.3, 1.0)).with_align(TextAlign::Center);
+        app_data.renderer.text.world(label, vec3(0.0, 62.0, 0.0));
+    }
+
+    if let Some(sphere) = app_data.scene.find("sphere") {
+        let angle = app_data.time.elapsed_seconds() * 0.1;
+        app_data.scene.set_rotation(sphere, Quat::from_rotation_y(angle));
+    }
+}
+
+fn select(app_data: &mut AppData, selection: &mut Option<Selection>, hit: Option<(usize, usize)>) {
+    if let Some(previous) = selection.take() {
+        set_color(app_data, previous.group, previous.instance, previous.color);
+    }
+    if let Some((group, instance)) = hit {
+        let color = app_data.instance_groups[group].instances()[instance].color;
+        set_color(app_data, group, instance, vec3(1.0, 0.9, 0.0));
+        *selection = Some(Selection { group, instance, color });
+    }
+}
+
+fn set_color(app_data: &mut AppData, group: usize, instance: usize, color: Vec3) {
+    let group = &mut app_data.instance_groups[group];
+    let mut data = group.instances()[instance];
+    data.color = color;
+    group.set_instance(instance, data);
 }
\ No newline at end of file
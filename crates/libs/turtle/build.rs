@@ -50,7 +50,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     in_path.file_name().unwrap().to_string_lossy()
                 );
 
-                std::fs::write(&out_path.clone(), &compiled_bytes)?;
+                std::fs::write(&out_path, &compiled_bytes)?;
             }
         }
     }
@@ -0,0 +1,508 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::Arc};
+
+use ash::vk as avk;
+use gpu_allocator::MemoryLocation;
+use crate::*;
+
+pub mod handle;
+pub use handle::*;
+
+pub mod loader;
+pub use loader::*;
+
+pub mod hot_reload;
+pub use hot_reload::*;
+
+enum PendingAsset {
+    Mesh(Handle<Mesh<tvk::Vertex>>),
+    /// The format tells color textures, stored as sRGB, from linear data.
+    Texture(Handle<Texture>, avk::Format),
+    Shader(Handle<tvk::ShaderModule>),
+}
+
+struct PendingLoad {
+    asset: PendingAsset,
+    /// Reloads keep the current value when they fail instead of falling back to the placeholder.
+    reload: bool,
+}
+
+/// A transfer-queue submission whose resources become visible to their handle once
+/// the fence signals.
+struct Upload {
+    fence: tvk::Fence,
+    finish: Box<dyn FnOnce()>,
+    _command_buffer: tvk::CommandBuffer,
+    _staging: Vec<tvk::Buffer>,
+}
+
+/// Loads meshes, textures and shaders by path.
+///
+/// Files are decoded on worker threads and uploaded through the transfer queue from
+/// `update`; handles return a placeholder until then. Each path is loaded once and
+/// shared by every handle that asks for it.
+pub struct Assets {
+    meshes: HashMap<PathBuf, Handle<Mesh<tvk::Vertex>>>,
+    textures: HashMap<PathBuf, Handle<Texture>>,
+    linear_textures: HashMap<PathBuf, Handle<Texture>>,
+    shaders: HashMap<PathBuf, Handle<tvk::ShaderModule>>,
+    pending: HashMap<u64, PendingLoad>,
+    /// Assets decoded on the main thread, uploaded by the next `update`.
+    decoded: Vec<JobResult>,
+    uploads: Vec<Upload>,
+    pool: WorkerPool,
+    next_job: u64,
+    hot_reloader: Option<HotReloader>,
+    watched_files: Vec<PathBuf>,
+    changed_files: Vec<PathBuf>,
+    pub placeholder_mesh: Arc<Mesh<tvk::Vertex>>,
+    pub placeholder_texture: Arc<Texture>,
+}
+
+impl Assets {
+    pub fn new(context: &tvk::Context) -> AnyResult<Self> {
+        let placeholder_mesh = Arc::new(context.create_mesh_from_cube()?);
+        let (upload, texture) = upload_texture(context, 1, 1, &[255, 255, 255, 255], avk::Format::R8G8B8A8_SRGB)?;
+        upload.fence.wait(u64::MAX)?;
+        let workers = std::thread::available_parallelism().map(|n| n.get().clamp(1, 4)).unwrap_or(2);
+
+        Ok(Self {
+            meshes: HashMap::new(),
+            textures: HashMap::new(),
+            linear_textures: HashMap::new(),
+            shaders: HashMap::new(),
+            pending: HashMap::new(),
+            decoded: Vec::new(),
+            uploads: Vec::new(),
+            pool: WorkerPool::new(workers),
+            next_job: 0,
+            hot_reloader: None,
+            watched_files: Vec::new(),
+            changed_files: Vec::new(),
+            placeholder_mesh,
+            placeholder_texture: Arc::new(texture),
+        })
+    }
+
+    pub fn load_mesh(&mut self, path: impl AsRef<Path>) -> Handle<Mesh<tvk::Vertex>> {
+        let key = cache_key(path.as_ref());
+        if let Some(handle) = self.meshes.get(&key) {
+            return handle.clone();
+        }
+        let handle = Handle::loading(path.as_ref().to_path_buf(), Some(self.placeholder_mesh.clone()));
+        self.meshes.insert(key, handle.clone());
+        self.submit(AssetKind::Mesh, path.as_ref(), PendingAsset::Mesh(handle.clone()), false);
+        self.watch_asset(path.as_ref());
+        handle
+    }
+
+    /// Loads a color texture, whose texels are sRGB encoded.
+    pub fn load_texture(&mut self, path: impl AsRef<Path>) -> Handle<Texture> {
+        let key = cache_key(path.as_ref());
+        if let Some(handle) = self.textures.get(&key) {
+            return handle.clone();
+        }
+        let handle = Handle::loading(path.as_ref().to_path_buf(), Some(self.placeholder_texture.clone()));
+        self.textures.insert(key, handle.clone());
+        self.submit(AssetKind::Texture, path.as_ref(), PendingAsset::Texture(handle.clone(), avk::Format::R8G8B8A8_SRGB), false);
+        self.watch_asset(path.as_ref());
+        handle
+    }
+
+    /// Loads a texture of data other than colors, such as normals or roughness, that is
+    /// sampled without sRGB decoding.
+    pub fn load_linear_texture(&mut self, path: impl AsRef<Path>) -> Handle<Texture> {
+        let key = cache_key(path.as_ref());
+        if let Some(handle) = self.linear_textures.get(&key) {
+            return handle.clone();
+        }
+        let handle = Handle::loading(path.as_ref().to_path_buf(), Some(self.placeholder_texture.clone()));
+        self.linear_textures.insert(key, handle.clone());
+        self.submit(AssetKind::Texture, path.as_ref(), PendingAsset::Texture(handle.clone(), avk::Format::R8G8B8A8_UNORM), false);
+        self.watch_asset(path.as_ref());
+        handle
+    }
+
+    /// Materials of a glTF file in document order. Textures in files of their own are
+    /// loaded through `load_texture` and `load_linear_texture`; those embedded in the
+    /// file, as data URIs or buffer views, are decoded right away and uploaded by the
+    /// next `update`.
+    pub fn load_gltf_materials(&mut self, path: impl AsRef<Path>) -> AnyResult<Vec<Material>> {
+        let path = path.as_ref();
+        let gltf = gltf::Gltf::open(path)?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let buffers = gltf::import_buffers(&gltf.document, Some(directory), gltf.blob.clone())?;
+        // Embedded images are decoded once per color space, however many textures use them.
+        let mut embedded = HashMap::new();
+        let mut load = |assets: &mut Self, texture: gltf::Texture, srgb: bool| {
+            let image = texture.source();
+            if let gltf::image::Source::Uri { uri, .. } = image.source()
+                && !uri.starts_with("data:") {
+                let texture_path = directory.join(uri);
+                return if srgb { assets.load_texture(texture_path) } else { assets.load_linear_texture(texture_path) };
+            }
+            embedded.entry((image.index(), srgb)).or_insert_with(|| {
+                let result = load_gltf_image(image.source(), directory, &buffers)
+                    .map(|(width, height, pixels)| LoadedAsset::Texture { width, height, pixels })
+                    .map_err(|e| format!("{}: image {}: {}", path.display(), image.index(), e));
+                let format = if srgb { avk::Format::R8G8B8A8_SRGB } else { avk::Format::R8G8B8A8_UNORM };
+                assets.add_decoded_texture(result, format)
+            }).clone()
+        };
+
+        Ok(gltf.document.materials().map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            let normal = material.normal_texture();
+            let occlusion = material.occlusion_texture();
+            Material {
+                base_color: pbr.base_color_factor().into(),
+                metallic: pbr.metallic_factor(),
+                roughness: pbr.roughness_factor(),
+                emissive: material.emissive_factor().into(),
+                normal_scale: normal.as_ref().map_or(1.0, |info| info.scale()),
+                occlusion_strength: occlusion.as_ref().map_or(1.0, |info| info.strength()),
+                alpha_mode: match material.alpha_mode() {
+                    gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+                    gltf::material::AlphaMode::Mask => AlphaMode::Mask { cutoff: material.alpha_cutoff().unwrap_or(0.5) },
+                    gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+                },
+                base_color_texture: pbr.base_color_texture().map(|info| load(self, info.texture(), true)),
+                metallic_roughness_texture: pbr.metallic_roughness_texture().map(|info| load(self, info.texture(), false)),
+                normal_texture: normal.as_ref().map(|info| load(self, info.texture(), false)),
+                occlusion_texture: occlusion.as_ref().map(|info| load(self, info.texture(), false)),
+                emissive_texture: material.emissive_texture().map(|info| load(self, info.texture(), true)),
+            }
+        }).collect())
+    }
+
+    /// Texture for texels decoded on the main thread, uploaded by the next `update`.
+    fn add_decoded_texture(&mut self, result: Result<LoadedAsset, String>, format: avk::Format) -> Handle<Texture> {
+        let handle = Handle::loading_embedded(Some(self.placeholder_texture.clone()));
+        let id = self.next_job;
+        self.next_job += 1;
+        self.pending.insert(id, PendingLoad { asset: PendingAsset::Texture(handle.clone(), format), reload: false });
+        self.decoded.push((id, result));
+        handle
+    }
+
+    pub fn load_shader(&mut self, path: impl AsRef<Path>) -> Handle<tvk::ShaderModule> {
+        let key = cache_key(path.as_ref());
+        if let Some(handle) = self.shaders.get(&key) {
+            return handle.clone();
+        }
+        let handle = Handle::loading(path.as_ref().to_path_buf(), None);
+        self.shaders.insert(key, handle.clone());
+        self.submit(AssetKind::Shader, path.as_ref(), PendingAsset::Shader(handle.clone()), false);
+        self.watch_asset(path.as_ref());
+        handle
+    }
+
+    /// Reloads `handle`, a shader loaded outside `Assets` such as one of the renderer's,
+    /// whenever its file changes, like the shaders from `load_shader`.
+    pub(crate) fn watch_shader(&mut self, handle: &Handle<tvk::ShaderModule>) {
+        let Some(path) = handle.path() else {
+            return;
+        };
+        self.shaders.entry(cache_key(path)).or_insert_with(|| handle.clone());
+        self.watch_asset(path);
+    }
+
+    fn submit(&mut self, kind: AssetKind, path: &Path, asset: PendingAsset, reload: bool) {
+        let id = self.next_job;
+        self.next_job += 1;
+        self.pending.insert(id, PendingLoad { asset, reload });
+        self.pool.submit(id, kind, path.to_path_buf());
+    }
+
+    /// Watches every loaded file and reloads it in the background when it changes on
+    /// disk. The new data is swapped into the existing handles, so every instance group
+    /// sharing an asset picks it up.
+    pub fn enable_hot_reload(&mut self) -> AnyResult<()> {
+        if self.hot_reloader.is_some() {
+            return Ok(());
+        }
+
+        let mut hot_reloader = HotReloader::new()?;
+        let paths = self.meshes.values().filter_map(|h| h.path().map(Path::to_path_buf))
+            .chain(self.textures.values().filter_map(|h| h.path().map(Path::to_path_buf)))
+            .chain(self.linear_textures.values().filter_map(|h| h.path().map(Path::to_path_buf)))
+            .chain(self.shaders.values().filter_map(|h| h.path().map(Path::to_path_buf)))
+            .chain(self.watched_files.iter().cloned());
+        for path in paths {
+            if let Err(error) = hot_reloader.watch(&path) {
+                log::warn!("cannot watch {}: {}", path.display(), error);
+            }
+        }
+        self.hot_reloader = Some(hot_reloader);
+        Ok(())
+    }
+
+    /// Reports changes to a file that is not an asset, such as a scene file, through
+    /// `take_changed_files` once hot reloading is enabled.
+    pub fn watch(&mut self, path: &Path) {
+        if !self.watched_files.iter().any(|p| p == path) {
+            self.watched_files.push(path.to_path_buf());
+        }
+        self.watch_asset(path);
+    }
+
+    fn watch_asset(&mut self, path: &Path) {
+        if let Some(hot_reloader) = &mut self.hot_reloader
+            && let Err(error) = hot_reloader.watch(path) {
+            log::warn!("cannot watch {}: {}", path.display(), error);
+        }
+    }
+
+    /// Watched non-asset files that changed since the last call, as canonical paths.
+    pub fn take_changed_files(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.changed_files)
+    }
+
+    fn reload_changed(&mut self) {
+        let Some(hot_reloader) = &mut self.hot_reloader else {
+            return;
+        };
+
+        for path in hot_reloader.poll() {
+            if let Some(handle) = self.meshes.get(&path).cloned() {
+                log::info!("reloading mesh {}", path.display());
+                self.submit(AssetKind::Mesh, &path, PendingAsset::Mesh(handle), true);
+            } else if self.textures.contains_key(&path) || self.linear_textures.contains_key(&path) {
+                log::info!("reloading texture {}", path.display());
+                // A file may be loaded both as color and as data.
+                let textures = [(&self.textures, avk::Format::R8G8B8A8_SRGB), (&self.linear_textures, avk::Format::R8G8B8A8_UNORM)];
+                let reloads = textures.into_iter()
+                    .filter_map(|(textures, format)| Some(PendingAsset::Texture(textures.get(&path)?.clone(), format)))
+                    .collect::<Vec<_>>();
+                for reload in reloads {
+                    self.submit(AssetKind::Texture, &path, reload, true);
+                }
+            } else if let Some(handle) = self.shaders.get(&path).cloned() {
+                log::info!("reloading shader {}", path.display());
+                self.submit(AssetKind::Shader, &path, PendingAsset::Shader(handle), true);
+            } else {
+                self.changed_files.push(path);
+            }
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_empty() && self.uploads.is_empty()
+    }
+
+    /// Starts GPU uploads for files the workers finished decoding and publishes the
+    /// uploads whose transfer has completed. Call once per frame.
+    pub fn update(&mut self, context: &tvk::Context) -> AnyResult<()> {
+        self.reload_changed();
+
+        for (id, result) in std::mem::take(&mut self.decoded) {
+            self.finish_job(context, id, result);
+        }
+        while let Some((id, result)) = self.pool.try_recv() {
+            self.finish_job(context, id, result);
+        }
+
+        let mut index = 0;
+        while index < self.uploads.len() {
+            if self.uploads[index].fence.is_signaled()? {
+                let upload = self.uploads.swap_remove(index);
+                (upload.finish)();
+            } else {
+                index += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish_job(&mut self, context: &tvk::Context, id: u64, result: Result<LoadedAsset, String>) {
+        let Some(pending) = self.pending.remove(&id) else {
+            return;
+        };
+        if let Err(error) = self.start_upload(context, pending, result) {
+            log::error!("failed to load asset: {}", error);
+        }
+    }
+
+    fn start_upload(&mut self, context: &tvk::Context, pending: PendingLoad, result: Result<LoadedAsset, String>) -> AnyResult<()> {
+        let PendingLoad { asset: pending, reload } = pending;
+        let loaded = match result {
+            Ok(loaded) => loaded,
+            Err(error) if reload => return Err(format!("{} (keeping the previous version)", error).into()),
+            Err(error) => {
+                match &pending {
+                    PendingAsset::Mesh(handle) => handle.set_failed(error.clone()),
+                    PendingAsset::Texture(handle, _) => handle.set_failed(error.clone()),
+                    PendingAsset::Shader(handle) => handle.set_failed(error.clone()),
+                }
+                return Err(error.into());
+            }
+        };
+
+        match (pending, loaded) {
+            (PendingAsset::Mesh(handle), LoadedAsset::Mesh { vertices, indices }) => {
+                match upload_mesh(context, vertices, indices) {
+                    Ok((mut upload, mesh)) => {
+                        let target = handle.clone();
+                        upload.finish = Box::new(move || target.set_ready(mesh));
+                        self.uploads.push(upload);
+                    }
+                    Err(error) => {
+                        if !reload {
+                            handle.set_failed(error.to_string());
+                        }
+                        return Err(error);
+                    }
+                }
+            }
+            (PendingAsset::Texture(handle, format), LoadedAsset::Texture { width, height, pixels }) => {
+                match upload_texture(context, width, height, &pixels, format) {
+                    Ok((mut upload, texture)) => {
+                        let target = handle.clone();
+                        upload.finish = Box::new(move || target.set_ready(texture));
+                        self.uploads.push(upload);
+                    }
+                    Err(error) => {
+                        if !reload {
+                            handle.set_failed(error.to_string());
+                        }
+                        return Err(error);
+                    }
+                }
+            }
+            (PendingAsset::Shader(handle), LoadedAsset::Shader { code }) => {
+                match tvk::ShaderModule::from_code(context.logical_device.clone(), &code) {
+                    Ok(module) => handle.set_ready(module),
+                    Err(error) => {
+                        if !reload {
+                            handle.set_failed(error.to_string());
+                        }
+                        return Err(error);
+                    }
+                }
+            }
+            _ => return Err(String::from("asset loader returned data of the wrong kind").into()),
+        }
+        Ok(())
+    }
+
+    /// Drops cached assets that no handle outside `Assets` refers to any more.
+    pub fn collect_unused(&mut self, context: &tvk::Context) -> AnyResult<()> {
+        context.logical_device.device_wait_idle()?;
+        self.meshes.retain(|_, h| h.handle_count() > 1 || !is_settled(h));
+        self.textures.retain(|_, h| h.handle_count() > 1 || !is_settled(h));
+        self.linear_textures.retain(|_, h| h.handle_count() > 1 || !is_settled(h));
+        self.shaders.retain(|_, h| h.handle_count() > 1 || !is_settled(h));
+        Ok(())
+    }
+}
+
+impl Drop for Assets {
+    fn drop(&mut self) {
+        for upload in self.uploads.iter() {
+            let _ = upload.fence.wait(u64::MAX);
+        }
+    }
+}
+
+fn is_settled<T>(handle: &Handle<T>) -> bool {
+    handle.state() != LoadState::Loading
+}
+
+fn cache_key(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn begin_transfer(context: &tvk::Context) -> AnyResult<tvk::CommandBuffer> {
+    let command_buffer = context.allocate_command_buffers(avk::CommandBufferLevel::PRIMARY, tvk::QueueType::Transfer, 1)?.remove(0);
+    command_buffer.begin(avk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?;
+    Ok(command_buffer)
+}
+
+fn submit_transfer(context: &tvk::Context, command_buffer: tvk::CommandBuffer, staging: Vec<tvk::Buffer>) -> AnyResult<Upload> {
+    command_buffer.end()?;
+    let fence = context.create_fence(false)?;
+    let command_buffers = [command_buffer.inner];
+    let submits = [avk::SubmitInfo::default().command_buffers(&command_buffers)];
+    context.queues.get(&tvk::QueueType::Transfer).unwrap().submit(&submits, fence.inner)?;
+
+    Ok(Upload {
+        fence,
+        finish: Box::new(|| {}),
+        _command_buffer: command_buffer,
+        _staging: staging,
+    })
+}
+
+fn create_staging_buffer<T: Copy>(context: &tvk::Context, data: &[T]) -> AnyResult<tvk::Buffer> {
+    let mut buffer = context.create_buffer(
+        avk::BufferUsageFlags::TRANSFER_SRC,
+        MemoryLocation::CpuToGpu,
+        size_of_val(data) as u64
+    )?;
+    buffer.copy_memory(data)?;
+    Ok(buffer)
+}
+
+fn upload_mesh(context: &tvk::Context, vertices: Vec<tvk::Vertex>, indices: Vec<u32>) -> AnyResult<(Upload, Mesh<tvk::Vertex>)> {
+    let vertex_staging = create_staging_buffer(context, &vertices)?;
+    let index_staging = create_staging_buffer(context, &indices)?;
+    let vertex_buffer = context.create_transfer_buffer(
+        avk::BufferUsageFlags::VERTEX_BUFFER | avk::BufferUsageFlags::TRANSFER_DST,
+        MemoryLocation::GpuOnly,
+        vertex_staging.size
+    )?;
+    let index_buffer = context.create_transfer_buffer(
+        avk::BufferUsageFlags::INDEX_BUFFER | avk::BufferUsageFlags::TRANSFER_DST,
+        MemoryLocation::GpuOnly,
+        index_staging.size
+    )?;
+
+    let command_buffer = begin_transfer(context)?;
+    command_buffer.copy_buffer(&vertex_staging, &vertex_buffer);
+    command_buffer.copy_buffer(&index_staging, &index_buffer);
+    let upload = submit_transfer(context, command_buffer, vec![vertex_staging, index_staging])?;
+
+    Ok((upload, Mesh {
+        vertices,
+        indices,
+        vertex_buffer,
+        index_buffer,
+        source: None,
+        bounds: Default::default(),
+        unindexed_buffer: Default::default(),
+    }))
+}
+
+fn upload_texture(context: &tvk::Context, width: u32, height: u32, pixels: &[u8], format: avk::Format) -> AnyResult<(Upload, Texture)> {
+    let staging = create_staging_buffer(context, pixels)?;
+    let image = context.create_transfer_image(
+        avk::Extent2D { width, height },
+        format,
+        avk::ImageUsageFlags::SAMPLED | avk::ImageUsageFlags::TRANSFER_DST
+    )?;
+
+    let command_buffer = begin_transfer(context)?;
+    command_buffer.transition_image_layout(
+        &image,
+        avk::ImageLayout::UNDEFINED,
+        avk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        (avk::PipelineStageFlags::TOP_OF_PIPE, avk::AccessFlags::empty()),
+        (avk::PipelineStageFlags::TRANSFER, avk::AccessFlags::TRANSFER_WRITE),
+    );
+    command_buffer.copy_buffer_to_image(&staging, &image, avk::ImageLayout::TRANSFER_DST_OPTIMAL);
+    command_buffer.transition_image_layout(
+        &image,
+        avk::ImageLayout::TRANSFER_DST_OPTIMAL,
+        avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        (avk::PipelineStageFlags::TRANSFER, avk::AccessFlags::TRANSFER_WRITE),
+        (avk::PipelineStageFlags::BOTTOM_OF_PIPE, avk::AccessFlags::empty()),
+    );
+    let upload = submit_transfer(context, command_buffer, vec![staging])?;
+
+    let image_view = context.create_image_view(&image, format, avk::ImageAspectFlags::COLOR)?;
+    let sampler = context.create_sampler(avk::Filter::LINEAR, avk::SamplerAddressMode::REPEAT)?;
+    Ok((upload, Texture {
+        image_view,
+        sampler,
+        image,
+    }))
+}
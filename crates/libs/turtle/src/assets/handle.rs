@@ -0,0 +1,127 @@
+use std::{path::{Path, PathBuf}, sync::{Arc, RwLock}};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LoadState {
+    Loading,
+    Ready,
+    Failed(String),
+}
+
+enum Slot<T> {
+    Loading,
+    Ready(Arc<T>),
+    Failed(String),
+}
+
+struct Entry<T> {
+    path: Option<PathBuf>,
+    slot: RwLock<Slot<T>>,
+    placeholder: Option<Arc<T>>,
+}
+
+/// Reference-counted handle to an asset that may still be loading.
+///
+/// Clones share the same slot, so replacing the value (a finished load or a reload)
+/// is seen by every holder.
+pub struct Handle<T> {
+    entry: Arc<Entry<T>>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            entry: self.entry.clone()
+        }
+    }
+}
+
+impl<T> Handle<T> {
+    /// Wraps an already loaded value that is not backed by a file.
+    pub fn ready(value: T) -> Self {
+        Self {
+            entry: Arc::new(Entry {
+                path: None,
+                slot: RwLock::new(Slot::Ready(Arc::new(value))),
+                placeholder: None,
+            })
+        }
+    }
+
+    pub(crate) fn loading(path: PathBuf, placeholder: Option<Arc<T>>) -> Self {
+        Self {
+            entry: Arc::new(Entry {
+                path: Some(path),
+                slot: RwLock::new(Slot::Loading),
+                placeholder,
+            })
+        }
+    }
+
+    /// Loading asset that is not backed by a file of its own, such as an image embedded
+    /// in a glTF file.
+    pub(crate) fn loading_embedded(placeholder: Option<Arc<T>>) -> Self {
+        Self {
+            entry: Arc::new(Entry {
+                path: None,
+                slot: RwLock::new(Slot::Loading),
+                placeholder,
+            })
+        }
+    }
+
+    /// Wraps a value that the caller keeps a reference to as well.
+    pub(crate) fn shared(value: Arc<T>) -> Self {
+        Self {
+            entry: Arc::new(Entry {
+                path: None,
+                slot: RwLock::new(Slot::Ready(value)),
+                placeholder: None,
+            })
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.entry.path.as_deref()
+    }
+
+    pub fn state(&self) -> LoadState {
+        match &*self.entry.slot.read().unwrap() {
+            Slot::Loading => LoadState::Loading,
+            Slot::Ready(_) => LoadState::Ready,
+            Slot::Failed(error) => LoadState::Failed(error.clone()),
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        matches!(&*self.entry.slot.read().unwrap(), Slot::Ready(_))
+    }
+
+    /// The loaded value, or the placeholder while loading or after a failure.
+    pub fn get(&self) -> Option<Arc<T>> {
+        match &*self.entry.slot.read().unwrap() {
+            Slot::Ready(value) => Some(value.clone()),
+            _ => self.entry.placeholder.clone(),
+        }
+    }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.entry, &other.entry)
+    }
+
+    /// Number of handles sharing this asset, including the one held by `Assets`.
+    pub fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.entry)
+    }
+
+    pub(crate) fn set_ready(&self, value: T) {
+        *self.entry.slot.write().unwrap() = Slot::Ready(Arc::new(value));
+    }
+
+    pub(crate) fn set_shared(&self, value: Arc<T>) {
+        *self.entry.slot.write().unwrap() = Slot::Ready(value);
+    }
+
+    pub(crate) fn set_failed(&self, error: String) {
+        *self.entry.slot.write().unwrap() = Slot::Failed(error);
+    }
+}
@@ -0,0 +1,78 @@
+use std::{collections::{HashMap, HashSet}, path::{Path, PathBuf}, sync::mpsc, time::{Duration, Instant}};
+
+use notify::Watcher;
+use crate::*;
+
+/// Editors often save in several steps, so a file is only reported once it has been
+/// quiet for this long.
+const SETTLE_TIME: Duration = Duration::from_millis(150);
+
+/// Watches the directories of loaded files and reports files that changed on disk.
+pub struct HotReloader {
+    watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    watched_directories: HashSet<PathBuf>,
+    watched_files: HashSet<PathBuf>,
+    changed: HashMap<PathBuf, Instant>,
+}
+
+impl HotReloader {
+    pub fn new() -> AnyResult<Self> {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })?;
+
+        Ok(Self {
+            watcher,
+            events,
+            watched_directories: HashSet::new(),
+            watched_files: HashSet::new(),
+            changed: HashMap::new(),
+        })
+    }
+
+    /// Starts reporting changes to `path`. The parent directory is watched rather than
+    /// the file itself so that editors replacing the file through a rename are noticed.
+    pub fn watch(&mut self, path: &Path) -> AnyResult<()> {
+        let path = std::fs::canonicalize(path)?;
+        if let Some(directory) = path.parent()
+            && self.watched_directories.insert(directory.to_path_buf()) {
+            self.watcher.watch(directory, notify::RecursiveMode::NonRecursive)?;
+        }
+        self.watched_files.insert(path);
+        Ok(())
+    }
+
+    /// Returns the watched files whose last change settled since the previous call.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        while let Ok(event) = self.events.try_recv() {
+            let event = match event {
+                Ok(event) => event,
+                Err(error) => {
+                    log::warn!("file watcher error: {}", error);
+                    continue;
+                }
+            };
+            if !(event.kind.is_modify() || event.kind.is_create()) {
+                continue;
+            }
+            for path in event.paths {
+                let path = std::fs::canonicalize(&path).unwrap_or(path);
+                if self.watched_files.contains(&path) {
+                    self.changed.insert(path, Instant::now());
+                }
+            }
+        }
+
+        let now = Instant::now();
+        let settled = self.changed.iter()
+            .filter(|(_, time)| now.duration_since(**time) >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        for path in settled.iter() {
+            self.changed.remove(path);
+        }
+        settled
+    }
+}
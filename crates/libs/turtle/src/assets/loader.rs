@@ -0,0 +1,254 @@
+use std::{path::{Path, PathBuf}, sync::{mpsc, Arc, Mutex}, thread::JoinHandle};
+
+use crate::*;
+
+/// CPU-side result of decoding an asset file, ready to be uploaded to the GPU.
+pub enum LoadedAsset {
+    Mesh {
+        vertices: Vec<tvk::Vertex>,
+        indices: Vec<u32>,
+    },
+    Texture {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    },
+    Shader {
+        code: Vec<u32>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    Mesh,
+    Texture,
+    Shader,
+}
+
+pub fn load_mesh_file(path: &Path) -> AnyResult<(Vec<tvk::Vertex>, Vec<u32>)> {
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("obj") => {
+            let options = tobj::LoadOptions {
+                single_index: true,
+                triangulate: true,
+                ..Default::default()
+            };
+            let (models, _) = tobj::load_obj(path, &options)?;
+            let mut vertices = Vec::new();
+            let mut indices = Vec::new();
+            for model in models.iter() {
+                let mesh = &model.mesh;
+                let first = vertices.len();
+                vertices.extend(mesh.positions.chunks_exact(3).map(|p| tvk::Vertex::new(glam::vec3(p[0], p[1], p[2]))));
+                // OBJ texture coordinates start at the bottom.
+                for (vertex, uv) in vertices[first..].iter_mut().zip(mesh.texcoords.chunks_exact(2)) {
+                    vertex.uv = glam::vec2(uv[0], 1.0 - uv[1]);
+                }
+                let added = &mut vertices[first..];
+                if mesh.normals.len() == mesh.positions.len() {
+                    for (vertex, n) in added.iter_mut().zip(mesh.normals.chunks_exact(3)) {
+                        vertex.normal = glam::vec3(n[0], n[1], n[2]);
+                    }
+                } else {
+                    generate_normals(added, &mesh.indices);
+                }
+                generate_tangents(added, &mesh.indices);
+                indices.extend(mesh.indices.iter().map(|&i| i + first as u32));
+            }
+            if indices.is_empty() {
+                return Err(format!("{} contains no triangles", path.display()).into());
+            }
+            Ok((vertices, indices))
+        }
+        Some("gltf") | Some("glb") => {
+            let gltf = gltf::Gltf::open(path)?;
+            let buffers = gltf::import_buffers(&gltf.document, path.parent(), gltf.blob.clone())?;
+            let mut vertices = Vec::new();
+            let mut indices = Vec::new();
+            for primitive in gltf.document.meshes().flat_map(|mesh| mesh.primitives()) {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    continue;
+                }
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                let Some(positions) = reader.read_positions() else {
+                    continue;
+                };
+                let base = vertices.len() as u32;
+                vertices.extend(positions.map(|p| tvk::Vertex::new(glam::Vec3::from(p))));
+                let added = &mut vertices[base as usize..];
+                let primitive_indices = match reader.read_indices() {
+                    Some(read) => read.into_u32().collect(),
+                    None => (0..added.len() as u32).collect::<Vec<_>>(),
+                };
+                if let Some(uvs) = reader.read_tex_coords(0) {
+                    for (vertex, uv) in added.iter_mut().zip(uvs.into_f32()) {
+                        vertex.uv = glam::Vec2::from(uv);
+                    }
+                }
+                match reader.read_normals() {
+                    Some(normals) => for (vertex, normal) in added.iter_mut().zip(normals) {
+                        vertex.normal = glam::Vec3::from(normal);
+                    },
+                    None => generate_normals(added, &primitive_indices),
+                }
+                match reader.read_tangents() {
+                    Some(tangents) => for (vertex, tangent) in added.iter_mut().zip(tangents) {
+                        vertex.tangent = glam::Vec4::from(tangent);
+                    },
+                    None => generate_tangents(added, &primitive_indices),
+                }
+                indices.extend(primitive_indices.iter().map(|i| base + i));
+            }
+            if indices.is_empty() {
+                return Err(format!("{} contains no triangles", path.display()).into());
+            }
+            Ok((vertices, indices))
+        }
+        _ => Err(format!("unsupported mesh format: {}", path.display()).into()),
+    }
+}
+
+pub fn load_texture_file(path: &Path) -> AnyResult<(u32, u32, Vec<u8>)> {
+    let image = image::open(path)?.into_rgba8();
+    Ok((image.width(), image.height(), image.into_raw()))
+}
+
+/// Decodes an image of a glTF document into RGBA texels, whether it is a file next to
+/// the document, a data URI or a buffer view.
+pub fn load_gltf_image(source: gltf::image::Source, directory: &Path, buffers: &[gltf::buffer::Data]) -> AnyResult<(u32, u32, Vec<u8>)> {
+    let data = gltf::image::Data::from_source(source, Some(directory), buffers)?;
+    let pixels = match data.format {
+        gltf::image::Format::R8G8B8A8 => data.pixels,
+        gltf::image::Format::R8G8B8 => data.pixels.chunks_exact(3).flat_map(|t| [t[0], t[1], t[2], 255]).collect(),
+        // Grayscale, with alpha in the second channel.
+        gltf::image::Format::R8G8 => data.pixels.chunks_exact(2).flat_map(|t| [t[0], t[0], t[0], t[1]]).collect(),
+        gltf::image::Format::R8 => data.pixels.iter().flat_map(|&t| [t, t, t, 255]).collect(),
+        format => return Err(format!("unsupported image format {:?}", format).into()),
+    };
+    Ok((data.width, data.height, pixels))
+}
+
+/// Decodes a Radiance HDR image into linear RGBA texels.
+pub fn load_hdr_file(path: &Path) -> AnyResult<(u32, u32, Vec<f32>)> {
+    let image = image::open(path)?.into_rgba32f();
+    Ok((image.width(), image.height(), image.into_raw()))
+}
+
+pub fn load_shader_file(path: &Path) -> AnyResult<Vec<u32>> {
+    let mut file = std::fs::File::open(path)?;
+    Ok(ash::util::read_spv(&mut file)?)
+}
+
+pub fn load_asset(kind: AssetKind, path: &Path) -> AnyResult<LoadedAsset> {
+    Ok(match kind {
+        AssetKind::Mesh => {
+            let (vertices, indices) = load_mesh_file(path)?;
+            LoadedAsset::Mesh { vertices, indices }
+        }
+        AssetKind::Texture => {
+            let (width, height, pixels) = load_texture_file(path)?;
+            LoadedAsset::Texture { width, height, pixels }
+        }
+        AssetKind::Shader => LoadedAsset::Shader { code: load_shader_file(path)? },
+    })
+}
+
+struct Job {
+    id: u64,
+    kind: AssetKind,
+    path: PathBuf,
+}
+
+pub(crate) type JobResult = (u64, Result<LoadedAsset, String>);
+
+/// Fixed set of threads decoding asset files off the main thread.
+pub(crate) struct WorkerPool {
+    jobs: Option<mpsc::Sender<Job>>,
+    results: mpsc::Receiver<JobResult>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub fn new(count: usize) -> Self {
+        let (job_sender, job_receiver) = mpsc::channel::<Job>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..count).map(|i| {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            std::thread::Builder::new()
+                .name(format!("turtle-assets-{}", i))
+                .spawn(move || loop {
+                    let job = job_receiver.lock().unwrap().recv();
+                    let Ok(job) = job else {
+                        break;
+                    };
+                    let result = load_asset(job.kind, &job.path).map_err(|e| format!("{}: {}", job.path.display(), e));
+                    if result_sender.send((job.id, result)).is_err() {
+                        break;
+                    }
+                })
+                .expect("failed to spawn asset worker")
+        }).collect();
+
+        Self {
+            jobs: Some(job_sender),
+            results,
+            workers,
+        }
+    }
+
+    pub fn submit(&self, id: u64, kind: AssetKind, path: PathBuf) {
+        self.jobs.as_ref().unwrap().send(Job { id, kind, path }).unwrap();
+    }
+
+    pub fn try_recv(&self) -> Option<JobResult> {
+        self.results.try_recv().ok()
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        self.jobs.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2×1 RGB PNG, red then blue.
+    const PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAIAAAABCAIAAAB7QOjdAAAADUlEQVR4nGP4zwAE/wEHAAH/4iOeWQAAAABJRU5ErkJggg==";
+    const RED_BLUE: [u8; 8] = [255, 0, 0, 255, 0, 0, 255, 255];
+
+    fn load_images(json: &str) -> Vec<(u32, u32, Vec<u8>)> {
+        let gltf = gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+        let buffers = gltf::import_buffers(&gltf.document, Some(Path::new("")), None).unwrap();
+        gltf.document.images().map(|image| load_gltf_image(image.source(), Path::new(""), &buffers).unwrap()).collect()
+    }
+
+    #[test]
+    fn gltf_images_load_from_data_uris() {
+        let json = format!(r#"{{
+            "asset": {{ "version": "2.0" }},
+            "images": [{{ "uri": "data:image/png;base64,{}" }}]
+        }}"#, PNG);
+        assert_eq!(load_images(&json), vec![(2, 1, RED_BLUE.to_vec())]);
+    }
+
+    #[test]
+    fn gltf_images_load_from_buffer_views() {
+        let json = format!(r#"{{
+            "asset": {{ "version": "2.0" }},
+            "buffers": [{{ "byteLength": 70, "uri": "data:application/octet-stream;base64,{}" }}],
+            "bufferViews": [{{ "buffer": 0, "byteLength": 70 }}],
+            "images": [{{ "bufferView": 0, "mimeType": "image/png" }}]
+        }}"#, PNG);
+        assert_eq!(load_images(&json), vec![(2, 1, RED_BLUE.to_vec())]);
+    }
+}
@@ -1,6 +1,20 @@
-use glam::{Mat4, Vec3};
+use ash::vk as avk;
+use glam::{Mat4, Vec2, Vec3, Vec3Swizzles, Vec4Swizzles};
 use crate::*;
 
+pub mod projection;
+pub use projection::*;
+pub mod controller;
+pub use controller::*;
+pub mod free_fly;
+pub use free_fly::*;
+pub mod orbit;
+pub use orbit::*;
+pub mod arcball;
+pub use arcball::*;
+pub mod follow;
+pub use follow::*;
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub struct Matrix {
@@ -8,71 +22,108 @@ pub struct Matrix {
     pub proj: Mat4,
 }
 
+/// Camera pose and projection. Movement is left to a `CameraController`.
 pub struct Camera {
     pub position: Vec3,
-    pub projection: Mat4,
-    pub fov: f32,
+    pub projection: Projection,
+    /// Width over height of the view, kept in sync with the swapchain.
+    pub aspect_ratio: f32,
     pub yaw: f32,
     pub pitch: f32,
+    /// Up direction of the view. Stays `Vec3::Y` except for controllers that roll.
+    pub up: Vec3,
 }
 
 impl Default for Camera {
     fn default() -> Self {
         Self {
             position: Vec3::new(0.0, 0.0, -5.0),
-            projection: glam::Mat4::perspective_infinite_rh(f32::to_radians(45.0), 1.0, 0.1),
-            fov: 45.0,
+            projection: Projection::default(),
+            aspect_ratio: 1.0,
             yaw: 180.0,
             pitch: 0.0,
+            up: Vec3::Y,
         }
     }
 }
 
 impl Camera {
-    pub fn update(&mut self, swapchain: &tvk::Swapchain, input_manager: &InputManager) {
-        let aspect_ratio = swapchain.extent.width as f32 / swapchain.extent.height as f32;
-        self.projection = glam::Mat4::perspective_infinite_rh(f32::to_radians(self.fov), aspect_ratio, 0.1);
-        let sensitivity = 0.25;
-        let delta = input_manager.mouse().delta;
-        let (dx, dy) = (delta.0 * sensitivity, delta.1 * sensitivity);
-        self.yaw += dx;
-        self.pitch += dy;
-        self.pitch = self.pitch.clamp(-89.0, 89.0);
-
-        let yaw_rad = self.yaw.to_radians();
-        let speed = 0.25;
-        let keyboard = input_manager.keyboard();
-        let forward = Vec3::new(yaw_rad.sin(), 0.0, -yaw_rad.cos()).normalize();
-        let right = forward.cross(Vec3::Y).normalize();
-        if keyboard.is_pressed(KeyCode::KeyW) {
-            self.position += forward * speed;
-        }
-        if keyboard.is_pressed(KeyCode::KeyS) {
-            self.position -= forward * speed;
-        }
-        if keyboard.is_pressed(KeyCode::KeyA) {
-            self.position -= right * speed;
-        }
-        if keyboard.is_pressed(KeyCode::KeyD) {
-            self.position += right * speed;
-        }
-        if keyboard.is_pressed(KeyCode::Space) {
-            self.position -= Vec3::Y * speed;
-        }
-        if keyboard.is_pressed(KeyCode::ShiftLeft) {
-            self.position += Vec3::Y * speed;
-        }
-        
+    pub fn projection_matrix(&self) -> Mat4 {
+        self.projection.matrix(self.aspect_ratio)
     }
 
-    pub fn view_matrix(&self) -> Mat4 {
+    /// Direction the camera looks in.
+    pub fn forward(&self) -> Vec3 {
         let yaw_rad = self.yaw.to_radians();
         let pitch_rad = self.pitch.to_radians();
-        let front = Vec3::new(
+        Vec3::new(
             yaw_rad.sin() * pitch_rad.cos(),
             pitch_rad.sin(),
             -yaw_rad.cos() * pitch_rad.cos()
-        ).normalize();
-        Mat4::look_to_rh(self.position, front, Vec3::Y)
+        ).normalize()
+    }
+
+    /// Horizontal direction to the camera's right.
+    pub fn right(&self) -> Vec3 {
+        let yaw_rad = self.yaw.to_radians();
+        Vec3::new(yaw_rad.cos(), 0.0, yaw_rad.sin())
+    }
+
+    /// Points the camera along `direction`.
+    pub fn look_to(&mut self, direction: Vec3) {
+        let direction = direction.normalize();
+        self.pitch = direction.y.clamp(-1.0, 1.0).asin().to_degrees();
+        self.yaw = direction.x.atan2(-direction.z).to_degrees();
     }
-}
\ No newline at end of file
+
+    pub fn look_at(&mut self, target: Vec3) {
+        if target != self.position {
+            self.look_to(target - self.position);
+        }
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_to_rh(self.position, self.forward(), self.up)
+    }
+
+    pub fn view_projection_matrix(&self) -> Mat4 {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    /// World-space ray through a pixel, e.g. `MouseState::position`, of a view of size `extent`.
+    pub fn screen_to_ray(&self, position: Vec2, extent: avk::Extent2D) -> Ray {
+        let size = Vec2::new(extent.width as f32, extent.height as f32);
+        // The projection already flips Y, so screen and NDC Y both point down.
+        let ndc = position / size * 2.0 - Vec2::ONE;
+        let inverse = self.view_projection_matrix().inverse();
+        // Depth 0.5 stays finite even with an infinite far plane.
+        let near_depth = if self.projection.is_reverse_z() { 1.0 } else { 0.0 };
+        let near = inverse.project_point3(ndc.extend(near_depth));
+        let far = inverse.project_point3(ndc.extend(0.5));
+        Ray::new(near, far - near)
+    }
+
+    /// Pixel position of a world-space point in a view of size `extent`.
+    pub fn world_to_screen(&self, point: Vec3, extent: avk::Extent2D) -> ScreenPoint {
+        let clip = self.view_projection_matrix() * point.extend(1.0);
+        let behind = self.view_matrix().transform_point3(point).z > 0.0;
+        let w = if clip.w.abs() < f32::EPSILON { f32::EPSILON.copysign(clip.w) } else { clip.w };
+        let ndc = clip.xyz() / w;
+        let size = Vec2::new(extent.width as f32, extent.height as f32);
+        ScreenPoint {
+            position: (ndc.xy() + Vec2::ONE) * 0.5 * size,
+            depth: ndc.z,
+            behind,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenPoint {
+    /// Pixel coordinates with the origin at the top left. Meaningless if `behind` is set.
+    pub position: Vec2,
+    /// Depth buffer value the point would be drawn with.
+    pub depth: f32,
+    /// The point is behind the camera.
+    pub behind: bool,
+}
@@ -0,0 +1,83 @@
+use glam::{Mat4, Quat, Vec3};
+use winit::event::MouseButton;
+use crate::*;
+
+#[derive(Clone, Debug)]
+pub struct ArcballConfig {
+    /// Degrees of rotation per unit of mouse motion.
+    pub sensitivity: f32,
+    /// Button that must be held to rotate. `None` rotates on any mouse motion.
+    pub rotate_button: Option<MouseButton>,
+    /// Fraction of the distance zoomed per scroll notch.
+    pub zoom_sensitivity: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl Default for ArcballConfig {
+    fn default() -> Self {
+        Self {
+            sensitivity: 0.25,
+            rotate_button: Some(MouseButton::Left),
+            zoom_sensitivity: 0.1,
+            min_distance: 0.5,
+            max_distance: 1000.0,
+        }
+    }
+}
+
+/// Rotates the view around a target like a trackball. Unlike `OrbitController`
+/// there is no fixed up axis, so the camera can roll over the poles.
+pub struct ArcballController {
+    pub config: ArcballConfig,
+    pub target: Vec3,
+    pub distance: f32,
+    rotation: Quat,
+}
+
+impl ArcballController {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        Self {
+            config: ArcballConfig::default(),
+            target,
+            distance,
+            rotation: Quat::IDENTITY,
+        }
+    }
+
+    pub fn with_config(mut self, config: ArcballConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Orientation of the camera; it looks along `rotation * -Z`.
+    pub fn rotation(&self) -> Quat {
+        self.rotation
+    }
+}
+
+impl CameraController for ArcballController {
+    fn activate(&mut self, camera: &mut Camera) {
+        // Keep viewing the target from the camera's side, at the configured distance.
+        camera.look_at(self.target);
+        let view = Mat4::look_to_rh(camera.position, camera.forward(), camera.up);
+        self.rotation = Quat::from_mat4(&view.inverse()).normalize();
+    }
+
+    fn update(&mut self, camera: &mut Camera, input: &ControllerInput) {
+        let config = &self.config;
+        let (dx, dy) = drag_delta(input.input_manager, config.rotate_button);
+        // Dragging turns the ball under the cursor, so the camera moves the other way.
+        let up = self.rotation * Vec3::Y;
+        let right = self.rotation * Vec3::X;
+        let turn = Quat::from_axis_angle(up, (-dx * config.sensitivity).to_radians())
+            * Quat::from_axis_angle(right, (-dy * config.sensitivity).to_radians());
+        self.rotation = (turn * self.rotation).normalize();
+        self.distance = zoom(self.distance, input.input_manager, config.zoom_sensitivity, (config.min_distance, config.max_distance));
+
+        let forward = self.rotation * Vec3::NEG_Z;
+        camera.look_to(forward);
+        camera.up = self.rotation * Vec3::Y;
+        camera.position = self.target - forward * self.distance;
+    }
+}
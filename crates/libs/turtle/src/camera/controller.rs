@@ -0,0 +1,137 @@
+use winit::event::MouseButton;
+use crate::*;
+
+/// Everything a controller may read while updating the camera.
+pub struct ControllerInput<'a> {
+    pub input_manager: &'a InputManager,
+    pub scene: &'a Scene,
+    /// Real time since the previous frame, unaffected by pausing or time scale.
+    pub delta_seconds: f32,
+}
+
+/// Drives a `Camera` from user input. Install one with `AppData::set_camera_controller`.
+pub trait CameraController {
+    /// Called when the controller takes over `camera`, so it can pick up from the
+    /// current view instead of jumping.
+    fn activate(&mut self, _camera: &mut Camera) {}
+
+    fn update(&mut self, camera: &mut Camera, input: &ControllerInput);
+}
+
+impl AppData {
+    pub fn set_camera_controller<C: CameraController + 'static>(&mut self, mut controller: C) {
+        controller.activate(&mut self.camera);
+        self.camera_controller = Box::new(controller);
+    }
+}
+
+/// Mouse motion for this frame, or zero if `button` is set and not held.
+pub(crate) fn drag_delta(input_manager: &InputManager, button: Option<MouseButton>) -> (f32, f32) {
+    let mouse = input_manager.mouse();
+    match button {
+        Some(button) if !mouse.is_pressed(button) => (0.0, 0.0),
+        _ => mouse.delta,
+    }
+}
+
+/// Scales `distance` by the scroll wheel, so each notch zooms by the same ratio.
+pub(crate) fn zoom(distance: f32, input_manager: &InputManager, sensitivity: f32, range: (f32, f32)) -> f32 {
+    let scroll = input_manager.mouse().scroll;
+    (distance * (-scroll * sensitivity).exp()).clamp(range.0, range.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+    use winit::event::DeviceEvent;
+    use super::*;
+
+    fn dragged(delta: (f64, f64)) -> InputManager {
+        let mut input_manager = InputManager::default();
+        input_manager.handle_device_event(&DeviceEvent::MouseMotion { delta });
+        input_manager
+    }
+
+    fn update(controller: &mut impl CameraController, camera: &mut Camera, input_manager: &InputManager, scene: &Scene) {
+        controller.update(camera, &ControllerInput { input_manager, scene, delta_seconds: 0.1 });
+    }
+
+    fn assert_near(actual: Vec3, expected: Vec3) {
+        assert!(actual.abs_diff_eq(expected, 1e-3), "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn orbit_keeps_the_target_centered_and_stops_short_of_the_poles() {
+        let target = Vec3::new(1.0, 2.0, 3.0);
+        let mut camera = Camera::default();
+        let mut controller = OrbitController::new(target, 4.0).with_config(OrbitConfig { rotate_button: None, ..Default::default() });
+        controller.activate(&mut camera);
+
+        let scene = Scene::new();
+        for delta in [(40.0, 0.0), (0.0, 10_000.0), (-25.0, -30.0)] {
+            update(&mut controller, &mut camera, &dragged(delta), &scene);
+            assert!((camera.position.distance(target) - 4.0).abs() < 1e-3);
+            assert_near(camera.forward(), (target - camera.position).normalize());
+            assert!(camera.pitch.abs() <= 89.0);
+        }
+    }
+
+    #[test]
+    fn orbit_pitches_up_with_the_mouse_unless_inverted() {
+        for (invert_y, looks_up) in [(false, true), (true, false)] {
+            let mut camera = Camera::default();
+            let config = OrbitConfig { rotate_button: None, invert_y, ..Default::default() };
+            let mut controller = OrbitController::new(Vec3::ZERO, 4.0).with_config(config);
+            controller.activate(&mut camera);
+            let pitch = camera.pitch;
+            update(&mut controller, &mut camera, &dragged((0.0, -10.0)), &Scene::new());
+            assert_eq!(camera.pitch > pitch, looks_up);
+        }
+    }
+
+    #[test]
+    fn arcball_rolls_over_the_poles() {
+        let mut camera = Camera::default();
+        let mut controller = ArcballController::new(Vec3::ZERO, 5.0).with_config(ArcballConfig { rotate_button: None, ..Default::default() });
+        controller.activate(&mut camera);
+
+        // Half a turn around the camera's right axis ends up behind the target, upside down.
+        let half_turn = 180.0 / controller.config.sensitivity as f64;
+        update(&mut controller, &mut camera, &dragged((0.0, half_turn)), &Scene::new());
+        assert_near(camera.position, Vec3::new(0.0, 0.0, 5.0));
+        assert_near(camera.forward(), Vec3::NEG_Z);
+        assert_near(camera.up, Vec3::NEG_Y);
+        assert!((controller.rotation().length() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn follow_trails_the_node_in_its_own_frame() {
+        let mut scene = Scene::new();
+        let transform = Transform {
+            translation: Vec3::new(10.0, 0.0, 0.0),
+            rotation: Quat::from_rotation_y(90f32.to_radians()),
+            ..Default::default()
+        };
+        let node = scene.add_node("target", None, transform);
+        scene.update(&mut []);
+
+        let input_manager = InputManager::default();
+        let snap = |rotate_with_target| {
+            let mut camera = Camera::default();
+            let config = FollowConfig { rotate_with_target, smoothing: f32::INFINITY, ..Default::default() };
+            update(&mut FollowController::new(node).with_config(config), &mut camera, &input_manager, &scene);
+            camera
+        };
+        let camera = snap(true);
+        assert_near(camera.position, Vec3::new(16.0, 2.0, 0.0));
+        assert_near(camera.forward(), (transform.translation - camera.position).normalize());
+        assert_near(snap(false).position, Vec3::new(10.0, 2.0, 6.0));
+
+        // Smoothed, each update closes the same fraction of the remaining distance.
+        let mut camera = Camera::default();
+        let start = camera.position;
+        update(&mut FollowController::new(node), &mut camera, &input_manager, &scene);
+        let t = 1.0 - (-FollowConfig::default().smoothing * 0.1).exp();
+        assert_near(camera.position, start.lerp(Vec3::new(16.0, 2.0, 0.0), t));
+    }
+}
@@ -0,0 +1,69 @@
+use glam::Vec3;
+use crate::*;
+
+#[derive(Clone, Debug)]
+pub struct FollowConfig {
+    /// Camera position relative to the followed node.
+    pub offset: Vec3,
+    /// Point the camera looks at, relative to the followed node.
+    pub look_offset: Vec3,
+    /// Whether `offset` and `look_offset` turn with the node or stay in world space.
+    pub rotate_with_target: bool,
+    /// How quickly the camera catches up with the node, per second.
+    /// `f32::INFINITY` snaps to it every frame.
+    pub smoothing: f32,
+}
+
+impl Default for FollowConfig {
+    fn default() -> Self {
+        Self {
+            offset: Vec3::new(0.0, 2.0, 6.0),
+            look_offset: Vec3::ZERO,
+            rotate_with_target: true,
+            smoothing: 5.0,
+        }
+    }
+}
+
+/// Third-person camera trailing a scene node.
+pub struct FollowController {
+    pub config: FollowConfig,
+    pub target: NodeId,
+}
+
+impl FollowController {
+    pub fn new(target: NodeId) -> Self {
+        Self {
+            config: FollowConfig::default(),
+            target,
+        }
+    }
+
+    pub fn with_config(mut self, config: FollowConfig) -> Self {
+        self.config = config;
+        self
+    }
+}
+
+impl CameraController for FollowController {
+    fn update(&mut self, camera: &mut Camera, input: &ControllerInput) {
+        let Some(node) = input.scene.get(self.target) else {
+            return;
+        };
+        let config = &self.config;
+        let (_, rotation, translation) = node.world_matrix().to_scale_rotation_translation();
+        let (offset, look_offset) = if config.rotate_with_target {
+            (rotation * config.offset, rotation * config.look_offset)
+        } else {
+            (config.offset, config.look_offset)
+        };
+
+        let desired = translation + offset;
+        let t = 1.0 - (-config.smoothing * input.delta_seconds).exp();
+        if t.is_finite() {
+            camera.position = camera.position.lerp(desired, t);
+        }
+        camera.up = Vec3::Y;
+        camera.look_at(translation + look_offset);
+    }
+}
@@ -0,0 +1,184 @@
+use glam::Vec3;
+use winit::keyboard::KeyCode;
+use crate::*;
+
+#[derive(Clone, Debug)]
+pub struct FreeFlyBindings {
+    pub forward: KeyCode,
+    pub back: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub up: KeyCode,
+    pub down: KeyCode,
+}
+
+impl Default for FreeFlyBindings {
+    fn default() -> Self {
+        Self {
+            forward: KeyCode::KeyW,
+            back: KeyCode::KeyS,
+            left: KeyCode::KeyA,
+            right: KeyCode::KeyD,
+            up: KeyCode::Space,
+            down: KeyCode::ShiftLeft,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FreeFlyConfig {
+    /// Top speed in units per second.
+    pub speed: f32,
+    /// Degrees of rotation per unit of mouse motion.
+    pub sensitivity: f32,
+    /// Moving the mouse up looks up unless inverted.
+    pub invert_y: bool,
+    /// How quickly the velocity reaches `speed` while a key is held, per second.
+    /// `f32::INFINITY` starts and stops instantly.
+    pub acceleration: f32,
+    /// How quickly the velocity falls to zero once the keys are released, per second.
+    pub damping: f32,
+    pub bindings: FreeFlyBindings,
+}
+
+impl Default for FreeFlyConfig {
+    fn default() -> Self {
+        Self {
+            speed: 15.0,
+            sensitivity: 0.25,
+            invert_y: false,
+            acceleration: 12.0,
+            damping: 8.0,
+            bindings: FreeFlyBindings::default(),
+        }
+    }
+}
+
+/// Mouse look with WASD movement on the horizontal plane and vertical movement on
+/// separate keys.
+#[derive(Default)]
+pub struct FreeFlyController {
+    pub config: FreeFlyConfig,
+    velocity: Vec3,
+}
+
+impl FreeFlyController {
+    pub fn new(config: FreeFlyConfig) -> Self {
+        Self {
+            config,
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    /// Eases the velocity towards full speed along `direction`, or towards rest when it
+    /// is zero, and returns the new velocity.
+    fn accelerate(&mut self, direction: Vec3, delta_seconds: f32) -> Vec3 {
+        let target = direction.normalize_or_zero() * self.config.speed;
+        let sharpness = if target == Vec3::ZERO { self.config.damping } else { self.config.acceleration };
+        self.velocity = self.velocity.lerp(target, 1.0 - (-sharpness * delta_seconds).exp());
+        self.velocity
+    }
+}
+
+impl CameraController for FreeFlyController {
+    fn activate(&mut self, camera: &mut Camera) {
+        camera.up = Vec3::Y;
+        self.velocity = Vec3::ZERO;
+    }
+
+    fn update(&mut self, camera: &mut Camera, input: &ControllerInput) {
+        let (input_manager, delta_seconds) = (input.input_manager, input.delta_seconds);
+        let config = &self.config;
+        let (dx, dy) = input_manager.mouse().delta;
+        let dy = if config.invert_y { dy } else { -dy };
+        camera.yaw += dx * config.sensitivity;
+        camera.pitch = (camera.pitch + dy * config.sensitivity).clamp(-89.0, 89.0);
+
+        let keyboard = input_manager.keyboard();
+        let bindings = &config.bindings;
+        let axis = |positive: KeyCode, negative: KeyCode| {
+            keyboard.is_pressed(positive) as i32 as f32 - keyboard.is_pressed(negative) as i32 as f32
+        };
+        let right = camera.right();
+        let forward = Vec3::Y.cross(right);
+        let direction = forward * axis(bindings.forward, bindings.back)
+            + right * axis(bindings.right, bindings.left)
+            + Vec3::Y * axis(bindings.up, bindings.down);
+
+        if delta_seconds <= 0.0 {
+            return;
+        }
+        camera.position += self.accelerate(direction, delta_seconds) * delta_seconds;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::DeviceEvent;
+
+    fn run(controller: &mut FreeFlyController, direction: Vec3, seconds: f32, fps: u32) {
+        for _ in 0..(seconds * fps as f32).round() as u32 {
+            controller.accelerate(direction, 1.0 / fps as f32);
+        }
+    }
+
+    #[test]
+    fn velocity_does_not_depend_on_the_frame_rate() {
+        let (mut slow, mut fast) = (FreeFlyController::default(), FreeFlyController::default());
+        run(&mut slow, Vec3::X, 0.5, 30);
+        run(&mut fast, Vec3::X, 0.5, 240);
+        assert!(slow.velocity().abs_diff_eq(fast.velocity(), 1e-3), "{} != {}", slow.velocity(), fast.velocity());
+
+        let config = FreeFlyConfig::default();
+        let expected = config.speed * (1.0 - (-config.acceleration * 0.5).exp());
+        assert!((slow.velocity().x - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn velocity_is_capped_at_speed_along_diagonals() {
+        let mut controller = FreeFlyController::default();
+        run(&mut controller, Vec3::new(1.0, 1.0, 1.0), 5.0, 60);
+        assert!((controller.velocity().length() - controller.config.speed).abs() < 1e-3);
+    }
+
+    #[test]
+    fn damping_brings_the_camera_to_rest() {
+        let mut controller = FreeFlyController::default();
+        run(&mut controller, Vec3::Z, 5.0, 60);
+        let moving = controller.velocity().z;
+        run(&mut controller, Vec3::ZERO, 0.25, 60);
+        let expected = moving * (-controller.config.damping * 0.25).exp();
+        assert!((controller.velocity().z - expected).abs() < 1e-3);
+        run(&mut controller, Vec3::ZERO, 5.0, 60);
+        assert!(controller.velocity().length() < 1e-3);
+    }
+
+    #[test]
+    fn infinite_acceleration_starts_and_stops_instantly() {
+        let mut controller = FreeFlyController::new(FreeFlyConfig {
+            acceleration: f32::INFINITY,
+            damping: f32::INFINITY,
+            ..Default::default()
+        });
+        assert_eq!(controller.accelerate(Vec3::X, 1.0 / 60.0), Vec3::X * controller.config.speed);
+        assert_eq!(controller.accelerate(Vec3::ZERO, 1.0 / 60.0), Vec3::ZERO);
+    }
+
+    #[test]
+    fn moving_the_mouse_up_looks_up_unless_inverted() {
+        let mut input_manager = InputManager::default();
+        input_manager.handle_device_event(&DeviceEvent::MouseMotion { delta: (0.0, -10.0) });
+        for (invert_y, looks_up) in [(false, true), (true, false)] {
+            let mut camera = Camera::default();
+            let pitch = camera.pitch;
+            let mut controller = FreeFlyController::new(FreeFlyConfig { invert_y, ..Default::default() });
+            controller.update(&mut camera, &ControllerInput { input_manager: &input_manager, scene: &Scene::new(), delta_seconds: 1.0 / 60.0 });
+            assert_eq!(camera.pitch > pitch, looks_up);
+        }
+    }
+}
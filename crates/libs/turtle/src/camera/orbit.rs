@@ -0,0 +1,81 @@
+use glam::Vec3;
+use winit::event::MouseButton;
+use crate::*;
+
+#[derive(Clone, Debug)]
+pub struct OrbitConfig {
+    /// Degrees of rotation per unit of mouse motion.
+    pub sensitivity: f32,
+    /// Moving the mouse up pitches the camera up, like `FreeFlyConfig`, so it circles
+    /// below the target. Inverted, it circles above.
+    pub invert_y: bool,
+    /// Button that must be held to rotate. `None` rotates on any mouse motion.
+    pub rotate_button: Option<MouseButton>,
+    /// Fraction of the distance zoomed per scroll notch.
+    pub zoom_sensitivity: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl Default for OrbitConfig {
+    fn default() -> Self {
+        Self {
+            sensitivity: 0.25,
+            invert_y: false,
+            rotate_button: Some(MouseButton::Left),
+            zoom_sensitivity: 0.1,
+            min_distance: 0.5,
+            max_distance: 1000.0,
+        }
+    }
+}
+
+/// Circles a target point, keeping the world up axis vertical, and zooms with the
+/// scroll wheel.
+pub struct OrbitController {
+    pub config: OrbitConfig,
+    pub target: Vec3,
+    pub distance: f32,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl OrbitController {
+    pub fn new(target: Vec3, distance: f32) -> Self {
+        Self {
+            config: OrbitConfig::default(),
+            target,
+            distance,
+            yaw: 180.0,
+            pitch: 0.0,
+        }
+    }
+
+    pub fn with_config(mut self, config: OrbitConfig) -> Self {
+        self.config = config;
+        self
+    }
+}
+
+impl CameraController for OrbitController {
+    fn activate(&mut self, camera: &mut Camera) {
+        // Keep viewing the target from the camera's side, at the configured distance.
+        camera.look_at(self.target);
+        self.yaw = camera.yaw;
+        self.pitch = camera.pitch;
+    }
+
+    fn update(&mut self, camera: &mut Camera, input: &ControllerInput) {
+        let config = &self.config;
+        let (dx, dy) = drag_delta(input.input_manager, config.rotate_button);
+        let dy = if config.invert_y { dy } else { -dy };
+        self.yaw += dx * config.sensitivity;
+        self.pitch = (self.pitch + dy * config.sensitivity).clamp(-89.0, 89.0);
+        self.distance = zoom(self.distance, input.input_manager, config.zoom_sensitivity, (config.min_distance, config.max_distance));
+
+        camera.yaw = self.yaw;
+        camera.pitch = self.pitch;
+        camera.up = Vec3::Y;
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+}
@@ -0,0 +1,155 @@
+use glam::Mat4;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Projection {
+    Perspective {
+        /// Vertical field of view in degrees.
+        fov: f32,
+        near: f32,
+        /// `None` pushes the far plane to infinity.
+        far: Option<f32>,
+        /// Maps the near plane to depth 1 and the far plane to 0, which spreads depth
+        /// precision far more evenly with a floating point depth buffer.
+        reverse_z: bool,
+    },
+    Orthographic {
+        /// World units visible from the bottom to the top of the view at zoom 1.
+        height: f32,
+        zoom: f32,
+        near: f32,
+        far: f32,
+        reverse_z: bool,
+    },
+    /// Used as-is, so it must already target Vulkan's clip space (Y down, depth 0..1).
+    Custom {
+        matrix: Mat4,
+        /// Whether `matrix` maps the near plane to depth 1 and the far plane to 0.
+        reverse_z: bool,
+    },
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self::perspective(45.0)
+    }
+}
+
+impl Projection {
+    pub fn perspective(fov: f32) -> Self {
+        Self::Perspective {
+            fov,
+            near: 0.1,
+            far: None,
+            reverse_z: false,
+        }
+    }
+
+    pub fn orthographic(height: f32) -> Self {
+        Self::Orthographic {
+            height,
+            zoom: 1.0,
+            near: -1000.0,
+            far: 1000.0,
+            reverse_z: false,
+        }
+    }
+
+    /// Whether depth decreases with distance, which needs a `GREATER` depth test and
+    /// a depth buffer cleared to 0.
+    pub fn is_reverse_z(&self) -> bool {
+        match *self {
+            Self::Perspective { reverse_z, .. } | Self::Orthographic { reverse_z, .. } | Self::Custom { reverse_z, .. } => reverse_z,
+        }
+    }
+
+    pub fn matrix(&self, aspect_ratio: f32) -> Mat4 {
+        let mut matrix = match *self {
+            Self::Perspective { fov, near, far, reverse_z } => {
+                let fov = fov.to_radians();
+                match (far, reverse_z) {
+                    (None, false) => Mat4::perspective_infinite_rh(fov, aspect_ratio, near),
+                    (None, true) => Mat4::perspective_infinite_reverse_rh(fov, aspect_ratio, near),
+                    (Some(far), false) => Mat4::perspective_rh(fov, aspect_ratio, near, far),
+                    (Some(far), true) => Mat4::perspective_rh(fov, aspect_ratio, far, near),
+                }
+            }
+            Self::Orthographic { height, zoom, near, far, reverse_z } => {
+                let half_height = height * 0.5 / zoom;
+                let half_width = half_height * aspect_ratio;
+                let (near, far) = if reverse_z { (far, near) } else { (near, far) };
+                Mat4::orthographic_rh(-half_width, half_width, -half_height, half_height, near, far)
+            }
+            Self::Custom { matrix, .. } => return matrix,
+        };
+        // Vulkan's clip space has Y pointing down; flip it to keep +Y up on screen.
+        matrix.y_axis.y *= -1.0;
+        matrix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use super::*;
+
+    fn depth(projection: Projection, distance: f32) -> f32 {
+        projection.matrix(1.5).project_point3(Vec3::new(0.0, 0.0, -distance)).z
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn perspective_maps_near_and_far_to_the_depth_range() {
+        let finite = |reverse_z| Projection::Perspective { fov: 60.0, near: 0.5, far: Some(100.0), reverse_z };
+        assert_near(depth(finite(false), 0.5), 0.0);
+        assert_near(depth(finite(false), 100.0), 1.0);
+        assert_near(depth(finite(true), 0.5), 1.0);
+        assert_near(depth(finite(true), 100.0), 0.0);
+
+        let infinite = |reverse_z| Projection::Perspective { fov: 60.0, near: 0.5, far: None, reverse_z };
+        assert_near(depth(infinite(false), 0.5), 0.0);
+        assert!(depth(infinite(false), 1.0e6) > 0.999);
+        assert_near(depth(infinite(true), 0.5), 1.0);
+        assert!(depth(infinite(true), 1.0e6) < 0.001);
+    }
+
+    #[test]
+    fn orthographic_zoom_narrows_the_view() {
+        let orthographic = |reverse_z| Projection::Orthographic { height: 8.0, zoom: 2.0, near: -10.0, far: 10.0, reverse_z };
+        let projection = orthographic(false);
+        let matrix = projection.matrix(2.0);
+        // Half the height is visible at zoom 2, and Y points down in clip space.
+        let corner = matrix.project_point3(Vec3::new(4.0, 2.0, 0.0));
+        assert_near(corner.x, 1.0);
+        assert_near(corner.y, -1.0);
+        assert_near(depth(projection, -10.0), 0.0);
+        assert_near(depth(projection, 10.0), 1.0);
+
+        let reversed = orthographic(true);
+        assert_near(depth(reversed, -10.0), 1.0);
+        assert_near(depth(reversed, 10.0), 0.0);
+    }
+
+    #[test]
+    fn up_is_flipped_for_vulkan_clip_space() {
+        for projection in [Projection::perspective(45.0), Projection::orthographic(2.0)] {
+            let up = projection.matrix(1.0).project_point3(Vec3::new(0.0, 0.5, -1.0));
+            assert!(up.y < 0.0);
+        }
+    }
+
+    #[test]
+    fn reverse_z_follows_the_projection() {
+        assert!(!Projection::perspective(45.0).is_reverse_z());
+        assert!(Projection::Perspective { fov: 45.0, near: 0.1, far: None, reverse_z: true }.is_reverse_z());
+        assert!(Projection::Orthographic { height: 1.0, zoom: 1.0, near: 0.0, far: 1.0, reverse_z: true }.is_reverse_z());
+
+        let matrix = Mat4::from_scale(Vec3::new(1.0, -1.0, 0.5));
+        assert_eq!(Projection::Custom { matrix, reverse_z: false }.matrix(3.0), matrix);
+        assert!(!Projection::Custom { matrix, reverse_z: false }.is_reverse_z());
+        assert!(Projection::Custom { matrix, reverse_z: true }.is_reverse_z());
+    }
+}
@@ -6,17 +6,28 @@ pub struct MouseState {
     pub position: (f32, f32),
     pub delta: (f32, f32),
     pub scroll: f32,
-    buttons: [bool; 3]
+    buttons: [bool; 3],
+    just_pressed: [bool; 3],
 }
 
 impl MouseState {
     pub fn process_button(&mut self, button: MouseButton, state: ElementState) {
         let pressed = matches!(state, ElementState::Pressed);
+        let Some(index) = Self::button_index(button) else {
+            return;
+        };
+        if pressed && !self.buttons[index] {
+            self.just_pressed[index] = true;
+        }
+        self.buttons[index] = pressed;
+    }
+
+    fn button_index(button: MouseButton) -> Option<usize> {
         match button {
-            MouseButton::Left => self.buttons[0] = pressed,
-            MouseButton::Right => self.buttons[1] = pressed,
-            MouseButton::Middle => self.buttons[2] = pressed,
-            _ => {}
+            MouseButton::Left => Some(0),
+            MouseButton::Right => Some(1),
+            MouseButton::Middle => Some(2),
+            _ => None
         }
     }
 
@@ -25,11 +36,12 @@ impl MouseState {
     }
 
     pub fn process_motion(&mut self, delta: &(f64, f64)) {
-        self.delta = (delta.0 as f32, delta.1 as f32);
+        self.delta.0 += delta.0 as f32;
+        self.delta.1 += delta.1 as f32;
     }
 
     pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
-        self.scroll = match delta {
+        self.scroll += match delta {
             MouseScrollDelta::LineDelta(_, y) => *y,
             MouseScrollDelta::PixelDelta(p) => p.y as f32,
         }
@@ -38,14 +50,14 @@ impl MouseState {
     pub fn update(&mut self) {
         self.delta = (0.0, 0.0);
         self.scroll = 0.0;
+        self.just_pressed = [false; 3];
     }
 
     pub fn is_pressed(&self, button: MouseButton) -> bool {
-        match button {
-            MouseButton::Left => self.buttons[0],
-            MouseButton::Right => self.buttons[1],
-            MouseButton::Middle => self.buttons[2],
-            _ => false
-        }
+        Self::button_index(button).is_some_and(|i| self.buttons[i])
+    }
+
+    pub fn just_pressed(&self, button: MouseButton) -> bool {
+        Self::button_index(button).is_some_and(|i| self.just_pressed[i])
     }
 }
\ No newline at end of file
@@ -5,22 +5,44 @@ pub mod input_manager;
 pub use input_manager::*;
 pub mod camera;
 pub use camera::*;
+pub mod scene;
+pub use scene::*;
+pub mod assets;
+pub use assets::*;
+pub mod time;
+pub use time::*;
+pub mod picking;
+pub use picking::*;
 
 use winit::{application::ApplicationHandler, event::WindowEvent, keyboard::KeyCode, window::{CursorGrabMode, Window}};
 pub type AnyResult<T> = Result<T, Box<dyn std::error::Error>>;
+pub type AppCallback<'a> = Box<dyn FnMut(&mut AppData) + 'a>;
 
 #[derive(Default)]
 pub struct TurtleApp<'a> {
-    pub init: Option<Box<dyn Fn(&mut AppData) + 'a>>,
+    pub init: Option<AppCallback<'a>>,
+    pub update: Option<AppCallback<'a>>,
+    pub fixed_update: Option<AppCallback<'a>>,
+    fixed_timestep: Option<std::time::Duration>,
     pub app_data: Option<AppData>
 }
 
 pub struct AppData {
     pub instance_groups: Vec<InstanceGroup>,
+    pub scene: Scene,
+    pub assets: Assets,
     pub renderer: Renderer,
     pub window: Window,
-    pub time: std::time::Instant,
+    pub scene_path: Option<std::path::PathBuf>,
+    pub time: Time,
     pub camera: Camera,
+    /// Part of the window the main camera draws into.
+    pub viewport: Viewport,
+    /// Further cameras drawn after the main one, e.g. split-screen or a minimap.
+    pub views: Vec<View>,
+    /// Render target textures drawn over the window after every view.
+    pub overlays: Vec<Overlay>,
+    pub camera_controller: Box<dyn CameraController>,
     pub input_manager: InputManager,
 }
 
@@ -30,6 +52,10 @@ impl AppData {
             .with_maximized(true);
         let window = event_loop.create_window(window_attributes)?;
         let renderer = Renderer::new(&window)?;
+        let mut assets = Assets::new(&renderer.context)?;
+        for shader in renderer.shaders.handles() {
+            assets.watch_shader(shader);
+        }
 
         window.set_cursor_visible(false);
         window.set_cursor_grab(CursorGrabMode::Locked).unwrap();
@@ -37,10 +63,17 @@ impl AppData {
         Ok(Self {
             window,
             renderer,
-            time: std::time::Instant::now(),
+            scene_path: None,
+            time: Time::default(),
             input_manager: InputManager::default(),
             camera: Camera::default(),
-            instance_groups: Vec::new()
+            viewport: Viewport::FULL,
+            views: Vec::new(),
+            overlays: Vec::new(),
+            camera_controller: Box::new(FreeFlyController::default()),
+            instance_groups: Vec::new(),
+            scene: Scene::new(),
+            assets
         })
     }
 }
@@ -49,13 +82,72 @@ impl<'a> TurtleApp<'a> {
     pub fn new() -> Self {
         Self {
             app_data: None,
-            init: None
+            init: None,
+            update: None,
+            fixed_update: None,
+            fixed_timestep: None
         }
     }
 
-    pub fn set_init_function<F>(&mut self, handle: F) where F: Fn(&mut AppData) + 'a {
+    pub fn set_init_function<F>(&mut self, handle: F) where F: FnMut(&mut AppData) + 'a {
         self.init = Some(Box::new(handle));
     }
+
+    /// Called once per frame, after the fixed updates, with `AppData::time` advanced.
+    pub fn set_update_function<F>(&mut self, handle: F) where F: FnMut(&mut AppData) + 'a {
+        self.update = Some(Box::new(handle));
+    }
+
+    /// Called zero or more times per frame, every `timestep` of scaled time. Use
+    /// `Time::alpha` in the regular update to interpolate between fixed steps.
+    pub fn set_fixed_update_function<F>(&mut self, timestep: std::time::Duration, handle: F) where F: FnMut(&mut AppData) + 'a {
+        self.fixed_update = Some(Box::new(handle));
+        self.fixed_timestep = Some(timestep);
+    }
+
+    /// Advances `AppData::time` and runs the fixed and regular updates of the frame
+    /// about to be drawn.
+    fn run_updates(&mut self) {
+        if let Some(app_data) = &mut self.app_data {
+            app_data.time.tick();
+            if let Some(handle) = &mut self.fixed_update {
+                for _ in 0..app_data.time.take_fixed_steps() {
+                    handle(app_data);
+                }
+            }
+
+            // The camera is driven by real time so it keeps moving while the game is paused.
+            let input = ControllerInput {
+                input_manager: &app_data.input_manager,
+                scene: &app_data.scene,
+                delta_seconds: app_data.time.unscaled_delta().as_secs_f32(),
+            };
+            app_data.camera_controller.update(&mut app_data.camera, &input);
+            let extent = app_data.renderer.swapchain.extent;
+            app_data.camera.aspect_ratio = app_data.viewport.aspect_ratio(extent);
+            for view in app_data.views.iter_mut() {
+                let extent = view.target
+                    .and_then(|target| app_data.renderer.render_target(target))
+                    .map_or(extent, RenderTarget::extent);
+                view.camera.aspect_ratio = view.viewport.aspect_ratio(extent);
+            }
+            if let Some(handle) = &mut self.update {
+                handle(app_data);
+            }
+            
+            // F1 cycles the view modes.
+            if app_data.input_manager.keyboard().just_pressed(KeyCode::F1) {
+                app_data.renderer.view_mode = app_data.renderer.view_mode.next();
+            }
+
+            if app_data.input_manager.keyboard().just_pressed(KeyCode::Escape) {
+                app_data.window.set_cursor_visible(true);
+                app_data.window.set_cursor_grab(CursorGrabMode::None).unwrap();
+            }
+            
+            app_data.input_manager.update();
+        }
+    }
 }
 
 impl<'a> ApplicationHandler for TurtleApp<'a> {
@@ -64,8 +156,12 @@ impl<'a> ApplicationHandler for TurtleApp<'a> {
             self.app_data = Some(AppData::new(event_loop).unwrap());
         }
 
-        if let Some(handle) = &self.init {
-            handle(self.app_data.as_mut().unwrap());
+        let app_data = self.app_data.as_mut().unwrap();
+        if let Some(timestep) = self.fixed_timestep.take() {
+            app_data.time.set_fixed_delta(timestep);
+        }
+        if let Some(handle) = &mut self.init {
+            handle(app_data);
         }
 
         self.app_data.as_ref().unwrap().window.request_redraw();
@@ -89,8 +185,15 @@ impl<'a> ApplicationHandler for TurtleApp<'a> {
                 event_loop.exit();
             },
             WindowEvent::RedrawRequested => {
+                self.run_updates();
                 if let Some(app_data) = &mut self.app_data {
-                    if app_data.renderer.render(&app_data.camera, &app_data.instance_groups).unwrap() {
+                    app_data.assets.update(&app_data.renderer.context).unwrap();
+                    if let Err(error) = app_data.reload_changed_scene() {
+                        log::error!("failed to reload scene: {}", error);
+                    }
+                    app_data.scene.update(&mut app_data.instance_groups);
+                    let views = collect_render_views(&app_data.camera, app_data.viewport, &app_data.views);
+                    if app_data.renderer.render(&views, &app_data.overlays, &mut app_data.instance_groups).unwrap() {
                         app_data.renderer.recreate_swapchain(&app_data.window).unwrap();
                     }
                     app_data.window.request_redraw();
@@ -110,17 +213,4 @@ impl<'a> ApplicationHandler for TurtleApp<'a> {
             app_data.input_manager.handle_device_event(&event);
         }
     }
-
-    fn about_to_wait(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
-        if let Some(app_data) = &mut self.app_data {
-            app_data.camera.update(&app_data.renderer.swapchain, &app_data.input_manager);
-            
-            if app_data.input_manager.keyboard().just_pressed(KeyCode::Escape) {
-                app_data.window.set_cursor_visible(true);
-                app_data.window.set_cursor_grab(CursorGrabMode::None).unwrap();
-            }
-            
-            app_data.input_manager.update();
-        }
-    }
-}
\ No newline at end of file
+}
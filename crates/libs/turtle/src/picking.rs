@@ -0,0 +1,243 @@
+use glam::{Mat4, Vec3};
+use crate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Unit length for rays built by `Camera::screen_to_ray`, so hit distances are in
+    /// world units.
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// Transforms the ray without renormalizing, so distances along the result match
+    /// distances along `self`.
+    pub fn transform(&self, matrix: Mat4) -> Self {
+        Self {
+            origin: matrix.transform_point3(self.origin),
+            direction: matrix.transform_vector3(self.direction),
+        }
+    }
+
+    /// Distance to the nearest intersection with the box, or zero if the origin is inside.
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let (mut near, mut far) = (0.0_f32, f32::INFINITY);
+        for axis in 0..3 {
+            let (origin, direction) = (self.origin[axis], self.direction[axis]);
+            // Parallel to the slab, the ray stays inside it or never enters. Dividing
+            // would give NaN for an origin on one of its planes.
+            if direction == 0.0 {
+                if origin < aabb.min[axis] || origin > aabb.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let t1 = (aabb.min[axis] - origin) / direction;
+            let t2 = (aabb.max[axis] - origin) / direction;
+            near = near.max(t1.min(t2));
+            far = far.min(t1.max(t2));
+        }
+        (near <= far).then_some(near)
+    }
+
+    /// Möller–Trumbore intersection with a triangle, hitting both faces.
+    pub fn intersect_triangle(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = self.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let inverse = 1.0 / determinant;
+        let s = self.origin - a;
+        let u = s.dot(p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(edge1);
+        let v = self.direction.dot(q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let distance = edge2.dot(q) * inverse;
+        (distance >= 0.0).then_some(distance)
+    }
+}
+
+/// Axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        points.into_iter().fold(Self {
+            min: Vec3::splat(f32::INFINITY),
+            max: Vec3::splat(f32::NEG_INFINITY),
+        }, |aabb, point| Self {
+            min: aabb.min.min(point),
+            max: aabb.max.max(point),
+        })
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    /// Smallest box containing this one after `matrix` is applied.
+    pub fn transform(&self, matrix: Mat4) -> Self {
+        Self::from_points((0..8).map(|i| {
+            matrix.transform_point3(Vec3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            ))
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PickMode {
+    /// Test only the mesh bounds of each instance. Fast but coarse.
+    Bounds,
+    /// Test the bounds first, then every triangle of the mesh.
+    #[default]
+    Triangles,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub group: usize,
+    pub instance: usize,
+    pub distance: f32,
+    pub point: Vec3,
+}
+
+impl InstanceGroup {
+    /// Nearest visible instance hit by `ray`, with the hit distance along it.
+    pub fn raycast(&self, ray: &Ray, mode: PickMode) -> Option<(usize, f32)> {
+        let mesh = self.mesh.get()?;
+        let bounds = mesh.bounds();
+        let mut nearest: Option<(usize, f32)> = None;
+        for &instance in self.visible_indices() {
+            let local = ray.transform(self.instances()[instance].model.inverse());
+            let Some(bounds_distance) = local.intersect_aabb(&bounds) else {
+                continue;
+            };
+            if nearest.is_some_and(|(_, d)| bounds_distance >= d) {
+                continue;
+            }
+
+            let distance = match mode {
+                PickMode::Bounds => Some(bounds_distance),
+                PickMode::Triangles => mesh.indices.chunks_exact(3).filter_map(|triangle| {
+                    let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize].position);
+                    local.intersect_triangle(a, b, c)
+                }).min_by(f32::total_cmp),
+            };
+            if let Some(distance) = distance
+                && nearest.is_none_or(|(_, d)| distance < d) {
+                nearest = Some((instance, distance));
+            }
+        }
+        nearest
+    }
+}
+
+/// Nearest visible instance across all groups hit by `ray`.
+pub fn raycast(instance_groups: &[InstanceGroup], ray: &Ray, mode: PickMode) -> Option<RayHit> {
+    instance_groups.iter().enumerate()
+        .filter_map(|(group, g)| g.raycast(ray, mode).map(|(instance, distance)| RayHit {
+            group,
+            instance,
+            distance,
+            point: ray.at(distance),
+        }))
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+impl AppData {
+    /// Ray through the cursor from the camera of the view under it.
+    pub fn cursor_ray(&self) -> Option<Ray> {
+        let (x, y) = self.input_manager.mouse().position;
+        let cursor = glam::vec2(x, y);
+        let (camera, rect) = self.view_at(cursor)?;
+        let offset = glam::vec2(rect.offset.x as f32, rect.offset.y as f32);
+        Some(camera.screen_to_ray(cursor - offset, rect.extent))
+    }
+
+    /// Instance under the cursor, if any.
+    pub fn pick(&self, mode: PickMode) -> Option<RayHit> {
+        raycast(&self.instance_groups, &self.cursor_ray()?, mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb { min: Vec3::splat(-1.0), max: Vec3::ONE }
+    }
+
+    #[test]
+    fn axis_aligned_rays_hit_boxes() {
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::NEG_Z);
+        assert_eq!(ray.intersect_aabb(&unit_box()), Some(4.0));
+        // On the plane of two faces, where the zero direction components used to give NaN.
+        let ray = Ray::new(Vec3::new(1.0, -1.0, 5.0), Vec3::NEG_Z);
+        assert_eq!(ray.intersect_aabb(&unit_box()), Some(4.0));
+    }
+
+    #[test]
+    fn rays_from_inside_boxes_hit_at_zero() {
+        let ray = Ray::new(Vec3::new(0.5, 0.0, 0.0), Vec3::new(1.0, 2.0, 0.0));
+        assert_eq!(ray.intersect_aabb(&unit_box()), Some(0.0));
+        let ray = Ray::new(Vec3::ZERO, Vec3::X);
+        assert_eq!(ray.intersect_aabb(&unit_box()), Some(0.0));
+    }
+
+    #[test]
+    fn rays_miss_boxes_beside_or_behind_them() {
+        // Parallel to a face, just outside it.
+        let ray = Ray::new(Vec3::new(1.5, 0.0, 5.0), Vec3::NEG_Z);
+        assert_eq!(ray.intersect_aabb(&unit_box()), None);
+        // Pointing away from the box.
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::Z);
+        assert_eq!(ray.intersect_aabb(&unit_box()), None);
+        // Diagonal, passing the corner.
+        let ray = Ray::new(Vec3::new(3.0, 0.0, 0.0), Vec3::new(-1.0, 2.0, 0.0));
+        assert_eq!(ray.intersect_aabb(&unit_box()), None);
+    }
+
+    #[test]
+    fn triangles_are_hit_from_both_sides() {
+        let (a, b, c) = (Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        // Counter-clockwise seen from +Z, so this ray sees the front face and the other the back.
+        let front = Ray::new(Vec3::new(0.0, 0.0, 2.0), Vec3::NEG_Z);
+        let back = Ray::new(Vec3::new(0.0, 0.0, -3.0), Vec3::Z);
+        assert_eq!(front.intersect_triangle(a, b, c), Some(2.0));
+        assert_eq!(back.intersect_triangle(a, b, c), Some(3.0));
+        let beside = Ray::new(Vec3::new(2.0, 0.0, 2.0), Vec3::NEG_Z);
+        assert_eq!(beside.intersect_triangle(a, b, c), None);
+    }
+}
@@ -1,4 +1,4 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{any::Any, collections::HashMap, path::PathBuf, sync::Arc};
 use winit::window::Window;
 
 use ash::vk as avk;
@@ -10,122 +10,783 @@ pub use mesh::*;
 pub mod instance_group;
 pub use instance_group::*;
 
-const MAX_FRAMES_IN_FLIGHT: usize = 2;
+pub mod texture;
+pub use texture::*;
 
-pub struct Renderer {
-    pub frame_buffers: Vec<tvk::FrameBuffer>,
+pub mod shaders;
+pub(crate) use shaders::*;
+
+pub mod object_id;
+pub use object_id::*;
+
+pub mod view;
+pub use view::*;
+
+pub mod render_target;
+pub use render_target::*;
+
+pub mod render_graph;
+pub use render_graph::*;
+
+pub mod post_process;
+pub use post_process::*;
+
+pub mod light;
+pub use light::*;
+
+pub mod shadow;
+pub use shadow::*;
+
+pub mod clusters;
+pub use clusters::*;
+
+pub mod material;
+pub use material::*;
+
+pub mod environment;
+pub use environment::*;
+
+pub mod background;
+pub use background::*;
+
+pub mod transparency;
+pub use transparency::*;
+
+pub mod debug_draw;
+pub use debug_draw::*;
+
+pub mod view_mode;
+pub use view_mode::*;
+
+pub mod text;
+pub use text::*;
+
+pub(crate) const MAX_FRAMES_IN_FLIGHT: usize = 2;
+/// Vertex shaders of the scene pipelines, then their fragment shaders in every view mode.
+const SCENE_SHADERS: [&str; 4] = ["shader.vert.spv", "compact_instance.vert.spv", "shader.frag.spv", "view_mode.frag.spv"];
+/// Samples per pixel of the window views unless `set_msaa_samples` says otherwise.
+const DEFAULT_MSAA_SAMPLES: u32 = 4;
+
+pub(crate) fn shader_directory() -> PathBuf {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let workspace_root = manifest_dir
+        .parent()
+        .and_then(|p| p.parent())
+        .and_then(|p| p.parent())
+    .unwrap();
+    workspace_root.join("assets/generated/shaders")
+}
+
+pub(crate) fn vertex_fragment_shaders<'a>(vertex: &'a tvk::ShaderModule, fragment: &'a tvk::ShaderModule) -> [tvk::PipelineShaderCreateInfo<'a>; 2] {
+    [
+        tvk::PipelineShaderCreateInfo {
+            stage: avk::ShaderStageFlags::VERTEX,
+            module: vertex
+        },
+        tvk::PipelineShaderCreateInfo {
+            stage: avk::ShaderStageFlags::FRAGMENT,
+            module: fragment
+        }
+    ]
+}
+
+/// Every pipeline the scene passes of one render pass draw with.
+pub(crate) struct ScenePipelines {
     pub pipeline: tvk::Pipeline,
+    pub compact_pipeline: tvk::Pipeline,
+    pub background: BackgroundPipelines,
+    pub transparency: TransparencyPipelines,
+    pub debug: DebugPipelines,
+    pub text: tvk::Pipeline,
+}
+
+/// Descriptor set layouts the scene pipelines are built against.
+struct SceneLayouts {
+    /// Camera matrices, then the lights and shadow map, then the material textures.
+    scene: [avk::DescriptorSetLayout; 3],
+    background: avk::DescriptorSetLayout,
+    composite: avk::DescriptorSetLayout,
+}
+
+impl ScenePipelines {
+    /// Builds the set for `render_pass`, whose color attachment has `format`.
+    fn build(
+        (context, shaders): (&tvk::Context, &Shaders),
+        (layouts, text_pass): (&SceneLayouts, &TextPass),
+        (render_pass, format): (&tvk::RenderPass, avk::Format),
+        (reverse_z, samples): (bool, avk::SampleCountFlags),
+        view_mode: ViewMode,
+    ) -> AnyResult<Self> {
+        let [vertex_source, compact_vertex_source] = shaders.get_all(["shader.vert.spv", "compact_instance.vert.spv"])?;
+        let state = tvk::PipelineState {
+            depth_compare_op: if reverse_z { avk::CompareOp::GREATER } else { avk::CompareOp::LESS },
+            samples,
+            // Material factors and the index of the view being drawn.
+            push_constant_size: size_of::<MaterialConstants>() as u32,
+            ..Default::default()
+        };
+        let (state, fragment_shader) = view_mode.pipeline_variant(context, state);
+        let fragment_source = shaders.get(fragment_shader)?;
+        let pipeline = context.create_pipeline::<tvk::FullInstanceData>(
+            render_pass,
+            &layouts.scene,
+            &vertex_fragment_shaders(&vertex_source, &fragment_source),
+            &state
+        )?;
+        let compact_pipeline = context.create_pipeline::<tvk::CompactInstanceData>(
+            render_pass,
+            &layouts.scene,
+            &vertex_fragment_shaders(&compact_vertex_source, &fragment_source),
+            &state
+        )?;
+
+        Ok(Self {
+            pipeline,
+            compact_pipeline,
+            background: BackgroundPipelines::new(context, shaders, render_pass, layouts.background, reverse_z, samples)?,
+            transparency: TransparencyPipelines::new(context, shaders, (render_pass, format), &layouts.scene, layouts.composite, reverse_z, samples)?,
+            debug: DebugPipelines::new(context, shaders, render_pass, layouts.scene[0], reverse_z, samples)?,
+            text: text_pass.create_world_pipeline(context, shaders, render_pass, reverse_z, samples)?,
+        })
+    }
+}
+
+/// Color and depth the scene passes of a set of views draw into.
+struct SceneTarget {
+    name: &'static str,
+    color: GraphImage,
+    depth: GraphImage,
+    /// Receives the final color when `color` is multisampled.
+    resolve: Option<GraphImage>,
+    clear_color: [f32; 4],
+    extent: avk::Extent2D,
+    samples: avk::SampleCountFlags,
+}
+
+pub struct Renderer {
+    /// Created by the first `request_object_id`.
+    pub object_id_pass: Option<ObjectIdPass>,
     pub descriptor: tvk::Descriptor,
     pub render_pass: tvk::RenderPass,
     pub command_buffers: Vec<tvk::CommandBuffer>,
     pub sync_objects: tvk::SyncObjects,
-    pub depth_buffer: tvk::DepthBuffer,
     pub swapchain: tvk::Swapchain,
     pub uniform_buffers: Vec<tvk::Buffer>,
     pub context: tvk::Context,
     pub frame_index: usize,
+    /// Linear HDR color the window is cleared to before the views are drawn.
+    pub clear_color: [f32; 4],
+    pub(crate) shaders: Shaders,
+    /// Drawn behind the geometry of every view without a clear color of its own.
+    pub background: Background,
+    pub post_process: PostProcess,
+    pub light: DirectionalLight,
+    /// `None` turns shadows off.
+    pub shadows: Option<Shadows>,
+    /// Point and spot lights, sorted into clusters of each view every frame.
+    pub lights: Vec<Light>,
+    /// Image-based lighting replacing the directional light's ambient term. See
+    /// `load_environment`.
+    pub environment: Option<Arc<Environment>>,
+    pub environment_intensity: f32,
+    /// Lines drawn over the scene of every view. Shapes added during an update show on
+    /// the frame rendered after it.
+    pub debug_draw: DebugDraw,
+    pub view_mode: ViewMode,
+    /// Text drawn over the window and as billboards in every view. Fonts come from
+    /// `load_font`.
+    pub text: TextDraw,
+    /// Whether the pipelines test depth for a reverse-Z projection. Follows the views'
+    /// cameras, which `render` requires to agree.
+    reverse_z: bool,
+    msaa_samples: avk::SampleCountFlags,
+    /// Pipelines of the window views.
+    scene_pipelines: ScenePipelines,
+    view_records: Vec<ViewRecord>,
+    /// Indexed by `RenderTargetId`. Destroyed targets leave `None` so ids stay unique.
+    render_targets: Vec<Option<Arc<RenderTarget>>>,
+    target_pipelines: HashMap<RenderTargetFormat, TargetPipelines>,
+    overlay_pass: OverlayPass,
+    overlay_records: Vec<OverlayRecord>,
+    /// Targets whose texture a material samples this frame.
+    sampled_targets: Vec<RenderTargetId>,
+    post_process_pass: PostProcessPass,
+    lighting: Lighting,
+    shadow_pass: ShadowPass,
+    materials: MaterialSets,
+    environment_baker: EnvironmentBaker,
+    /// Black environment bound while `environment` is `None`.
+    empty_environment: Arc<Environment>,
+    background_pass: BackgroundPass,
+    transparent_queue: TransparentQueue,
+    debug_pass: DebugPass,
+    fonts: Vec<Font>,
+    text_pass: TextPass,
+    /// View mode the scene pipelines were built for.
+    pipeline_view_mode: ViewMode,
+    graph_cache: RenderGraphCache,
+    /// Assets referenced by each frame in flight. Holding them here lets a reloaded
+    /// asset replace its handle's value while older frames still read the previous one.
+    retained_resources: Vec<Vec<Arc<dyn Any>>>,
 }
 
 impl Renderer {
     pub fn new(window: &Window) -> AnyResult<Self> {
         let context: tvk::Context = tvk::Context::new(window)?;
         let swapchain = context.create_swapchain(window)?;
-        let depth_buffer = context.create_depth_buffer(&swapchain)?;
-        let render_pass = context.create_render_pass(&swapchain)?;
-        let frame_buffers = context.create_frame_buffers(&swapchain, &render_pass, &depth_buffer.image_view)?;
-        
-        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-        let workspace_root = manifest_dir
-            .parent()
-            .and_then(|p| p.parent())
-            .and_then(|p| p.parent())
-        .unwrap();
-        let vertex_source = workspace_root.join("assets/generated/shaders/shader.vert.spv");
-        let fragment_source = workspace_root.join("assets/generated/shaders/shader.frag.spv");
+        let msaa_samples = supported_sample_count(&context, DEFAULT_MSAA_SAMPLES);
+        let render_pass = context.create_multisampled_render_pass(HDR_FORMAT, msaa_samples, avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)?;
+
+        let shaders = Shaders::load(&context)?;
         let mut descriptor = context.create_descriptor_dependecies(MAX_FRAMES_IN_FLIGHT as u32)?;
         descriptor.allocate_sets()?;
-        let pipeline = context.create_pipeline(
-            &render_pass,
-            descriptor.layout, 
-            &vec![
-                tvk::PipelineShaderCreateInfo {
-                    stage: avk::ShaderStageFlags::VERTEX,
-                    path: vertex_source.as_path()
-                },
-                tvk::PipelineShaderCreateInfo {
-                    stage: avk::ShaderStageFlags::FRAGMENT,
-                    path: fragment_source.as_path()
-                }
-            ]
+        let lighting = Lighting::new(&context, MAX_FRAMES_IN_FLIGHT)?;
+        let shadow_pass = ShadowPass::new(&context, &shaders, &descriptor)?;
+        let materials = MaterialSets::new(&context, MAX_FRAMES_IN_FLIGHT)?;
+        let environment_baker = EnvironmentBaker::new(&context, &shaders)?;
+        let empty_environment = Arc::new(environment_baker.bake(&context, 1, 1, &[0.0, 0.0, 0.0, 1.0])?);
+        let background_pass = BackgroundPass::new(&context, MAX_FRAMES_IN_FLIGHT)?;
+        let transparent_queue = TransparentQueue::new(&context, MAX_FRAMES_IN_FLIGHT)?;
+        let debug_pass = DebugPass::new(&context, MAX_FRAMES_IN_FLIGHT)?;
+        let overlay_render_pass = context.create_color_render_pass(swapchain.format, avk::ImageLayout::PRESENT_SRC_KHR)?;
+        let overlay_pass = OverlayPass::new(&context, &shaders, &overlay_render_pass, MAX_FRAMES_IN_FLIGHT)?;
+        let post_process_pass = PostProcessPass::new(&context, &shaders, swapchain.format, MAX_FRAMES_IN_FLIGHT)?;
+        let text_pass = TextPass::new(&context, &shaders, &overlay_render_pass, MAX_FRAMES_IN_FLIGHT)?;
+        let scene_layouts = SceneLayouts {
+            scene: [descriptor.layout, lighting.descriptor.layout, materials.descriptor.layout],
+            background: background_pass.descriptor.layout,
+            composite: transparent_queue.composite_descriptor.layout,
+        };
+        let scene_pipelines = ScenePipelines::build(
+            (&context, &shaders),
+            (&scene_layouts, &text_pass),
+            (&render_pass, HDR_FORMAT),
+            (false, msaa_samples),
+            ViewMode::Solid
         )?;
-        
+
         let sync_objects = context.create_sync_objects(swapchain.images.len(), MAX_FRAMES_IN_FLIGHT)?;
         let command_buffers = context.allocate_command_buffers(avk::CommandBufferLevel::PRIMARY, tvk::QueueType::Graphics, MAX_FRAMES_IN_FLIGHT as u32)?;
-        let uniform_buffers = (0..MAX_FRAMES_IN_FLIGHT).into_iter().map(|_| {
+        let uniform_buffers = (0..MAX_FRAMES_IN_FLIGHT).map(|_| {
             context.create_buffer(
                 avk::BufferUsageFlags::UNIFORM_BUFFER,
                 gpu_allocator::MemoryLocation::CpuToGpu,
                 size_of::<camera::Matrix>() as u64
             )
         }).collect::<AnyResult<Vec<_>>>()?;
-        descriptor.update(&uniform_buffers)?;
+        descriptor.update(&uniform_buffers, size_of::<camera::Matrix>() as u64)?;
 
         Ok(Self {
             frame_index: 0,
+            clear_color: [0.0, 0.0, 0.08, 1.0],
+            background: Background::default(),
+            post_process: PostProcess::default(),
+            light: DirectionalLight::default(),
+            shadows: Some(Shadows::default()),
+            lights: Vec::new(),
+            environment: None,
+            environment_intensity: 1.0,
+            debug_draw: DebugDraw::default(),
+            view_mode: ViewMode::Solid,
+            pipeline_view_mode: ViewMode::Solid,
+            text: TextDraw::default(),
+            fonts: Vec::new(),
+            text_pass,
+            reverse_z: false,
+            msaa_samples,
+            scene_pipelines,
+            view_records: Vec::new(),
+            render_targets: Vec::new(),
+            target_pipelines: HashMap::new(),
+            overlay_pass,
+            overlay_records: Vec::new(),
+            sampled_targets: Vec::new(),
+            post_process_pass,
+            lighting,
+            shadow_pass,
+            materials,
+            environment_baker,
+            empty_environment,
+            background_pass,
+            transparent_queue,
+            debug_pass,
+            graph_cache: RenderGraphCache::default(),
+            object_id_pass: None,
+            retained_resources: (0..MAX_FRAMES_IN_FLIGHT).map(|_| Vec::new()).collect(),
+            shaders,
             context,
             swapchain,
             render_pass,
-            frame_buffers,
-            pipeline,
             sync_objects,
             command_buffers,
             descriptor,
             uniform_buffers,
-            depth_buffer
         })
     }
 
+    /// Rebuilds the pipelines when the camera switches between regular and reverse-Z depth.
+    fn set_reverse_z(&mut self, reverse_z: bool) -> AnyResult<()> {
+        if self.reverse_z == reverse_z {
+            return Ok(());
+        }
+        self.rebuild_scene_pipelines(reverse_z, self.pipeline_view_mode)?;
+        if let Some(object_id_pass) = &mut self.object_id_pass {
+            object_id_pass.set_reverse_z(&self.context, &self.shaders, &self.descriptor, reverse_z)?;
+        }
+        self.reverse_z = reverse_z;
+        Ok(())
+    }
+
+    /// Rebuilds the scene pipelines when `view_mode` has changed since the last frame.
+    fn apply_view_mode(&mut self) -> AnyResult<()> {
+        if self.pipeline_view_mode == self.view_mode {
+            return Ok(());
+        }
+        self.rebuild_scene_pipelines(self.reverse_z, self.view_mode)?;
+        self.pipeline_view_mode = self.view_mode;
+        Ok(())
+    }
+
+    /// Replaces the scene pipelines of the window and of every render target format
+    /// once all of them have been built.
+    fn rebuild_scene_pipelines(&mut self, reverse_z: bool, view_mode: ViewMode) -> AnyResult<()> {
+        let window = self.create_scene_pipelines((&self.render_pass, HDR_FORMAT), (reverse_z, self.msaa_samples), view_mode)?;
+        let targets = self.target_pipelines.iter().map(|(&format, target_pipelines)| {
+            let scene_pass = (&target_pipelines.render_pass, format.vk_format());
+            Ok((format, self.create_scene_pipelines(scene_pass, (reverse_z, avk::SampleCountFlags::TYPE_1), view_mode)?))
+        }).collect::<AnyResult<Vec<_>>>()?;
+
+        self.context.logical_device.device_wait_idle()?;
+        self.scene_pipelines = window;
+        for (format, pipelines) in targets {
+            self.target_pipelines.get_mut(&format).unwrap().pipelines = pipelines;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the pipelines whose shaders hot reloading replaced since the last frame.
+    /// If any of them fails to build from the new shaders, every pipeline is kept as it
+    /// was.
+    fn reload_shaders(&mut self) -> AnyResult<()> {
+        let reloaded = self.shaders.take_reloaded();
+        if reloaded.is_empty() {
+            return Ok(());
+        }
+        match self.rebuild_pipelines(&reloaded) {
+            Ok(swaps) => {
+                self.context.logical_device.device_wait_idle()?;
+                for swap in swaps {
+                    swap(self);
+                }
+            },
+            Err(error) => log::error!("failed to rebuild pipelines with the reloaded shaders: {}", error),
+        }
+        Ok(())
+    }
+
+    /// Creates every pipeline set using one of the `reloaded` shaders, without replacing
+    /// anything yet.
+    fn rebuild_pipelines(&self, reloaded: &[String]) -> AnyResult<Vec<PipelineSwap<Self>>> {
+        let uses = |shaders: &[&str]| shaders.iter().any(|shader| reloaded.iter().any(|name| name == shader));
+        let mut swaps: Vec<PipelineSwap<Self>> = Vec::new();
+
+        let scene_shaders = [
+            &SCENE_SHADERS[..],
+            &BackgroundPipelines::SHADERS,
+            &TransparencyPipelines::SHADERS,
+            &DebugPipelines::SHADERS,
+            &TextPass::SHADERS,
+        ].concat();
+        if uses(&scene_shaders) {
+            let pipelines = self.create_scene_pipelines((&self.render_pass, HDR_FORMAT), (self.reverse_z, self.msaa_samples), self.pipeline_view_mode)?;
+            swaps.push(Box::new(move |renderer| renderer.scene_pipelines = pipelines));
+            for (&format, target_pipelines) in self.target_pipelines.iter() {
+                let scene_pass = (&target_pipelines.render_pass, format.vk_format());
+                let pipelines = self.create_scene_pipelines(scene_pass, (self.reverse_z, avk::SampleCountFlags::TYPE_1), self.pipeline_view_mode)?;
+                swaps.push(Box::new(move |renderer| renderer.target_pipelines.get_mut(&format).unwrap().pipelines = pipelines));
+            }
+        }
+        if uses(&TextPass::SHADERS) {
+            let screen_render_pass = self.context.create_color_render_pass(self.swapchain.format, avk::ImageLayout::PRESENT_SRC_KHR)?;
+            let swap = self.text_pass.reload_shaders(&self.context, &self.shaders, &screen_render_pass)?;
+            swaps.push(Box::new(move |renderer| swap(&mut renderer.text_pass)));
+        }
+        if uses(&ShadowPass::SHADERS) {
+            let swap = self.shadow_pass.reload_shaders(&self.context, &self.shaders, &self.descriptor)?;
+            swaps.push(Box::new(move |renderer| swap(&mut renderer.shadow_pass)));
+        }
+        if uses(&EnvironmentBaker::SHADERS) {
+            let swap = self.environment_baker.reload_shaders(&self.context, &self.shaders)?;
+            swaps.push(Box::new(move |renderer| swap(&mut renderer.environment_baker)));
+        }
+        if uses(&OverlayPass::SHADERS) {
+            let screen_render_pass = self.context.create_color_render_pass(self.swapchain.format, avk::ImageLayout::PRESENT_SRC_KHR)?;
+            let swap = self.overlay_pass.reload_shaders(&self.context, &self.shaders, &screen_render_pass)?;
+            swaps.push(Box::new(move |renderer| swap(&mut renderer.overlay_pass)));
+        }
+        if uses(&PostProcessPass::SHADERS) {
+            let swap = self.post_process_pass.reload_shaders(&self.context, &self.shaders, self.swapchain.format)?;
+            swaps.push(Box::new(move |renderer| swap(&mut renderer.post_process_pass)));
+        }
+        if let Some(object_id_pass) = self.object_id_pass.as_ref().filter(|_| uses(&ObjectIdPass::SHADERS)) {
+            let swap = object_id_pass.reload_shaders(&self.context, &self.shaders, &self.descriptor)?;
+            swaps.push(Box::new(move |renderer| swap(renderer.object_id_pass.as_mut().unwrap())));
+        }
+        Ok(swaps)
+    }
+
+    /// Builds the scene pipelines for `scene_pass`, a render pass and its color format.
+    fn create_scene_pipelines(
+        &self,
+        scene_pass: (&tvk::RenderPass, avk::Format),
+        (reverse_z, samples): (bool, avk::SampleCountFlags),
+        view_mode: ViewMode,
+    ) -> AnyResult<ScenePipelines> {
+        let layouts = SceneLayouts {
+            scene: [self.descriptor.layout, self.lighting.descriptor.layout, self.materials.descriptor.layout],
+            background: self.background_pass.descriptor.layout,
+            composite: self.transparent_queue.composite_descriptor.layout,
+        };
+        ScenePipelines::build((&self.context, &self.shaders), (&layouts, &self.text_pass), scene_pass, (reverse_z, samples), view_mode)
+    }
+
+    /// Samples per pixel of the window views.
+    pub fn msaa_samples(&self) -> u32 {
+        self.msaa_samples.as_raw()
+    }
+
+    /// Draws the window views with `samples` samples per pixel, resolved before
+    /// post-processing. Counts the device doesn't support are lowered to the nearest
+    /// one it does; 1 turns multisampling off.
+    pub fn set_msaa_samples(&mut self, samples: u32) -> AnyResult<()> {
+        let samples = supported_sample_count(&self.context, samples);
+        if samples == self.msaa_samples {
+            return Ok(());
+        }
+        let render_pass = self.context.create_multisampled_render_pass(HDR_FORMAT, samples, avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)?;
+        let pipelines = self.create_scene_pipelines((&render_pass, HDR_FORMAT), (self.reverse_z, samples), self.pipeline_view_mode)?;
+        self.context.logical_device.device_wait_idle()?;
+        self.render_pass = render_pass;
+        self.scene_pipelines = pipelines;
+        self.msaa_samples = samples;
+        Ok(())
+    }
+
     pub fn recreate_swapchain(&mut self, window: &Window) -> AnyResult<()> {
         self.context.logical_device.device_wait_idle()?;
-        self.frame_buffers.clear();
         self.swapchain.recreate(&self.context, window)?;
-        self.frame_buffers = self.context.create_frame_buffers(&self.swapchain, &self.render_pass, &self.depth_buffer.image_view)?;
+        if let Some(object_id_pass) = &mut self.object_id_pass {
+            object_id_pass.resize();
+        }
         Ok(())
     }
     
+    /// Records the frame as a render graph: the shadow cascades, the scene passes of
+    /// each render target drawn this frame, those of the window views into an HDR image,
+    /// post-processing into the swapchain image, the overlays, then the object id passes.
+    /// Returns what the graph needs kept alive until the frame has finished.
     pub fn record_command_buffer(
         &self,
         command_buffer: &tvk::CommandBuffer,
         instance_groups: &[InstanceGroup],
-        image_index: usize
-    ) -> AnyResult<()> {
+        image_index: usize,
+        graph_cache: &mut RenderGraphCache,
+    ) -> AnyResult<Vec<Arc<dyn Any>>> {
         command_buffer.begin(avk::CommandBufferUsageFlags::default())?;
-        let clear_values = [avk::ClearValue {
-            color: avk::ClearColorValue { float32: [0.0, 0.0, 0.08, 1.0] },
-        },
-        avk::ClearValue {
-            depth_stencil: avk::ClearDepthStencilValue { depth: 1.0, stencil: 0}
-        }]; 
-        command_buffer.begin_render_pass(
-            &self.swapchain, 
-            &self.render_pass, 
-            &self.frame_buffers[image_index], 
-            avk::SubpassContents::INLINE,
-            &clear_values
-        );
-        command_buffer.bind_pipeline(&self.pipeline);
-        command_buffer.set_scissor(self.swapchain.get_scissor());
-        command_buffer.set_viewport(self.swapchain.get_viewport());
-        command_buffer.bind_descriptor_sets(self.pipeline.layout, self.descriptor.sets[self.frame_index]);
-        for instance_group in instance_groups.iter() {
-            let buffers = [instance_group.mesh.vertex_buffer.inner, instance_group.instance_buffer.as_ref().unwrap().inner];
-            command_buffer.bind_vertex_buffers(&buffers);
-            command_buffer.bind_index_buffer(&instance_group.mesh.index_buffer);
-            command_buffer.draw_indexed(instance_group.mesh.indices.len() as u32, instance_group.visible_count as u32, 0, 0, 0);
-        }
-        command_buffer.end_render_pass();
+        // View modes other than solid show their colors as they are.
+        let unprocessed = PostProcess {
+            exposure: 1.0,
+            tonemapper: Tonemapper::None,
+            bloom: None,
+            color_grading_lut: None,
+        };
+        let post_process = if self.view_mode == ViewMode::Solid { &self.post_process } else { &unprocessed };
+        let lut = post_process.color_grading_lut.as_ref()
+            .and_then(Handle::get)
+            .filter(|lut| is_color_grading_lut(lut));
+        let mut graph = RenderGraph::new();
+        let depth_format = self.context.physical_device.depth_format;
+
+        let shadow_map = self.shadow_pass.add_passes(&mut graph, self.frame_index, self.shadows.as_ref(), self.descriptor.sets[self.frame_index], instance_groups);
+        graph.before_passes(move |pass| self.lighting.set_shadow_map(self.frame_index, pass.image_view(shadow_map)));
+
+        // Targets sampled by materials are imported first, so that every scene pass
+        // sampling them is ordered against the views drawing into them.
+        let mut target_images = self.sampled_targets.iter()
+            .map(|&id| (id, graph.import_image(self.render_targets[id.0].as_ref().unwrap().graph_image())))
+            .collect::<Vec<_>>();
+        let sampled_images = target_images.clone();
+        let mut drawn_targets = Vec::new();
+        for id in self.view_records.iter().filter_map(|view| view.target) {
+            if drawn_targets.contains(&id) {
+                continue;
+            }
+            drawn_targets.push(id);
+            let target = self.render_targets[id.0].as_ref().unwrap();
+            let color = match target_images.iter().find(|&&(imported, _)| imported == id) {
+                Some(&(_, image)) => image,
+                None => {
+                    let image = graph.import_image(target.graph_image());
+                    target_images.push((id, image));
+                    image
+                }
+            };
+            let scene_target = SceneTarget {
+                name: "render target",
+                color,
+                depth: graph.create_image(TransientImageDesc::new(target.extent(), depth_format)),
+                resolve: None,
+                clear_color: [0.0; 4],
+                extent: target.extent(),
+                samples: avk::SampleCountFlags::TYPE_1,
+            };
+            let views = self.view_records.iter().filter(|view| view.target == Some(id)).copied().collect();
+            let pipelines = &self.target_pipelines[&target.format()].pipelines;
+            let sampled = sampled_images.iter().filter(|&&(sampled, _)| sampled != id).map(|&(_, image)| image).collect();
+            self.add_scene_passes(&mut graph, scene_target, views, pipelines, (shadow_map, sampled, instance_groups));
+        }
+
+        let swapchain_image = graph.import_image(ImportedImage {
+            image: self.swapchain.images[image_index],
+            view: self.swapchain.image_views[image_index].inner,
+            extent: self.swapchain.extent,
+            format: self.swapchain.format,
+            initial_layout: avk::ImageLayout::UNDEFINED,
+            final_layout: Some(avk::ImageLayout::PRESENT_SRC_KHR),
+        });
+        let overlay_images = self.overlay_records.iter().map(|overlay| {
+            match target_images.iter().find(|&&(id, _)| id == overlay.target) {
+                Some(&(_, image)) => image,
+                None => graph.import_image(self.render_targets[overlay.target.0].as_ref().unwrap().graph_image()),
+            }
+        }).collect::<Vec<_>>();
+        let extent = self.swapchain.extent;
+        let hdr = graph.create_image(TransientImageDesc::new(extent, HDR_FORMAT));
+        let depth = graph.create_image(TransientImageDesc::new(extent, depth_format).with_samples(self.msaa_samples));
+        let multisampled_color = (self.msaa_samples != avk::SampleCountFlags::TYPE_1).then(|| {
+            graph.create_image(TransientImageDesc::new(extent, HDR_FORMAT).with_samples(self.msaa_samples))
+        });
+        let scene_target = SceneTarget {
+            name: "forward",
+            color: multisampled_color.unwrap_or(hdr),
+            depth,
+            resolve: multisampled_color.map(|_| hdr),
+            clear_color: self.clear_color,
+            extent,
+            samples: self.msaa_samples,
+        };
+        let views = self.view_records.iter().filter(|view| view.target.is_none()).copied().collect();
+        let sampled = sampled_images.iter().map(|&(_, image)| image).collect();
+        self.add_scene_passes(&mut graph, scene_target, views, &self.scene_pipelines, (shadow_map, sampled, instance_groups));
+
+        self.post_process_pass.add_passes(&mut graph, self.frame_index, post_process, lut.as_deref(), (hdr, extent), swapchain_image);
+
+        if !self.overlay_records.is_empty() {
+            let mut overlays = graph.add_pass("overlays").color_attachment(swapchain_image, AttachmentLoad::Load);
+            for image in overlay_images {
+                overlays = overlays.sample(image);
+            }
+            overlays.execute(move |pass| self.overlay_pass.record(pass.command_buffer, &self.overlay_records));
+        }
+        if self.text_pass.has_screen_text() {
+            graph.add_pass("text")
+                .color_attachment(swapchain_image, AttachmentLoad::Load)
+                .execute(move |pass| self.text_pass.record_screen(pass.command_buffer, self.frame_index, extent));
+        }
+
+        if let Some(object_id_pass) = &self.object_id_pass {
+            object_id_pass.add_passes(&mut graph, self.frame_index, self.swapchain.extent, self.descriptor.sets[self.frame_index], &self.view_records, instance_groups);
+        }
+        let mut retained = graph.execute(&self.context, command_buffer, graph_cache)?;
+        retained.extend(lut.map(|lut| lut as Arc<dyn Any>));
         command_buffer.end()?;
-        Ok(())
+        Ok(retained)
+    }
+
+    /// Draws `views` into the target's color and depth. Each view with order-independent
+    /// instances ends a scene pass; its instances are accumulated in a pass of their own
+    /// against the scene depth, then composited over the color before the next views
+    /// are drawn. `sampled` are the render targets that materials sample.
+    fn add_scene_passes<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        target: SceneTarget,
+        views: Vec<ViewRecord>,
+        pipelines: &'a ScenePipelines,
+        (shadow_map, sampled, instance_groups): (GraphImage, Vec<GraphImage>, &'a [InstanceGroup]),
+    ) {
+        let depth_clear = AttachmentLoad::clear_depth(if self.reverse_z { 0.0 } else { 1.0 });
+        let mut segments = views.split_inclusive(|view| self.transparent_queue.has_order_independent(view))
+            .map(<[ViewRecord]>::to_vec)
+            .collect::<Vec<_>>();
+        if segments.is_empty() {
+            // The pass still clears the target.
+            segments.push(Vec::new());
+        }
+
+        // Accumulation and revealage, shared by the target's order-independent views.
+        let accumulation = views.iter().any(|view| self.transparent_queue.has_order_independent(view)).then(|| {
+            let mut image = |format, samples| graph.create_image(TransientImageDesc::new(target.extent, format).with_samples(samples));
+            let multisampled = (image(ACCUMULATION_FORMAT, target.samples), image(REVEALAGE_FORMAT, target.samples));
+            let resolved = (target.samples != avk::SampleCountFlags::TYPE_1).then(|| {
+                (image(ACCUMULATION_FORMAT, avk::SampleCountFlags::TYPE_1), image(REVEALAGE_FORMAT, avk::SampleCountFlags::TYPE_1))
+            });
+            (multisampled, resolved)
+        });
+        if let Some((multisampled, resolved)) = accumulation {
+            let (accumulated, revealage) = resolved.unwrap_or(multisampled);
+            let views = views.clone();
+            graph.before_passes(move |pass| {
+                for view in views.iter() {
+                    self.transparent_queue.set_accumulation(view, pass.image_view(accumulated), pass.image_view(revealage));
+                }
+            });
+        }
+
+        let segment_count = segments.len();
+        for (i, segment) in segments.into_iter().enumerate() {
+            let last = i + 1 == segment_count;
+            let composited = segment.last().filter(|view| self.transparent_queue.has_order_independent(view)).copied();
+            let (color_load, depth_load) = if i == 0 {
+                (AttachmentLoad::clear_color(target.clear_color), depth_clear)
+            } else {
+                (AttachmentLoad::Load, AttachmentLoad::Load)
+            };
+            let mut scene = graph.add_pass(target.name)
+                .color_attachment(target.color, color_load)
+                .depth_attachment(target.depth, depth_load)
+                .sample(shadow_map);
+            for &image in sampled.iter() {
+                scene = scene.sample(image);
+            }
+            if let Some(resolve) = target.resolve.filter(|_| last && composited.is_none()) {
+                scene = scene.resolve_attachment(resolve);
+            }
+            scene.execute(move |pass| {
+                for view in segment.iter() {
+                    self.record_view(pass.command_buffer, view, instance_groups, pipelines);
+                }
+            });
+
+            let (Some(view), Some((multisampled, resolved))) = (composited, accumulation) else {
+                continue;
+            };
+            let mut accumulate = graph.add_pass("transparency accumulation")
+                .color_attachment(multisampled.0, AttachmentLoad::clear_color([0.0; 4]))
+                .color_attachment(multisampled.1, AttachmentLoad::clear_color([1.0; 4]))
+                .depth_attachment(target.depth, AttachmentLoad::Load)
+                .sample(shadow_map);
+            for &image in sampled.iter() {
+                accumulate = accumulate.sample(image);
+            }
+            if let Some((accumulated, revealage)) = resolved {
+                accumulate = accumulate.resolve_attachment(accumulated).resolve_attachment(revealage);
+            }
+            accumulate.execute(move |pass| {
+                view.restrict(pass.command_buffer);
+                self.record_transparent(pass.command_buffer, &view, instance_groups, &pipelines.transparency.accumulate, true);
+            });
+
+            let (accumulated, revealage) = resolved.unwrap_or(multisampled);
+            let mut composite = graph.add_pass("transparency composite")
+                .color_attachment(target.color, AttachmentLoad::Load)
+                .sample(accumulated)
+                .sample(revealage);
+            if let Some(resolve) = target.resolve.filter(|_| last) {
+                composite = composite.resolve_attachment(resolve);
+            }
+            composite.execute(move |pass| self.transparent_queue.record_composite(pass.command_buffer, &pipelines.transparency, &view));
+        }
+    }
+
+    /// Draws the view's opaque instance groups, the background behind them, its blended
+    /// instances back to front, then the debug lines and text billboards. View modes
+    /// other than solid draw every group as opaque, without the background.
+    fn record_view(
+        &self,
+        command_buffer: &tvk::CommandBuffer,
+        view: &ViewRecord,
+        instance_groups: &[InstanceGroup],
+        pipelines: &ScenePipelines,
+    ) {
+        view.begin(command_buffer, self.reverse_z, view.clear_color.map(|float32| avk::ClearColorValue { float32 }));
+        let solid = self.view_mode == ViewMode::Solid;
+        let shader_mode = self.view_mode.shader_mode(&self.context);
+        let barycentric_wireframe = self.view_mode.barycentric_wireframe(&self.context);
+        let mut bound_format = None;
+        for (group_index, instance_group) in instance_groups.iter().enumerate() {
+            if solid && !instance_group.material.alpha_mode.is_opaque() || self.samples_own_target(view, instance_group) {
+                continue;
+            }
+            let Some(mesh) = instance_group.mesh.get() else {
+                continue;
+            };
+            let pipeline = instance_group.format().pipeline(&pipelines.pipeline, &pipelines.compact_pipeline);
+            if bound_format != Some(instance_group.format()) {
+                command_buffer.bind_pipeline(pipeline);
+                command_buffer.bind_descriptor_sets(pipeline.layout, self.descriptor.sets[self.frame_index], &[view.uniform_offset]);
+                self.lighting.bind(command_buffer, pipeline.layout, self.frame_index);
+                bound_format = Some(instance_group.format());
+            }
+            self.materials.bind(command_buffer, pipeline.layout, group_index);
+            let constants = MaterialConstants::new(&instance_group.material, view.index, false).with_view_mode(shader_mode, group_index);
+            command_buffer.push_constants(pipeline.layout, &constants);
+            let instance_buffer = instance_group.instance_buffer(self.frame_index).unwrap().inner;
+            // Created by `render` before recording.
+            if let Some(unindexed_buffer) = mesh.unindexed_buffer.get().filter(|_| barycentric_wireframe) {
+                command_buffer.bind_vertex_buffers(&[unindexed_buffer.inner, instance_buffer]);
+                command_buffer.draw(mesh.indices.len() as u32, instance_group.visible_count() as u32, 0, 0);
+                continue;
+            }
+            command_buffer.bind_vertex_buffers(&[mesh.vertex_buffer.inner, instance_buffer]);
+            command_buffer.bind_index_buffer(&mesh.index_buffer);
+            command_buffer.draw_indexed(mesh.indices.len() as u32, instance_group.visible_count() as u32, 0, 0, 0);
+        }
+        // After the geometry, so depth testing skips every covered pixel.
+        if solid && view.clear_color.is_none() {
+            self.background_pass.record(command_buffer, &pipelines.background, self.frame_index, (&self.background, &self.light), view, self.reverse_z);
+        }
+        self.record_transparent(command_buffer, view, instance_groups, &pipelines.transparency.blended, false);
+        self.debug_pass.record(command_buffer, &pipelines.debug, self.frame_index, (self.descriptor.sets[self.frame_index], view.uniform_offset));
+        self.text_pass.record_world(command_buffer, &pipelines.text, self.frame_index, view);
+    }
+
+    /// Draws the view's blended or order-independent batches from the transparent queue.
+    fn record_transparent(
+        &self,
+        command_buffer: &tvk::CommandBuffer,
+        view: &ViewRecord,
+        instance_groups: &[InstanceGroup],
+        pipeline: &tvk::Pipeline,
+        order_independent: bool,
+    ) {
+        let batches = self.transparent_queue.batches(view, order_independent);
+        if batches.is_empty() {
+            return;
+        }
+        command_buffer.bind_pipeline(pipeline);
+        command_buffer.bind_descriptor_sets(pipeline.layout, self.descriptor.sets[self.frame_index], &[view.uniform_offset]);
+        self.lighting.bind(command_buffer, pipeline.layout, self.frame_index);
+        let instance_buffer = self.transparent_queue.instance_buffer(self.frame_index).inner;
+        for batch in batches.iter() {
+            let instance_group = &instance_groups[batch.group];
+            let Some(mesh) = instance_group.mesh.get().filter(|_| !self.samples_own_target(view, instance_group)) else {
+                continue;
+            };
+            self.materials.bind(command_buffer, pipeline.layout, batch.group);
+            command_buffer.push_constants(pipeline.layout, &MaterialConstants::new(&instance_group.material, view.index, order_independent));
+            command_buffer.bind_vertex_buffers(&[mesh.vertex_buffer.inner, instance_buffer]);
+            command_buffer.bind_index_buffer(&mesh.index_buffer);
+            command_buffer.draw_indexed(mesh.indices.len() as u32, batch.instance_count, 0, 0, batch.first_instance);
+        }
+    }
+
+    /// Whether the group's material samples the target `view` draws into, which it can't
+    /// while being drawn.
+    fn samples_own_target(&self, view: &ViewRecord, instance_group: &InstanceGroup) -> bool {
+        view.target
+            .and_then(|id| self.render_targets.get(id.0)?.as_deref())
+            .is_some_and(|target| target.is_sampled_by(&instance_group.material))
     }
 
     pub fn reset_command_buffers(&self) -> AnyResult<()> {
@@ -135,27 +796,69 @@ impl Renderer {
         Ok(())
     }
 
-    pub fn render(&mut self, camera: &Camera, instance_groups: &[InstanceGroup]) -> AnyResult<bool> {
+    /// Draws `instance_groups` once per view, in order. Views with a render target are
+    /// drawn into it first, then the window views and `overlays` into the next
+    /// swapchain image. Changed instances are uploaded once the frame's buffers are free.
+    /// Fails if some views use reverse-Z depth and others don't.
+    pub fn render(&mut self, views: &[RenderView], overlays: &[Overlay], instance_groups: &mut [InstanceGroup]) -> AnyResult<bool> {
+        if let Some(reverse_z) = views_reverse_z(views)? {
+            self.set_reverse_z(reverse_z)?;
+        }
+        self.apply_view_mode()?;
+        self.reload_shaders()?;
 
         self.sync_objects.in_flight_fences[self.frame_index].wait(u64::MAX)?;
+        self.retained_resources[self.frame_index].clear();
+        for instance_group in instance_groups.iter_mut() {
+            instance_group.update_gpu_buffer(self.frame_index)?;
+        }
+        let instance_groups = &*instance_groups;
+        if let Some(object_id_pass) = &mut self.object_id_pass {
+            object_id_pass.resolve(self.frame_index)?;
+            object_id_pass.prepare(self.frame_index, self.swapchain.extent, instance_groups);
+        }
         let (image_index, _) = self.swapchain.acquire_next_image(
                 u64::MAX,
                 self.sync_objects.image_available_semaphores[self.frame_index].inner,
                 avk::Fence::null()
         )?;
 
-        if let Some(weak_images_in_flight) = &self.sync_objects.images_in_flight[image_index as usize] {
-            if let Some(arc_images_in_flight) = weak_images_in_flight.upgrade() {
-                arc_images_in_flight.wait(u64::MAX)?;
-            }
+        if let Some(weak_images_in_flight) = &self.sync_objects.images_in_flight[image_index as usize]
+            && let Some(arc_images_in_flight) = weak_images_in_flight.upgrade() {
+            arc_images_in_flight.wait(u64::MAX)?;
         }
         self.sync_objects.images_in_flight[image_index as usize] = Some(Arc::downgrade(
             &self.sync_objects.in_flight_fences[self.frame_index]
         ));
 
-        self.update_uniform_buffer(camera, image_index as usize)?;
+        self.retained_resources[self.frame_index].extend(
+            instance_groups.iter().filter_map(|g| g.mesh.get()).map(|mesh| mesh as Arc<dyn Any>)
+        );
+        self.update_uniform_buffer(views)?;
+        // The view modes draw transparent groups along with the opaque ones.
+        let transparent_groups = if self.view_mode == ViewMode::Solid { instance_groups } else { &[] };
+        self.transparent_queue.prepare(self.frame_index, views, &self.view_records, transparent_groups)?;
+        if self.view_mode.barycentric_wireframe(&self.context) {
+            for mesh in instance_groups.iter().filter_map(|group| group.mesh.get()) {
+                mesh.unindexed_buffer(&self.context)?;
+            }
+        }
+        self.debug_pass.prepare(self.frame_index, &mut self.debug_draw)?;
+        let atlases = self.text_pass.prepare(&self.context, self.frame_index, (&mut self.fonts, &mut self.text), views)?;
+        self.retained_resources[self.frame_index].extend(atlases);
+        let textures = self.materials.prepare(self.frame_index, instance_groups);
+        self.retained_resources[self.frame_index].extend(textures);
+        let skybox = self.background_pass.prepare(self.frame_index, &self.background);
+        self.retained_resources[self.frame_index].extend(skybox);
+        self.prepare_render_targets(overlays, instance_groups);
         self.command_buffers[self.frame_index].reset(avk::CommandBufferResetFlags::empty())?;
-        self.record_command_buffer(&self.command_buffers[self.frame_index], instance_groups, image_index as usize)?;
+        let mut graph_cache = std::mem::take(&mut self.graph_cache);
+        let recorded = self.record_command_buffer(&self.command_buffers[self.frame_index], instance_groups, image_index as usize, &mut graph_cache);
+        self.graph_cache = graph_cache;
+        self.retained_resources[self.frame_index].extend(recorded?);
+        for id in self.view_records.iter().filter_map(|view| view.target) {
+            self.render_targets[id.0].as_ref().unwrap().mark_drawn();
+        }
         
         self.sync_objects.in_flight_fences[self.frame_index].reset()?;
 
@@ -179,16 +882,158 @@ impl Renderer {
         Ok(is_suboptimal)
     }
 
-    pub fn update_uniform_buffer(&mut self, camera: &Camera, index: usize) -> AnyResult<()>{
-        let ubos = [camera::Matrix {
-            view: camera.view_matrix(),
-            proj: camera.projection
-        }];
-        self.uniform_buffers[index].copy_memory(&ubos)?;
-        Ok(())
+    /// Retains the render targets used this frame, finds those that materials sample and
+    /// resolves the overlays.
+    fn prepare_render_targets(&mut self, overlays: &[Overlay], instance_groups: &[InstanceGroup]) {
+        self.sampled_targets = self.render_targets.iter().enumerate()
+            .filter(|(_, target)| target.as_ref().is_some_and(|target| instance_groups.iter().any(|group| target.is_sampled_by(&group.material))))
+            .map(|(index, _)| RenderTargetId(index))
+            .collect();
+        let retained = &mut self.retained_resources[self.frame_index];
+        for id in self.view_records.iter().filter_map(|view| view.target) {
+            retained.push(self.render_targets[id.0].as_ref().unwrap().clone());
+        }
+
+        let view_records = &self.view_records;
+        let overlays = overlays.iter().filter_map(|overlay| {
+            let target = self.render_targets.get(overlay.target.0)?.as_ref()?;
+            retained.push(target.clone());
+            let drawn = target.is_drawn() || view_records.iter().any(|view| view.target == Some(overlay.target));
+            drawn.then_some((overlay.target, target.as_ref(), overlay.viewport))
+        }).collect::<Vec<_>>();
+        self.overlay_records = self.overlay_pass.prepare(self.frame_index, &overlays, self.swapchain.extent);
+    }
+
+    /// Writes the matrices of every view, then of every shadow cascade, into the
+    /// current frame's uniform buffer, one aligned slot each, resolves the views' pixel
+    /// rectangles and updates the lights.
+    pub fn update_uniform_buffer(&mut self, views: &[RenderView]) -> AnyResult<()> {
+        let alignment = self.context.physical_device.properties.limits.min_uniform_buffer_offset_alignment.max(1);
+        let stride = (size_of::<camera::Matrix>() as u64).next_multiple_of(alignment);
+        // Cascades follow the main camera; the other views reuse them.
+        let cascades = match (&self.shadows, views.first()) {
+            (Some(shadows), Some(main)) => fit_cascades(main.camera, self.light.direction, shadows),
+            _ => Vec::new(),
+        };
+        let uniform_buffer = &mut self.uniform_buffers[self.frame_index];
+        if uniform_buffer.reserve(stride * (views.len() + cascades.len()) as u64)? {
+            self.descriptor.update_set(self.frame_index, uniform_buffer, size_of::<camera::Matrix>() as u64);
+        }
+
+        self.view_records.clear();
+        for (i, view) in views.iter().enumerate() {
+            let extent = match view.target {
+                None => self.swapchain.extent,
+                Some(id) => match self.render_targets.get(id.0).and_then(Option::as_ref) {
+                    Some(target) => target.extent(),
+                    None => {
+                        log::warn!("skipping view into missing render target {:?}", id);
+                        continue;
+                    }
+                },
+            };
+            let offset = stride * i as u64;
+            let (view_matrix, projection) = (view.camera.view_matrix(), view.camera.projection_matrix());
+            uniform_buffer.copy_memory_at(offset, &[camera::Matrix {
+                view: view_matrix,
+                proj: projection
+            }])?;
+            let clear_depth = self.view_records.iter().any(|record| record.target == view.target);
+            self.view_records.push(ViewRecord {
+                index: i as u32,
+                rect: view.viewport.to_rect(extent),
+                uniform_offset: offset as u32,
+                inverse_view_projection: (projection * view_matrix).inverse(),
+                target: view.target,
+                clear_color: view.clear_color,
+                clear_depth,
+            });
+        }
+
+        let first_cascade = views.len() as u64;
+        for (i, cascade) in cascades.iter().enumerate() {
+            uniform_buffer.copy_memory_at(stride * (first_cascade + i as u64), &[cascade.matrix])?;
+        }
+        self.shadow_pass.set_cascade_offsets((0..cascades.len()).map(|i| (stride * (first_cascade + i as u64)) as u32).collect());
+        let environment = self.environment.clone().unwrap_or_else(|| self.empty_environment.clone());
+        self.lighting.set_environment(self.frame_index, &environment, &self.environment_baker.brdf_lut);
+        self.retained_resources[self.frame_index].push(environment);
+        let environment_intensity = self.environment.is_some().then_some(self.environment_intensity);
+        let uniform = LightUniform::new(&self.light, &cascades, self.shadows.as_ref(), environment_intensity);
+        let cameras = views.iter().map(|view| view.camera).collect::<Vec<_>>();
+        self.lighting.update(self.frame_index, &uniform, &self.lights, &cameras)
     }
 } 
 
+impl Renderer {
+    /// Bakes the equirectangular HDR panorama at `path` into image-based lighting and
+    /// makes it the scene's environment. Blocks until the GPU has finished.
+    pub fn load_environment(&mut self, path: impl AsRef<std::path::Path>) -> AnyResult<()> {
+        let environment = self.environment_baker.load(&self.context, path.as_ref())?;
+        self.environment = Some(Arc::new(environment));
+        Ok(())
+    }
+
+    /// Resamples the equirectangular HDR panorama at `path` into a cube map for
+    /// `Background::Skybox`. Blocks until the GPU has finished.
+    pub fn load_skybox(&self, path: impl AsRef<std::path::Path>) -> AnyResult<Arc<Skybox>> {
+        Ok(Arc::new(self.environment_baker.load_skybox(&self.context, path.as_ref())?))
+    }
+
+    /// Loads a TTF or OTF font for `Text`. At most `MAX_FONTS` can be loaded.
+    pub fn load_font(&mut self, path: impl AsRef<std::path::Path>) -> AnyResult<FontId> {
+        if self.fonts.len() == MAX_FONTS {
+            return Err(format!("at most {MAX_FONTS} fonts can be loaded").into());
+        }
+        self.fonts.push(Font::load(path.as_ref())?);
+        Ok(FontId(self.fonts.len() - 1))
+    }
+
+    /// Size of `text` as it would be drawn, in the units of its size.
+    pub fn measure_text(&self, text: &Text) -> glam::Vec2 {
+        self.fonts.get(text.font.0).map_or(glam::Vec2::ZERO, |font| font.measure(text))
+    }
+
+    /// Creates an offscreen target that views can draw into and overlays can show.
+    pub fn create_render_target(&mut self, width: u32, height: u32, format: RenderTargetFormat) -> AnyResult<RenderTargetId> {
+        let target = self.context.create_render_target(width, height, format)?;
+        if !self.target_pipelines.contains_key(&format) {
+            // Pipelines only need a compatible render pass; the graph creates the ones they draw in.
+            let render_pass = self.context.create_offscreen_render_pass(format.vk_format(), avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)?;
+            let pipelines = self.create_scene_pipelines((&render_pass, format.vk_format()), (self.reverse_z, avk::SampleCountFlags::TYPE_1), self.pipeline_view_mode)?;
+            self.target_pipelines.insert(format, TargetPipelines { pipelines, render_pass });
+        }
+        self.render_targets.push(Some(Arc::new(target)));
+        Ok(RenderTargetId(self.render_targets.len() - 1))
+    }
+
+    pub fn render_target(&self, id: RenderTargetId) -> Option<&RenderTarget> {
+        self.render_targets.get(id.0)?.as_deref()
+    }
+
+    /// Replaces the target's attachments with new ones of the given size. Frames in
+    /// flight keep drawing into the old ones; materials sample the new ones from now on.
+    pub fn resize_render_target(&mut self, id: RenderTargetId, width: u32, height: u32) -> AnyResult<()> {
+        let target = self.render_target(id).ok_or("no such render target")?.resized(&self.context, width, height)?;
+        self.render_targets[id.0] = Some(Arc::new(target));
+        Ok(())
+    }
+
+    /// Views and overlays that still refer to the target are skipped from now on.
+    pub fn destroy_render_target(&mut self, id: RenderTargetId) {
+        if let Some(slot) = self.render_targets.get_mut(id.0) {
+            *slot = None;
+        }
+    }
+}
+
+/// Largest sample count the device supports that is no more than `requested`.
+fn supported_sample_count(context: &tvk::Context, requested: u32) -> avk::SampleCountFlags {
+    let max = context.physical_device.max_sample_count().as_raw();
+    let samples = requested.clamp(1, max);
+    avk::SampleCountFlags::from_raw(1 << samples.ilog2())
+}
+
 impl Drop for Renderer {
     fn drop(&mut self) {
         self.context.logical_device.device_wait_idle().unwrap();
@@ -0,0 +1,179 @@
+use std::{any::Any, path::{Path, PathBuf}, sync::Arc};
+
+use ash::vk as avk;
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+use crate::*;
+
+/// What views show wherever no geometry was drawn. Views with a clear color of their
+/// own show it instead.
+#[derive(Clone, Default)]
+pub enum Background {
+    /// `Renderer::clear_color`.
+    #[default]
+    ClearColor,
+    /// Cube map scaled by `intensity`. See `Renderer::load_skybox`.
+    Skybox { skybox: Arc<Skybox>, intensity: f32 },
+    Sky(ProceduralSky),
+}
+
+/// Cube map of linear HDR radiance in every direction.
+pub struct Skybox {
+    pub(crate) cube: Texture,
+    pub(crate) path: PathBuf,
+}
+
+impl Skybox {
+    /// Panorama the cube map was resampled from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Sky computed from single Rayleigh and Mie scattering in an Earth-like atmosphere,
+/// with the sun's disk on top and a flat ground below the horizon.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProceduralSky {
+    /// Direction towards the sun. `None` faces the directional light.
+    pub sun_direction: Option<Vec3>,
+    /// Radiance of the sunlight entering the atmosphere.
+    pub intensity: f32,
+    /// Angular diameter of the sun's disk in degrees; 0 hides it.
+    pub sun_size: f32,
+    /// Linear albedo of the ground.
+    pub ground_color: Vec3,
+}
+
+impl Default for ProceduralSky {
+    fn default() -> Self {
+        Self {
+            sun_direction: None,
+            intensity: 20.0,
+            sun_size: 1.0,
+            ground_color: Vec3::new(0.3, 0.28, 0.25),
+        }
+    }
+}
+
+/// Matches the push constants of the background shaders.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BackgroundConstants {
+    inverse_view_projection: Mat4,
+    /// Unit direction towards the sun, with the cosine of its angular radius in `w`,
+    /// or 1 without a disk.
+    sun: [f32; 4],
+    ground_color: [f32; 4],
+    intensity: f32,
+    far_depth: f32,
+    near_depth: f32,
+    _padding: f32,
+}
+
+/// Full-screen pipelines drawing the background behind the geometry of one render pass.
+pub(crate) struct BackgroundPipelines {
+    skybox: tvk::Pipeline,
+    sky: tvk::Pipeline,
+}
+
+impl BackgroundPipelines {
+    pub const SHADERS: [&str; 3] = ["background.vert.spv", "skybox.frag.spv", "sky.frag.spv"];
+
+    pub fn new(
+        context: &tvk::Context,
+        shaders: &Shaders,
+        render_pass: &tvk::RenderPass,
+        skybox_layout: avk::DescriptorSetLayout,
+        reverse_z: bool,
+        samples: avk::SampleCountFlags,
+    ) -> AnyResult<Self> {
+        let [vertex_source, skybox_source, sky_source] = shaders.get_all(Self::SHADERS)?;
+        // Drawn at the far plane where nothing else was, without hiding later geometry.
+        let state = tvk::PipelineState {
+            vertex_input: false,
+            depth_compare_op: if reverse_z { avk::CompareOp::GREATER_OR_EQUAL } else { avk::CompareOp::LESS_OR_EQUAL },
+            depth_write: false,
+            samples,
+            push_constant_size: size_of::<BackgroundConstants>() as u32,
+            ..Default::default()
+        };
+        Ok(Self {
+            skybox: context.create_pipeline::<()>(
+                render_pass,
+                &[skybox_layout],
+                &vertex_fragment_shaders(&vertex_source, &skybox_source),
+                &state
+            )?,
+            sky: context.create_pipeline::<()>(
+                render_pass,
+                &[],
+                &vertex_fragment_shaders(&vertex_source, &sky_source),
+                &state
+            )?,
+        })
+    }
+}
+
+/// Per-frame skybox sets for the background pipelines.
+pub(crate) struct BackgroundPass {
+    pub descriptor: tvk::TextureDescriptor,
+}
+
+impl BackgroundPass {
+    pub fn new(context: &tvk::Context, frames_in_flight: usize) -> AnyResult<Self> {
+        Ok(Self {
+            descriptor: context.create_texture_descriptor(frames_in_flight as u32)?,
+        })
+    }
+
+    /// Points this frame's set at the skybox, if any, and returns it so it can be kept
+    /// alive while the frame is in flight.
+    pub fn prepare(&self, frame_index: usize, background: &Background) -> Option<Arc<dyn Any>> {
+        let Background::Skybox { skybox, .. } = background else {
+            return None;
+        };
+        self.descriptor.update_set(frame_index, &skybox.cube.image_view, &skybox.cube.sampler);
+        Some(skybox.clone())
+    }
+
+    /// Draws the background into the view begun on `command_buffer`.
+    pub fn record(
+        &self,
+        command_buffer: &tvk::CommandBuffer,
+        pipelines: &BackgroundPipelines,
+        frame_index: usize,
+        (background, light): (&Background, &DirectionalLight),
+        view: &ViewRecord,
+        reverse_z: bool,
+    ) {
+        let mut constants = BackgroundConstants {
+            inverse_view_projection: view.inverse_view_projection,
+            sun: [0.0, 1.0, 0.0, 1.0],
+            ground_color: [0.0; 4],
+            intensity: 1.0,
+            far_depth: if reverse_z { 0.0 } else { 1.0 },
+            near_depth: if reverse_z { 1.0 } else { 0.0 },
+            _padding: 0.0,
+        };
+        let pipeline = match background {
+            Background::ClearColor => return,
+            Background::Skybox { intensity, .. } => {
+                constants.intensity = *intensity;
+                command_buffer.bind_pipeline(&pipelines.skybox);
+                command_buffer.bind_descriptor_sets(pipelines.skybox.layout, self.descriptor.sets[frame_index], &[]);
+                &pipelines.skybox
+            }
+            Background::Sky(sky) => {
+                let sun = sky.sun_direction.unwrap_or(-light.direction).normalize_or(Vec3::Y);
+                let radius = (sky.sun_size.max(0.0) * 0.5).to_radians();
+                constants.sun = sun.extend(if radius > 0.0 { radius.cos() } else { 1.0 }).to_array();
+                constants.ground_color = sky.ground_color.extend(1.0).to_array();
+                constants.intensity = sky.intensity;
+                command_buffer.bind_pipeline(&pipelines.sky);
+                &pipelines.sky
+            }
+        };
+        command_buffer.push_constants(pipeline.layout, &constants);
+        command_buffer.draw(3, 1, 0, 0);
+    }
+}
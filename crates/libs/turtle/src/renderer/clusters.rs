@@ -0,0 +1,259 @@
+use glam::{Mat4, Vec3};
+use crate::*;
+
+/// Clusters across, down and in depth of each view. Must match the scene fragment shader.
+pub const CLUSTER_GRID: [u32; 3] = [16, 9, 24];
+const CLUSTER_COUNT: usize = (CLUSTER_GRID[0] * CLUSTER_GRID[1] * CLUSTER_GRID[2]) as usize;
+
+/// Matches `ClusterGrid` in the scene fragment shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct ClusterGrid {
+    /// Scale and bias turning view depth, or its logarithm when the third component
+    /// is set, into a slice index.
+    depth: [f32; 4],
+    /// Offset of the grid's cells in the cluster items.
+    first_cell: [u32; 4],
+}
+
+/// Lights of every view sorted into view-space clusters: tiles of the view split into
+/// depth slices. The fragment shader finds its cluster and only evaluates the lights
+/// touching it.
+///
+/// `items` holds each view's cells as a first index and a count, followed by the
+/// light indices the cells point into.
+#[derive(Default)]
+pub(crate) struct LightClusters {
+    pub grids: Vec<ClusterGrid>,
+    pub items: Vec<u32>,
+    /// Light and cell pairs of the view being built, reused between views.
+    pairs: Vec<(u32, u32)>,
+}
+
+impl LightClusters {
+    pub fn clear(&mut self) {
+        self.grids.clear();
+        self.items.clear();
+    }
+
+    /// Adds the grid of a view seen through `camera`.
+    pub fn add_view(&mut self, camera: &Camera, lights: &[Light]) {
+        let view = camera.view_matrix();
+        let centers = lights.iter().map(|light| view.transform_point3(light.position())).collect::<Vec<_>>();
+
+        // Slices only need to reach the furthest light.
+        let projection = camera.projection_matrix();
+        let inverse_projection = projection.inverse();
+        let near_depth = if camera.projection.is_reverse_z() { 1.0 } else { 0.0 };
+        let (near, logarithmic) = match camera.projection {
+            Projection::Perspective { near, .. } => (near, true),
+            Projection::Orthographic { near, .. } => (near, false),
+            Projection::Custom { .. } => (-inverse_projection.project_point3(Vec3::new(0.0, 0.0, near_depth)).z, true),
+        };
+        let logarithmic = logarithmic && near > 0.0;
+        let far = match camera.projection {
+            Projection::Perspective { far: Some(far), .. } | Projection::Orthographic { far, .. } => far,
+            _ => f32::INFINITY,
+        };
+        let reach = centers.iter().zip(lights).map(|(center, light)| -center.z + light.range()).fold(near, f32::max);
+        let far = far.min(reach).max(near + 1e-3);
+        let slices = CLUSTER_GRID[2] as f32;
+        let (scale, bias) = if logarithmic {
+            let scale = slices / (far / near).ln();
+            (scale, -near.ln() * scale)
+        } else {
+            let scale = slices / (far - near);
+            (scale, -near * scale)
+        };
+        let slice_depth = |slice: u32| {
+            let t = slice as f32 / slices;
+            if logarithmic { near * (far / near).powf(t) } else { near + (far - near) * t }
+        };
+        let slice_of = |depth: f32| {
+            let depth = if logarithmic { depth.max(near).ln() } else { depth };
+            (depth * scale + bias).floor().clamp(0.0, slices - 1.0) as u32
+        };
+
+        let bounds = cluster_bounds(inverse_projection, near_depth, slice_depth);
+        self.pairs.clear();
+        for (index, (center, light)) in centers.iter().zip(lights).enumerate() {
+            let (depth, range) = (-center.z, light.range());
+            if depth + range < near || depth - range > far {
+                continue;
+            }
+            let tiles = (CLUSTER_GRID[0] * CLUSTER_GRID[1]) as usize;
+            for slice in slice_of(depth - range)..=slice_of(depth + range) {
+                let first = slice as usize * tiles;
+                for (cell, (min, max)) in bounds[first..first + tiles].iter().enumerate() {
+                    if center.clamp(*min, *max).distance_squared(*center) <= range * range {
+                        self.pairs.push(((first + cell) as u32, index as u32));
+                    }
+                }
+            }
+        }
+
+        // Counting sort of the pairs by cell.
+        let first_cell = self.items.len();
+        let first_index = first_cell + 2 * CLUSTER_COUNT;
+        self.items.resize(first_index + self.pairs.len(), 0);
+        for &(cell, _) in self.pairs.iter() {
+            self.items[first_cell + 2 * cell as usize + 1] += 1;
+        }
+        let mut next = first_index as u32;
+        for cell in 0..CLUSTER_COUNT {
+            self.items[first_cell + 2 * cell] = next;
+            next += self.items[first_cell + 2 * cell + 1];
+        }
+        let mut filled = vec![0; CLUSTER_COUNT];
+        for &(cell, light) in self.pairs.iter() {
+            let position = self.items[first_cell + 2 * cell as usize] + filled[cell as usize];
+            self.items[position as usize] = light;
+            filled[cell as usize] += 1;
+        }
+
+        self.grids.push(ClusterGrid {
+            depth: [scale, bias, if logarithmic { 1.0 } else { 0.0 }, 0.0],
+            first_cell: [first_cell as u32, 0, 0, 0],
+        });
+    }
+}
+
+/// View-space bounding box of every cluster, ordered by slice, then row, then column.
+fn cluster_bounds(inverse_projection: Mat4, near_depth: f32, slice_depth: impl Fn(u32) -> f32) -> Vec<(Vec3, Vec3)> {
+    let [columns, rows, slices] = CLUSTER_GRID;
+    // Rays through the tile corners, which works for perspective and orthographic
+    // projections alike.
+    let rays = (0..=rows).flat_map(|row| (0..=columns).map(move |column| (column, row))).map(|(column, row)| {
+        let x = column as f32 / columns as f32 * 2.0 - 1.0;
+        let y = row as f32 / rows as f32 * 2.0 - 1.0;
+        let origin = inverse_projection.project_point3(Vec3::new(x, y, near_depth));
+        (origin, inverse_projection.project_point3(Vec3::new(x, y, 0.5)) - origin)
+    }).collect::<Vec<_>>();
+    let at_depth = |(origin, ray): (Vec3, Vec3), depth: f32| origin + ray * ((-depth - origin.z) / ray.z);
+
+    let mut bounds = Vec::with_capacity(CLUSTER_COUNT);
+    for slice in 0..slices {
+        let depths = [slice_depth(slice), slice_depth(slice + 1)];
+        for row in 0..rows {
+            for column in 0..columns {
+                let corners = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| rays[((row + dy) * (columns + 1) + column + dx) as usize]);
+                let (mut min, mut max) = (Vec3::INFINITY, Vec3::NEG_INFINITY);
+                for depth in depths {
+                    for ray in corners {
+                        let point = at_depth(ray, depth);
+                        min = min.min(point);
+                        max = max.max(point);
+                    }
+                }
+                bounds.push((min, max));
+            }
+        }
+    }
+    bounds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TILES: u32 = CLUSTER_GRID[0] * CLUSTER_GRID[1];
+
+    fn lights() -> Vec<Light> {
+        // In front of the default camera at z = -5, one off to the upper left and one
+        // small light further away to the lower right.
+        vec![
+            PointLight::new(Vec3::new(-2.3, 1.1, 3.7), Vec3::ONE, 2.6).into(),
+            PointLight::new(Vec3::new(3.1, -1.7, 24.3), Vec3::ONE, 1.4).into(),
+        ]
+    }
+
+    /// Cells of the first view that list `light`, as `(column, row, slice)`.
+    fn cells_with_light(clusters: &LightClusters, light: u32) -> Vec<(u32, u32, u32)> {
+        let first_cell = clusters.grids[0].first_cell[0] as usize;
+        (0..CLUSTER_COUNT as u32).filter(|&cell| {
+            let start = clusters.items[first_cell + 2 * cell as usize] as usize;
+            let count = clusters.items[first_cell + 2 * cell as usize + 1] as usize;
+            clusters.items[start..start + count].contains(&light)
+        }).map(|cell| (cell % CLUSTER_GRID[0], cell % TILES / CLUSTER_GRID[0], cell / TILES)).collect()
+    }
+
+    /// Cells whose view-space box the light's sphere overlaps, with the boxes worked out
+    /// from the frustum extents rather than by unprojecting tile corners.
+    fn expected_cells(
+        center: Vec3,
+        range: f32,
+        half_extent_at: impl Fn(f32) -> (f32, f32),
+        slice_depth: impl Fn(u32) -> f32,
+    ) -> Vec<(u32, u32, u32)> {
+        let [columns, rows, slices] = CLUSTER_GRID;
+        let mut cells = Vec::new();
+        for slice in 0..slices {
+            for row in 0..rows {
+                for column in 0..columns {
+                    let (mut min, mut max) = (Vec3::INFINITY, Vec3::NEG_INFINITY);
+                    for depth in [slice_depth(slice), slice_depth(slice + 1)] {
+                        let (half_width, half_height) = half_extent_at(depth);
+                        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                            let x = (column + dx) as f32 / columns as f32 * 2.0 - 1.0;
+                            let y = (row + dy) as f32 / rows as f32 * 2.0 - 1.0;
+                            // The first row is at the top of the screen.
+                            let point = Vec3::new(x * half_width, -y * half_height, -depth);
+                            min = min.min(point);
+                            max = max.max(point);
+                        }
+                    }
+                    if center.clamp(min, max).distance_squared(center) <= range * range {
+                        cells.push((column, row, slice));
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    fn assert_lights_in_overlapped_cells(camera: &Camera, half_extent_at: impl Fn(f32) -> (f32, f32) + Copy, slice_depth: impl Fn(u32) -> f32 + Copy) {
+        let lights = lights();
+        let mut clusters = LightClusters::default();
+        clusters.add_view(camera, &lights);
+
+        for (index, light) in lights.iter().enumerate() {
+            let center = camera.view_matrix().transform_point3(light.position());
+            let cells = cells_with_light(&clusters, index as u32);
+            assert!(!cells.is_empty());
+            assert_eq!(cells, expected_cells(center, light.range(), half_extent_at, slice_depth), "light {}", index);
+        }
+    }
+
+    #[test]
+    fn logarithmic_slices_hold_only_overlapped_cells() {
+        let camera = Camera {
+            aspect_ratio: 16.0 / 9.0,
+            projection: Projection::perspective(60.0),
+            ..Camera::default()
+        };
+        let near = 0.1f32;
+        // The slices end at the far side of the furthest light.
+        let far = 29.3f32 + 1.4;
+        let tan = 30f32.to_radians().tan();
+        assert_lights_in_overlapped_cells(
+            &camera,
+            |depth| (depth * tan * camera.aspect_ratio, depth * tan),
+            |slice| near * (far / near).powf(slice as f32 / CLUSTER_GRID[2] as f32),
+        );
+    }
+
+    #[test]
+    fn orthographic_slices_hold_only_overlapped_cells() {
+        let camera = Camera {
+            aspect_ratio: 16.0 / 9.0,
+            projection: Projection::Orthographic { height: 12.0, zoom: 1.0, near: 0.5, far: 100.0, reverse_z: false },
+            ..Camera::default()
+        };
+        let (near, far) = (0.5f32, 29.3f32 + 1.4);
+        assert_lights_in_overlapped_cells(
+            &camera,
+            |_| (6.0 * camera.aspect_ratio, 6.0),
+            |slice| near + (far - near) * slice as f32 / CLUSTER_GRID[2] as f32,
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use std::time::{Duration, Instant};
+
+use ash::vk as avk;
+use glam::{Mat4, Vec3};
+use gpu_allocator::MemoryLocation;
+use crate::*;
+
+/// Segments in each circle of `DebugDraw::sphere`.
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Immediate-mode lines drawn over the scene of every view, e.g. bounds, picking rays or
+/// camera frustums. Each shape is drawn on the next frame only, unless given a duration
+/// through the `DebugShape` its method returns.
+#[derive(Default)]
+pub struct DebugDraw {
+    lines: Vec<DebugLine>,
+}
+
+#[derive(Clone, Copy)]
+struct DebugLine {
+    start: Vec3,
+    end: Vec3,
+    color: Vec3,
+    depth_test: bool,
+    /// `None` removes the line once it has been drawn.
+    expires: Option<Instant>,
+}
+
+/// Lines of the shape just added, to change how they are drawn.
+pub struct DebugShape<'d> {
+    lines: &'d mut [DebugLine],
+}
+
+impl DebugShape<'_> {
+    /// Keeps drawing the shape for `seconds` of real time.
+    pub fn duration(self, seconds: f32) -> Self {
+        let expires = Instant::now() + Duration::from_secs_f32(seconds.max(0.0));
+        for line in self.lines.iter_mut() {
+            line.expires = Some(expires);
+        }
+        self
+    }
+
+    /// Whether geometry in front hides the shape. On by default.
+    pub fn depth_test(self, depth_test: bool) -> Self {
+        for line in self.lines.iter_mut() {
+            line.depth_test = depth_test;
+        }
+        self
+    }
+}
+
+impl DebugDraw {
+    fn add(&mut self, color: Vec3, segments: impl IntoIterator<Item = (Vec3, Vec3)>) -> DebugShape<'_> {
+        let first = self.lines.len();
+        self.lines.extend(segments.into_iter().map(|(start, end)| DebugLine {
+            start,
+            end,
+            color,
+            depth_test: true,
+            expires: None,
+        }));
+        DebugShape { lines: &mut self.lines[first..] }
+    }
+
+    /// Linear HDR color, like the instance colors.
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: Vec3) -> DebugShape<'_> {
+        self.add(color, [(start, end)])
+    }
+
+    /// The first `length` units of the ray.
+    pub fn ray(&mut self, ray: &Ray, length: f32, color: Vec3) -> DebugShape<'_> {
+        self.add(color, [(ray.origin, ray.origin + ray.direction.normalize_or_zero() * length)])
+    }
+
+    pub fn aabb(&mut self, aabb: &Aabb, color: Vec3) -> DebugShape<'_> {
+        let corner = |i: usize| Vec3::select(glam::BVec3::new(i & 1 != 0, i & 2 != 0, i & 4 != 0), aabb.max, aabb.min);
+        // Corner pairs differing in one axis.
+        let edges = (0..8).flat_map(|i| [1, 2, 4].into_iter().filter(move |bit| i & bit == 0).map(move |bit| (i, i | bit)));
+        self.add(color, edges.map(|(a, b)| (corner(a), corner(b))).collect::<Vec<_>>())
+    }
+
+    /// Circles around the sphere in the three axis planes.
+    pub fn sphere(&mut self, center: Vec3, radius: f32, color: Vec3) -> DebugShape<'_> {
+        let point = |axis: usize, i: usize| {
+            let (sin, cos) = (std::f32::consts::TAU * i as f32 / CIRCLE_SEGMENTS as f32).sin_cos();
+            let circle = [Vec3::new(0.0, cos, sin), Vec3::new(cos, 0.0, sin), Vec3::new(cos, sin, 0.0)][axis];
+            center + circle * radius
+        };
+        let segments = (0..3).flat_map(|axis| (0..CIRCLE_SEGMENTS).map(move |i| (point(axis, i), point(axis, i + 1))));
+        self.add(color, segments.collect::<Vec<_>>())
+    }
+
+    /// The camera's view volume from its near plane to `distance` units along its
+    /// view direction, which also bounds infinite far planes.
+    pub fn frustum(&mut self, camera: &Camera, distance: f32, color: Vec3) -> DebugShape<'_> {
+        let inverse = camera.view_projection_matrix().inverse();
+        let near_depth = if camera.projection.is_reverse_z() { 1.0 } else { 0.0 };
+        let forward = camera.forward();
+        let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(x, y)| {
+            let near = inverse.project_point3(Vec3::new(x, y, near_depth));
+            let direction = inverse.project_point3(Vec3::new(x, y, 0.5)) - near;
+            // Where the corner's ray reaches `distance` along the view direction.
+            let t = (distance - (near - camera.position).dot(forward)) / direction.dot(forward);
+            (near, near + direction * t)
+        });
+        let segments = (0..4).flat_map(|i| {
+            let ((near, far), (next_near, next_far)) = (corners[i], corners[(i + 1) % 4]);
+            [(near, far), (near, next_near), (far, next_far)]
+        });
+        self.add(color, segments.collect::<Vec<_>>())
+    }
+
+    /// The X, Y and Z axes of `transform` in red, green and blue, `size` units long
+    /// before its scale.
+    pub fn axes(&mut self, transform: Mat4, size: f32) -> DebugShape<'_> {
+        let first = self.lines.len();
+        let origin = transform.transform_point3(Vec3::ZERO);
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            self.add(axis, [(origin, transform.transform_point3(axis * size))]);
+        }
+        DebugShape { lines: &mut self.lines[first..] }
+    }
+
+    /// Square grid in the XZ plane around `center`, `size` units wide with `divisions`
+    /// cells along each side.
+    pub fn grid(&mut self, center: Vec3, size: f32, divisions: u32, color: Vec3) -> DebugShape<'_> {
+        let divisions = divisions.max(1);
+        let half = size * 0.5;
+        let segments = (0..=divisions).flat_map(|i| {
+            let offset = -half + size * i as f32 / divisions as f32;
+            [
+                (center + Vec3::new(offset, 0.0, -half), center + Vec3::new(offset, 0.0, half)),
+                (center + Vec3::new(-half, 0.0, offset), center + Vec3::new(half, 0.0, offset)),
+            ]
+        });
+        self.add(color, segments.collect::<Vec<_>>())
+    }
+
+    /// Three axis-aligned lines `size` units long crossing at `center`.
+    pub fn cross(&mut self, center: Vec3, size: f32, color: Vec3) -> DebugShape<'_> {
+        let half = size * 0.5;
+        self.add(color, [Vec3::X, Vec3::Y, Vec3::Z].map(|axis| (center - axis * half, center + axis * half)))
+    }
+
+    /// Removes every shape, including those with time left.
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+/// Vertex of the debug line pipelines.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct DebugVertex {
+    position: Vec3,
+    color: Vec3,
+}
+
+impl tvk::VertexDescription for DebugVertex {
+    fn get_binding_descriptions() -> Vec<avk::VertexInputBindingDescription> {
+        vec![avk::VertexInputBindingDescription {
+            binding: 0,
+            stride: size_of::<DebugVertex>() as u32,
+            input_rate: avk::VertexInputRate::VERTEX,
+        }]
+    }
+
+    fn get_attribute_descriptions() -> Vec<avk::VertexInputAttributeDescription> {
+        vec![
+            avk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: avk::Format::R32G32B32_SFLOAT,
+                offset: std::mem::offset_of!(DebugVertex, position) as u32,
+            },
+            avk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: avk::Format::R32G32B32_SFLOAT,
+                offset: std::mem::offset_of!(DebugVertex, color) as u32,
+            },
+        ]
+    }
+}
+
+/// Line list pipelines of one render pass, with and without depth testing. Neither
+/// writes depth.
+pub(crate) struct DebugPipelines {
+    depth_tested: tvk::Pipeline,
+    overlay: tvk::Pipeline,
+}
+
+impl DebugPipelines {
+    pub const SHADERS: [&str; 2] = ["debug_line.vert.spv", "debug_line.frag.spv"];
+
+    pub fn new(
+        context: &tvk::Context,
+        shaders: &Shaders,
+        render_pass: &tvk::RenderPass,
+        camera_layout: avk::DescriptorSetLayout,
+        reverse_z: bool,
+        samples: avk::SampleCountFlags,
+    ) -> AnyResult<Self> {
+        let [vertex_source, fragment_source] = shaders.get_all(Self::SHADERS)?;
+        let state = tvk::PipelineState {
+            mesh_vertices: false,
+            topology: avk::PrimitiveTopology::LINE_LIST,
+            // Lines exactly on a surface still show.
+            depth_compare_op: if reverse_z { avk::CompareOp::GREATER_OR_EQUAL } else { avk::CompareOp::LESS_OR_EQUAL },
+            depth_write: false,
+            samples,
+            ..Default::default()
+        };
+        let create = |state: &tvk::PipelineState| context.create_pipeline::<DebugVertex>(
+            render_pass,
+            &[camera_layout],
+            &vertex_fragment_shaders(&vertex_source, &fragment_source),
+            state
+        );
+        Ok(Self {
+            depth_tested: create(&state)?,
+            overlay: create(&tvk::PipelineState { depth_test: false, ..state })?,
+        })
+    }
+}
+
+/// Per-frame vertex buffers of the debug lines: the depth-tested ones, then the others.
+pub(crate) struct DebugPass {
+    vertex_buffers: Vec<tvk::Buffer>,
+    vertices: Vec<DebugVertex>,
+    depth_tested_count: u32,
+    overlay_count: u32,
+}
+
+impl DebugPass {
+    pub fn new(context: &tvk::Context, frames_in_flight: usize) -> AnyResult<Self> {
+        let vertex_buffers = (0..frames_in_flight).map(|_| {
+            context.create_buffer(avk::BufferUsageFlags::VERTEX_BUFFER, MemoryLocation::CpuToGpu, 1)
+        }).collect::<AnyResult<Vec<_>>>()?;
+        Ok(Self {
+            vertex_buffers,
+            vertices: Vec::new(),
+            depth_tested_count: 0,
+            overlay_count: 0,
+        })
+    }
+
+    /// Uploads the lines into this frame's vertex buffer, then drops those that have
+    /// been drawn for long enough.
+    pub fn prepare(&mut self, frame_index: usize, debug_draw: &mut DebugDraw) -> AnyResult<()> {
+        self.vertices.clear();
+        for depth_test in [true, false] {
+            let lines = debug_draw.lines.iter().filter(|line| line.depth_test == depth_test);
+            self.vertices.extend(lines.flat_map(|line| [
+                DebugVertex { position: line.start, color: line.color },
+                DebugVertex { position: line.end, color: line.color },
+            ]));
+            if depth_test {
+                self.depth_tested_count = self.vertices.len() as u32;
+            }
+        }
+        self.overlay_count = self.vertices.len() as u32 - self.depth_tested_count;
+        if !self.vertices.is_empty() {
+            self.vertex_buffers[frame_index].copy_memory(&self.vertices)?;
+        }
+
+        let now = Instant::now();
+        debug_draw.lines.retain(|line| line.expires.is_some_and(|expires| expires > now));
+        Ok(())
+    }
+
+    /// Draws the lines into the view begun on `command_buffer`, seen through the camera
+    /// at `camera_set`'s dynamic offset.
+    pub fn record(&self, command_buffer: &tvk::CommandBuffer, pipelines: &DebugPipelines, frame_index: usize, camera_set: (avk::DescriptorSet, u32)) {
+        let vertex_buffer = self.vertex_buffers[frame_index].inner;
+        let draws = [
+            (&pipelines.depth_tested, 0, self.depth_tested_count),
+            (&pipelines.overlay, self.depth_tested_count, self.overlay_count),
+        ];
+        for (pipeline, first_vertex, vertex_count) in draws {
+            if vertex_count == 0 {
+                continue;
+            }
+            command_buffer.bind_pipeline(pipeline);
+            command_buffer.bind_descriptor_sets(pipeline.layout, camera_set.0, &[camera_set.1]);
+            command_buffer.bind_vertex_buffers(&[vertex_buffer]);
+            command_buffer.draw(vertex_count, 1, first_vertex, 0);
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::path::Path;
+
+use ash::vk as avk;
+use crate::*;
+
+const ENVIRONMENT_FORMAT: avk::Format = avk::Format::R16G16B16A16_SFLOAT;
+const IRRADIANCE_SIZE: u32 = 32;
+const SPECULAR_SIZE: u32 = 128;
+/// Levels of the specular cube map, from roughness 0 at the top to 1 at the last.
+/// Must match the scene fragment shader.
+pub const SPECULAR_MIP_LEVELS: u32 = 6;
+const BRDF_LUT_SIZE: u32 = 128;
+const SAMPLE_COUNT: u32 = 1024;
+/// Largest face of a skybox resampled from a panorama, whatever the panorama's size.
+const SKYBOX_MAX_SIZE: u32 = 2048;
+
+/// Image-based lighting baked from an equirectangular HDR panorama: a cube map of the
+/// diffuse light arriving from every direction, and one of the specular reflections
+/// for increasing roughness down its mip levels.
+pub struct Environment {
+    pub(crate) irradiance: Texture,
+    pub(crate) specular: Texture,
+}
+
+/// Matches the push constants of the IBL fragment shaders.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct BakeConstants {
+    face: u32,
+    roughness: f32,
+    /// Width of the panorama's top level in texels.
+    source_size: f32,
+    sample_count: u32,
+}
+
+/// Full-screen pipelines baking environments into cube maps, one face and mip level
+/// at a time, and the BRDF lookup table every environment shares.
+pub(crate) struct EnvironmentBaker {
+    render_pass: tvk::RenderPass,
+    irradiance_pipeline: tvk::Pipeline,
+    specular_pipeline: tvk::Pipeline,
+    descriptor: tvk::TextureDescriptor,
+    source_sampler: tvk::Sampler,
+    /// Scale and bias of the specular reflectance by view angle and roughness, for the
+    /// split-sum approximation.
+    pub brdf_lut: Texture,
+}
+
+impl EnvironmentBaker {
+    /// Shaders of the pipelines baking environments. The BRDF lookup table is baked
+    /// once, when the baker is created.
+    pub const SHADERS: [&str; 3] = ["fullscreen.vert.spv", "ibl_irradiance.frag.spv", "ibl_specular.frag.spv"];
+
+    pub fn new(context: &tvk::Context, shaders: &Shaders) -> AnyResult<Self> {
+        let render_pass = context.create_color_render_pass(ENVIRONMENT_FORMAT, avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)?;
+        let descriptor = context.create_texture_descriptor(1)?;
+        let [irradiance_pipeline, specular_pipeline] = Self::create_pipelines(context, shaders, &render_pass, descriptor.layout)?;
+
+        let state = bake_pipeline_state();
+        let [vertex_source, brdf_source] = shaders.get_all(["fullscreen.vert.spv", "brdf_lut.frag.spv"])?;
+        let lut_format = avk::Format::R16G16_SFLOAT;
+        let lut_render_pass = context.create_color_render_pass(lut_format, avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)?;
+        let brdf_pipeline = context.create_pipeline::<()>(
+            &lut_render_pass,
+            &[],
+            &vertex_fragment_shaders(&vertex_source, &brdf_source),
+            &state
+        )?;
+        let extent = avk::Extent2D { width: BRDF_LUT_SIZE, height: BRDF_LUT_SIZE };
+        let image = context.create_image(extent, lut_format, avk::ImageUsageFlags::COLOR_ATTACHMENT | avk::ImageUsageFlags::SAMPLED)?;
+        let image_view = context.create_image_view(&image, lut_format, avk::ImageAspectFlags::COLOR)?;
+        let frame_buffer = tvk::FrameBuffer::with_attachments(context.logical_device.clone(), extent, &lut_render_pass, &[image_view.inner])?;
+        let constants = BakeConstants { face: 0, roughness: 0.0, source_size: 0.0, sample_count: SAMPLE_COUNT };
+        context.submit_and_wait(|command_buffer| {
+            draw_full_screen(command_buffer, (&lut_render_pass, &frame_buffer, extent), &brdf_pipeline, None, &constants);
+            finish_rendering(command_buffer);
+        })?;
+
+        Ok(Self {
+            render_pass,
+            irradiance_pipeline,
+            specular_pipeline,
+            descriptor,
+            source_sampler: context.create_sampler(avk::Filter::LINEAR, avk::SamplerAddressMode::REPEAT)?,
+            brdf_lut: Texture {
+                image_view,
+                sampler: context.create_sampler(avk::Filter::LINEAR, avk::SamplerAddressMode::CLAMP_TO_EDGE)?,
+                image,
+            },
+        })
+    }
+
+    /// Builds the baking pipelines again from `shaders`. They replace the current ones
+    /// when the returned function is called.
+    pub fn reload_shaders(&self, context: &tvk::Context, shaders: &Shaders) -> AnyResult<PipelineSwap<Self>> {
+        let pipelines = Self::create_pipelines(context, shaders, &self.render_pass, self.descriptor.layout)?;
+        Ok(Box::new(move |baker| [baker.irradiance_pipeline, baker.specular_pipeline] = pipelines))
+    }
+
+    fn create_pipelines(context: &tvk::Context, shaders: &Shaders, render_pass: &tvk::RenderPass, layout: avk::DescriptorSetLayout) -> AnyResult<[tvk::Pipeline; 2]> {
+        let [vertex_source, irradiance_source, specular_source] = shaders.get_all(Self::SHADERS)?;
+        let state = bake_pipeline_state();
+        Ok([
+            context.create_pipeline::<()>(
+                render_pass,
+                &[layout],
+                &vertex_fragment_shaders(&vertex_source, &irradiance_source),
+                &state
+            )?,
+            context.create_pipeline::<()>(
+                render_pass,
+                &[layout],
+                &vertex_fragment_shaders(&vertex_source, &specular_source),
+                &state
+            )?,
+        ])
+    }
+
+    /// Decodes the HDR panorama at `path` and bakes it.
+    pub fn load(&self, context: &tvk::Context, path: &Path) -> AnyResult<Environment> {
+        let (width, height, texels) = load_hdr_file(path)?;
+        self.bake(context, width, height, &texels)
+    }
+
+    /// Bakes an equirectangular panorama of linear RGBA texels, with +Y up along its
+    /// top edge. Waits for the GPU to finish.
+    pub fn bake(&self, context: &tvk::Context, width: u32, height: u32, texels: &[f32]) -> AnyResult<Environment> {
+        let usage = avk::ImageUsageFlags::COLOR_ATTACHMENT | avk::ImageUsageFlags::SAMPLED;
+        let irradiance = context.create_cube_image(IRRADIANCE_SIZE, ENVIRONMENT_FORMAT, usage, 1)?;
+        let specular = context.create_cube_image(SPECULAR_SIZE, ENVIRONMENT_FORMAT, usage, SPECULAR_MIP_LEVELS)?;
+        self.render_cubes(context, (width, height, texels), &[(&irradiance, &self.irradiance_pipeline), (&specular, &self.specular_pipeline)])?;
+        Ok(Environment {
+            irradiance: cube_texture(context, irradiance)?,
+            specular: cube_texture(context, specular)?,
+        })
+    }
+
+    /// Resamples the HDR panorama at `path` into a cube map of about the same detail.
+    pub fn load_skybox(&self, context: &tvk::Context, path: &Path) -> AnyResult<Skybox> {
+        let (width, height, texels) = load_hdr_file(path)?;
+        let usage = avk::ImageUsageFlags::COLOR_ATTACHMENT | avk::ImageUsageFlags::SAMPLED;
+        let cube = context.create_cube_image((width / 4).clamp(1, SKYBOX_MAX_SIZE), ENVIRONMENT_FORMAT, usage, 1)?;
+        // The specular filter at roughness 0 is a plain lookup.
+        self.render_cubes(context, (width, height, &texels), &[(&cube, &self.specular_pipeline)])?;
+        Ok(Skybox { cube: cube_texture(context, cube)?, path: path.to_path_buf() })
+    }
+
+    /// Uploads the panorama and renders every face and mip level of each cube image with
+    /// its pipeline, at a roughness rising from 0 at the top level to 1 at the last.
+    fn render_cubes(&self, context: &tvk::Context, (width, height, texels): (u32, u32, &[f32]), cubes: &[(&tvk::Image, &tvk::Pipeline)]) -> AnyResult<()> {
+        let max_size = context.physical_device.properties.limits.max_image_dimension2_d;
+        if width == 0 || height == 0 || width > max_size || height > max_size {
+            return Err(format!("environment of {}x{} texels is not supported", width, height).into());
+        }
+        if texels.len() != (width * height * 4) as usize {
+            return Err(String::from("environment texel count doesn't match its size").into());
+        }
+        // Half floats can be filtered and blitted on every device.
+        let half_texels = texels.iter().map(|&texel| to_half(texel)).collect::<Vec<_>>();
+        let mut staging = context.create_buffer(
+            avk::BufferUsageFlags::TRANSFER_SRC,
+            gpu_allocator::MemoryLocation::CpuToGpu,
+            size_of_val(half_texels.as_slice()) as u64
+        )?;
+        staging.copy_memory(&half_texels)?;
+        let source = context.create_mipmapped_image(
+            avk::Extent2D { width, height },
+            ENVIRONMENT_FORMAT,
+            avk::ImageUsageFlags::SAMPLED | avk::ImageUsageFlags::TRANSFER_SRC | avk::ImageUsageFlags::TRANSFER_DST
+        )?;
+        let source_view = context.create_subresource_image_view(&source, avk::ImageViewType::TYPE_2D, color_range(0, source.mip_levels, 0, 1))?;
+        self.descriptor.update_set(0, &source_view, &self.source_sampler);
+
+        // Faces of each level of each cube.
+        let targets = cubes.iter().map(|(image, _)| {
+            (0..image.mip_levels).map(|level| self.face_targets(context, image, level)).collect::<AnyResult<Vec<_>>>()
+        }).collect::<AnyResult<Vec<_>>>()?;
+
+        let descriptor_set = Some(self.descriptor.sets[0]);
+        context.submit_and_wait(|command_buffer| {
+            command_buffer.transition_image_layout(
+                &source,
+                avk::ImageLayout::UNDEFINED,
+                avk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                (avk::PipelineStageFlags::TOP_OF_PIPE, avk::AccessFlags::empty()),
+                (avk::PipelineStageFlags::TRANSFER, avk::AccessFlags::TRANSFER_WRITE),
+            );
+            command_buffer.copy_buffer_to_image(&staging, &source, avk::ImageLayout::TRANSFER_DST_OPTIMAL);
+            command_buffer.generate_mipmaps(&source);
+
+            let mut constants = BakeConstants { face: 0, roughness: 0.0, source_size: width as f32, sample_count: SAMPLE_COUNT };
+            for (&(image, pipeline), levels) in cubes.iter().zip(targets.iter()) {
+                for (level, faces) in levels.iter().enumerate() {
+                    constants.roughness = level as f32 / (image.mip_levels - 1).max(1) as f32;
+                    let size = (image.extent.width >> level).max(1);
+                    for (face, (_, frame_buffer)) in faces.iter().enumerate() {
+                        constants.face = face as u32;
+                        let extent = avk::Extent2D { width: size, height: size };
+                        draw_full_screen(command_buffer, (&self.render_pass, frame_buffer, extent), pipeline, descriptor_set, &constants);
+                    }
+                }
+            }
+            finish_rendering(command_buffer);
+        })?;
+        Ok(())
+    }
+
+    /// A view and frame buffer for each face of `level` of a cube image.
+    fn face_targets(&self, context: &tvk::Context, image: &tvk::Image, level: u32) -> AnyResult<Vec<(tvk::ImageView, tvk::FrameBuffer)>> {
+        let size = (image.extent.width >> level).max(1);
+        (0..6).map(|face| {
+            let view = context.create_subresource_image_view(image, avk::ImageViewType::TYPE_2D, color_range(level, 1, face, 1))?;
+            let extent = avk::Extent2D { width: size, height: size };
+            let frame_buffer = tvk::FrameBuffer::with_attachments(context.logical_device.clone(), extent, &self.render_pass, &[view.inner])?;
+            Ok((view, frame_buffer))
+        }).collect()
+    }
+}
+
+/// Full-screen quad drawing into the whole target, with the bake push constants.
+fn bake_pipeline_state() -> tvk::PipelineState {
+    tvk::PipelineState {
+        vertex_input: false,
+        depth_test: false,
+        push_constant_size: size_of::<BakeConstants>() as u32,
+        ..Default::default()
+    }
+}
+
+/// Samples every level and face of a baked cube image.
+fn cube_texture(context: &tvk::Context, image: tvk::Image) -> AnyResult<Texture> {
+    Ok(Texture {
+        image_view: context.create_subresource_image_view(&image, avk::ImageViewType::CUBE, color_range(0, image.mip_levels, 0, 6))?,
+        sampler: context.create_sampler(avk::Filter::LINEAR, avk::SamplerAddressMode::CLAMP_TO_EDGE)?,
+        image,
+    })
+}
+
+fn color_range(base_mip_level: u32, level_count: u32, base_array_layer: u32, layer_count: u32) -> avk::ImageSubresourceRange {
+    avk::ImageSubresourceRange {
+        aspect_mask: avk::ImageAspectFlags::COLOR,
+        base_mip_level,
+        level_count,
+        base_array_layer,
+        layer_count,
+    }
+}
+
+fn draw_full_screen(
+    command_buffer: &tvk::CommandBuffer,
+    (render_pass, frame_buffer, extent): (&tvk::RenderPass, &tvk::FrameBuffer, avk::Extent2D),
+    pipeline: &tvk::Pipeline,
+    descriptor_set: Option<avk::DescriptorSet>,
+    constants: &BakeConstants,
+) {
+    command_buffer.begin_render_pass(extent, render_pass, frame_buffer, avk::SubpassContents::INLINE, &[]);
+    command_buffer.set_viewport(avk::Viewport::default()
+        .width(extent.width as f32)
+        .height(extent.height as f32)
+        .max_depth(1.0));
+    command_buffer.set_scissor(avk::Rect2D { offset: avk::Offset2D::default(), extent });
+    command_buffer.bind_pipeline(pipeline);
+    if let Some(descriptor_set) = descriptor_set {
+        command_buffer.bind_descriptor_sets(pipeline.layout, descriptor_set, &[]);
+    }
+    command_buffer.push_constants(pipeline.layout, constants);
+    command_buffer.draw(3, 1, 0, 0);
+    command_buffer.end_render_pass();
+}
+
+/// Makes the baked images visible to the fragment shaders of later submissions.
+fn finish_rendering(command_buffer: &tvk::CommandBuffer) {
+    let barrier = avk::MemoryBarrier::default()
+        .src_access_mask(avk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+        .dst_access_mask(avk::AccessFlags::SHADER_READ);
+    command_buffer.pipeline_barrier(
+        avk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        avk::PipelineStageFlags::FRAGMENT_SHADER,
+        &[barrier],
+        &[]
+    );
+}
+
+/// Nearest half-precision float, saturating at the largest finite one.
+fn to_half(value: f32) -> u16 {
+    let sign = ((value.to_bits() >> 16) & 0x8000) as u16;
+    let value = value.abs().min(65504.0);
+    if value < 6.103_515_6e-5 {
+        // Subnormal, in steps of 2^-24.
+        return sign | (value * 16_777_216.0).round() as u16;
+    }
+    let bits = value.to_bits();
+    let exponent = (bits >> 23) - 127 + 15;
+    // Rounding may carry into the exponent, which is still the nearest value.
+    let rounded = ((exponent << 10) | ((bits >> 13) & 0x3ff)) + ((bits >> 12) & 1);
+    sign | rounded.min(0x7bff) as u16
+}
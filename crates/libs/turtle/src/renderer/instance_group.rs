@@ -1,67 +1,339 @@
+use std::ops::Range;
+
 use ash::vk as avk;
 use gpu_allocator::MemoryLocation;
+use serde::{Deserialize, Serialize};
 
 use crate::*;
 
+/// Layout of the per-instance data in the GPU buffer. The CPU side always keeps full
+/// `InstanceData`; `Compact` converts dirty instances to `CompactInstanceData` on upload.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InstanceFormat {
+    #[default]
+    Full,
+    Compact,
+}
+
+impl InstanceFormat {
+    pub fn stride(&self) -> u64 {
+        match self {
+            InstanceFormat::Full => size_of::<tvk::FullInstanceData>() as u64,
+            InstanceFormat::Compact => size_of::<tvk::CompactInstanceData>() as u64,
+        }
+    }
+
+    /// Whichever of a pass's two pipelines reads instances in this format.
+    pub(crate) fn pipeline<'a>(self, full: &'a tvk::Pipeline, compact: &'a tvk::Pipeline) -> &'a tvk::Pipeline {
+        match self {
+            InstanceFormat::Full => full,
+            InstanceFormat::Compact => compact,
+        }
+    }
+}
+
+/// A mesh drawn once per visible instance.
+///
+/// Visible instances are packed at the front of the instance buffer in the order of
+/// `visible_indices`, so the buffer slot of an instance can move when another one is
+/// hidden. Each frame in flight has its own buffer, which only receives the slots
+/// touched since it was last uploaded.
 pub struct InstanceGroup {
-    pub mesh: Mesh<tvk::Vertex>,
-    pub all_instances: Vec<tvk::InstanceData>,
-    pub visible_indices: Vec<usize>,
-    pub instance_buffer: Option<tvk::Buffer>,
-    pub visible_count: usize,
+    pub mesh: Handle<Mesh<tvk::Vertex>>,
+    pub material: Material,
+    all_instances: Vec<tvk::InstanceData>,
+    visible_indices: Vec<usize>,
+    instance_buffers: Vec<tvk::Buffer>,
+    visible_count: usize,
+    format: InstanceFormat,
+    slots: Vec<Option<usize>>,
+    /// Instances released by `remove_instance`, reused by `add_instance`.
+    free_instances: Vec<usize>,
+    dirty_slots: Vec<Range<usize>>,
+    /// Slots each frame's buffer still lacks, collected from `dirty_slots`.
+    pending_slots: Vec<Vec<Range<usize>>>,
+    staging: Vec<tvk::FullInstanceData>,
+    compact_staging: Vec<tvk::CompactInstanceData>,
 }
 
 impl From<Mesh<tvk::Vertex>> for InstanceGroup  {
     fn from(value: Mesh<tvk::Vertex>) -> Self {
+        Self::from(Handle::ready(value))
+    }
+}
+
+impl From<Handle<Mesh<tvk::Vertex>>> for InstanceGroup  {
+    fn from(value: Handle<Mesh<tvk::Vertex>>) -> Self {
         Self {
             mesh: value,
+            material: Material::default(),
             all_instances: Vec::new(),
             visible_indices: Vec::new(),
-            instance_buffer: None,
-            visible_count: 0
+            instance_buffers: Vec::new(),
+            visible_count: 0,
+            format: InstanceFormat::default(),
+            slots: Vec::new(),
+            free_instances: Vec::new(),
+            dirty_slots: Vec::new(),
+            pending_slots: Vec::new(),
+            staging: Vec::new(),
+            compact_staging: Vec::new(),
         }
     }
 }
 
 impl InstanceGroup {
-    pub fn add_instance(&mut self, data: tvk::InstanceData, visible: bool) {
-        self.all_instances.push(data);
+    pub fn with_format(mut self, format: InstanceFormat) -> Self {
+        self.set_format(format);
+        self
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub fn format(&self) -> InstanceFormat {
+        self.format
+    }
+
+    /// Every instance by index, including hidden ones and ones freed by `remove_instance`.
+    pub fn instances(&self) -> &[tvk::InstanceData] {
+        &self.all_instances
+    }
+
+    /// Instance indices in buffer slot order.
+    pub fn visible_indices(&self) -> &[usize] {
+        &self.visible_indices
+    }
+
+    /// Number of instances in the buffers since the last `update_gpu_buffer`.
+    pub fn visible_count(&self) -> usize {
+        self.visible_count
+    }
+
+    pub fn instance_buffer(&self, frame_index: usize) -> Option<&tvk::Buffer> {
+        self.instance_buffers.get(frame_index)
+    }
+
+    /// Switches the GPU layout. Every visible instance is re-uploaded on the next
+    /// `update_gpu_buffer`.
+    pub fn set_format(&mut self, format: InstanceFormat) {
+        if self.format != format {
+            self.format = format;
+            self.mark_all_dirty();
+        }
+    }
+
+    /// Returns the index of the new instance, which may be one freed by `remove_instance`.
+    pub fn add_instance(&mut self, data: tvk::InstanceData, visible: bool) -> usize {
+        let index = match self.free_instances.pop() {
+            Some(index) => {
+                self.all_instances[index] = data;
+                index
+            }
+            None => {
+                self.all_instances.push(data);
+                self.slots.push(None);
+                self.all_instances.len() - 1
+            }
+        };
         if visible {
-            self.visible_indices.push(self.all_instances.len() - 1);
+            self.set_visible(index, true);
         }
-        self.visible_count = self.visible_indices.len();
+        index
+    }
+
+    /// Hides an instance and lets `add_instance` reuse its index, so adding and removing
+    /// instances doesn't grow the group. The index must not be used afterwards.
+    pub fn remove_instance(&mut self, instance_index: usize) {
+        debug_assert!(!self.free_instances.contains(&instance_index), "instance {} was already removed", instance_index);
+        self.set_visible(instance_index, false);
+        self.free_instances.push(instance_index);
     }
 
     pub fn create_instance_buffer(&mut self, context: &tvk::Context) -> AnyResult<()> {
-        self.instance_buffer = Some(context.create_buffer(
-            avk::BufferUsageFlags::VERTEX_BUFFER,
-            MemoryLocation::CpuToGpu,
-            1
-        )?);
+        self.instance_buffers = (0..MAX_FRAMES_IN_FLIGHT).map(|_| {
+            context.create_buffer(
+                avk::BufferUsageFlags::VERTEX_BUFFER,
+                MemoryLocation::CpuToGpu,
+                1
+            )
+        }).collect::<AnyResult<_>>()?;
+        self.pending_slots = vec![Vec::new(); MAX_FRAMES_IN_FLIGHT];
+        self.mark_all_dirty();
 
         Ok(())
     }
 
-    pub fn update_gpu_buffer(&mut self) -> AnyResult<()> {
-        let visible_data: Vec<tvk::InstanceData> = self
-            .visible_indices
-            .iter()
-            .map(|&i| self.all_instances[i])
-            .collect();
-        if let Some(instance_buffer) = &mut self.instance_buffer {
-            instance_buffer.copy_memory(&self.all_instances)?;
+    /// Replaces the data of an instance and schedules its slot for upload if it is visible.
+    pub fn set_instance(&mut self, instance_index: usize, data: tvk::InstanceData) {
+        self.all_instances[instance_index] = data;
+        self.mark_dirty(instance_index);
+    }
+
+    fn mark_dirty(&mut self, instance_index: usize) {
+        if let Some(slot) = self.slots[instance_index] {
+            self.mark_slots_dirty(slot..slot + 1);
+        }
+    }
+
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty_slots.clear();
+        self.dirty_slots.push(0..self.visible_indices.len());
+    }
+
+    pub fn is_visible(&self, instance_index: usize) -> bool {
+        self.slots[instance_index].is_some()
+    }
+
+    /// Uploads the slots that the buffer of `frame_index` lacks. The GPU must be done
+    /// with that buffer, which `Renderer::render` ensures by calling this after waiting
+    /// for the frame's fence.
+    pub fn update_gpu_buffer(&mut self, frame_index: usize) -> AnyResult<()> {
+        let Some(instance_buffer) = self.instance_buffers.get_mut(frame_index) else {
+            return Ok(());
+        };
+        for pending in self.pending_slots.iter_mut() {
+            pending.extend(self.dirty_slots.iter().cloned());
+        }
+        self.dirty_slots.clear();
+        let pending = &mut self.pending_slots[frame_index];
+
+        let stride = self.format.stride();
+        let required_size = self.visible_indices.len() as u64 * stride;
+        if required_size > instance_buffer.size && instance_buffer.reserve(required_size.max(instance_buffer.size * 2))? {
+            pending.clear();
+            pending.push(0..self.visible_indices.len());
+        }
+
+        merge_ranges(pending, self.visible_indices.len());
+        for range in pending.iter().cloned() {
+            let instances = self.visible_indices[range.clone()].iter().map(|&i| (i, self.all_instances[i]));
+            let offset = range.start as u64 * stride;
+            match self.format {
+                InstanceFormat::Full => {
+                    self.staging.clear();
+                    self.staging.extend(instances.map(|(i, data)| tvk::FullInstanceData::new(data, i)));
+                    instance_buffer.copy_memory_at(offset, &self.staging)?;
+                }
+                InstanceFormat::Compact => {
+                    self.compact_staging.clear();
+                    self.compact_staging.extend(instances.map(|(i, data)| tvk::CompactInstanceData::from(data).with_index(i)));
+                    instance_buffer.copy_memory_at(offset, &self.compact_staging)?;
+                }
+            }
         }
-        self.visible_count = visible_data.len();
+        pending.clear();
+
+        self.visible_count = self.visible_indices.len();
         Ok(())
     }
 
     pub fn set_visible(&mut self, instance_index: usize, visible: bool) {
-        if visible {
-            if !self.visible_indices.contains(&instance_index) {
+        match (self.slots[instance_index], visible) {
+            (None, true) => {
+                let slot = self.visible_indices.len();
                 self.visible_indices.push(instance_index);
+                self.slots[instance_index] = Some(slot);
+                self.mark_slots_dirty(slot..slot + 1);
             }
+            (Some(slot), false) => {
+                self.visible_indices.swap_remove(slot);
+                self.slots[instance_index] = None;
+                if let Some(&moved) = self.visible_indices.get(slot) {
+                    self.slots[moved] = Some(slot);
+                    self.mark_slots_dirty(slot..slot + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn mark_slots_dirty(&mut self, range: Range<usize>) {
+        if let Some(last) = self.dirty_slots.last_mut()
+            && range.start >= last.start && range.start <= last.end {
+            last.end = last.end.max(range.end);
+            return;
+        }
+        self.dirty_slots.push(range);
+    }
+}
+
+/// Sorts `ranges` and merges the ones that overlap or touch, dropping what lies past `len`.
+fn merge_ranges(ranges: &mut Vec<Range<usize>>, len: usize) {
+    ranges.sort_unstable_by_key(|range| range.start);
+    let mut merged = 0;
+    for next in 0..ranges.len() {
+        let range = ranges[next].start..ranges[next].end.min(len);
+        if range.is_empty() {
+            continue;
+        }
+        if merged > 0 && range.start <= ranges[merged - 1].end {
+            ranges[merged - 1].end = ranges[merged - 1].end.max(range.end);
         } else {
-            self.visible_indices.retain(|&i| i != instance_index);
+            ranges[merged] = range;
+            merged += 1;
+        }
+    }
+    ranges.truncate(merged);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group_with_visible_instances(count: usize) -> InstanceGroup {
+        let mut group = InstanceGroup::from(Handle::loading("cube.obj".into(), None));
+        for _ in 0..count {
+            group.add_instance(tvk::InstanceData { model: glam::Mat4::IDENTITY, color: glam::Vec3::ONE }, true);
         }
+        // As if the instances had been uploaded.
+        group.dirty_slots.clear();
+        group
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn merges_overlapping_ranges() {
+        let mut ranges = vec![5..8, 0..3, 2..4, 6..7];
+        merge_ranges(&mut ranges, 10);
+        assert_eq!(ranges, [0..4, 5..8]);
+    }
+
+    #[test]
+    fn merges_adjacent_ranges() {
+        let mut ranges = vec![4..6, 0..2, 2..4, 7..9];
+        merge_ranges(&mut ranges, 10);
+        assert_eq!(ranges, [0..6, 7..9]);
+    }
+
+    #[test]
+    fn drops_ranges_past_the_visible_slots() {
+        let mut ranges = vec![3..6, 8..9, 1..1];
+        merge_ranges(&mut ranges, 5);
+        assert_eq!(ranges, vec![3..5]);
+    }
+
+    #[test]
+    fn hiding_the_last_slot_moves_nothing() {
+        let mut group = group_with_visible_instances(3);
+        group.set_visible(2, false);
+        assert_eq!(group.visible_indices, [0, 1]);
+        assert_eq!(group.slots, [Some(0), Some(1), None]);
+        assert!(group.dirty_slots.is_empty());
+    }
+
+    #[test]
+    fn hiding_a_middle_slot_moves_the_last_instance_into_it() {
+        let mut group = group_with_visible_instances(4);
+        group.set_visible(1, false);
+        assert_eq!(group.visible_indices, [0, 3, 2]);
+        assert_eq!(group.slots, [Some(0), None, Some(2), Some(1)]);
+        assert_eq!(group.dirty_slots, vec![1..2]);
+
+        // Editing the moved instance now uploads its new slot.
+        group.mark_dirty(3);
+        group.mark_dirty(1);
+        assert_eq!(group.dirty_slots, vec![1..2]);
+    }
+}
@@ -0,0 +1,303 @@
+use ash::vk as avk;
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+use crate::*;
+
+/// Sun-like light infinitely far away, so its rays are parallel everywhere.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DirectionalLight {
+    /// Direction the light travels in, from the light into the scene.
+    pub direction: Vec3,
+    /// Linear color, scaled by `intensity`.
+    pub color: Vec3,
+    pub intensity: f32,
+    /// Light every surface receives regardless of its orientation or shadows, as a
+    /// fraction of its own color.
+    pub ambient: f32,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::new(-0.4, -1.0, -0.3).normalize(),
+            color: Vec3::ONE,
+            intensity: 1.0,
+            ambient: 0.2,
+        }
+    }
+}
+
+/// Light radiating in all directions from a point, fading out at `range`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PointLight {
+    pub position: Vec3,
+    /// Linear color, scaled by `intensity`.
+    pub color: Vec3,
+    pub intensity: f32,
+    /// Distance at which the light has faded out completely.
+    pub range: f32,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, color: Vec3, range: f32) -> Self {
+        Self {
+            position,
+            color,
+            intensity: 1.0,
+            range,
+        }
+    }
+}
+
+/// Point light limited to a cone around `direction`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpotLight {
+    pub position: Vec3,
+    /// Axis of the cone, pointing away from the light.
+    pub direction: Vec3,
+    /// Linear color, scaled by `intensity`.
+    pub color: Vec3,
+    pub intensity: f32,
+    pub range: f32,
+    /// Angle from the axis in degrees inside which the light is at full strength.
+    pub inner_angle: f32,
+    /// Angle from the axis in degrees at which the light has faded out.
+    pub outer_angle: f32,
+}
+
+impl SpotLight {
+    pub fn new(position: Vec3, direction: Vec3, color: Vec3, range: f32) -> Self {
+        Self {
+            position,
+            direction,
+            color,
+            intensity: 1.0,
+            range,
+            inner_angle: 20.0,
+            outer_angle: 30.0,
+        }
+    }
+}
+
+/// Entry of `Renderer::lights`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Light {
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+impl From<PointLight> for Light {
+    fn from(light: PointLight) -> Self {
+        Light::Point(light)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Self {
+        Light::Spot(light)
+    }
+}
+
+impl Light {
+    pub fn position(&self) -> Vec3 {
+        match self {
+            Light::Point(light) => light.position,
+            Light::Spot(light) => light.position,
+        }
+    }
+
+    pub fn range(&self) -> f32 {
+        match self {
+            Light::Point(light) => light.range,
+            Light::Spot(light) => light.range,
+        }
+    }
+}
+
+const SPOT_LIGHT: f32 = 1.0;
+
+/// Matches `LightData` in the scene fragment shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct LightData {
+    /// Position and range.
+    position: [f32; 4],
+    /// Color times intensity.
+    color: [f32; 4],
+    /// Cone axis and the cosine of the outer angle.
+    direction: [f32; 4],
+    /// Cosine of the inner angle and the kind of light.
+    cone: [f32; 4],
+}
+
+impl From<&Light> for LightData {
+    fn from(light: &Light) -> Self {
+        match light {
+            Light::Point(light) => Self {
+                position: light.position.extend(light.range).to_array(),
+                color: (light.color * light.intensity).extend(0.0).to_array(),
+                direction: [0.0; 4],
+                cone: [0.0; 4],
+            },
+            Light::Spot(light) => {
+                let outer = light.outer_angle.to_radians().cos();
+                // The inner cone must be strictly inside the outer one for the falloff.
+                let inner = light.inner_angle.to_radians().cos().max(outer + 1e-4);
+                Self {
+                    position: light.position.extend(light.range).to_array(),
+                    color: (light.color * light.intensity).extend(0.0).to_array(),
+                    direction: light.direction.normalize_or(Vec3::NEG_Y).extend(outer).to_array(),
+                    cone: [inner, SPOT_LIGHT, 0.0, 0.0],
+                }
+            },
+        }
+    }
+}
+
+/// Matches the `Lighting` uniform block of the scene fragment shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct LightUniform {
+    direction: [f32; 4],
+    /// Color times intensity, with the ambient fraction in `w`.
+    color: [f32; 4],
+    cascades: [Mat4; MAX_SHADOW_CASCADES],
+    /// World-space size of a shadow map texel in each cascade.
+    texel_sizes: [f32; MAX_SHADOW_CASCADES],
+    /// Cascade count, normal bias in texels and the size of a texel in texture coordinates.
+    shadow: [f32; 4],
+    /// Intensity of the image-based lighting, or 0 to use the ambient fraction instead.
+    environment: [f32; 4],
+}
+
+impl LightUniform {
+    /// `environment_intensity` scales the environment's light, if there is one.
+    pub fn new(light: &DirectionalLight, cascades: &[Cascade], shadows: Option<&Shadows>, environment_intensity: Option<f32>) -> Self {
+        let mut uniform = Self {
+            direction: light.direction.normalize_or(Vec3::NEG_Y).extend(0.0).to_array(),
+            color: (light.color * light.intensity).extend(light.ambient).to_array(),
+            cascades: [Mat4::IDENTITY; MAX_SHADOW_CASCADES],
+            texel_sizes: [0.0; MAX_SHADOW_CASCADES],
+            shadow: [0.0; 4],
+            environment: [environment_intensity.unwrap_or(0.0), 0.0, 0.0, 0.0],
+        };
+        if let Some(shadows) = shadows {
+            for (i, cascade) in cascades.iter().enumerate() {
+                uniform.cascades[i] = cascade.matrix.proj * cascade.matrix.view;
+                uniform.texel_sizes[i] = cascade.texel_size;
+            }
+            uniform.shadow = [cascades.len() as f32, shadows.normal_bias, 1.0 / shadows.resolution.max(1) as f32, 0.0];
+        }
+        uniform
+    }
+}
+
+/// Per-frame light uniforms, shadow map, light list, light clusters and environment,
+/// bound as set 1 of the scene pipelines.
+pub(crate) struct Lighting {
+    pub descriptor: tvk::ResourceDescriptor,
+    uniform_buffers: Vec<tvk::Buffer>,
+    /// Light list, cluster grids and cluster items of each frame.
+    storage_buffers: Vec<[tvk::Buffer; 3]>,
+    shadow_sampler: tvk::Sampler,
+    light_data: Vec<LightData>,
+    clusters: LightClusters,
+}
+
+impl Lighting {
+    pub fn new(context: &tvk::Context, frames_in_flight: usize) -> AnyResult<Self> {
+        let descriptor = context.create_resource_descriptor(frames_in_flight as u32, &[
+            (avk::DescriptorType::UNIFORM_BUFFER, avk::ShaderStageFlags::FRAGMENT),
+            (avk::DescriptorType::COMBINED_IMAGE_SAMPLER, avk::ShaderStageFlags::FRAGMENT),
+            (avk::DescriptorType::STORAGE_BUFFER, avk::ShaderStageFlags::FRAGMENT),
+            (avk::DescriptorType::STORAGE_BUFFER, avk::ShaderStageFlags::FRAGMENT),
+            (avk::DescriptorType::STORAGE_BUFFER, avk::ShaderStageFlags::FRAGMENT),
+            (avk::DescriptorType::COMBINED_IMAGE_SAMPLER, avk::ShaderStageFlags::FRAGMENT),
+            (avk::DescriptorType::COMBINED_IMAGE_SAMPLER, avk::ShaderStageFlags::FRAGMENT),
+            (avk::DescriptorType::COMBINED_IMAGE_SAMPLER, avk::ShaderStageFlags::FRAGMENT),
+        ])?;
+        let uniform_buffers = (0..frames_in_flight).map(|_| {
+            context.create_buffer(
+                avk::BufferUsageFlags::UNIFORM_BUFFER,
+                gpu_allocator::MemoryLocation::CpuToGpu,
+                size_of::<LightUniform>() as u64
+            )
+        }).collect::<AnyResult<Vec<_>>>()?;
+        for (frame, buffer) in uniform_buffers.iter().enumerate() {
+            descriptor.write_buffer(frame, 0, buffer, size_of::<LightUniform>() as u64);
+        }
+        let storage_buffer = |size: usize| context.create_buffer(
+            avk::BufferUsageFlags::STORAGE_BUFFER,
+            gpu_allocator::MemoryLocation::CpuToGpu,
+            size as u64
+        );
+        let storage_buffers = (0..frames_in_flight).map(|frame| {
+            let buffers = [
+                storage_buffer(size_of::<LightData>())?,
+                storage_buffer(size_of::<ClusterGrid>())?,
+                storage_buffer(size_of::<u32>())?,
+            ];
+            for (i, buffer) in buffers.iter().enumerate() {
+                descriptor.write_buffer(frame, 2 + i as u32, buffer, avk::WHOLE_SIZE);
+            }
+            Ok(buffers)
+        }).collect::<AnyResult<Vec<_>>>()?;
+
+        Ok(Self {
+            descriptor,
+            uniform_buffers,
+            storage_buffers,
+            shadow_sampler: context.create_comparison_sampler(avk::CompareOp::LESS_OR_EQUAL)?,
+            light_data: Vec::new(),
+            clusters: LightClusters::default(),
+        })
+    }
+
+    /// Writes the frame's uniforms and light list, and clusters `lights` for each of
+    /// `cameras`, in the order of the views using them.
+    pub fn update(&mut self, frame_index: usize, uniform: &LightUniform, lights: &[Light], cameras: &[&Camera]) -> AnyResult<()> {
+        self.uniform_buffers[frame_index].copy_memory_at(0, &[*uniform])?;
+
+        self.light_data.clear();
+        self.light_data.extend(lights.iter().map(LightData::from));
+        self.clusters.clear();
+        for camera in cameras {
+            self.clusters.add_view(camera, lights);
+        }
+        let [light_buffer, grid_buffer, item_buffer] = &mut self.storage_buffers[frame_index];
+        write_storage(&self.descriptor, (frame_index, 2), light_buffer, &self.light_data)?;
+        write_storage(&self.descriptor, (frame_index, 3), grid_buffer, &self.clusters.grids)?;
+        write_storage(&self.descriptor, (frame_index, 4), item_buffer, &self.clusters.items)
+    }
+
+    /// Points the frame's set at the shadow map array, before any pass binds it.
+    pub fn set_shadow_map(&self, frame_index: usize, shadow_map: avk::ImageView) {
+        self.descriptor.write_image(frame_index, 1, shadow_map, &self.shadow_sampler);
+    }
+
+    /// Points the frame's set at the cube maps of `environment` and the BRDF lookup table.
+    pub fn set_environment(&self, frame_index: usize, environment: &Environment, brdf_lut: &Texture) {
+        let textures = [&environment.irradiance, &environment.specular, brdf_lut];
+        for (i, texture) in textures.into_iter().enumerate() {
+            self.descriptor.write_image(frame_index, 5 + i as u32, texture.image_view.inner, &texture.sampler);
+        }
+    }
+
+    /// Binds the frame's set. The view whose clusters are used is selected through the
+    /// push constants, among the cameras given to `update`.
+    pub fn bind(&self, command_buffer: &tvk::CommandBuffer, layout: avk::PipelineLayout, frame_index: usize) {
+        command_buffer.bind_descriptor_set_at(layout, 1, self.descriptor.sets[frame_index], &[]);
+    }
+}
+
+/// Copies `data` to the start of `buffer`, growing it and repointing the binding as needed.
+fn write_storage<T: Copy>(descriptor: &tvk::ResourceDescriptor, (set, binding): (usize, u32), buffer: &mut tvk::Buffer, data: &[T]) -> AnyResult<()> {
+    if data.is_empty() {
+        return Ok(());
+    }
+    if buffer.reserve(size_of_val(data) as u64)? {
+        descriptor.write_buffer(set, binding, buffer, avk::WHOLE_SIZE);
+    }
+    buffer.copy_memory_at(0, data)
+}
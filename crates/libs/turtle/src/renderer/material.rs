@@ -0,0 +1,286 @@
+use std::{any::Any, sync::Arc};
+
+use ash::vk as avk;
+use glam::{Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+use crate::*;
+
+/// Most instance groups with textures drawn in one frame. Further groups are drawn
+/// with their factors only.
+pub const MAX_TEXTURED_GROUPS: usize = 256;
+
+/// How a material's alpha combines it with what is behind it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum AlphaMode {
+    /// Alpha is ignored and the surface hides what is behind it.
+    #[default]
+    Opaque,
+    /// Opaque where alpha reaches `cutoff` and fully transparent elsewhere, as for
+    /// foliage or fences. Drawn with the opaque surfaces.
+    Mask { cutoff: f32 },
+    /// Alpha blended over what is behind, drawn back to front after every opaque
+    /// surface, across all instance groups of the same view.
+    Blend,
+    /// Weighted blended order-independent transparency: cheaper than sorting for many
+    /// overlapping instances, at the cost of approximate ordering. Drawn over every
+    /// `Blend` surface of the same view.
+    OrderIndependent,
+}
+
+impl AlphaMode {
+    /// Whether surfaces in this mode are drawn with the opaque ones, without sorting.
+    pub fn is_opaque(self) -> bool {
+        matches!(self, Self::Opaque | Self::Mask { .. })
+    }
+}
+
+/// glTF metallic-roughness material shared by every instance of a group. Each factor
+/// multiplies the matching texture, and the base color also multiplies the instance
+/// color. Color textures should be loaded with `Assets::load_texture`, the others with
+/// `Assets::load_linear_texture`.
+#[derive(Clone)]
+pub struct Material {
+    /// Linear color and alpha.
+    pub base_color: Vec4,
+    pub metallic: f32,
+    /// Perceptual roughness, from mirror-like at 0 to fully rough at 1.
+    pub roughness: f32,
+    /// Linear light the surface gives off, independent of any lighting.
+    pub emissive: Vec3,
+    /// Strength of the normal texture's tilt.
+    pub normal_scale: f32,
+    /// How much the occlusion texture darkens indirect light.
+    pub occlusion_strength: f32,
+    pub alpha_mode: AlphaMode,
+    pub base_color_texture: Option<Handle<Texture>>,
+    /// Roughness in the green channel and metalness in the blue one.
+    pub metallic_roughness_texture: Option<Handle<Texture>>,
+    /// Tangent-space normals.
+    pub normal_texture: Option<Handle<Texture>>,
+    /// Ambient occlusion in the red channel.
+    pub occlusion_texture: Option<Handle<Texture>>,
+    pub emissive_texture: Option<Handle<Texture>>,
+}
+
+/// A white, slightly rough dielectric without textures.
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color: Vec4::ONE,
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: Vec3::ZERO,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            alpha_mode: AlphaMode::Opaque,
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+        }
+    }
+}
+
+impl Material {
+    pub fn with_base_color(mut self, base_color: Vec4) -> Self {
+        self.base_color = base_color;
+        self
+    }
+
+    pub fn with_metallic_roughness(mut self, metallic: f32, roughness: f32) -> Self {
+        self.metallic = metallic;
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn with_emissive(mut self, emissive: Vec3) -> Self {
+        self.emissive = emissive;
+        self
+    }
+
+    pub fn with_alpha_mode(mut self, alpha_mode: AlphaMode) -> Self {
+        self.alpha_mode = alpha_mode;
+        self
+    }
+
+    /// Whether any texture slot holds `texture`.
+    pub fn uses_texture(&self, texture: &Handle<Texture>) -> bool {
+        self.textures().into_iter().flatten().any(|used| used.ptr_eq(texture))
+    }
+
+    /// Texture slots in the order of the material set bindings.
+    fn textures(&self) -> [Option<&Handle<Texture>>; 5] {
+        [
+            self.base_color_texture.as_ref(),
+            self.metallic_roughness_texture.as_ref(),
+            self.normal_texture.as_ref(),
+            self.occlusion_texture.as_ref(),
+            self.emissive_texture.as_ref(),
+        ]
+    }
+}
+
+/// Matches the push constants of the scene fragment shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub(crate) struct MaterialConstants {
+    base_color: [f32; 4],
+    /// Emissive color, with the normal scale in `w`.
+    emissive: [f32; 4],
+    /// Metallic, roughness, occlusion strength and the alpha cutoff of masked materials,
+    /// or 0.
+    params: [f32; 4],
+    /// Position of the view among the frame's views, selecting its light clusters.
+    view_index: u32,
+    /// Non-zero to write the weighted blended accumulation instead of the color.
+    order_independent: u32,
+    /// Index of the instance group, coloring its instances in `ViewMode::InstanceId`.
+    group: u32,
+    /// `ViewMode::shader_mode`, read by the view mode shader only.
+    view_mode: u32,
+}
+
+impl MaterialConstants {
+    pub fn new(material: &Material, view_index: u32, order_independent: bool) -> Self {
+        let cutoff = match material.alpha_mode {
+            AlphaMode::Mask { cutoff } => cutoff,
+            _ => 0.0,
+        };
+        Self {
+            base_color: material.base_color.to_array(),
+            emissive: material.emissive.extend(material.normal_scale).to_array(),
+            params: [material.metallic, material.roughness.clamp(0.0, 1.0), material.occlusion_strength, cutoff],
+            view_index,
+            order_independent: order_independent as u32,
+            group: 0,
+            view_mode: 0,
+        }
+    }
+
+    pub fn with_view_mode(mut self, view_mode: u32, group: usize) -> Self {
+        self.view_mode = view_mode;
+        self.group = group as u32;
+        self
+    }
+}
+
+/// Texture sets of the instance groups' materials, bound as set 2 of the scene
+/// pipelines. Each frame in flight has its own `MAX_TEXTURED_GROUPS` sets plus one
+/// with neutral textures for groups without any.
+pub(crate) struct MaterialSets {
+    pub descriptor: tvk::TextureDescriptor,
+    /// White, standing in for missing color, metallic-roughness and occlusion textures.
+    white: Texture,
+    /// Tangent-space normal pointing straight out, standing in for missing normal textures.
+    flat_normal: Texture,
+    /// Set of each instance group this frame.
+    group_sets: Vec<usize>,
+}
+
+impl MaterialSets {
+    pub fn new(context: &tvk::Context, frames_in_flight: usize) -> AnyResult<Self> {
+        let sets_per_frame = MAX_TEXTURED_GROUPS + 1;
+        let descriptor = context.create_texture_descriptor_with_bindings((frames_in_flight * sets_per_frame) as u32, 5)?;
+        let white = create_texture_from_pixels(context, (1, 1, &[255, 255, 255, 255]), avk::Format::R8G8B8A8_UNORM, avk::SamplerAddressMode::REPEAT)?;
+        let flat_normal = create_texture_from_pixels(context, (1, 1, &[128, 128, 255, 255]), avk::Format::R8G8B8A8_UNORM, avk::SamplerAddressMode::REPEAT)?;
+        let sets = Self {
+            descriptor,
+            white,
+            flat_normal,
+            group_sets: Vec::new(),
+        };
+        for frame in 0..frames_in_flight {
+            for binding in 0..5 {
+                let texture = sets.texture_or_default(None, binding);
+                sets.descriptor.update_binding(frame * sets_per_frame, binding as u32, texture.image_view.inner, &texture.sampler);
+            }
+        }
+        Ok(sets)
+    }
+
+    fn texture_or_default<'a>(&'a self, texture: Option<&'a Texture>, binding: usize) -> &'a Texture {
+        texture.unwrap_or(if binding == 2 { &self.flat_normal } else { &self.white })
+    }
+
+    /// Points this frame's sets at the textures of each instance group's material,
+    /// and returns the textures so they can be kept alive while the frame is in flight.
+    pub fn prepare(&mut self, frame_index: usize, instance_groups: &[InstanceGroup]) -> Vec<Arc<dyn Any>> {
+        let first_set = frame_index * (MAX_TEXTURED_GROUPS + 1);
+        let mut retained: Vec<Arc<dyn Any>> = Vec::new();
+        let mut next_set = first_set + 1;
+        let mut skipped = 0;
+        self.group_sets.clear();
+        for instance_group in instance_groups.iter() {
+            let textures = instance_group.material.textures().map(|texture| texture.and_then(Handle::get));
+            if textures.iter().all(Option::is_none) {
+                self.group_sets.push(first_set);
+                continue;
+            }
+            if next_set > first_set + MAX_TEXTURED_GROUPS {
+                skipped += 1;
+                self.group_sets.push(first_set);
+                continue;
+            }
+            for (binding, texture) in textures.iter().enumerate() {
+                let texture = self.texture_or_default(texture.as_deref(), binding);
+                self.descriptor.update_binding(next_set, binding as u32, texture.image_view.inner, &texture.sampler);
+            }
+            retained.extend(textures.into_iter().flatten().map(|texture| texture as Arc<dyn Any>));
+            self.group_sets.push(next_set);
+            next_set += 1;
+        }
+        if skipped > 0 {
+            log::warn!("drawing {} textured instance groups over the limit of {} without textures", skipped, MAX_TEXTURED_GROUPS);
+        }
+        retained
+    }
+
+    /// Binds the set of the instance group at `group_index` given to the last `prepare`.
+    pub fn bind(&self, command_buffer: &tvk::CommandBuffer, layout: avk::PipelineLayout, group_index: usize) {
+        command_buffer.bind_descriptor_set_at(layout, 2, self.descriptor.sets[self.group_sets[group_index]], &[]);
+    }
+}
+
+/// Uploads `(width, height, pixels)` into a sampled texture. Blocks until the GPU has finished.
+pub(crate) fn create_texture_from_pixels(
+    context: &tvk::Context,
+    (width, height, pixels): (u32, u32, &[u8]),
+    format: avk::Format,
+    address_mode: avk::SamplerAddressMode,
+) -> AnyResult<Texture> {
+    let mut staging = context.create_buffer(
+        avk::BufferUsageFlags::TRANSFER_SRC,
+        gpu_allocator::MemoryLocation::CpuToGpu,
+        pixels.len() as u64
+    )?;
+    staging.copy_memory(pixels)?;
+    let image = context.create_image(
+        avk::Extent2D { width, height },
+        format,
+        avk::ImageUsageFlags::SAMPLED | avk::ImageUsageFlags::TRANSFER_DST
+    )?;
+    context.submit_and_wait(|command_buffer| {
+        command_buffer.transition_image_layout(
+            &image,
+            avk::ImageLayout::UNDEFINED,
+            avk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            (avk::PipelineStageFlags::TOP_OF_PIPE, avk::AccessFlags::empty()),
+            (avk::PipelineStageFlags::TRANSFER, avk::AccessFlags::TRANSFER_WRITE),
+        );
+        command_buffer.copy_buffer_to_image(&staging, &image, avk::ImageLayout::TRANSFER_DST_OPTIMAL);
+        command_buffer.transition_image_layout(
+            &image,
+            avk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            (avk::PipelineStageFlags::TRANSFER, avk::AccessFlags::TRANSFER_WRITE),
+            (avk::PipelineStageFlags::FRAGMENT_SHADER, avk::AccessFlags::SHADER_READ),
+        );
+    })?;
+
+    Ok(Texture {
+        image_view: context.create_image_view(&image, format, avk::ImageAspectFlags::COLOR)?,
+        sampler: context.create_sampler(avk::Filter::LINEAR, address_mode)?,
+        image,
+    })
+}
@@ -1,13 +1,45 @@
 use ash::vk as avk;
-use glam::vec3;
+use glam::{vec2, vec3, vec4, Vec2, Vec3, Vec4};
 use gpu_allocator::MemoryLocation;
-use crate::{tvk::{self, Vertex}, AnyResult};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use crate::{tvk::{self, Vertex}, Aabb, AnyResult};
+
+/// Where a mesh came from, so it can be referenced instead of copied when saved.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MeshSource {
+    Cube,
+    File(std::path::PathBuf),
+    Inline {
+        positions: Vec<glam::Vec3>,
+        indices: Vec<u32>,
+    },
+}
 
 pub struct Mesh<V> where V: Copy, V: tvk::VertexDescription  {
     pub vertices: Vec<V>,
     pub indices: Vec<u32>,
     pub vertex_buffer: tvk::Buffer,
-    pub index_buffer: tvk::Buffer
+    pub index_buffer: tvk::Buffer,
+    pub source: Option<MeshSource>,
+    pub(crate) bounds: OnceLock<Aabb>,
+    /// Vertices repeated in index order, for the barycentric wireframe.
+    pub(crate) unindexed_buffer: OnceLock<tvk::Buffer>,
+}
+
+impl Mesh<Vertex> {
+    /// Returns the recorded source, falling back to the raw vertex data.
+    pub fn to_source(&self) -> MeshSource {
+        self.source.clone().unwrap_or_else(|| MeshSource::Inline {
+            positions: self.vertices.iter().map(|v| v.position).collect(),
+            indices: self.indices.clone(),
+        })
+    }
+
+    /// Bounds of the vertex positions, computed on first use.
+    pub fn bounds(&self) -> Aabb {
+        *self.bounds.get_or_init(|| Aabb::from_points(self.vertices.iter().map(|v| v.position)))
+    }
 }
 
 impl<V> Mesh<V> where V: Copy, V: tvk::VertexDescription {
@@ -26,12 +58,30 @@ impl<V> Mesh<V> where V: Copy, V: tvk::VertexDescription {
         index_buffer.copy_memory(&indices)?;
 
         Ok(Self {
-            vertices: vertices,
-            indices: indices,
+            vertices,
+            indices,
             vertex_buffer,
-            index_buffer
+            index_buffer,
+            source: None,
+            bounds: OnceLock::new(),
+            unindexed_buffer: OnceLock::new(),
         })
     }
+
+    /// Vertex buffer with the vertices of each triangle in turn, created on first use.
+    pub(crate) fn unindexed_buffer(&self, context: &tvk::Context) -> AnyResult<&tvk::Buffer> {
+        if let Some(buffer) = self.unindexed_buffer.get() {
+            return Ok(buffer);
+        }
+        let vertices = self.indices.iter().map(|&index| self.vertices[index as usize]).collect::<Vec<_>>();
+        let mut buffer = context.create_buffer(
+            avk::BufferUsageFlags::VERTEX_BUFFER,
+            MemoryLocation::CpuToGpu,
+            size_of_val(vertices.as_slice()) as u64
+        )?;
+        buffer.copy_memory(&vertices)?;
+        Ok(self.unindexed_buffer.get_or_init(|| buffer))
+    }
 }
 
 impl tvk::Context {
@@ -43,46 +93,114 @@ impl tvk::Context {
 
 impl tvk::Context {
     pub fn create_mesh_from_cube(&self) -> AnyResult<Mesh<Vertex>> {
-        Mesh::from_vertices(&self, CUBE_VERTICES.into(), CUBE_INDICES.into())
+        let mut mesh = Mesh::from_vertices(self, CUBE_VERTICES.into(), CUBE_INDICES.into())?;
+        mesh.source = Some(MeshSource::Cube);
+        Ok(mesh)
+    }
+
+    pub fn create_mesh_from_source(&self, source: &MeshSource) -> AnyResult<Mesh<Vertex>> {
+        match source {
+            MeshSource::Cube => self.create_mesh_from_cube(),
+            MeshSource::File(path) => {
+                let (vertices, indices) = crate::load_mesh_file(path)?;
+                let mut mesh = Mesh::from_vertices(self, vertices, indices)?;
+                mesh.source = Some(source.clone());
+                Ok(mesh)
+            }
+            MeshSource::Inline { positions, indices } => {
+                let mut vertices = positions.iter().map(|&position| Vertex::new(position)).collect::<Vec<_>>();
+                generate_normals(&mut vertices, indices);
+                let mut mesh = Mesh::from_vertices(self, vertices, indices.clone())?;
+                mesh.source = Some(source.clone());
+                Ok(mesh)
+            }
+        }
+    }
+}
+
+/// Smooth normals averaged from the faces around each vertex, weighted by area.
+pub fn generate_normals(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut normals = vec![Vec3::ZERO; vertices.len()];
+    for triangle in indices.chunks_exact(3).filter(|triangle| triangle.iter().all(|&i| (i as usize) < vertices.len())) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let normal = (vertices[b].position - vertices[a].position).cross(vertices[c].position - vertices[a].position);
+        for i in [a, b, c] {
+            normals[i] += normal;
+        }
+    }
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        vertex.normal = normal.normalize_or(Vec3::Y);
     }
 }
 
+/// Tangents following the texture coordinates, for meshes that come without them.
+/// Vertices without usable coordinates get any direction perpendicular to the normal.
+pub fn generate_tangents(vertices: &mut [Vertex], indices: &[u32]) {
+    let mut tangents = vec![(Vec3::ZERO, Vec3::ZERO); vertices.len()];
+    for triangle in indices.chunks_exact(3).filter(|triangle| triangle.iter().all(|&i| (i as usize) < vertices.len())) {
+        let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize]);
+        let (edge1, edge2) = (b.position - a.position, c.position - a.position);
+        let (duv1, duv2) = (b.uv - a.uv, c.uv - a.uv);
+        let determinant = duv1.perp_dot(duv2);
+        if determinant.abs() < 1e-12 {
+            continue;
+        }
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) / determinant;
+        let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / determinant;
+        for &i in triangle {
+            tangents[i as usize].0 += tangent;
+            tangents[i as usize].1 += bitangent;
+        }
+    }
+    for (vertex, (tangent, bitangent)) in vertices.iter_mut().zip(tangents) {
+        let normal = vertex.normal;
+        let tangent = (tangent - normal * normal.dot(tangent)).try_normalize().unwrap_or_else(|| normal.any_orthonormal_vector());
+        // glTF normal maps point green towards decreasing v.
+        let handedness = if normal.cross(tangent).dot(bitangent) > 0.0 { -1.0 } else { 1.0 };
+        vertex.tangent = tangent.extend(handedness);
+    }
+}
+
+const fn cube_vertex(position: Vec3, normal: Vec3, uv: Vec2, tangent: Vec4) -> Vertex {
+    Vertex { position, normal, uv, tangent }
+}
+
 pub const CUBE_VERTICES: [Vertex; 24] = [
     // Front face (Z+)
-    Vertex { position: vec3(-0.5, -0.5,  0.5) },
-    Vertex { position: vec3( 0.5, -0.5,  0.5) },
-    Vertex { position: vec3( 0.5,  0.5,  0.5) },
-    Vertex { position: vec3(-0.5,  0.5,  0.5) },
+    cube_vertex(vec3(-0.5, -0.5,  0.5), vec3( 0.0,  0.0,  1.0), vec2(0.0, 1.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5, -0.5,  0.5), vec3( 0.0,  0.0,  1.0), vec2(1.0, 1.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5,  0.5,  0.5), vec3( 0.0,  0.0,  1.0), vec2(1.0, 0.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3(-0.5,  0.5,  0.5), vec3( 0.0,  0.0,  1.0), vec2(0.0, 0.0), vec4( 1.0,  0.0,  0.0, 1.0)),
 
     // Back face (Z-)
-    Vertex { position: vec3( 0.5, -0.5, -0.5) },
-    Vertex { position: vec3(-0.5, -0.5, -0.5) },
-    Vertex { position: vec3(-0.5,  0.5, -0.5) },
-    Vertex { position: vec3( 0.5,  0.5, -0.5) },
+    cube_vertex(vec3( 0.5, -0.5, -0.5), vec3( 0.0,  0.0, -1.0), vec2(0.0, 1.0), vec4(-1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3(-0.5, -0.5, -0.5), vec3( 0.0,  0.0, -1.0), vec2(1.0, 1.0), vec4(-1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3(-0.5,  0.5, -0.5), vec3( 0.0,  0.0, -1.0), vec2(1.0, 0.0), vec4(-1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5,  0.5, -0.5), vec3( 0.0,  0.0, -1.0), vec2(0.0, 0.0), vec4(-1.0,  0.0,  0.0, 1.0)),
 
     // Left face (X-)
-    Vertex { position: vec3(-0.5, -0.5, -0.5) },
-    Vertex { position: vec3(-0.5, -0.5,  0.5) },
-    Vertex { position: vec3(-0.5,  0.5,  0.5) },
-    Vertex { position: vec3(-0.5,  0.5, -0.5) },
+    cube_vertex(vec3(-0.5, -0.5, -0.5), vec3(-1.0,  0.0,  0.0), vec2(0.0, 1.0), vec4( 0.0,  0.0,  1.0, 1.0)),
+    cube_vertex(vec3(-0.5, -0.5,  0.5), vec3(-1.0,  0.0,  0.0), vec2(1.0, 1.0), vec4( 0.0,  0.0,  1.0, 1.0)),
+    cube_vertex(vec3(-0.5,  0.5,  0.5), vec3(-1.0,  0.0,  0.0), vec2(1.0, 0.0), vec4( 0.0,  0.0,  1.0, 1.0)),
+    cube_vertex(vec3(-0.5,  0.5, -0.5), vec3(-1.0,  0.0,  0.0), vec2(0.0, 0.0), vec4( 0.0,  0.0,  1.0, 1.0)),
 
     // Right face (X+)
-    Vertex { position: vec3( 0.5, -0.5,  0.5) },
-    Vertex { position: vec3( 0.5, -0.5, -0.5) },
-    Vertex { position: vec3( 0.5,  0.5, -0.5) },
-    Vertex { position: vec3( 0.5,  0.5,  0.5) },
+    cube_vertex(vec3( 0.5, -0.5,  0.5), vec3( 1.0,  0.0,  0.0), vec2(0.0, 1.0), vec4( 0.0,  0.0, -1.0, 1.0)),
+    cube_vertex(vec3( 0.5, -0.5, -0.5), vec3( 1.0,  0.0,  0.0), vec2(1.0, 1.0), vec4( 0.0,  0.0, -1.0, 1.0)),
+    cube_vertex(vec3( 0.5,  0.5, -0.5), vec3( 1.0,  0.0,  0.0), vec2(1.0, 0.0), vec4( 0.0,  0.0, -1.0, 1.0)),
+    cube_vertex(vec3( 0.5,  0.5,  0.5), vec3( 1.0,  0.0,  0.0), vec2(0.0, 0.0), vec4( 0.0,  0.0, -1.0, 1.0)),
 
     // Top face (Y+)
-    Vertex { position: vec3(-0.5,  0.5,  0.5) },
-    Vertex { position: vec3( 0.5,  0.5,  0.5) },
-    Vertex { position: vec3( 0.5,  0.5, -0.5) },
-    Vertex { position: vec3(-0.5,  0.5, -0.5) },
+    cube_vertex(vec3(-0.5,  0.5,  0.5), vec3( 0.0,  1.0,  0.0), vec2(0.0, 1.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5,  0.5,  0.5), vec3( 0.0,  1.0,  0.0), vec2(1.0, 1.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5,  0.5, -0.5), vec3( 0.0,  1.0,  0.0), vec2(1.0, 0.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3(-0.5,  0.5, -0.5), vec3( 0.0,  1.0,  0.0), vec2(0.0, 0.0), vec4( 1.0,  0.0,  0.0, 1.0)),
 
     // Bottom face (Y-)
-    Vertex { position: vec3(-0.5, -0.5, -0.5) },
-    Vertex { position: vec3( 0.5, -0.5, -0.5) },
-    Vertex { position: vec3( 0.5, -0.5,  0.5) },
-    Vertex { position: vec3(-0.5, -0.5,  0.5) },
+    cube_vertex(vec3(-0.5, -0.5, -0.5), vec3( 0.0, -1.0,  0.0), vec2(0.0, 1.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5, -0.5, -0.5), vec3( 0.0, -1.0,  0.0), vec2(1.0, 1.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5, -0.5,  0.5), vec3( 0.0, -1.0,  0.0), vec2(1.0, 0.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3(-0.5, -0.5,  0.5), vec3( 0.0, -1.0,  0.0), vec2(0.0, 0.0), vec4( 1.0,  0.0,  0.0, 1.0)),
 ];
 
 pub const CUBE_INDICES: [u32; 36] = [
@@ -0,0 +1,283 @@
+use ash::vk as avk;
+use gpu_allocator::MemoryLocation;
+
+use crate::*;
+
+const ID_FORMAT: avk::Format = avk::Format::R32G32_UINT;
+const NO_OBJECT: u32 = u32::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ObjectId {
+    pub group: usize,
+    pub instance: usize,
+}
+
+/// Result of `Renderer::request_object_id`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ObjectIdReadback {
+    pub pixel: (u32, u32),
+    /// `None` when nothing was drawn at `pixel`.
+    pub object: Option<ObjectId>,
+}
+
+struct InFlightRequest {
+    frame_index: usize,
+    pixel: (u32, u32),
+    /// `visible_indices` of every group when the pass was recorded, to turn the
+    /// instance slot written by the shader back into an instance index.
+    visible_indices: Vec<Vec<usize>>,
+}
+
+/// Offscreen pass that draws every visible instance with its `(group, slot)` id into
+/// a transient `R32G32_UINT` image. It only runs in frames with a pending request, and
+/// the pixel under the request is copied to a host-visible buffer that is read once
+/// the frame's fence has signalled.
+pub struct ObjectIdPass {
+    pipeline: tvk::Pipeline,
+    compact_pipeline: tvk::Pipeline,
+    render_pass: tvk::RenderPass,
+    depth_format: avk::Format,
+    readback: tvk::Buffer,
+    reverse_z: bool,
+    requested: Option<(u32, u32)>,
+    in_flight: Option<InFlightRequest>,
+    result: Option<ObjectIdReadback>,
+}
+
+impl ObjectIdPass {
+    pub const SHADERS: [&str; 3] = ["object_id.vert.spv", "object_id_compact.vert.spv", "object_id.frag.spv"];
+
+    pub(crate) fn new(context: &tvk::Context, shaders: &Shaders, descriptor: &tvk::Descriptor, reverse_z: bool) -> AnyResult<Self> {
+        let render_pass = context.create_offscreen_render_pass(ID_FORMAT, avk::ImageLayout::TRANSFER_SRC_OPTIMAL)?;
+        let (pipeline, compact_pipeline) = Self::create_pipelines(context, shaders, &render_pass, descriptor, reverse_z)?;
+        let readback = context.create_buffer(
+            avk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuToCpu,
+            size_of::<[u32; 2]>() as u64
+        )?;
+
+        Ok(Self {
+            pipeline,
+            compact_pipeline,
+            render_pass,
+            depth_format: context.physical_device.depth_format,
+            readback,
+            reverse_z,
+            requested: None,
+            in_flight: None,
+            result: None,
+        })
+    }
+
+    fn create_pipelines(
+        context: &tvk::Context,
+        shaders: &Shaders,
+        render_pass: &tvk::RenderPass,
+        descriptor: &tvk::Descriptor,
+        reverse_z: bool,
+    ) -> AnyResult<(tvk::Pipeline, tvk::Pipeline)> {
+        let [vertex_source, compact_vertex_source, fragment_source] = shaders.get_all(Self::SHADERS)?;
+        let state = tvk::PipelineState {
+            depth_compare_op: if reverse_z { avk::CompareOp::GREATER } else { avk::CompareOp::LESS },
+            blend: false,
+            push_constant_size: size_of::<u32>() as u32,
+            ..Default::default()
+        };
+        let pipeline = context.create_pipeline::<tvk::FullInstanceData>(
+            render_pass,
+            &[descriptor.layout],
+            &vertex_fragment_shaders(&vertex_source, &fragment_source),
+            &state
+        )?;
+        let compact_pipeline = context.create_pipeline::<tvk::CompactInstanceData>(
+            render_pass,
+            &[descriptor.layout],
+            &vertex_fragment_shaders(&compact_vertex_source, &fragment_source),
+            &state
+        )?;
+        Ok((pipeline, compact_pipeline))
+    }
+
+    /// Drops a request in flight, since its pixel refers to the old swapchain size.
+    pub fn resize(&mut self) {
+        self.in_flight = None;
+    }
+
+    pub(crate) fn set_reverse_z(&mut self, context: &tvk::Context, shaders: &Shaders, descriptor: &tvk::Descriptor, reverse_z: bool) -> AnyResult<()> {
+        (self.pipeline, self.compact_pipeline) = Self::create_pipelines(context, shaders, &self.render_pass, descriptor, reverse_z)?;
+        self.reverse_z = reverse_z;
+        Ok(())
+    }
+
+    /// Builds the pipelines again from `shaders`. They replace the current ones when the
+    /// returned function is called.
+    pub(crate) fn reload_shaders(
+        &self,
+        context: &tvk::Context,
+        shaders: &Shaders,
+        descriptor: &tvk::Descriptor,
+    ) -> AnyResult<PipelineSwap<Self>> {
+        let pipelines = Self::create_pipelines(context, shaders, &self.render_pass, descriptor, self.reverse_z)?;
+        Ok(Box::new(move |pass| (pass.pipeline, pass.compact_pipeline) = pipelines))
+    }
+
+    pub fn request(&mut self, pixel: (u32, u32)) {
+        self.requested = Some(pixel);
+    }
+
+    pub fn take_result(&mut self) -> Option<ObjectIdReadback> {
+        self.result.take()
+    }
+
+    /// Reads back the request recorded in `frame_index`. Call once that frame's fence
+    /// has signalled.
+    pub fn resolve(&mut self, frame_index: usize) -> AnyResult<()> {
+        if self.in_flight.as_ref().is_none_or(|r| r.frame_index != frame_index) {
+            return Ok(());
+        }
+        let request = self.in_flight.take().unwrap();
+        let id = self.readback.read_memory::<[u32; 2]>(1)?[0];
+        self.result = Some(ObjectIdReadback {
+            pixel: request.pixel,
+            object: decode_id(id, &request.visible_indices),
+        });
+        Ok(())
+    }
+
+    /// Starts the pending request in `frame_index` if none is in flight. Returns
+    /// whether the pass has to be recorded this frame.
+    pub fn prepare(&mut self, frame_index: usize, extent: avk::Extent2D, instance_groups: &[InstanceGroup]) -> bool {
+        if self.in_flight.is_some() {
+            return false;
+        }
+        let Some(pixel) = self.requested.take() else {
+            return false;
+        };
+        if pixel.0 >= extent.width || pixel.1 >= extent.height {
+            self.result = Some(ObjectIdReadback { pixel, object: None });
+            return false;
+        }
+        self.in_flight = Some(InFlightRequest {
+            frame_index,
+            pixel,
+            visible_indices: instance_groups.iter().map(|g| g.visible_indices()[..g.visible_count()].to_vec()).collect(),
+        });
+        true
+    }
+
+    /// Adds the id and readback passes to `graph` if a request was started in
+    /// `frame_index`.
+    pub(crate) fn add_passes<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        frame_index: usize,
+        extent: avk::Extent2D,
+        descriptor_set: avk::DescriptorSet,
+        views: &'a [ViewRecord],
+        instance_groups: &'a [InstanceGroup],
+    ) {
+        let reverse_z = self.reverse_z;
+        let Some(request) = self.in_flight.as_ref().filter(|r| r.frame_index == frame_index) else {
+            return;
+        };
+        let image = graph.create_image(TransientImageDesc::new(extent, ID_FORMAT));
+        let depth = graph.create_image(TransientImageDesc::new(extent, self.depth_format));
+        graph.add_pass("object id")
+            .color_attachment(image, AttachmentLoad::Clear(avk::ClearValue {
+                color: avk::ClearColorValue { uint32: [NO_OBJECT; 4] },
+            }))
+            .depth_attachment(depth, AttachmentLoad::clear_depth(if reverse_z { 0.0 } else { 1.0 }))
+            .execute(move |pass| {
+                let command_buffer = pass.command_buffer;
+                for view in views.iter().filter(|view| view.target.is_none()) {
+                    // Views that hide what is underneath must hide it from picking too.
+                    view.begin(command_buffer, reverse_z, view.clear_color.map(|_| avk::ClearColorValue { uint32: [NO_OBJECT; 4] }));
+                    for (group_index, instance_group) in instance_groups.iter().enumerate() {
+                        let Some(mesh) = instance_group.mesh.get() else {
+                            continue;
+                        };
+                        let pipeline = instance_group.format().pipeline(&self.pipeline, &self.compact_pipeline);
+                        command_buffer.bind_pipeline(pipeline);
+                        command_buffer.bind_descriptor_sets(pipeline.layout, descriptor_set, &[view.uniform_offset]);
+                        command_buffer.push_constants(pipeline.layout, &(group_index as u32));
+                        let buffers = [mesh.vertex_buffer.inner, instance_group.instance_buffer(frame_index).unwrap().inner];
+                        command_buffer.bind_vertex_buffers(&buffers);
+                        command_buffer.bind_index_buffer(&mesh.index_buffer);
+                        command_buffer.draw_indexed(mesh.indices.len() as u32, instance_group.visible_count() as u32, 0, 0, 0);
+                    }
+                }
+            });
+
+        let readback = graph.import_buffer(&self.readback, true);
+        let pixel = request.pixel;
+        graph.add_pass("object id readback")
+            .copy_from(image)
+            .copy_to(readback)
+            .execute(move |pass| {
+                pass.command_buffer.copy_image_to_buffer(
+                    pass.image(image),
+                    avk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    &self.readback,
+                    avk::Offset2D { x: pixel.0 as i32, y: pixel.1 as i32 },
+                    avk::Extent2D { width: 1, height: 1 },
+                );
+            });
+    }
+}
+
+/// Turns the `(group, slot)` written by the shader into the instance drawn in that slot.
+fn decode_id(id: [u32; 2], visible_indices: &[Vec<usize>]) -> Option<ObjectId> {
+    if id[0] == NO_OBJECT {
+        return None;
+    }
+    let group = id[0] as usize;
+    let instance = *visible_indices.get(group)?.get(id[1] as usize)?;
+    Some(ObjectId { group, instance })
+}
+
+impl Renderer {
+    /// Asks for the instance drawn at `pixel` (in swapchain pixels). The id pass runs
+    /// in the next rendered frame and the answer is available from `take_object_id`
+    /// once that frame has finished on the GPU, usually a frame or two later. A new
+    /// request replaces one that has not started yet.
+    pub fn request_object_id(&mut self, pixel: (u32, u32)) -> AnyResult<()> {
+        if self.object_id_pass.is_none() {
+            self.object_id_pass = Some(ObjectIdPass::new(&self.context, &self.shaders, &self.descriptor, self.reverse_z)?);
+        }
+        self.object_id_pass.as_mut().unwrap().request(pixel);
+        Ok(())
+    }
+
+    pub fn take_object_id(&mut self) -> Option<ObjectIdReadback> {
+        self.object_id_pass.as_mut()?.take_result()
+    }
+}
+
+impl AppData {
+    /// Requests the instance under the cursor from the GPU id pass.
+    pub fn request_pick(&mut self) -> AnyResult<()> {
+        let (x, y) = self.input_manager.mouse().position;
+        self.renderer.request_object_id((x.max(0.0) as u32, y.max(0.0) as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_map_back_to_instances() {
+        // Group 0 draws instances 4 and 1; group 1 hides its first instance.
+        let visible_indices = vec![vec![4, 1], vec![2, 3]];
+        assert_eq!(decode_id([0, 1], &visible_indices), Some(ObjectId { group: 0, instance: 1 }));
+        assert_eq!(decode_id([1, 0], &visible_indices), Some(ObjectId { group: 1, instance: 2 }));
+        assert_eq!(decode_id([NO_OBJECT, NO_OBJECT], &visible_indices), None);
+    }
+
+    #[test]
+    fn stale_ids_are_ignored() {
+        let visible_indices = vec![vec![0]];
+        assert_eq!(decode_id([0, 1], &visible_indices), None);
+        assert_eq!(decode_id([3, 0], &visible_indices), None);
+    }
+}
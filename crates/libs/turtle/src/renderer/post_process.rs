@@ -0,0 +1,278 @@
+use ash::vk as avk;
+use crate::*;
+
+/// Format the scene is drawn in before post-processing.
+pub(crate) const HDR_FORMAT: avk::Format = avk::Format::R16G16B16A16_SFLOAT;
+/// Most halvings in the bloom pyramid.
+pub const MAX_BLOOM_LEVELS: u32 = 8;
+/// Downsample and upsample sets per bloom level, plus one for the tonemap pass.
+const SETS_PER_FRAME: usize = 2 * MAX_BLOOM_LEVELS as usize + 1;
+
+const BLOOM: u32 = 1;
+const COLOR_GRADING: u32 = 2;
+const PREFILTER: u32 = 4;
+
+/// Curve mapping HDR scene values to the displayable `0..1` range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tonemapper {
+    /// Clamps, clipping everything above 1.
+    None,
+    Reinhard,
+    #[default]
+    Aces,
+    AgX,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bloom {
+    /// How much of the blurred highlights is added back to the image.
+    pub intensity: f32,
+    /// Exposed brightness above which pixels bloom.
+    pub threshold: f32,
+    /// Halvings in the blur pyramid, up to `MAX_BLOOM_LEVELS`. More spreads the glow wider.
+    pub levels: u32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            intensity: 0.05,
+            threshold: 1.0,
+            levels: 6,
+        }
+    }
+}
+
+/// Settings of the full-screen passes between the HDR scene and the swapchain, read
+/// every frame.
+#[derive(Clone)]
+pub struct PostProcess {
+    /// Multiplier applied to the scene before tonemapping.
+    pub exposure: f32,
+    pub tonemapper: Tonemapper,
+    /// `None` turns bloom off.
+    pub bloom: Option<Bloom>,
+    /// Color grading lookup table applied after tonemapping: `N` slices of `N`×`N`
+    /// texels side by side, so `N*N` wide and `N` high, with red across each slice,
+    /// green down and blue selecting the slice. Tables of any other shape are ignored.
+    pub color_grading_lut: Option<Handle<Texture>>,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            tonemapper: Tonemapper::default(),
+            bloom: Some(Bloom::default()),
+            color_grading_lut: None,
+        }
+    }
+}
+
+/// Matches the push constant block of the post-processing shaders.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PostProcessConstants {
+    exposure: f32,
+    tonemapper: u32,
+    bloom_intensity: f32,
+    bloom_threshold: f32,
+    /// Texel size of the image being filtered.
+    texel_size: [f32; 2],
+    flags: u32,
+    lut_size: f32,
+}
+
+/// Pipelines and per-frame descriptor sets of the post-processing chain.
+pub(crate) struct PostProcessPass {
+    downsample_pipeline: tvk::Pipeline,
+    upsample_pipeline: tvk::Pipeline,
+    tonemap_pipeline: tvk::Pipeline,
+    sampler: tvk::Sampler,
+    descriptor: tvk::TextureDescriptor,
+}
+
+impl PostProcessPass {
+    pub const SHADERS: [&str; 4] = ["fullscreen.vert.spv", "bloom_downsample.frag.spv", "bloom_upsample.frag.spv", "tonemap.frag.spv"];
+
+    pub fn new(context: &tvk::Context, shaders: &Shaders, swapchain_format: avk::Format, frames_in_flight: usize) -> AnyResult<Self> {
+        let descriptor = context.create_texture_descriptor_with_bindings((frames_in_flight * SETS_PER_FRAME) as u32, 3)?;
+        let [downsample_pipeline, upsample_pipeline, tonemap_pipeline] = Self::create_pipelines(context, shaders, swapchain_format, descriptor.layout)?;
+        Ok(Self {
+            downsample_pipeline,
+            upsample_pipeline,
+            tonemap_pipeline,
+            sampler: context.create_sampler(avk::Filter::LINEAR, avk::SamplerAddressMode::CLAMP_TO_EDGE)?,
+            descriptor,
+        })
+    }
+
+    /// Builds the pipelines again from `shaders`. They replace the current ones when the
+    /// returned function is called.
+    pub fn reload_shaders(&self, context: &tvk::Context, shaders: &Shaders, swapchain_format: avk::Format) -> AnyResult<PipelineSwap<Self>> {
+        let pipelines = Self::create_pipelines(context, shaders, swapchain_format, self.descriptor.layout)?;
+        Ok(Box::new(move |pass| [pass.downsample_pipeline, pass.upsample_pipeline, pass.tonemap_pipeline] = pipelines))
+    }
+
+    fn create_pipelines(context: &tvk::Context, shaders: &Shaders, swapchain_format: avk::Format, layout: avk::DescriptorSetLayout) -> AnyResult<[tvk::Pipeline; 3]> {
+        let hdr_render_pass = context.create_color_render_pass(HDR_FORMAT, avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)?;
+        let swapchain_render_pass = context.create_color_render_pass(swapchain_format, avk::ImageLayout::PRESENT_SRC_KHR)?;
+        let state = tvk::PipelineState {
+            blend: false,
+            vertex_input: false,
+            depth_test: false,
+            push_constant_size: size_of::<PostProcessConstants>() as u32,
+            ..Default::default()
+        };
+        let [vertex_source, downsample_source, upsample_source, tonemap_source] = shaders.get_all(Self::SHADERS)?;
+        let create_pipeline = |render_pass, fragment_source: &tvk::ShaderModule| {
+            context.create_pipeline::<()>(render_pass, &[layout], &vertex_fragment_shaders(&vertex_source, fragment_source), &state)
+        };
+
+        Ok([
+            create_pipeline(&hdr_render_pass, &downsample_source)?,
+            create_pipeline(&hdr_render_pass, &upsample_source)?,
+            create_pipeline(&swapchain_render_pass, &tonemap_source)?,
+        ])
+    }
+
+    /// Adds bloom and tonemapping of `hdr` into `output` to the graph. `lut` is the
+    /// color grading table when it is loaded and well-formed.
+    pub fn add_passes<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        frame_index: usize,
+        settings: &PostProcess,
+        lut: Option<&'a Texture>,
+        (hdr, extent): (GraphImage, avk::Extent2D),
+        output: GraphImage,
+    ) {
+        let mut constants = PostProcessConstants {
+            exposure: settings.exposure,
+            tonemapper: settings.tonemapper as u32,
+            bloom_intensity: 0.0,
+            bloom_threshold: 0.0,
+            texel_size: [0.0; 2],
+            flags: 0,
+            lut_size: 0.0,
+        };
+        let first_set = frame_index * SETS_PER_FRAME;
+
+        let bloom = settings.bloom.and_then(|bloom| {
+            constants.bloom_intensity = bloom.intensity;
+            constants.bloom_threshold = bloom.threshold;
+            let bloom_image = self.add_bloom_passes(graph, first_set, constants, bloom.levels, (hdr, extent))?;
+            constants.flags |= BLOOM;
+            Some(bloom_image)
+        });
+        if let Some(lut) = lut {
+            constants.flags |= COLOR_GRADING;
+            constants.lut_size = lut.image.extent.height as f32;
+        }
+
+        let set = first_set + SETS_PER_FRAME - 1;
+        let mut pass = graph.add_pass("tonemap")
+            .color_attachment(output, AttachmentLoad::DontCare)
+            .sample(hdr);
+        if let Some(bloom) = bloom {
+            pass = pass.sample(bloom);
+        }
+        pass.execute(move |pass| {
+            self.descriptor.update_binding(set, 0, pass.image_view(hdr), &self.sampler);
+            self.descriptor.update_binding(set, 1, pass.image_view(bloom.unwrap_or(hdr)), &self.sampler);
+            match lut {
+                Some(lut) => self.descriptor.update_binding(set, 2, lut.image_view.inner, &lut.sampler),
+                None => self.descriptor.update_binding(set, 2, pass.image_view(hdr), &self.sampler),
+            }
+            self.draw(pass.command_buffer, &self.tonemap_pipeline, set, constants, extent);
+        });
+    }
+
+    /// Blurs the highlights of `hdr` through a pyramid of half-size images, returning
+    /// the half-size image that sums every level. `None` when the image is too small
+    /// to downsample.
+    fn add_bloom_passes<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        first_set: usize,
+        constants: PostProcessConstants,
+        levels: u32,
+        (hdr, extent): (GraphImage, avk::Extent2D),
+    ) -> Option<GraphImage> {
+        let mut sizes = Vec::new();
+        let mut size = extent;
+        while sizes.len() < levels.min(MAX_BLOOM_LEVELS) as usize && size.width >= 2 && size.height >= 2 {
+            size = avk::Extent2D { width: size.width / 2, height: size.height / 2 };
+            sizes.push(size);
+        }
+        if sizes.is_empty() {
+            return None;
+        }
+
+        let mut downsampled = Vec::with_capacity(sizes.len());
+        let (mut source, mut source_extent) = (hdr, extent);
+        for (level, &size) in sizes.iter().enumerate() {
+            let image = graph.create_image(TransientImageDesc::new(size, HDR_FORMAT));
+            let set = first_set + level;
+            let constants = PostProcessConstants {
+                texel_size: texel_size(source_extent),
+                flags: if level == 0 { PREFILTER } else { 0 },
+                ..constants
+            };
+            graph.add_pass("bloom downsample")
+                .color_attachment(image, AttachmentLoad::DontCare)
+                .sample(source)
+                .execute(move |pass| {
+                    self.descriptor.update_binding(set, 0, pass.image_view(source), &self.sampler);
+                    self.draw(pass.command_buffer, &self.downsample_pipeline, set, constants, size);
+                });
+            downsampled.push(image);
+            (source, source_extent) = (image, size);
+        }
+
+        let mut lower = *downsampled.last().unwrap();
+        for level in (0..sizes.len() - 1).rev() {
+            let (same_size, size) = (downsampled[level], sizes[level]);
+            let image = graph.create_image(TransientImageDesc::new(size, HDR_FORMAT));
+            let set = first_set + MAX_BLOOM_LEVELS as usize + level;
+            let constants = PostProcessConstants {
+                texel_size: texel_size(sizes[level + 1]),
+                ..constants
+            };
+            graph.add_pass("bloom upsample")
+                .color_attachment(image, AttachmentLoad::DontCare)
+                .sample(same_size)
+                .sample(lower)
+                .execute(move |pass| {
+                    self.descriptor.update_binding(set, 0, pass.image_view(same_size), &self.sampler);
+                    self.descriptor.update_binding(set, 1, pass.image_view(lower), &self.sampler);
+                    self.draw(pass.command_buffer, &self.upsample_pipeline, set, constants, size);
+                });
+            lower = image;
+        }
+        Some(lower)
+    }
+
+    fn draw(&self, command_buffer: &tvk::CommandBuffer, pipeline: &tvk::Pipeline, set: usize, constants: PostProcessConstants, extent: avk::Extent2D) {
+        let rect = avk::Rect2D { offset: avk::Offset2D::default(), extent };
+        command_buffer.set_scissor(rect);
+        command_buffer.set_viewport(avk::Viewport::default()
+            .width(extent.width as f32)
+            .height(extent.height as f32)
+            .max_depth(1.0));
+        command_buffer.bind_pipeline(pipeline);
+        command_buffer.bind_descriptor_sets(pipeline.layout, self.descriptor.sets[set], &[]);
+        command_buffer.push_constants(pipeline.layout, &constants);
+        command_buffer.draw(3, 1, 0, 0);
+    }
+}
+
+fn texel_size(extent: avk::Extent2D) -> [f32; 2] {
+    [1.0 / extent.width as f32, 1.0 / extent.height as f32]
+}
+
+/// Whether `texture` has the shape `PostProcess::color_grading_lut` expects.
+pub(crate) fn is_color_grading_lut(texture: &Texture) -> bool {
+    let extent = texture.image.extent;
+    extent.height >= 2 && extent.width == extent.height * extent.height
+}
@@ -0,0 +1,956 @@
+use std::{any::Any, cmp::Reverse, collections::{BinaryHeap, HashMap, HashSet}, sync::Arc};
+
+use ash::vk as avk;
+use crate::*;
+
+/// Image declared in a `RenderGraph`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GraphImage(usize);
+
+/// Buffer declared in a `RenderGraph`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GraphBuffer(usize);
+
+/// How an attachment starts out when its pass begins.
+#[derive(Clone, Copy)]
+pub enum AttachmentLoad {
+    Clear(avk::ClearValue),
+    /// Keep what earlier passes wrote. Counts as a read of the image.
+    Load,
+    DontCare,
+}
+
+impl AttachmentLoad {
+    pub fn clear_color(color: [f32; 4]) -> Self {
+        AttachmentLoad::Clear(avk::ClearValue { color: avk::ClearColorValue { float32: color } })
+    }
+
+    pub fn clear_depth(depth: f32) -> Self {
+        AttachmentLoad::Clear(avk::ClearValue { depth_stencil: avk::ClearDepthStencilValue { depth, stencil: 0 } })
+    }
+
+    fn op(&self) -> avk::AttachmentLoadOp {
+        match self {
+            AttachmentLoad::Clear(_) => avk::AttachmentLoadOp::CLEAR,
+            AttachmentLoad::Load => avk::AttachmentLoadOp::LOAD,
+            AttachmentLoad::DontCare => avk::AttachmentLoadOp::DONT_CARE,
+        }
+    }
+}
+
+/// Image the graph allocates for the frame. Transient images whose lifetimes don't
+/// overlap share memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TransientImageDesc {
+    pub extent: avk::Extent2D,
+    pub format: avk::Format,
+    pub samples: avk::SampleCountFlags,
+    /// Layers of an image array, sampled through a 2D array view. `None` makes a plain
+    /// 2D image.
+    pub layers: Option<u32>,
+}
+
+impl TransientImageDesc {
+    pub fn new(extent: avk::Extent2D, format: avk::Format) -> Self {
+        Self {
+            extent,
+            format,
+            samples: avk::SampleCountFlags::TYPE_1,
+            layers: None,
+        }
+    }
+
+    pub fn with_samples(mut self, samples: avk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn with_layers(mut self, layers: u32) -> Self {
+        self.layers = Some(layers);
+        self
+    }
+}
+
+/// Image owned outside the graph, like a swapchain image or a render target. Imported
+/// images outlive the frame, so passes writing them are never culled.
+#[derive(Clone, Copy, Debug)]
+pub struct ImportedImage {
+    pub image: avk::Image,
+    pub view: avk::ImageView,
+    pub extent: avk::Extent2D,
+    pub format: avk::Format,
+    /// Layout the image is in when the frame starts.
+    pub initial_layout: avk::ImageLayout,
+    /// Layout to leave the image in. `None` leaves it in whatever layout it was last used in.
+    pub final_layout: Option<avk::ImageLayout>,
+}
+
+enum ImageResource {
+    Imported(ImportedImage),
+    Transient(TransientImageDesc),
+}
+
+impl ImageResource {
+    fn extent(&self) -> avk::Extent2D {
+        match self {
+            ImageResource::Imported(image) => image.extent,
+            ImageResource::Transient(desc) => desc.extent,
+        }
+    }
+
+    fn format(&self) -> avk::Format {
+        match self {
+            ImageResource::Imported(image) => image.format,
+            ImageResource::Transient(desc) => desc.format,
+        }
+    }
+
+    fn samples(&self) -> avk::SampleCountFlags {
+        match self {
+            ImageResource::Imported(_) => avk::SampleCountFlags::TYPE_1,
+            ImageResource::Transient(desc) => desc.samples,
+        }
+    }
+
+    fn layers(&self) -> u32 {
+        match self {
+            ImageResource::Imported(_) => 1,
+            ImageResource::Transient(desc) => desc.layers.unwrap_or(1),
+        }
+    }
+}
+
+struct BufferResource {
+    buffer: avk::Buffer,
+    /// Make the last write visible to the host at the end of the frame.
+    host_read: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum ImageUse {
+    ColorAttachment,
+    DepthAttachment,
+    /// Receives the resolved samples of the color attachment at the same index.
+    ResolveAttachment,
+    Sampled,
+    TransferSrc,
+}
+
+impl ImageUse {
+    fn layout(self) -> avk::ImageLayout {
+        match self {
+            ImageUse::ColorAttachment | ImageUse::ResolveAttachment => avk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ImageUse::DepthAttachment => avk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ImageUse::Sampled => avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ImageUse::TransferSrc => avk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+        }
+    }
+
+    fn stages(self) -> avk::PipelineStageFlags {
+        match self {
+            ImageUse::ColorAttachment | ImageUse::ResolveAttachment => avk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            ImageUse::DepthAttachment => avk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | avk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            ImageUse::Sampled => avk::PipelineStageFlags::FRAGMENT_SHADER,
+            ImageUse::TransferSrc => avk::PipelineStageFlags::TRANSFER,
+        }
+    }
+
+    fn access(self) -> avk::AccessFlags {
+        match self {
+            ImageUse::ColorAttachment => avk::AccessFlags::COLOR_ATTACHMENT_READ | avk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ImageUse::DepthAttachment => avk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | avk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            ImageUse::ResolveAttachment => avk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ImageUse::Sampled => avk::AccessFlags::SHADER_READ,
+            ImageUse::TransferSrc => avk::AccessFlags::TRANSFER_READ,
+        }
+    }
+
+    fn usage_flags(self) -> avk::ImageUsageFlags {
+        match self {
+            ImageUse::ColorAttachment | ImageUse::ResolveAttachment => avk::ImageUsageFlags::COLOR_ATTACHMENT,
+            ImageUse::DepthAttachment => avk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            ImageUse::Sampled => avk::ImageUsageFlags::SAMPLED,
+            ImageUse::TransferSrc => avk::ImageUsageFlags::TRANSFER_SRC,
+        }
+    }
+
+    fn is_attachment(self) -> bool {
+        matches!(self, ImageUse::ColorAttachment | ImageUse::DepthAttachment | ImageUse::ResolveAttachment)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ImageAccess {
+    image: GraphImage,
+    usage: ImageUse,
+    /// Set for attachments.
+    load: Option<AttachmentLoad>,
+    /// Single layer of an image array the access is limited to. `None` covers them all.
+    layer: Option<u32>,
+}
+
+impl ImageAccess {
+    fn reads(&self) -> bool {
+        match self.load {
+            Some(load) => matches!(load, AttachmentLoad::Load),
+            None => true,
+        }
+    }
+
+    fn writes(&self) -> bool {
+        self.usage.is_attachment()
+    }
+}
+
+/// Vulkan objects behind a graph image for the frame being recorded.
+#[derive(Clone)]
+struct ResolvedImage {
+    image: avk::Image,
+    /// Covers every layer.
+    view: avk::ImageView,
+    /// One view per layer of image arrays, empty otherwise.
+    layer_views: Vec<avk::ImageView>,
+}
+
+/// Everything a pass's callback needs to find the resources it declared.
+pub struct PassContext<'c> {
+    pub command_buffer: &'c tvk::CommandBuffer,
+    images: &'c [ResolvedImage],
+    buffers: &'c [BufferResource],
+}
+
+impl PassContext<'_> {
+    pub fn image(&self, image: GraphImage) -> avk::Image {
+        self.images[image.0].image
+    }
+
+    /// View of the whole image; a 2D array view for image arrays.
+    pub fn image_view(&self, image: GraphImage) -> avk::ImageView {
+        self.images[image.0].view
+    }
+
+    pub fn buffer(&self, buffer: GraphBuffer) -> avk::Buffer {
+        self.buffers[buffer.0].buffer
+    }
+}
+
+type PassCallback<'a> = Box<dyn FnOnce(&PassContext) + 'a>;
+
+struct Pass<'a> {
+    name: &'static str,
+    images: Vec<ImageAccess>,
+    /// Buffers written by transfers.
+    buffers: Vec<GraphBuffer>,
+    side_effects: bool,
+    execute: Option<PassCallback<'a>>,
+}
+
+impl Pass<'_> {
+    /// Color attachments in declaration order, the depth attachment, then the resolve
+    /// attachments, matching the attachment order of the pass's render pass.
+    fn attachments(&self) -> impl Iterator<Item = &ImageAccess> {
+        let of = move |usage| self.images.iter().filter(move |access| access.usage == usage);
+        of(ImageUse::ColorAttachment)
+            .chain(of(ImageUse::DepthAttachment).take(1))
+            .chain(of(ImageUse::ResolveAttachment))
+    }
+}
+
+/// Declares the resources of a pass, then `execute` adds it to the graph.
+pub struct PassBuilder<'g, 'a> {
+    graph: &'g mut RenderGraph<'a>,
+    pass: Pass<'a>,
+}
+
+impl<'a> PassBuilder<'_, 'a> {
+    /// Color attachments are bound in the order they are declared. Passes with
+    /// attachments run inside a render pass covering all of them.
+    pub fn color_attachment(mut self, image: GraphImage, load: AttachmentLoad) -> Self {
+        self.pass.images.push(ImageAccess { image, usage: ImageUse::ColorAttachment, load: Some(load), layer: None });
+        self
+    }
+
+    pub fn depth_attachment(mut self, image: GraphImage, load: AttachmentLoad) -> Self {
+        self.pass.images.push(ImageAccess { image, usage: ImageUse::DepthAttachment, load: Some(load), layer: None });
+        self
+    }
+
+    /// Renders depth into one layer of an image array, leaving the other layers as
+    /// they are.
+    pub fn depth_attachment_layer(mut self, image: GraphImage, layer: u32, load: AttachmentLoad) -> Self {
+        self.pass.images.push(ImageAccess { image, usage: ImageUse::DepthAttachment, load: Some(load), layer: Some(layer) });
+        self
+    }
+
+    /// Resolves the multisampled color attachment declared at the same position among
+    /// the color attachments into `image`. Either every color attachment has a resolve
+    /// attachment or none does.
+    pub fn resolve_attachment(mut self, image: GraphImage) -> Self {
+        self.pass.images.push(ImageAccess { image, usage: ImageUse::ResolveAttachment, load: Some(AttachmentLoad::DontCare), layer: None });
+        self
+    }
+
+    /// Reads the image from fragment shaders. Sees what passes declared earlier wrote
+    /// to it.
+    pub fn sample(mut self, image: GraphImage) -> Self {
+        self.pass.images.push(ImageAccess { image, usage: ImageUse::Sampled, load: None, layer: None });
+        self
+    }
+
+    /// Reads the image with transfer commands.
+    pub fn copy_from(mut self, image: GraphImage) -> Self {
+        self.pass.images.push(ImageAccess { image, usage: ImageUse::TransferSrc, load: None, layer: None });
+        self
+    }
+
+    /// Writes the buffer with transfer commands. Buffer writes are the only buffer
+    /// accesses the graph tracks: passes reading a graph buffer on the GPU get no
+    /// barrier, so buffers are for results read back by the host.
+    pub fn copy_to(mut self, buffer: GraphBuffer) -> Self {
+        self.pass.buffers.push(buffer);
+        self
+    }
+
+    /// Keeps the pass even when nothing reads what it writes.
+    pub fn side_effects(mut self) -> Self {
+        self.pass.side_effects = true;
+        self
+    }
+
+    pub fn execute(mut self, execute: impl FnOnce(&PassContext) + 'a) {
+        self.pass.execute = Some(Box::new(execute));
+        self.graph.passes.push(self.pass);
+    }
+}
+
+/// One frame of passes and the resources they use.
+///
+/// Passes only declare what they read and write. `execute` orders them so that the
+/// accesses to each resource happen in declaration order, culls passes whose results
+/// are never used, allocates transient images (aliasing their memory where lifetimes
+/// allow) and records the layout transitions and barriers between passes.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    images: Vec<ImageResource>,
+    buffers: Vec<BufferResource>,
+    passes: Vec<Pass<'a>>,
+    setup: Vec<PassCallback<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn import_image(&mut self, image: ImportedImage) -> GraphImage {
+        self.images.push(ImageResource::Imported(image));
+        GraphImage(self.images.len() - 1)
+    }
+
+    pub fn create_image(&mut self, desc: TransientImageDesc) -> GraphImage {
+        self.images.push(ImageResource::Transient(desc));
+        GraphImage(self.images.len() - 1)
+    }
+
+    pub fn import_buffer(&mut self, buffer: &tvk::Buffer, host_read: bool) -> GraphBuffer {
+        self.buffers.push(BufferResource { buffer: buffer.inner, host_read });
+        GraphBuffer(self.buffers.len() - 1)
+    }
+
+    /// Runs `callback` once the graph's images exist, before any pass is recorded. For
+    /// writing descriptor sets that several passes bind, which can't be updated once a
+    /// pass has bound them.
+    pub fn before_passes(&mut self, callback: impl FnOnce(&PassContext) + 'a) {
+        self.setup.push(Box::new(callback));
+    }
+
+    pub fn add_pass(&mut self, name: &'static str) -> PassBuilder<'_, 'a> {
+        PassBuilder {
+            graph: self,
+            pass: Pass {
+                name,
+                images: Vec::new(),
+                buffers: Vec::new(),
+                side_effects: false,
+                execute: None,
+            },
+        }
+    }
+
+    /// Records every pass that contributes to an imported resource or has side effects.
+    /// Returns what must stay alive until the command buffer has finished executing.
+    pub fn execute(
+        mut self,
+        context: &tvk::Context,
+        command_buffer: &tvk::CommandBuffer,
+        cache: &mut RenderGraphCache,
+    ) -> AnyResult<Vec<Arc<dyn Any>>> {
+        let order = self.sorted_passes()?;
+        let order = self.cull(order);
+        let mut retained: Vec<Arc<dyn Any>> = Vec::new();
+
+        let lifetimes = self.lifetimes(&order);
+        let mut usage = vec![avk::ImageUsageFlags::empty(); self.images.len()];
+        for access in order.iter().flat_map(|&pass| self.passes[pass].images.iter()) {
+            usage[access.image.0] |= access.usage.usage_flags();
+        }
+        let transients = self.images.iter().enumerate().filter_map(|(i, image)| match image {
+            ImageResource::Transient(desc) => lifetimes[i].map(|lifetime| TransientKey { image: i, desc: *desc, usage: usage[i], lifetime }),
+            ImageResource::Imported(_) => None,
+        }).collect::<Vec<_>>();
+        retained.extend(cache.prepare_transients(context, transients)?);
+
+        let resolved = self.images.iter().enumerate().map(|(i, image)| match image {
+            ImageResource::Imported(image) => ResolvedImage { image: image.image, view: image.view, layer_views: Vec::new() },
+            ImageResource::Transient(_) => cache.transients.get(&i).map_or(
+                ResolvedImage { image: avk::Image::null(), view: avk::ImageView::null(), layer_views: Vec::new() },
+                |t| ResolvedImage {
+                    image: t.image.inner,
+                    view: t.view.inner,
+                    layer_views: t.layer_views.iter().map(|view| view.inner).collect(),
+                },
+            ),
+        }).collect::<Vec<_>>();
+
+        // Tracked per layer, so passes can render into the layers of an array one by one.
+        let mut image_states = self.images.iter().map(|image| {
+            let layout = match image {
+                ImageResource::Imported(image) => image.initial_layout,
+                ImageResource::Transient(_) => avk::ImageLayout::UNDEFINED,
+            };
+            vec![ResourceState::external(layout); image.layers() as usize]
+        }).collect::<Vec<_>>();
+        let mut buffer_states = vec![ResourceState::external(avk::ImageLayout::UNDEFINED); self.buffers.len()];
+
+        let setup_context = PassContext { command_buffer, images: &resolved, buffers: &self.buffers };
+        for setup in self.setup.drain(..) {
+            setup(&setup_context);
+        }
+
+        for (position, &pass_index) in order.iter().enumerate() {
+            self.record_barriers(command_buffer, pass_index, &resolved, &mut image_states, &mut buffer_states);
+
+            let pass = &mut self.passes[pass_index];
+            let execute = pass.execute.take().unwrap();
+            let pass_context = PassContext { command_buffer, images: &resolved, buffers: &self.buffers };
+            if pass.attachments().next().is_none() {
+                execute(&pass_context);
+                continue;
+            }
+
+            let extent = self.images[pass.attachments().next().unwrap().image.0].extent();
+            if pass.attachments().any(|access| self.images[access.image.0].extent() != extent) {
+                return Err(format!("attachments of pass '{}' differ in size", pass.name).into());
+            }
+            let key = RenderPassKey {
+                attachments: pass.attachments().map(|access| {
+                    let image = access.image.0;
+                    let kept = matches!(self.images[image], ImageResource::Imported(_))
+                        || lifetimes[image].is_some_and(|(_, last)| last > position);
+                    (self.images[image].format(), self.images[image].samples(), access.usage, access.load.unwrap().op(), kept)
+                }).collect(),
+            };
+            let render_pass = cache.render_pass(context, &key)?;
+            let views = pass.attachments().map(|access| {
+                let image = &resolved[access.image.0];
+                access.layer.map_or(image.view, |layer| image.layer_views[layer as usize])
+            }).collect::<Vec<_>>();
+            let frame_buffer = Arc::new(tvk::FrameBuffer::with_attachments(context.logical_device.clone(), extent, render_pass, &views)?);
+            let clear_values = pass.attachments().map(|access| match access.load {
+                Some(AttachmentLoad::Clear(value)) => value,
+                _ => avk::ClearValue::default(),
+            }).collect::<Vec<_>>();
+
+            command_buffer.begin_render_pass(extent, render_pass, &frame_buffer, avk::SubpassContents::INLINE, &clear_values);
+            execute(&pass_context);
+            command_buffer.end_render_pass();
+            retained.push(frame_buffer);
+        }
+
+        self.record_final_barriers(command_buffer, &resolved, &image_states, &buffer_states);
+        Ok(retained)
+    }
+
+    /// Topological order of all passes, preferring declaration order.
+    fn sorted_passes(&self) -> AnyResult<Vec<usize>> {
+        let mut edges: Vec<HashSet<usize>> = vec![HashSet::new(); self.passes.len()];
+        // Accesses in declaration order: a reader depends on the last writer before it,
+        // a writer on the last writer and every reader since.
+        let mut add_dependencies = |accesses: Vec<(usize, bool, bool)>| {
+            let mut last_writer: Option<usize> = None;
+            let mut readers: Vec<usize> = Vec::new();
+            for (pass, reads, writes) in accesses {
+                if let Some(writer) = last_writer.filter(|&writer| writer != pass) {
+                    edges[writer].insert(pass);
+                }
+                if writes {
+                    for reader in readers.drain(..).filter(|&reader| reader != pass) {
+                        edges[reader].insert(pass);
+                    }
+                    last_writer = Some(pass);
+                } else if reads {
+                    readers.push(pass);
+                }
+            }
+        };
+        for image in 0..self.images.len() {
+            add_dependencies(self.passes.iter().enumerate().flat_map(|(pass, p)| {
+                p.images.iter().filter(|access| access.image.0 == image).map(move |access| (pass, access.reads(), access.writes()))
+            }).collect());
+        }
+        for buffer in 0..self.buffers.len() {
+            add_dependencies(self.passes.iter().enumerate()
+                .filter(|(_, p)| p.buffers.contains(&GraphBuffer(buffer)))
+                .map(|(pass, _)| (pass, false, true))
+                .collect());
+        }
+
+        let mut incoming = vec![0; self.passes.len()];
+        for targets in edges.iter() {
+            for &target in targets.iter() {
+                incoming[target] += 1;
+            }
+        }
+        let mut ready = (0..self.passes.len()).filter(|&pass| incoming[pass] == 0).map(Reverse).collect::<BinaryHeap<_>>();
+        let mut order = Vec::with_capacity(self.passes.len());
+        while let Some(Reverse(pass)) = ready.pop() {
+            order.push(pass);
+            for &target in edges[pass].iter() {
+                incoming[target] -= 1;
+                if incoming[target] == 0 {
+                    ready.push(Reverse(target));
+                }
+            }
+        }
+        if order.len() < self.passes.len() {
+            let stuck = (0..self.passes.len()).filter(|pass| !order.contains(pass)).map(|pass| self.passes[pass].name).collect::<Vec<_>>();
+            return Err(format!("render graph passes depend on each other in a cycle: {}", stuck.join(", ")).into());
+        }
+        Ok(order)
+    }
+
+    /// Drops passes whose writes are never read, walking backwards from the imported
+    /// resources and the passes with side effects.
+    fn cull(&self, order: Vec<usize>) -> Vec<usize> {
+        let mut wanted_images = self.images.iter().map(|image| matches!(image, ImageResource::Imported(_))).collect::<Vec<_>>();
+        let mut kept = Vec::with_capacity(order.len());
+        for &pass_index in order.iter().rev() {
+            let pass = &self.passes[pass_index];
+            let needed = pass.side_effects
+                || !pass.buffers.is_empty()
+                || pass.images.iter().any(|access| access.writes() && wanted_images[access.image.0]);
+            if !needed {
+                log::trace!("culling render graph pass '{}'", pass.name);
+                continue;
+            }
+            for access in pass.images.iter() {
+                let imported = matches!(self.images[access.image.0], ImageResource::Imported(_));
+                // Writing a single layer leaves the others wanted.
+                if access.writes() && !access.reads() && !imported && access.layer.is_none() {
+                    wanted_images[access.image.0] = false;
+                }
+            }
+            for access in pass.images.iter().filter(|access| access.reads()) {
+                wanted_images[access.image.0] = true;
+            }
+            kept.push(pass_index);
+        }
+        kept.reverse();
+        kept
+    }
+
+    /// Position in `order` of the first and last pass using each image.
+    fn lifetimes(&self, order: &[usize]) -> Vec<Option<(usize, usize)>> {
+        let mut lifetimes = vec![None; self.images.len()];
+        for (position, &pass) in order.iter().enumerate() {
+            for access in self.passes[pass].images.iter() {
+                let lifetime: &mut Option<(usize, usize)> = &mut lifetimes[access.image.0];
+                *lifetime = Some(lifetime.map_or((position, position), |(first, _)| (first, position)));
+            }
+        }
+        lifetimes
+    }
+
+    fn record_barriers(
+        &self,
+        command_buffer: &tvk::CommandBuffer,
+        pass_index: usize,
+        resolved: &[ResolvedImage],
+        image_states: &mut [Vec<ResourceState>],
+        buffer_states: &mut [ResourceState],
+    ) {
+        let pass = &self.passes[pass_index];
+        let mut src_stages = avk::PipelineStageFlags::empty();
+        let mut dst_stages = avk::PipelineStageFlags::empty();
+        let mut image_barriers = Vec::new();
+        let mut memory_barriers = Vec::new();
+
+        for access in pass.images.iter() {
+            let (layout, stages, access_flags) = (access.usage.layout(), access.usage.stages(), access.usage.access());
+            let states = &mut image_states[access.image.0];
+            let layers = match access.layer {
+                Some(layer) => layer as usize..layer as usize + 1,
+                None => 0..states.len(),
+            };
+            for layer in layers {
+                let state = &mut states[layer];
+                if state.layout == layout && !state.written && !access.writes() {
+                    state.stages |= stages;
+                    continue;
+                }
+
+                let discard = access.load.is_some_and(|load| !matches!(load, AttachmentLoad::Load));
+                image_barriers.push(avk::ImageMemoryBarrier::default()
+                    .image(resolved[access.image.0].image)
+                    .old_layout(if discard { avk::ImageLayout::UNDEFINED } else { state.layout })
+                    .new_layout(layout)
+                    .src_queue_family_index(avk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(avk::QUEUE_FAMILY_IGNORED)
+                    .src_access_mask(if state.written { state.access } else { avk::AccessFlags::empty() })
+                    .dst_access_mask(access_flags)
+                    .subresource_range(layer_range(self.images[access.image.0].format(), layer as u32)));
+                src_stages |= state.stages;
+                dst_stages |= stages;
+                *state = ResourceState { layout, stages, access: access_flags, written: access.writes() };
+            }
+        }
+
+        for buffer in pass.buffers.iter() {
+            let state = &mut buffer_states[buffer.0];
+            memory_barriers.push(avk::MemoryBarrier::default()
+                .src_access_mask(if state.written { state.access } else { avk::AccessFlags::empty() })
+                .dst_access_mask(avk::AccessFlags::TRANSFER_WRITE));
+            src_stages |= state.stages;
+            dst_stages |= avk::PipelineStageFlags::TRANSFER;
+            *state = ResourceState {
+                layout: avk::ImageLayout::UNDEFINED,
+                stages: avk::PipelineStageFlags::TRANSFER,
+                access: avk::AccessFlags::TRANSFER_WRITE,
+                written: true,
+            };
+        }
+
+        if !image_barriers.is_empty() || !memory_barriers.is_empty() {
+            command_buffer.pipeline_barrier(src_stages, dst_stages, &memory_barriers, &image_barriers);
+        }
+    }
+
+    /// Moves imported images to their final layouts and makes buffer writes visible
+    /// to the host.
+    fn record_final_barriers(
+        &self,
+        command_buffer: &tvk::CommandBuffer,
+        resolved: &[ResolvedImage],
+        image_states: &[Vec<ResourceState>],
+        buffer_states: &[ResourceState],
+    ) {
+        let mut src_stages = avk::PipelineStageFlags::empty();
+        let mut image_barriers = Vec::new();
+        for (i, image) in self.images.iter().enumerate() {
+            let ImageResource::Imported(ImportedImage { final_layout: Some(final_layout), .. }) = image else {
+                continue;
+            };
+            // Imported images have a single layer.
+            let state = &image_states[i][0];
+            if state.layout == *final_layout && !state.written {
+                continue;
+            }
+            image_barriers.push(avk::ImageMemoryBarrier::default()
+                .image(resolved[i].image)
+                .old_layout(state.layout)
+                .new_layout(*final_layout)
+                .src_queue_family_index(avk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(avk::QUEUE_FAMILY_IGNORED)
+                .src_access_mask(if state.written { state.access } else { avk::AccessFlags::empty() })
+                .dst_access_mask(avk::AccessFlags::empty())
+                .subresource_range(full_range(image.format())));
+            src_stages |= state.stages;
+        }
+        if !image_barriers.is_empty() {
+            command_buffer.pipeline_barrier(src_stages, avk::PipelineStageFlags::BOTTOM_OF_PIPE, &[], &image_barriers);
+        }
+
+        let host_writes = self.buffers.iter().zip(buffer_states.iter())
+            .filter(|(buffer, state)| buffer.host_read && state.written)
+            .fold(avk::PipelineStageFlags::empty(), |stages, (_, state)| stages | state.stages);
+        if !host_writes.is_empty() {
+            let barrier = avk::MemoryBarrier::default()
+                .src_access_mask(avk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(avk::AccessFlags::HOST_READ);
+            command_buffer.pipeline_barrier(host_writes, avk::PipelineStageFlags::HOST, &[barrier], &[]);
+        }
+    }
+}
+
+/// Last access to a resource in the frame being recorded.
+#[derive(Clone, Copy)]
+struct ResourceState {
+    layout: avk::ImageLayout,
+    stages: avk::PipelineStageFlags,
+    access: avk::AccessFlags,
+    written: bool,
+}
+
+impl ResourceState {
+    /// State at the start of the frame. Earlier frames, or earlier users of aliased
+    /// memory, may still be accessing the resource in any way.
+    fn external(layout: avk::ImageLayout) -> Self {
+        Self {
+            layout,
+            stages: avk::PipelineStageFlags::ALL_COMMANDS,
+            access: avk::AccessFlags::MEMORY_WRITE,
+            written: true,
+        }
+    }
+}
+
+fn layer_range(format: avk::Format, layer: u32) -> avk::ImageSubresourceRange {
+    avk::ImageSubresourceRange {
+        base_array_layer: layer,
+        layer_count: 1,
+        ..full_range(format)
+    }
+}
+
+fn full_range(format: avk::Format) -> avk::ImageSubresourceRange {
+    let aspect_mask = match format {
+        avk::Format::D16_UNORM | avk::Format::D32_SFLOAT | avk::Format::X8_D24_UNORM_PACK32 => avk::ImageAspectFlags::DEPTH,
+        avk::Format::D16_UNORM_S8_UINT | avk::Format::D24_UNORM_S8_UINT | avk::Format::D32_SFLOAT_S8_UINT => {
+            avk::ImageAspectFlags::DEPTH | avk::ImageAspectFlags::STENCIL
+        },
+        _ => avk::ImageAspectFlags::COLOR,
+    };
+    avk::ImageSubresourceRange {
+        aspect_mask,
+        base_mip_level: 0,
+        level_count: avk::REMAINING_MIP_LEVELS,
+        base_array_layer: 0,
+        layer_count: avk::REMAINING_ARRAY_LAYERS,
+    }
+}
+
+/// Greedily packs images into memory blocks, sharing a block between images whose
+/// lifetimes don't overlap. Returns the requirements of each block and the indices of
+/// the images bound to it.
+fn alias_memory(lifetimes: &[(usize, usize)], requirements: &[avk::MemoryRequirements]) -> Vec<(avk::MemoryRequirements, Vec<usize>)> {
+    let mut by_start = (0..lifetimes.len()).collect::<Vec<_>>();
+    by_start.sort_by_key(|&i| lifetimes[i].0);
+    let mut blocks: Vec<(avk::MemoryRequirements, usize, Vec<usize>)> = Vec::new();
+    for i in by_start {
+        let (first, last) = lifetimes[i];
+        let block = blocks.iter_mut().find(|(block, block_last, _)| {
+            *block_last < first && block.memory_type_bits & requirements[i].memory_type_bits != 0
+        });
+        match block {
+            Some((block, block_last, members)) => {
+                block.size = block.size.max(requirements[i].size);
+                block.alignment = block.alignment.max(requirements[i].alignment);
+                block.memory_type_bits &= requirements[i].memory_type_bits;
+                *block_last = last;
+                members.push(i);
+            },
+            None => blocks.push((requirements[i], last, vec![i])),
+        }
+    }
+    blocks.into_iter().map(|(requirements, _, members)| (requirements, members)).collect()
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct RenderPassKey {
+    /// Format, sample count, use, load op and whether the contents are kept after the pass.
+    attachments: Vec<(avk::Format, avk::SampleCountFlags, ImageUse, avk::AttachmentLoadOp, bool)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct TransientKey {
+    image: usize,
+    desc: TransientImageDesc,
+    usage: avk::ImageUsageFlags,
+    lifetime: (usize, usize),
+}
+
+struct TransientImage {
+    view: tvk::ImageView,
+    layer_views: Vec<tvk::ImageView>,
+    image: tvk::Image,
+}
+
+/// State kept between frames: render passes by attachment setup, and the transient
+/// images of the last frame, reused while the graph keeps the same shape.
+#[derive(Default)]
+pub struct RenderGraphCache {
+    render_passes: HashMap<RenderPassKey, tvk::RenderPass>,
+    transient_keys: Vec<TransientKey>,
+    transients: HashMap<usize, Arc<TransientImage>>,
+}
+
+impl RenderGraphCache {
+    fn render_pass(&mut self, context: &tvk::Context, key: &RenderPassKey) -> AnyResult<&tvk::RenderPass> {
+        if !self.render_passes.contains_key(key) {
+            let describe = |&(format, samples, usage, load_op, kept): &(avk::Format, avk::SampleCountFlags, ImageUse, avk::AttachmentLoadOp, bool)| {
+                avk::AttachmentDescription::default()
+                    .format(format)
+                    .samples(samples)
+                    .load_op(load_op)
+                    .store_op(if kept { avk::AttachmentStoreOp::STORE } else { avk::AttachmentStoreOp::DONT_CARE })
+                    .stencil_load_op(avk::AttachmentLoadOp::DONT_CARE)
+                    .stencil_store_op(avk::AttachmentStoreOp::DONT_CARE)
+                    .initial_layout(usage.layout())
+                    .final_layout(usage.layout())
+            };
+            let of = |usage| key.attachments.iter().filter(move |a| a.2 == usage).map(describe);
+            let colors = of(ImageUse::ColorAttachment).collect::<Vec<_>>();
+            let resolves = of(ImageUse::ResolveAttachment).collect::<Vec<_>>();
+            let depth = of(ImageUse::DepthAttachment).next();
+            let render_pass = tvk::RenderPass::with_attachments(context.logical_device.clone(), &colors, depth, &resolves)?;
+            self.render_passes.insert(key.clone(), render_pass);
+        }
+        Ok(&self.render_passes[key])
+    }
+
+    /// Makes `transients` available, recreating all of them when the set changed since
+    /// the last frame. Returns the replaced images, which earlier frames may still use.
+    fn prepare_transients(&mut self, context: &tvk::Context, transients: Vec<TransientKey>) -> AnyResult<Vec<Arc<dyn Any>>> {
+        if transients == self.transient_keys {
+            return Ok(Vec::new());
+        }
+        let replaced = self.transients.drain().map(|(_, image)| image as Arc<dyn Any>).collect();
+
+        let mut images = transients.iter().map(|key| {
+            context.create_unbound_image(key.desc.extent, key.desc.format, key.usage, key.desc.samples, key.desc.layers.unwrap_or(1))
+        }).collect::<AnyResult<Vec<_>>>()?;
+
+        let requirements = images.iter().map(|image| image.memory_requirements()).collect::<Vec<_>>();
+        let lifetimes = transients.iter().map(|key| key.lifetime).collect::<Vec<_>>();
+        for (requirements, members) in alias_memory(&lifetimes, &requirements) {
+            let memory = Arc::new(context.allocate_memory("Transient image", requirements)?);
+            for &i in members.iter() {
+                images[i].bind_memory(memory.clone())?;
+            }
+        }
+
+        for (key, image) in transients.iter().zip(images) {
+            let aspect = full_range(key.desc.format).aspect_mask;
+            let (view, layer_views) = match key.desc.layers {
+                None => (context.create_image_view(&image, key.desc.format, aspect)?, Vec::new()),
+                Some(layers) => {
+                    // Sampling reads a single aspect.
+                    let sampled_aspect = if aspect.contains(avk::ImageAspectFlags::DEPTH) { avk::ImageAspectFlags::DEPTH } else { aspect };
+                    let view = context.create_layered_image_view(&image, key.desc.format, sampled_aspect, avk::ImageViewType::TYPE_2D_ARRAY, 0, layers)?;
+                    let layer_views = (0..layers).map(|layer| {
+                        context.create_layered_image_view(&image, key.desc.format, aspect, avk::ImageViewType::TYPE_2D, layer, 1)
+                    }).collect::<AnyResult<Vec<_>>>()?;
+                    (view, layer_views)
+                },
+            };
+            self.transients.insert(key.image, Arc::new(TransientImage { view, layer_views, image }));
+        }
+        self.transient_keys = transients;
+        Ok(replaced)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXTENT: avk::Extent2D = avk::Extent2D { width: 64, height: 64 };
+
+    fn imported(graph: &mut RenderGraph) -> GraphImage {
+        graph.import_image(ImportedImage {
+            image: avk::Image::null(),
+            view: avk::ImageView::null(),
+            extent: EXTENT,
+            format: avk::Format::B8G8R8A8_UNORM,
+            initial_layout: avk::ImageLayout::UNDEFINED,
+            final_layout: None,
+        })
+    }
+
+    fn transient(graph: &mut RenderGraph) -> GraphImage {
+        graph.create_image(TransientImageDesc::new(EXTENT, avk::Format::R16G16B16A16_SFLOAT))
+    }
+
+    fn names(graph: &RenderGraph, order: &[usize]) -> Vec<&'static str> {
+        order.iter().map(|&pass| graph.passes[pass].name).collect()
+    }
+
+    #[test]
+    fn independent_passes_keep_declaration_order() {
+        let mut graph = RenderGraph::new();
+        let (a, b, output) = (transient(&mut graph), transient(&mut graph), imported(&mut graph));
+        let clear = AttachmentLoad::clear_color([0.0; 4]);
+        // Two chains interleaved, each of which could run entirely before the other.
+        graph.add_pass("a1").color_attachment(a, clear).execute(|_| {});
+        graph.add_pass("b1").color_attachment(b, clear).execute(|_| {});
+        graph.add_pass("a2").color_attachment(a, AttachmentLoad::Load).execute(|_| {});
+        graph.add_pass("b2").color_attachment(b, AttachmentLoad::Load).execute(|_| {});
+        graph.add_pass("compose").sample(b).sample(a).color_attachment(output, clear).execute(|_| {});
+
+        let order = graph.sorted_passes().unwrap();
+        assert_eq!(names(&graph, &order), ["a1", "b1", "a2", "b2", "compose"]);
+    }
+
+    #[test]
+    fn passes_nobody_reads_from_are_culled() {
+        let mut graph = RenderGraph::new();
+        let (scene, unused, overwritten, output) = (transient(&mut graph), transient(&mut graph), transient(&mut graph), imported(&mut graph));
+        let clear = AttachmentLoad::clear_color([0.0; 4]);
+        graph.add_pass("scene").color_attachment(scene, clear).execute(|_| {});
+        graph.add_pass("unused").color_attachment(unused, clear).execute(|_| {});
+        // Cleared again before anything reads it.
+        graph.add_pass("overwritten").color_attachment(overwritten, clear).execute(|_| {});
+        graph.add_pass("clear again").color_attachment(overwritten, clear).execute(|_| {});
+        graph.add_pass("present").sample(scene).sample(overwritten).color_attachment(output, clear).execute(|_| {});
+        graph.add_pass("capture").sample(unused).side_effects().execute(|_| {});
+        graph.add_pass("debug").sample(scene).execute(|_| {});
+
+        let order = graph.cull(graph.sorted_passes().unwrap());
+        assert_eq!(names(&graph, &order), ["scene", "unused", "clear again", "present", "capture"]);
+    }
+
+    #[test]
+    fn aliased_transients_never_overlap() {
+        let mut graph = RenderGraph::new();
+        let images = (0..5).map(|_| transient(&mut graph)).collect::<Vec<_>>();
+        let output = imported(&mut graph);
+        let clear = AttachmentLoad::clear_color([0.0; 4]);
+        // A chain where each pass reads the image the previous one wrote, plus one image
+        // read by the last pass that stays alive throughout.
+        graph.add_pass("first").color_attachment(images[0], clear).color_attachment(images[4], clear).execute(|_| {});
+        for i in 1..4 {
+            graph.add_pass("step").sample(images[i - 1]).color_attachment(images[i], clear).execute(|_| {});
+        }
+        graph.add_pass("last").sample(images[3]).sample(images[4]).color_attachment(output, clear).execute(|_| {});
+
+        let order = graph.cull(graph.sorted_passes().unwrap());
+        let lifetimes = graph.lifetimes(&order)[..images.len()].iter().map(|lifetime| lifetime.unwrap()).collect::<Vec<_>>();
+        let requirements = (0..images.len()).map(|i| avk::MemoryRequirements {
+            size: 1024 * (i as u64 + 1),
+            alignment: 256,
+            memory_type_bits: 0b11,
+        }).collect::<Vec<_>>();
+        let blocks = alias_memory(&lifetimes, &requirements);
+
+        let mut members = blocks.iter().flat_map(|(_, members)| members.iter().copied()).collect::<Vec<_>>();
+        members.sort();
+        assert_eq!(members, [0, 1, 2, 3, 4]);
+        assert!(blocks.len() < images.len(), "nothing was aliased");
+        for (block, members) in blocks.iter() {
+            for (n, &i) in members.iter().enumerate() {
+                assert!(block.size >= requirements[i].size);
+                for &j in members[n + 1..].iter() {
+                    let ((first_i, last_i), (first_j, last_j)) = (lifetimes[i], lifetimes[j]);
+                    assert!(last_i < first_j || last_j < first_i, "images {} and {} overlap in lifetime", i, j);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn incompatible_memory_types_are_not_aliased() {
+        let requirements = [0b01, 0b10].map(|memory_type_bits| avk::MemoryRequirements { size: 1024, alignment: 256, memory_type_bits });
+        let blocks = alias_memory(&[(0, 0), (1, 1)], &requirements);
+        assert_eq!(blocks.len(), 2);
+    }
+}
@@ -0,0 +1,283 @@
+use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+
+use ash::vk as avk;
+use crate::*;
+
+/// Most overlays drawn in one frame. Further overlays are skipped.
+pub const MAX_OVERLAYS: usize = 8;
+
+/// Color formats a `RenderTarget` can be created with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RenderTargetFormat {
+    #[default]
+    Rgba8Srgb,
+    Rgba8Unorm,
+    /// Half-float color, for values outside `0..1`.
+    Rgba16Float,
+}
+
+impl RenderTargetFormat {
+    pub(crate) fn vk_format(self) -> avk::Format {
+        match self {
+            RenderTargetFormat::Rgba8Srgb => avk::Format::R8G8B8A8_SRGB,
+            RenderTargetFormat::Rgba8Unorm => avk::Format::R8G8B8A8_UNORM,
+            RenderTargetFormat::Rgba16Float => avk::Format::R16G16B16A16_SFLOAT,
+        }
+    }
+}
+
+/// Offscreen color image that views draw into. It starts out transparent black and
+/// the render graph leaves it in `SHADER_READ_ONLY_OPTIMAL`, so its `texture` can be
+/// sampled by later passes and, through `texture_handle`, by materials. Depth is a
+/// transient of the graph.
+pub struct RenderTarget {
+    texture: Arc<Texture>,
+    /// Shares `texture` with materials, and is pointed at the new one on resize.
+    handle: Handle<Texture>,
+    format: RenderTargetFormat,
+    /// Set once a frame has drawn into the target.
+    drawn: AtomicBool,
+}
+
+impl RenderTarget {
+    pub fn new(context: &tvk::Context, width: u32, height: u32, format: RenderTargetFormat) -> AnyResult<Self> {
+        let extent = avk::Extent2D { width: width.max(1), height: height.max(1) };
+        let vk_format = format.vk_format();
+        let image = context.create_image(
+            extent,
+            vk_format,
+            avk::ImageUsageFlags::COLOR_ATTACHMENT | avk::ImageUsageFlags::SAMPLED | avk::ImageUsageFlags::TRANSFER_DST
+        )?;
+        // Materials may sample the target before any view has drawn into it.
+        context.submit_and_wait(|command_buffer| {
+            command_buffer.transition_image_layout(
+                &image,
+                avk::ImageLayout::UNDEFINED,
+                avk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                (avk::PipelineStageFlags::TOP_OF_PIPE, avk::AccessFlags::empty()),
+                (avk::PipelineStageFlags::TRANSFER, avk::AccessFlags::TRANSFER_WRITE),
+            );
+            command_buffer.clear_color_image(&image, avk::ClearColorValue { float32: [0.0; 4] });
+            command_buffer.transition_image_layout(
+                &image,
+                avk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                (avk::PipelineStageFlags::TRANSFER, avk::AccessFlags::TRANSFER_WRITE),
+                (avk::PipelineStageFlags::FRAGMENT_SHADER, avk::AccessFlags::SHADER_READ),
+            );
+        })?;
+        let image_view = context.create_image_view(&image, vk_format, avk::ImageAspectFlags::COLOR)?;
+        let sampler = context.create_sampler(avk::Filter::LINEAR, avk::SamplerAddressMode::CLAMP_TO_EDGE)?;
+        let texture = Arc::new(Texture {
+            image_view,
+            sampler,
+            image,
+        });
+
+        Ok(Self {
+            handle: Handle::shared(texture.clone()),
+            texture,
+            format,
+            drawn: AtomicBool::new(false),
+        })
+    }
+
+    /// A target of the same format and a new size, which takes over the texture handle
+    /// so that materials sampling this target sample the new one.
+    pub(crate) fn resized(&self, context: &tvk::Context, width: u32, height: u32) -> AnyResult<Self> {
+        let target = Self::new(context, width, height, self.format)?;
+        self.handle.set_shared(target.texture.clone());
+        Ok(Self { handle: self.handle.clone(), ..target })
+    }
+
+    pub fn extent(&self) -> avk::Extent2D {
+        self.texture.image.extent
+    }
+
+    pub fn format(&self) -> RenderTargetFormat {
+        self.format
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    /// The color image for a material's texture slots. Instance groups whose material
+    /// samples the target are left out of the views drawing into it.
+    pub fn texture_handle(&self) -> Handle<Texture> {
+        self.handle.clone()
+    }
+
+    pub(crate) fn is_sampled_by(&self, material: &Material) -> bool {
+        material.uses_texture(&self.handle)
+    }
+
+    pub fn is_drawn(&self) -> bool {
+        self.drawn.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn mark_drawn(&self) {
+        self.drawn.store(true, Ordering::Relaxed);
+    }
+
+    /// The color image as a render graph resource.
+    pub(crate) fn graph_image(&self) -> ImportedImage {
+        ImportedImage {
+            image: self.texture.image.inner,
+            view: self.texture.image_view.inner,
+            extent: self.extent(),
+            format: self.format.vk_format(),
+            initial_layout: avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            final_layout: Some(avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+        }
+    }
+}
+
+impl tvk::Context {
+    pub fn create_render_target(&self, width: u32, height: u32, format: RenderTargetFormat) -> AnyResult<RenderTarget> {
+        RenderTarget::new(self, width, height, format)
+    }
+}
+
+/// Render target owned by the `Renderer`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RenderTargetId(pub(crate) usize);
+
+/// Draws the texture of a render target over part of the window, after every view and
+/// after post-processing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Overlay {
+    pub target: RenderTargetId,
+    pub viewport: Viewport,
+}
+
+impl Overlay {
+    pub fn new(target: RenderTargetId, viewport: Viewport) -> Self {
+        Self { target, viewport }
+    }
+}
+
+/// Scene pipelines for render targets of one format, with a render pass to rebuild
+/// them against.
+pub(crate) struct TargetPipelines {
+    pub pipelines: ScenePipelines,
+    pub render_pass: tvk::RenderPass,
+}
+
+/// Overlay resolved for the frame being recorded.
+pub(crate) struct OverlayRecord {
+    pub target: RenderTargetId,
+    pub rect: avk::Rect2D,
+    pub descriptor_index: usize,
+}
+
+/// Full-screen-triangle pipeline that samples a render target into the overlay's
+/// viewport. Each frame in flight has its own `MAX_OVERLAYS` descriptor sets.
+pub(crate) struct OverlayPass {
+    pipeline: tvk::Pipeline,
+    descriptor: tvk::TextureDescriptor,
+}
+
+impl OverlayPass {
+    pub const SHADERS: [&str; 2] = ["fullscreen.vert.spv", "overlay.frag.spv"];
+
+    pub fn new(context: &tvk::Context, shaders: &Shaders, render_pass: &tvk::RenderPass, frames_in_flight: usize) -> AnyResult<Self> {
+        let descriptor = context.create_texture_descriptor((frames_in_flight * MAX_OVERLAYS) as u32)?;
+        let pipeline = Self::create_pipeline(context, shaders, render_pass, descriptor.layout)?;
+        Ok(Self {
+            pipeline,
+            descriptor,
+        })
+    }
+
+    /// Builds the pipeline again from `shaders`. It replaces the current one when the
+    /// returned function is called.
+    pub fn reload_shaders(&self, context: &tvk::Context, shaders: &Shaders, render_pass: &tvk::RenderPass) -> AnyResult<PipelineSwap<Self>> {
+        let pipeline = Self::create_pipeline(context, shaders, render_pass, self.descriptor.layout)?;
+        Ok(Box::new(move |pass| pass.pipeline = pipeline))
+    }
+
+    fn create_pipeline(context: &tvk::Context, shaders: &Shaders, render_pass: &tvk::RenderPass, layout: avk::DescriptorSetLayout) -> AnyResult<tvk::Pipeline> {
+        let [vertex_source, fragment_source] = shaders.get_all(Self::SHADERS)?;
+        context.create_pipeline::<()>(
+            render_pass,
+            &[layout],
+            &vertex_fragment_shaders(&vertex_source, &fragment_source),
+            &tvk::PipelineState {
+                vertex_input: false,
+                depth_test: false,
+                ..Default::default()
+            }
+        )
+    }
+
+    /// Points the sets of `frame_index` at the targets of `overlays` and returns what
+    /// to draw. Every target must have been drawn, in this frame or an earlier one.
+    pub fn prepare(&self, frame_index: usize, overlays: &[(RenderTargetId, &RenderTarget, Viewport)], extent: avk::Extent2D) -> Vec<OverlayRecord> {
+        if overlays.len() > MAX_OVERLAYS {
+            log::warn!("skipping {} overlays over the limit of {}", overlays.len() - MAX_OVERLAYS, MAX_OVERLAYS);
+        }
+        overlays.iter().take(MAX_OVERLAYS).enumerate().map(|(i, &(id, target, viewport))| {
+            let descriptor_index = frame_index * MAX_OVERLAYS + i;
+            let texture = target.texture();
+            self.descriptor.update_set(descriptor_index, &texture.image_view, &texture.sampler);
+            OverlayRecord {
+                target: id,
+                rect: viewport.to_rect(extent),
+                descriptor_index,
+            }
+        }).collect()
+    }
+
+    pub fn record(&self, command_buffer: &tvk::CommandBuffer, overlays: &[OverlayRecord]) {
+        for overlay in overlays.iter() {
+            let view = ViewRecord {
+                index: 0,
+                rect: overlay.rect,
+                uniform_offset: 0,
+                inverse_view_projection: glam::Mat4::IDENTITY,
+                target: None,
+                clear_color: None,
+                clear_depth: false,
+            };
+            view.begin(command_buffer, false, None);
+            command_buffer.bind_pipeline(&self.pipeline);
+            command_buffer.bind_descriptor_sets(self.pipeline.layout, self.descriptor.sets[overlay.descriptor_index], &[]);
+            command_buffer.draw(3, 1, 0, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_map_to_distinct_vulkan_formats() {
+        let formats = [RenderTargetFormat::Rgba8Srgb, RenderTargetFormat::Rgba8Unorm, RenderTargetFormat::Rgba16Float];
+        let vk_formats: std::collections::HashSet<_> = formats.iter().map(|format| format.vk_format()).collect();
+        assert_eq!(vk_formats.len(), formats.len());
+        assert_eq!(RenderTargetFormat::default().vk_format(), avk::Format::R8G8B8A8_SRGB);
+    }
+
+    #[test]
+    fn materials_know_which_textures_they_sample() {
+        let (sampled, other) = (Handle::loading_embedded(None), Handle::loading_embedded(None));
+        let material = Material { emissive_texture: Some(sampled.clone()), ..Default::default() };
+        assert!(material.uses_texture(&sampled));
+        assert!(!material.uses_texture(&other));
+        assert!(!Material::default().uses_texture(&sampled));
+    }
+
+    #[test]
+    fn views_keep_their_target() {
+        let camera = Camera::default();
+        let views = [
+            View::new(Camera::default(), Viewport::default()).with_target(RenderTargetId(1)),
+            View::new(Camera::default(), Viewport::new(0.0, 0.0, 0.5, 0.5)),
+        ];
+        let render_views = collect_render_views(&camera, Viewport::default(), &views);
+        let targets: Vec<_> = render_views.iter().map(|view| view.target).collect();
+        assert_eq!(targets, [None, Some(RenderTargetId(1)), None]);
+    }
+}
@@ -0,0 +1,67 @@
+use std::{collections::HashMap, sync::Arc};
+
+use crate::*;
+
+/// Installs pipelines rebuilt from reloaded shaders into a `T`. Reloading builds every
+/// affected pipeline first and only then swaps them in, so a failure changes nothing.
+pub(crate) type PipelineSwap<T> = Box<dyn FnOnce(&mut T)>;
+
+/// Compiled shaders the renderer's pipelines are built from, by file name.
+///
+/// The handles are shared with `Assets`, which swaps in the recompiled module when a
+/// file changes while hot reloading is enabled. `take_reloaded` then tells which
+/// pipelines to rebuild.
+pub(crate) struct Shaders {
+    /// Each handle with the module the pipelines were last built from.
+    modules: HashMap<String, (Handle<tvk::ShaderModule>, Arc<tvk::ShaderModule>)>,
+}
+
+impl Shaders {
+    /// Loads every shader in the generated shader directory.
+    pub fn load(context: &tvk::Context) -> AnyResult<Self> {
+        let directory = shader_directory();
+        let mut modules = HashMap::new();
+        for entry in std::fs::read_dir(&directory).map_err(|e| format!("cannot read {}: {}", directory.display(), e))? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != "spv") {
+                continue;
+            }
+            let module = tvk::ShaderModule::create(context.logical_device.clone(), &path)
+                .map_err(|e| format!("cannot load shader {}: {}", path.display(), e))?;
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            let handle = Handle::loading(path, None);
+            handle.set_ready(module);
+            let built = handle.get().unwrap();
+            modules.insert(name, (handle, built));
+        }
+        Ok(Self { modules })
+    }
+
+    /// The current module of the shader `name`, such as `"shader.vert.spv"`.
+    pub fn get(&self, name: &str) -> AnyResult<Arc<tvk::ShaderModule>> {
+        self.modules.get(name)
+            .and_then(|(handle, _)| handle.get())
+            .ok_or_else(|| format!("missing shader {}", name).into())
+    }
+
+    pub fn get_all<const N: usize>(&self, names: [&str; N]) -> AnyResult<[Arc<tvk::ShaderModule>; N]> {
+        let modules = names.into_iter().map(|name| self.get(name)).collect::<AnyResult<Vec<_>>>()?;
+        Ok(modules.try_into().unwrap_or_else(|_| unreachable!()))
+    }
+
+    pub fn handles(&self) -> impl Iterator<Item = &Handle<tvk::ShaderModule>> {
+        self.modules.values().map(|(handle, _)| handle)
+    }
+
+    /// Names of the shaders whose module was replaced since the last call.
+    pub fn take_reloaded(&mut self) -> Vec<String> {
+        self.modules.iter_mut().filter_map(|(name, (handle, built))| {
+            let current = handle.get()?;
+            if Arc::ptr_eq(&current, built) {
+                return None;
+            }
+            *built = current;
+            Some(name.clone())
+        }).collect()
+    }
+}
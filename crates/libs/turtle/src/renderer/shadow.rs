@@ -0,0 +1,294 @@
+use ash::vk as avk;
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+use crate::*;
+
+/// Most slices the view frustum is split into for shadows.
+pub const MAX_SHADOW_CASCADES: usize = 4;
+
+/// Shadows of the directional light, cast and received by every instance group.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Shadows {
+    /// Width and height in texels of each cascade's layer of the shadow map.
+    pub resolution: u32,
+    /// Slices of the main camera's frustum with their own shadow map layer, up to
+    /// `MAX_SHADOW_CASCADES`. More keeps shadows sharp further away.
+    pub cascades: u32,
+    /// Distance from the main camera beyond which nothing is shadowed.
+    pub distance: f32,
+    /// Blend between evenly spaced (0) and logarithmic (1) cascade splits. Higher
+    /// values spend more of the resolution close to the camera.
+    pub split_lambda: f32,
+    /// Constant depth offset when rendering the shadow map, in the depth format's
+    /// smallest steps.
+    pub depth_bias: f32,
+    /// Depth offset scaled by how steeply a surface faces away from the light.
+    pub slope_bias: f32,
+    /// Offset of shadow lookups along the surface normal, in shadow map texels.
+    /// Removes acne on surfaces the light grazes.
+    pub normal_bias: f32,
+}
+
+impl Default for Shadows {
+    fn default() -> Self {
+        Self {
+            resolution: 2048,
+            cascades: 4,
+            distance: 100.0,
+            split_lambda: 0.75,
+            depth_bias: 1.25,
+            slope_bias: 1.75,
+            normal_bias: 1.0,
+        }
+    }
+}
+
+/// Light-space projection of one slice of the camera frustum.
+#[derive(Clone, Copy)]
+pub(crate) struct Cascade {
+    pub matrix: camera::Matrix,
+    /// World-space size of one shadow map texel.
+    pub texel_size: f32,
+}
+
+/// Splits `camera`'s frustum up to `shadows.distance` into cascades and fits an
+/// orthographic projection along `direction` around each. Projections enclose a
+/// bounding sphere of their slice and move in whole texels, so shadow edges don't
+/// shimmer as the camera turns and moves.
+pub(crate) fn fit_cascades(camera: &Camera, direction: Vec3, shadows: &Shadows) -> Vec<Cascade> {
+    let (near, far) = match camera.projection {
+        Projection::Perspective { near, far, .. } => (near, far.unwrap_or(f32::INFINITY)),
+        Projection::Orthographic { near, far, .. } => (near.max(0.0), far),
+        Projection::Custom { .. } => (0.1, f32::INFINITY),
+    };
+    let far = far.min(shadows.distance);
+    if far <= near {
+        return Vec::new();
+    }
+    let splits = cascade_splits(near, far, shadows.cascades, shadows.split_lambda);
+
+    // Rays through the corners of the view in view space, which works for both
+    // perspective and orthographic projections.
+    let inverse_projection = camera.projection_matrix().inverse();
+    let near_depth = if camera.projection.is_reverse_z() { 1.0 } else { 0.0 };
+    let corner_rays = [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].map(|(x, y)| {
+        let origin = inverse_projection.project_point3(Vec3::new(x, y, near_depth));
+        (origin, inverse_projection.project_point3(Vec3::new(x, y, 0.5)) - origin)
+    });
+    let inverse_view = camera.view_matrix().inverse();
+
+    let direction = direction.normalize_or(Vec3::NEG_Y);
+    let up = if direction.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+    let light_view = Mat4::look_to_rh(Vec3::ZERO, direction, up);
+
+    splits.windows(2).map(|slice| {
+        let corners = slice.iter().flat_map(|&depth| corner_rays.iter().map(move |&(origin, ray)| {
+            inverse_view.transform_point3(origin + ray * ((-depth - origin.z) / ray.z))
+        })).collect::<Vec<_>>();
+        let center = corners.iter().sum::<Vec3>() / corners.len() as f32;
+        let radius = corners.iter().map(|corner| corner.distance(center)).fold(0.0, f32::max);
+        let radius = (radius * 16.0).ceil() / 16.0;
+
+        let texel_size = 2.0 * radius / shadows.resolution.max(1) as f32;
+        let mut center = light_view.transform_point3(center);
+        center.x = (center.x / texel_size).floor() * texel_size;
+        center.y = (center.y / texel_size).floor() * texel_size;
+        // Casters up to `distance` towards the light still shadow the slice.
+        let mut proj = Mat4::orthographic_rh(
+            center.x - radius,
+            center.x + radius,
+            center.y - radius,
+            center.y + radius,
+            -center.z - radius - shadows.distance,
+            -center.z + radius,
+        );
+        proj.y_axis.y *= -1.0;
+        Cascade {
+            matrix: camera::Matrix { view: light_view, proj },
+            texel_size,
+        }
+    }).collect()
+}
+
+/// View-space distances bounding each cascade, from `near` to `far`: one more than
+/// the number of cascades.
+fn cascade_splits(near: f32, far: f32, cascades: u32, split_lambda: f32) -> Vec<f32> {
+    let count = cascades.clamp(1, MAX_SHADOW_CASCADES as u32);
+    let log_near = near.max(0.01);
+    (0..=count).map(|i| {
+        let t = i as f32 / count as f32;
+        let logarithmic = log_near * (far / log_near).powf(t);
+        let linear = near + (far - near) * t;
+        if i == 0 { near } else { split_lambda * logarithmic + (1.0 - split_lambda) * linear }
+    }).collect()
+}
+
+/// Depth-only pipelines rendering the instance groups into the layers of the shadow map.
+pub(crate) struct ShadowPass {
+    pipeline: tvk::Pipeline,
+    compact_pipeline: tvk::Pipeline,
+    depth_format: avk::Format,
+    max_resolution: u32,
+    /// Uniform buffer offsets of this frame's cascade matrices.
+    cascade_offsets: Vec<u32>,
+}
+
+impl ShadowPass {
+    /// The scene vertex shaders, without a fragment stage.
+    pub const SHADERS: [&str; 2] = ["shader.vert.spv", "compact_instance.vert.spv"];
+
+    pub fn new(context: &tvk::Context, shaders: &Shaders, descriptor: &tvk::Descriptor) -> AnyResult<Self> {
+        let (pipeline, compact_pipeline) = Self::create_pipelines(context, shaders, descriptor)?;
+        Ok(Self {
+            pipeline,
+            compact_pipeline,
+            depth_format: context.physical_device.depth_format,
+            max_resolution: context.physical_device.properties.limits.max_image_dimension2_d,
+            cascade_offsets: Vec::new(),
+        })
+    }
+
+    /// Builds the pipelines again from `shaders`. They replace the current ones when the
+    /// returned function is called.
+    pub fn reload_shaders(&self, context: &tvk::Context, shaders: &Shaders, descriptor: &tvk::Descriptor) -> AnyResult<PipelineSwap<Self>> {
+        let pipelines = Self::create_pipelines(context, shaders, descriptor)?;
+        Ok(Box::new(move |pass| (pass.pipeline, pass.compact_pipeline) = pipelines))
+    }
+
+    fn create_pipelines(context: &tvk::Context, shaders: &Shaders, descriptor: &tvk::Descriptor) -> AnyResult<(tvk::Pipeline, tvk::Pipeline)> {
+        let render_pass = context.create_depth_render_pass()?;
+        let state = tvk::PipelineState {
+            color_output: false,
+            depth_bias: true,
+            ..Default::default()
+        };
+        let [vertex_source, compact_vertex_source] = shaders.get_all(Self::SHADERS)?;
+        let pipeline = context.create_pipeline::<tvk::FullInstanceData>(
+            &render_pass,
+            &[descriptor.layout],
+            &[tvk::PipelineShaderCreateInfo { module: &vertex_source, stage: avk::ShaderStageFlags::VERTEX }],
+            &state
+        )?;
+        let compact_pipeline = context.create_pipeline::<tvk::CompactInstanceData>(
+            &render_pass,
+            &[descriptor.layout],
+            &[tvk::PipelineShaderCreateInfo { module: &compact_vertex_source, stage: avk::ShaderStageFlags::VERTEX }],
+            &state
+        )?;
+        Ok((pipeline, compact_pipeline))
+    }
+
+    pub fn set_cascade_offsets(&mut self, offsets: Vec<u32>) {
+        self.cascade_offsets = offsets;
+    }
+
+    /// Adds one pass per cascade rendering `instance_groups` into its layer of a new
+    /// shadow map array, and returns the array. Without shadows the array is a single
+    /// cleared texel, so the scene shaders always have one to bind.
+    pub fn add_passes<'a>(
+        &'a self,
+        graph: &mut RenderGraph<'a>,
+        frame_index: usize,
+        shadows: Option<&Shadows>,
+        descriptor_set: avk::DescriptorSet,
+        instance_groups: &'a [InstanceGroup],
+    ) -> GraphImage {
+        let Some(shadows) = shadows.filter(|_| !self.cascade_offsets.is_empty()) else {
+            let extent = avk::Extent2D { width: 1, height: 1 };
+            let shadow_map = graph.create_image(TransientImageDesc::new(extent, self.depth_format).with_layers(1));
+            graph.add_pass("shadow map clear")
+                .depth_attachment_layer(shadow_map, 0, AttachmentLoad::clear_depth(1.0))
+                .execute(|_| {});
+            return shadow_map;
+        };
+
+        let resolution = shadows.resolution.clamp(1, self.max_resolution);
+        let extent = avk::Extent2D { width: resolution, height: resolution };
+        let layers = self.cascade_offsets.len() as u32;
+        let shadow_map = graph.create_image(TransientImageDesc::new(extent, self.depth_format).with_layers(layers));
+        let (depth_bias, slope_bias) = (shadows.depth_bias, shadows.slope_bias);
+        for (layer, &offset) in self.cascade_offsets.iter().enumerate() {
+            graph.add_pass("shadow cascade")
+                .depth_attachment_layer(shadow_map, layer as u32, AttachmentLoad::clear_depth(1.0))
+                .execute(move |pass| {
+                    let command_buffer = pass.command_buffer;
+                    command_buffer.set_scissor(avk::Rect2D { offset: avk::Offset2D::default(), extent });
+                    command_buffer.set_viewport(avk::Viewport::default()
+                        .width(extent.width as f32)
+                        .height(extent.height as f32)
+                        .max_depth(1.0));
+                    command_buffer.set_depth_bias(depth_bias, 0.0, slope_bias);
+                    let mut bound_format = None;
+                    for instance_group in instance_groups.iter() {
+                        let Some(mesh) = instance_group.mesh.get() else {
+                            continue;
+                        };
+                        if bound_format != Some(instance_group.format()) {
+                            let pipeline = instance_group.format().pipeline(&self.pipeline, &self.compact_pipeline);
+                            command_buffer.bind_pipeline(pipeline);
+                            command_buffer.bind_descriptor_sets(pipeline.layout, descriptor_set, &[offset]);
+                            bound_format = Some(instance_group.format());
+                        }
+                        let buffers = [mesh.vertex_buffer.inner, instance_group.instance_buffer(frame_index).unwrap().inner];
+                        command_buffer.bind_vertex_buffers(&buffers);
+                        command_buffer.bind_index_buffer(&mesh.index_buffer);
+                        command_buffer.draw_indexed(mesh.indices.len() as u32, instance_group.visible_count() as u32, 0, 0, 0);
+                    }
+                });
+        }
+        shadow_map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn splits_blend_linear_and_logarithmic() {
+        let linear = cascade_splits(1.0, 100.0, 4, 0.0);
+        for (split, expected) in linear.iter().zip([1.0, 25.75, 50.5, 75.25, 100.0]) {
+            assert_near(*split, expected);
+        }
+        let logarithmic = cascade_splits(1.0, 100.0, 2, 1.0);
+        for (split, expected) in logarithmic.iter().zip([1.0, 10.0, 100.0]) {
+            assert_near(*split, expected);
+        }
+        assert_eq!(cascade_splits(1.0, 100.0, 10, 0.5).len(), MAX_SHADOW_CASCADES + 1);
+        assert_eq!(cascade_splits(1.0, 100.0, 0, 0.5).len(), 2);
+    }
+
+    #[test]
+    fn cascades_stop_at_the_shadow_distance() {
+        let camera = Camera::default();
+        let shadows = Shadows { distance: 50.0, ..Default::default() };
+        assert_eq!(fit_cascades(&camera, Vec3::NEG_Y, &shadows).len(), shadows.cascades as usize);
+
+        let shadows = Shadows { distance: 0.05, ..Default::default() };
+        assert!(fit_cascades(&camera, Vec3::NEG_Y, &shadows).is_empty());
+    }
+
+    #[test]
+    fn cascades_snap_to_whole_texels() {
+        let shadows = Shadows::default();
+        let direction = Vec3::new(0.3, -1.0, 0.2);
+        let mut camera = Camera::default();
+        let reference = fit_cascades(&camera, direction, &shadows);
+        for step in 1..5 {
+            camera.position.x += 0.013 * step as f32;
+            camera.yaw += 7.0;
+            for (cascade, reference) in fit_cascades(&camera, direction, &shadows).iter().zip(&reference) {
+                // The size only depends on the slice, not on where the camera looks.
+                assert_near(cascade.texel_size, reference.texel_size);
+                // The light-space center is a whole number of texels from the origin.
+                let offset = cascade.matrix.proj.w_axis.x * shadows.resolution as f32 / 2.0;
+                assert_near(offset, offset.round());
+            }
+        }
+    }
+}
@@ -0,0 +1,608 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ab_glyph::{Font as _, FontVec, GlyphId, PxScale, ScaleFont};
+use ash::vk as avk;
+use glam::{Mat4, UVec2, Vec2, Vec3, Vec4};
+use gpu_allocator::MemoryLocation;
+use crate::*;
+
+/// Most fonts a renderer can load.
+pub const MAX_FONTS: usize = 8;
+/// Side of each font's glyph atlas in texels.
+const ATLAS_SIZE: u32 = 1024;
+/// Pixel size glyphs are rasterized at before being scaled to any text size.
+const RASTER_SIZE: f32 = 48.0;
+/// Texels the distance fields reach either side of a glyph's outline.
+const SPREAD: u32 = 6;
+
+/// Font loaded with `Renderer::load_font`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct FontId(pub(crate) usize);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    /// Lines start at the text's position.
+    #[default]
+    Left,
+    /// Lines are centered on it.
+    Center,
+    /// Lines end at it.
+    Right,
+}
+
+/// A string laid out with kerning, `\n` line breaks and optional word wrapping.
+#[derive(Clone, Debug)]
+pub struct Text {
+    pub string: String,
+    pub font: FontId,
+    /// Font size: pixels on screen, world units for billboards.
+    pub size: f32,
+    /// Linear color with alpha.
+    pub color: Vec4,
+    pub align: TextAlign,
+    /// Wraps lines at word boundaries to fit, in the units of `size`.
+    pub max_width: Option<f32>,
+}
+
+impl Text {
+    pub fn new(string: impl Into<String>, font: FontId, size: f32) -> Self {
+        Self {
+            string: string.into(),
+            font,
+            size,
+            color: Vec4::ONE,
+            align: TextAlign::default(),
+            max_width: None,
+        }
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+}
+
+/// Immediate-mode text, drawn on the next frame only. Positions are the top of the
+/// text's first line, aligned horizontally as the text says.
+#[derive(Default)]
+pub struct TextDraw {
+    screen: Vec<(Text, Vec2)>,
+    world: Vec<(Text, Vec3)>,
+}
+
+impl TextDraw {
+    /// Over everything else, at `position` pixels from the window's top left corner.
+    pub fn screen(&mut self, text: Text, position: Vec2) {
+        self.screen.push((text, position));
+    }
+
+    /// Billboard facing the camera of every view, hidden behind the geometry in front.
+    pub fn world(&mut self, text: Text, position: Vec3) {
+        self.world.push((text, position));
+    }
+
+    pub fn clear(&mut self) {
+        self.screen.clear();
+        self.world.clear();
+    }
+}
+
+/// Glyph in a font's atlas.
+#[derive(Clone, Copy)]
+struct AtlasGlyph {
+    min: UVec2,
+    size: UVec2,
+    /// Top left corner relative to the pen position on the baseline, in pixels at
+    /// `RASTER_SIZE`.
+    offset: Vec2,
+}
+
+/// Glyph placed by `Font::layout`.
+struct PlacedGlyph {
+    id: GlyphId,
+    /// Pen position on the baseline, relative to the top of the text.
+    position: Vec2,
+}
+
+/// A TTF or OTF font with the signed distance fields of the glyphs drawn so far,
+/// packed in rows into its atlas.
+pub(crate) struct Font {
+    font: FontVec,
+    /// `None` for glyphs without an outline or that didn't fit.
+    glyphs: HashMap<GlyphId, Option<AtlasGlyph>>,
+    pixels: Vec<u8>,
+    /// Where the next glyph goes, and the height of the row it goes in.
+    cursor: UVec2,
+    row_height: u32,
+    /// Uploaded atlas, replaced when glyphs have been added since.
+    texture: Option<Arc<Texture>>,
+    dirty: bool,
+}
+
+impl Font {
+    pub fn load(path: &std::path::Path) -> AnyResult<Self> {
+        let font = FontVec::try_from_vec(std::fs::read(path)?)?;
+        Ok(Self {
+            font,
+            glyphs: HashMap::new(),
+            pixels: vec![0; (ATLAS_SIZE * ATLAS_SIZE) as usize],
+            cursor: UVec2::ZERO,
+            row_height: 0,
+            texture: None,
+            dirty: true,
+        })
+    }
+
+    /// Places the glyphs of `text` into `glyphs` and returns the size of the text.
+    fn layout(&self, text: &Text, glyphs: &mut Vec<PlacedGlyph>) -> Vec2 {
+        let font = self.font.as_scaled(PxScale::from(text.size));
+        let line_height = font.height() + font.line_gap();
+        // Width of `word` placed after `previous`, kerning included.
+        let advance = |mut previous: Option<GlyphId>, word: &str| {
+            let mut pen = 0.0;
+            for id in word.chars().filter(|c| !c.is_control()).map(|c| font.glyph_id(c)) {
+                pen += previous.map_or(0.0, |previous| font.kern(previous, id)) + font.h_advance(id);
+                previous = Some(id);
+            }
+            pen
+        };
+        // Glyph range and width of each line.
+        let mut lines = Vec::new();
+        for paragraph in text.string.split('\n') {
+            let mut line_start = glyphs.len();
+            let (mut pen, mut width, mut previous) = (0.0, 0.0, None);
+            for word in paragraph.split_inclusive(char::is_whitespace) {
+                let fits = text.max_width.is_none_or(|max_width| pen + advance(previous, word.trim_end()) <= max_width);
+                if !fits && glyphs.len() > line_start {
+                    lines.push((line_start..glyphs.len(), width));
+                    line_start = glyphs.len();
+                    (pen, width, previous) = (0.0, 0.0, None);
+                }
+                for c in word.chars().filter(|c| !c.is_control()) {
+                    let id = font.glyph_id(c);
+                    if let Some(previous) = previous {
+                        pen += font.kern(previous, id);
+                    }
+                    glyphs.push(PlacedGlyph { id, position: Vec2::new(pen, 0.0) });
+                    pen += font.h_advance(id);
+                    if !c.is_whitespace() {
+                        width = pen;
+                    }
+                    previous = Some(id);
+                }
+            }
+            lines.push((line_start..glyphs.len(), width));
+        }
+
+        let mut size = Vec2::new(0.0, line_height * lines.len() as f32);
+        for (line, (range, width)) in lines.into_iter().enumerate() {
+            let x = match text.align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => -width * 0.5,
+                TextAlign::Right => -width,
+            };
+            let baseline = font.ascent() + line_height * line as f32;
+            for glyph in glyphs[range].iter_mut() {
+                glyph.position += Vec2::new(x, baseline);
+            }
+            size.x = size.x.max(width);
+        }
+        size
+    }
+
+    fn glyph(&mut self, id: GlyphId) -> Option<AtlasGlyph> {
+        if let Some(&glyph) = self.glyphs.get(&id) {
+            return glyph;
+        }
+        let glyph = self.rasterize(id);
+        self.glyphs.insert(id, glyph);
+        glyph
+    }
+
+    /// Adds the glyph's distance field to the atlas.
+    fn rasterize(&mut self, id: GlyphId) -> Option<AtlasGlyph> {
+        let outline = self.font.outline_glyph(id.with_scale(RASTER_SIZE))?;
+        let bounds = outline.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        let mut coverage = vec![0.0; (width * height) as usize];
+        outline.draw(|x, y, c| coverage[(y * width + x) as usize] = c);
+
+        let size = UVec2::new(width, height) + 2 * SPREAD;
+        if self.cursor.x + size.x > ATLAS_SIZE {
+            self.cursor = UVec2::new(0, self.cursor.y + self.row_height);
+            self.row_height = 0;
+        }
+        if self.cursor.y + size.y > ATLAS_SIZE {
+            log::warn!("glyph atlas is full, skipping glyph {}", id.0);
+            return None;
+        }
+        let min = self.cursor;
+        self.cursor.x += size.x;
+        self.row_height = self.row_height.max(size.y);
+
+        // Coverage of the texel `SPREAD` texels in from the field's corner.
+        let sample = |x: i32, y: i32| {
+            let (x, y) = (x - SPREAD as i32, y - SPREAD as i32);
+            let inside_bounds = x >= 0 && y >= 0 && x < width as i32 && y < height as i32;
+            if inside_bounds { coverage[(y as u32 * width + x as u32) as usize] } else { 0.0 }
+        };
+        let spread = SPREAD as i32;
+        for y in 0..size.y as i32 {
+            for x in 0..size.x as i32 {
+                let texel = sample(x, y);
+                let inside = texel >= 0.5;
+                let mut nearest = SPREAD as f32;
+                for dy in -spread..=spread {
+                    for dx in -spread..=spread {
+                        if (sample(x + dx, y + dy) >= 0.5) != inside {
+                            nearest = nearest.min(((dx * dx + dy * dy) as f32).sqrt());
+                        }
+                    }
+                }
+                // The outline runs about halfway to the nearest texel on its other side,
+                // or through partly covered texels where their coverage says.
+                let distance = if texel > 0.0 && texel < 1.0 && nearest <= 1.5 {
+                    texel - 0.5
+                } else if inside {
+                    nearest - 0.5
+                } else {
+                    0.5 - nearest
+                };
+                let value = (0.5 + distance / (2.0 * SPREAD as f32)).clamp(0.0, 1.0);
+                let index = (min.y + y as u32) * ATLAS_SIZE + min.x + x as u32;
+                self.pixels[index as usize] = (value * 255.0).round() as u8;
+            }
+        }
+        self.dirty = true;
+        Some(AtlasGlyph {
+            min,
+            size,
+            offset: Vec2::new(bounds.min.x, bounds.min.y) - SPREAD as f32,
+        })
+    }
+
+    /// Appends two triangles per glyph of `text`, at `anchor` with the offsets in the
+    /// units of the text's size.
+    fn push_vertices(&mut self, text: &Text, anchor: Vec3, glyphs: &mut Vec<PlacedGlyph>, vertices: &mut Vec<TextVertex>) {
+        glyphs.clear();
+        self.layout(text, glyphs);
+        let scale = text.size / RASTER_SIZE;
+        for placed in glyphs.iter() {
+            let Some(glyph) = self.glyph(placed.id) else {
+                continue;
+            };
+            let min = placed.position + glyph.offset * scale;
+            let max = min + glyph.size.as_vec2() * scale;
+            let uv_min = glyph.min.as_vec2() / ATLAS_SIZE as f32;
+            let uv_max = (glyph.min + glyph.size).as_vec2() / ATLAS_SIZE as f32;
+            let corner = |x: bool, y: bool| TextVertex {
+                anchor,
+                offset: Vec2::new(if x { max.x } else { min.x }, if y { max.y } else { min.y }),
+                uv: Vec2::new(if x { uv_max.x } else { uv_min.x }, if y { uv_max.y } else { uv_min.y }),
+                color: text.color,
+            };
+            // Counter-clockwise on screen, with y pointing down.
+            vertices.extend([
+                corner(false, false), corner(false, true), corner(true, true),
+                corner(false, false), corner(true, true), corner(true, false),
+            ]);
+        }
+    }
+
+    /// Size of `text` when drawn with this font.
+    pub fn measure(&self, text: &Text) -> Vec2 {
+        self.layout(text, &mut Vec::new())
+    }
+}
+
+/// Vertex of the text pipelines.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TextVertex {
+    anchor: Vec3,
+    offset: Vec2,
+    uv: Vec2,
+    color: Vec4,
+}
+
+impl tvk::VertexDescription for TextVertex {
+    fn get_binding_descriptions() -> Vec<avk::VertexInputBindingDescription> {
+        vec![avk::VertexInputBindingDescription {
+            binding: 0,
+            stride: size_of::<TextVertex>() as u32,
+            input_rate: avk::VertexInputRate::VERTEX,
+        }]
+    }
+
+    fn get_attribute_descriptions() -> Vec<avk::VertexInputAttributeDescription> {
+        let attribute = |location, format, offset| avk::VertexInputAttributeDescription {
+            binding: 0,
+            location,
+            format,
+            offset: offset as u32,
+        };
+        vec![
+            attribute(0, avk::Format::R32G32B32_SFLOAT, std::mem::offset_of!(TextVertex, anchor)),
+            attribute(1, avk::Format::R32G32_SFLOAT, std::mem::offset_of!(TextVertex, offset)),
+            attribute(2, avk::Format::R32G32_SFLOAT, std::mem::offset_of!(TextVertex, uv)),
+            attribute(3, avk::Format::R32G32B32A32_SFLOAT, std::mem::offset_of!(TextVertex, color)),
+        ]
+    }
+}
+
+/// Matches the push constants of the text vertex shader.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TextPlacement {
+    transform: Mat4,
+    right: Vec4,
+    down: Vec4,
+}
+
+/// Vertices of one font's text in the frame's vertex buffer.
+struct TextBatch {
+    font: usize,
+    first_vertex: u32,
+    vertex_count: u32,
+}
+
+/// Batches the frame's text into a vertex buffer, drawn with one call per font:
+/// billboards in the scene pass of every view, screen text over the window.
+pub(crate) struct TextPass {
+    descriptor: tvk::TextureDescriptor,
+    screen_pipeline: tvk::Pipeline,
+    vertex_buffers: Vec<tvk::Buffer>,
+    vertices: Vec<TextVertex>,
+    glyphs: Vec<PlacedGlyph>,
+    world_batches: Vec<TextBatch>,
+    screen_batches: Vec<TextBatch>,
+    /// Billboard placement of each view, by view index.
+    view_placements: Vec<TextPlacement>,
+}
+
+impl TextPass {
+    pub const SHADERS: [&str; 2] = ["text.vert.spv", "text.frag.spv"];
+
+    /// `screen_render_pass` is compatible with the one drawing over the swapchain image.
+    pub fn new(context: &tvk::Context, shaders: &Shaders, screen_render_pass: &tvk::RenderPass, frames_in_flight: usize) -> AnyResult<Self> {
+        let descriptor = context.create_texture_descriptor((frames_in_flight * MAX_FONTS) as u32)?;
+        let screen_pipeline = Self::create_screen_pipeline(context, shaders, screen_render_pass, descriptor.layout)?;
+        let vertex_buffers = (0..frames_in_flight).map(|_| {
+            context.create_buffer(avk::BufferUsageFlags::VERTEX_BUFFER, MemoryLocation::CpuToGpu, 1)
+        }).collect::<AnyResult<Vec<_>>>()?;
+        Ok(Self {
+            descriptor,
+            screen_pipeline,
+            vertex_buffers,
+            vertices: Vec::new(),
+            glyphs: Vec::new(),
+            world_batches: Vec::new(),
+            screen_batches: Vec::new(),
+            view_placements: Vec::new(),
+        })
+    }
+
+    /// Builds the screen pipeline again from `shaders`. It replaces the current one when
+    /// the returned function is called. Billboard pipelines belong to the scene passes
+    /// and are rebuilt with `create_world_pipeline`.
+    pub fn reload_shaders(&self, context: &tvk::Context, shaders: &Shaders, screen_render_pass: &tvk::RenderPass) -> AnyResult<PipelineSwap<Self>> {
+        let pipeline = Self::create_screen_pipeline(context, shaders, screen_render_pass, self.descriptor.layout)?;
+        Ok(Box::new(move |pass| pass.screen_pipeline = pipeline))
+    }
+
+    fn create_screen_pipeline(context: &tvk::Context, shaders: &Shaders, render_pass: &tvk::RenderPass, layout: avk::DescriptorSetLayout) -> AnyResult<tvk::Pipeline> {
+        Self::create_pipeline(context, shaders, render_pass, layout, tvk::PipelineState {
+            depth_test: false,
+            ..Default::default()
+        })
+    }
+
+    /// Billboard pipeline of a scene render pass, tested against its depth without
+    /// writing it.
+    pub fn create_world_pipeline(&self, context: &tvk::Context, shaders: &Shaders, render_pass: &tvk::RenderPass, reverse_z: bool, samples: avk::SampleCountFlags) -> AnyResult<tvk::Pipeline> {
+        Self::create_pipeline(context, shaders, render_pass, self.descriptor.layout, tvk::PipelineState {
+            depth_compare_op: if reverse_z { avk::CompareOp::GREATER_OR_EQUAL } else { avk::CompareOp::LESS_OR_EQUAL },
+            depth_write: false,
+            samples,
+            ..Default::default()
+        })
+    }
+
+    fn create_pipeline(context: &tvk::Context, shaders: &Shaders, render_pass: &tvk::RenderPass, layout: avk::DescriptorSetLayout, state: tvk::PipelineState) -> AnyResult<tvk::Pipeline> {
+        let [vertex_source, fragment_source] = shaders.get_all(Self::SHADERS)?;
+        context.create_pipeline::<TextVertex>(
+            render_pass,
+            &[layout],
+            &vertex_fragment_shaders(&vertex_source, &fragment_source),
+            &tvk::PipelineState {
+                mesh_vertices: false,
+                push_constant_size: size_of::<TextPlacement>() as u32,
+                ..state
+            }
+        )
+    }
+
+    /// Lays out the text drawn this frame into its vertex buffer, uploads the atlases
+    /// that gained glyphs and clears `text_draw`. Returns the atlases to keep alive
+    /// until the frame has finished.
+    pub fn prepare(
+        &mut self,
+        context: &tvk::Context,
+        frame_index: usize,
+        (fonts, text_draw): (&mut [Font], &mut TextDraw),
+        views: &[RenderView],
+    ) -> AnyResult<Vec<Arc<dyn std::any::Any>>> {
+        self.vertices.clear();
+        self.world_batches.clear();
+        self.screen_batches.clear();
+        let world = text_draw.world.iter().map(|(text, position)| (text, *position));
+        self.push_batches(fonts, world, false);
+        let screen = text_draw.screen.iter().map(|(text, position)| (text, position.extend(0.0)));
+        self.push_batches(fonts, screen, true);
+        text_draw.clear();
+        if !self.vertices.is_empty() {
+            self.vertex_buffers[frame_index].copy_memory(&self.vertices)?;
+        }
+
+        self.view_placements.clear();
+        self.view_placements.extend(views.iter().map(|view| {
+            let view_matrix = view.camera.view_matrix();
+            TextPlacement {
+                transform: view.camera.view_projection_matrix(),
+                right: view_matrix.row(0).truncate().extend(0.0),
+                down: -view_matrix.row(1).truncate().extend(0.0),
+            }
+        }));
+
+        let mut atlases: Vec<Arc<dyn std::any::Any>> = Vec::new();
+        for (font_index, font) in fonts.iter_mut().enumerate() {
+            if font.dirty {
+                let pixels = (ATLAS_SIZE, ATLAS_SIZE, font.pixels.as_slice());
+                let texture = create_texture_from_pixels(context, pixels, avk::Format::R8_UNORM, avk::SamplerAddressMode::CLAMP_TO_EDGE)?;
+                font.texture = Some(Arc::new(texture));
+                font.dirty = false;
+            }
+            let texture = font.texture.clone().unwrap();
+            self.descriptor.update_set(frame_index * MAX_FONTS + font_index, &texture.image_view, &texture.sampler);
+            atlases.push(texture);
+        }
+        Ok(atlases)
+    }
+
+    /// Appends the vertices of `items` font by font, with a batch for each font used.
+    fn push_batches<'t>(&mut self, fonts: &mut [Font], items: impl Iterator<Item = (&'t Text, Vec3)> + Clone, screen: bool) {
+        for (font_index, font) in fonts.iter_mut().enumerate() {
+            let first_vertex = self.vertices.len() as u32;
+            for (text, anchor) in items.clone().filter(|(text, _)| text.font.0 == font_index) {
+                font.push_vertices(text, anchor, &mut self.glyphs, &mut self.vertices);
+            }
+            let vertex_count = self.vertices.len() as u32 - first_vertex;
+            if vertex_count > 0 {
+                let batches = if screen { &mut self.screen_batches } else { &mut self.world_batches };
+                batches.push(TextBatch { font: font_index, first_vertex, vertex_count });
+            }
+        }
+    }
+
+    pub fn has_screen_text(&self) -> bool {
+        !self.screen_batches.is_empty()
+    }
+
+    /// Draws the billboards into the view begun on `command_buffer`.
+    pub fn record_world(&self, command_buffer: &tvk::CommandBuffer, pipeline: &tvk::Pipeline, frame_index: usize, view: &ViewRecord) {
+        let Some(placement) = self.view_placements.get(view.index as usize) else {
+            return;
+        };
+        self.record_batches(command_buffer, pipeline, frame_index, &self.world_batches, placement);
+    }
+
+    /// Draws the screen text over the whole of a target of size `extent`.
+    pub fn record_screen(&self, command_buffer: &tvk::CommandBuffer, frame_index: usize, extent: avk::Extent2D) {
+        let view = ViewRecord {
+            index: 0,
+            rect: avk::Rect2D { offset: avk::Offset2D::default(), extent },
+            uniform_offset: 0,
+            inverse_view_projection: Mat4::IDENTITY,
+            target: None,
+            clear_color: None,
+            clear_depth: false,
+        };
+        view.begin(command_buffer, false, None);
+        // Pixels to clip space, with y pointing down in both.
+        let size = Vec2::new(extent.width as f32, extent.height as f32);
+        let placement = TextPlacement {
+            transform: Mat4::from_translation(Vec3::new(-1.0, -1.0, 0.0)) * Mat4::from_scale((2.0 / size).extend(1.0)),
+            right: Vec4::X,
+            down: Vec4::Y,
+        };
+        self.record_batches(command_buffer, &self.screen_pipeline, frame_index, &self.screen_batches, &placement);
+    }
+
+    fn record_batches(&self, command_buffer: &tvk::CommandBuffer, pipeline: &tvk::Pipeline, frame_index: usize, batches: &[TextBatch], placement: &TextPlacement) {
+        if batches.is_empty() {
+            return;
+        }
+        command_buffer.bind_pipeline(pipeline);
+        command_buffer.push_constants(pipeline.layout, placement);
+        command_buffer.bind_vertex_buffers(&[self.vertex_buffers[frame_index].inner]);
+        for batch in batches.iter() {
+            command_buffer.bind_descriptor_sets(pipeline.layout, self.descriptor.sets[frame_index * MAX_FONTS + batch.font], &[]);
+            command_buffer.draw(batch.vertex_count, 1, batch.first_vertex, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: f32 = 20.0;
+
+    /// Monospaced, so every glyph advances the pen by the same amount.
+    fn font() -> Font {
+        Font::load(&std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/DejaVuSansMono.ttf")).unwrap()
+    }
+
+    /// Glyph advance and line height at `SIZE`.
+    fn metrics(font: &Font) -> (f32, f32) {
+        let scaled = font.font.as_scaled(PxScale::from(SIZE));
+        (scaled.h_advance(scaled.glyph_id('a')), scaled.height() + scaled.line_gap())
+    }
+
+    fn assert_near(actual: Vec2, expected: Vec2) {
+        assert!(actual.abs_diff_eq(expected, 1e-3), "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn measure_wraps_at_word_boundaries() {
+        let font = font();
+        let (advance, line_height) = metrics(&font);
+        let text = Text::new("aaa bbb cc", FontId(0), SIZE);
+        assert_near(font.measure(&text), Vec2::new(10.0 * advance, line_height));
+
+        // The space after "bbb" hangs past the edge without counting towards the width.
+        let text = text.with_max_width(7.5 * advance);
+        assert_near(font.measure(&text), Vec2::new(7.0 * advance, 2.0 * line_height));
+
+        // A word that fits nowhere gets a line of its own rather than being split.
+        let text = Text::new("a bbbbbb a", FontId(0), SIZE).with_max_width(3.0 * advance);
+        assert_near(font.measure(&text), Vec2::new(6.0 * advance, 3.0 * line_height));
+    }
+
+    #[test]
+    fn measure_counts_line_breaks() {
+        let font = font();
+        let (advance, line_height) = metrics(&font);
+        let text = Text::new("aa\n\naaaa", FontId(0), SIZE).with_max_width(100.0 * advance);
+        assert_near(font.measure(&text), Vec2::new(4.0 * advance, 3.0 * line_height));
+    }
+
+    #[test]
+    fn alignment_offsets_each_line_by_its_own_width() {
+        let font = font();
+        let (advance, line_height) = metrics(&font);
+        let ascent = font.font.as_scaled(PxScale::from(SIZE)).ascent();
+        for (align, shift) in [(TextAlign::Left, 0.0), (TextAlign::Center, -0.5), (TextAlign::Right, -1.0)] {
+            let mut glyphs = Vec::new();
+            let size = font.layout(&Text::new("ab\nabcd", FontId(0), SIZE).with_align(align), &mut glyphs);
+            assert_near(size, Vec2::new(4.0 * advance, 2.0 * line_height));
+            assert_eq!(glyphs.len(), 6);
+            for (i, glyph) in glyphs[..2].iter().enumerate() {
+                assert_near(glyph.position, Vec2::new((i as f32 + 2.0 * shift) * advance, ascent));
+            }
+            for (i, glyph) in glyphs[2..].iter().enumerate() {
+                assert_near(glyph.position, Vec2::new((i as f32 + 4.0 * shift) * advance, ascent + line_height));
+            }
+        }
+    }
+}
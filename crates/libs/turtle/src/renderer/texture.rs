@@ -0,0 +1,7 @@
+use crate::*;
+
+pub struct Texture {
+    pub image_view: tvk::ImageView,
+    pub sampler: tvk::Sampler,
+    pub image: tvk::Image,
+}
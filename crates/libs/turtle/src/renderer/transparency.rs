@@ -0,0 +1,320 @@
+use std::ops::Range;
+
+use ash::vk as avk;
+use gpu_allocator::MemoryLocation;
+use crate::*;
+
+/// Most views per frame drawing order-independent transparency. Views beyond it sort
+/// their order-independent instances along with the blended ones.
+pub const MAX_ORDER_INDEPENDENT_VIEWS: usize = 16;
+pub(crate) const ACCUMULATION_FORMAT: avk::Format = avk::Format::R16G16B16A16_SFLOAT;
+pub(crate) const REVEALAGE_FORMAT: avk::Format = avk::Format::R16_SFLOAT;
+
+/// Consecutive instances of one group in the frame's transparent instance buffer.
+#[derive(Clone, Copy)]
+pub(crate) struct TransparentBatch {
+    pub group: usize,
+    pub first_instance: u32,
+    pub instance_count: u32,
+}
+
+/// Transparent batches of one view.
+struct ViewBatches {
+    view_index: u32,
+    /// Back to front.
+    blended: Range<usize>,
+    order_independent: Range<usize>,
+    /// Descriptor set compositing the view's order-independent instances, if it has any.
+    composite_set: Option<usize>,
+}
+
+/// Pipelines drawing the transparent instances of a render pass's views.
+pub(crate) struct TransparencyPipelines {
+    /// Alpha blended, drawn into the scene pass after the opaque geometry.
+    pub blended: tvk::Pipeline,
+    /// Weighted blended accumulation, in a pass of its own sharing the scene depth.
+    pub accumulate: tvk::Pipeline,
+    /// Blends the accumulated color over the scene.
+    pub composite: tvk::Pipeline,
+}
+
+impl TransparencyPipelines {
+    pub const SHADERS: [&str; 4] = ["shader.vert.spv", "shader.frag.spv", "fullscreen.vert.spv", "oit_composite.frag.spv"];
+
+    /// `render_pass` is the scene pass the views are drawn in, with a color attachment
+    /// of `color_format`.
+    pub fn new(
+        context: &tvk::Context,
+        shaders: &Shaders,
+        (render_pass, color_format): (&tvk::RenderPass, avk::Format),
+        scene_layouts: &[avk::DescriptorSetLayout],
+        composite_layout: avk::DescriptorSetLayout,
+        reverse_z: bool,
+        samples: avk::SampleCountFlags,
+    ) -> AnyResult<Self> {
+        let [vertex_source, fragment_source, composite_vertex_source, composite_fragment_source] = shaders.get_all(Self::SHADERS)?;
+        // Tested against the opaque depth without hiding each other.
+        let state = tvk::PipelineState {
+            depth_compare_op: if reverse_z { avk::CompareOp::GREATER } else { avk::CompareOp::LESS },
+            depth_write: false,
+            samples,
+            push_constant_size: size_of::<MaterialConstants>() as u32,
+            ..Default::default()
+        };
+        let blended = context.create_pipeline::<tvk::FullInstanceData>(
+            render_pass,
+            scene_layouts,
+            &vertex_fragment_shaders(&vertex_source, &fragment_source),
+            &state
+        )?;
+
+        // Pipelines only need compatible render passes; the graph creates the ones they draw in.
+        let resolves = if samples == avk::SampleCountFlags::TYPE_1 {
+            Vec::new()
+        } else {
+            vec![attachment(ACCUMULATION_FORMAT, avk::SampleCountFlags::TYPE_1), attachment(REVEALAGE_FORMAT, avk::SampleCountFlags::TYPE_1)]
+        };
+        let accumulation_pass = tvk::RenderPass::with_attachments(
+            context.logical_device.clone(),
+            &[attachment(ACCUMULATION_FORMAT, samples), attachment(REVEALAGE_FORMAT, samples)],
+            Some(attachment(context.physical_device.depth_format, samples).final_layout(avk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)),
+            &resolves,
+        )?;
+        let accumulate = context.create_pipeline::<tvk::FullInstanceData>(
+            &accumulation_pass,
+            scene_layouts,
+            &vertex_fragment_shaders(&vertex_source, &fragment_source),
+            &tvk::PipelineState { order_independent: true, ..state }
+        )?;
+
+        let composite_pass = tvk::RenderPass::with_attachments(context.logical_device.clone(), &[attachment(color_format, samples)], None, &[])?;
+        let composite = context.create_pipeline::<()>(
+            &composite_pass,
+            &[composite_layout],
+            &vertex_fragment_shaders(&composite_vertex_source, &composite_fragment_source),
+            &tvk::PipelineState {
+                vertex_input: false,
+                depth_test: false,
+                samples,
+                ..Default::default()
+            }
+        )?;
+        Ok(Self { blended, accumulate, composite })
+    }
+}
+
+fn attachment(format: avk::Format, samples: avk::SampleCountFlags) -> avk::AttachmentDescription {
+    avk::AttachmentDescription::default()
+        .format(format)
+        .samples(samples)
+        .load_op(avk::AttachmentLoadOp::DONT_CARE)
+        .store_op(avk::AttachmentStoreOp::STORE)
+        .stencil_load_op(avk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(avk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(avk::ImageLayout::UNDEFINED)
+        .final_layout(avk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+}
+
+/// Transparent instances of every view, copied each frame into an instance buffer in
+/// drawing order: blended ones sorted back to front across instance groups, then the
+/// order-independent ones group by group.
+pub(crate) struct TransparentQueue {
+    /// Accumulation and revealage images of each order-independent view.
+    pub composite_descriptor: tvk::TextureDescriptor,
+    composite_sampler: tvk::Sampler,
+    instance_buffers: Vec<tvk::Buffer>,
+    staging: Vec<tvk::FullInstanceData>,
+    /// Sort distance, group and instance of each blended instance of a view.
+    sort_keys: Vec<(f32, usize, usize)>,
+    batches: Vec<TransparentBatch>,
+    views: Vec<ViewBatches>,
+}
+
+impl TransparentQueue {
+    pub fn new(context: &tvk::Context, frames_in_flight: usize) -> AnyResult<Self> {
+        let instance_buffers = (0..frames_in_flight).map(|_| {
+            context.create_buffer(avk::BufferUsageFlags::VERTEX_BUFFER, MemoryLocation::CpuToGpu, 1)
+        }).collect::<AnyResult<Vec<_>>>()?;
+        Ok(Self {
+            composite_descriptor: context.create_texture_descriptor_with_bindings((frames_in_flight * MAX_ORDER_INDEPENDENT_VIEWS) as u32, 2)?,
+            composite_sampler: context.create_sampler(avk::Filter::NEAREST, avk::SamplerAddressMode::CLAMP_TO_EDGE)?,
+            instance_buffers,
+            staging: Vec::new(),
+            sort_keys: Vec::new(),
+            batches: Vec::new(),
+            views: Vec::new(),
+        })
+    }
+
+    /// Sorts the visible instances of the transparent groups for each view and uploads
+    /// them into this frame's instance buffer. Perspective views sort by distance from
+    /// the camera, orthographic ones by depth along it.
+    pub fn prepare(&mut self, frame_index: usize, views: &[RenderView], view_records: &[ViewRecord], instance_groups: &[InstanceGroup]) -> AnyResult<()> {
+        self.staging.clear();
+        self.batches.clear();
+        self.views.clear();
+        let mut composites = 0;
+        for record in view_records.iter() {
+            let camera = views[record.index as usize].camera;
+            let orthographic = matches!(camera.projection, Projection::Orthographic { .. });
+            let forward = camera.forward();
+            let order_independent = composites < MAX_ORDER_INDEPENDENT_VIEWS;
+
+            self.sort_keys.clear();
+            for (group_index, instance_group) in instance_groups.iter().enumerate() {
+                let sorted = match instance_group.material.alpha_mode {
+                    AlphaMode::Opaque | AlphaMode::Mask { .. } => false,
+                    AlphaMode::Blend => true,
+                    AlphaMode::OrderIndependent => !order_independent,
+                };
+                let Some(mesh) = instance_group.mesh.get().filter(|_| sorted) else {
+                    continue;
+                };
+                let center = mesh.bounds().center();
+                for &instance in instance_group.visible_indices() {
+                    let offset = instance_group.instances()[instance].model.transform_point3(center) - camera.position;
+                    let distance = if orthographic { offset.dot(forward) } else { offset.length_squared() };
+                    self.sort_keys.push((distance, group_index, instance));
+                }
+            }
+            sort_back_to_front(&mut self.sort_keys);
+            let blended_start = self.batches.len();
+            for &(_, group, instance) in self.sort_keys.iter() {
+                let data = tvk::FullInstanceData::new(instance_groups[group].instances()[instance], instance);
+                push_instance(&mut self.batches, &mut self.staging, blended_start, group, data);
+            }
+
+            let order_independent_start = self.batches.len();
+            if order_independent {
+                for (group_index, instance_group) in instance_groups.iter().enumerate() {
+                    if instance_group.material.alpha_mode != AlphaMode::OrderIndependent || instance_group.mesh.get().is_none() {
+                        continue;
+                    }
+                    for &instance in instance_group.visible_indices() {
+                        let data = tvk::FullInstanceData::new(instance_group.instances()[instance], instance);
+                        push_instance(&mut self.batches, &mut self.staging, order_independent_start, group_index, data);
+                    }
+                }
+            }
+            let composite_set = (self.batches.len() > order_independent_start).then(|| {
+                composites += 1;
+                frame_index * MAX_ORDER_INDEPENDENT_VIEWS + composites - 1
+            });
+            self.views.push(ViewBatches {
+                view_index: record.index,
+                blended: blended_start..order_independent_start,
+                order_independent: order_independent_start..self.batches.len(),
+                composite_set,
+            });
+        }
+        if !self.staging.is_empty() {
+            self.instance_buffers[frame_index].copy_memory(&self.staging)?;
+        }
+        Ok(())
+    }
+
+    fn view(&self, view: &ViewRecord) -> Option<&ViewBatches> {
+        self.views.iter().find(|batches| batches.view_index == view.index)
+    }
+
+    /// Whether the view has order-independent instances to accumulate and composite.
+    pub fn has_order_independent(&self, view: &ViewRecord) -> bool {
+        self.view(view).is_some_and(|batches| batches.composite_set.is_some())
+    }
+
+    /// The view's blended batches back to front, or its order-independent ones.
+    pub fn batches(&self, view: &ViewRecord, order_independent: bool) -> &[TransparentBatch] {
+        match self.view(view) {
+            Some(batches) if order_independent => &self.batches[batches.order_independent.clone()],
+            Some(batches) => &self.batches[batches.blended.clone()],
+            None => &[],
+        }
+    }
+
+    pub fn instance_buffer(&self, frame_index: usize) -> &tvk::Buffer {
+        &self.instance_buffers[frame_index]
+    }
+
+    /// Points the view's composite set at the resolved accumulation and revealage.
+    /// Must run before any pass is recorded.
+    pub fn set_accumulation(&self, view: &ViewRecord, accumulation: avk::ImageView, revealage: avk::ImageView) {
+        if let Some(set) = self.view(view).and_then(|batches| batches.composite_set) {
+            self.composite_descriptor.update_binding(set, 0, accumulation, &self.composite_sampler);
+            self.composite_descriptor.update_binding(set, 1, revealage, &self.composite_sampler);
+        }
+    }
+
+    /// Blends the view's accumulated order-independent instances over the scene.
+    pub fn record_composite(&self, command_buffer: &tvk::CommandBuffer, pipelines: &TransparencyPipelines, view: &ViewRecord) {
+        let Some(set) = self.view(view).and_then(|batches| batches.composite_set) else {
+            return;
+        };
+        view.restrict(command_buffer);
+        command_buffer.bind_pipeline(&pipelines.composite);
+        command_buffer.bind_descriptor_sets(pipelines.composite.layout, self.composite_descriptor.sets[set], &[]);
+        command_buffer.draw(3, 1, 0, 0);
+    }
+}
+
+/// Farthest first. The sort is stable, so ties keep their group's batch.
+fn sort_back_to_front(sort_keys: &mut [(f32, usize, usize)]) {
+    sort_keys.sort_by(|a, b| b.0.total_cmp(&a.0));
+}
+
+/// Appends an instance, extending the last batch from `first_batch` on when it is of
+/// the same group.
+fn push_instance(batches: &mut Vec<TransparentBatch>, staging: &mut Vec<tvk::FullInstanceData>, first_batch: usize, group: usize, data: tvk::FullInstanceData) {
+    let len = batches.len();
+    if len > first_batch && batches[len - 1].group == group {
+        batches[len - 1].instance_count += 1;
+    } else {
+        batches.push(TransparentBatch { group, first_instance: staging.len() as u32, instance_count: 1 });
+    }
+    staging.push(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Mat4, Vec3};
+    use super::*;
+
+    fn batch_sorted(sort_keys: &mut [(f32, usize, usize)]) -> (Vec<TransparentBatch>, Vec<tvk::FullInstanceData>) {
+        sort_back_to_front(sort_keys);
+        let (mut batches, mut staging) = (Vec::new(), Vec::new());
+        for &(_, group, instance) in sort_keys.iter() {
+            let data = tvk::InstanceData { model: Mat4::IDENTITY, color: Vec3::ZERO };
+            push_instance(&mut batches, &mut staging, 0, group, tvk::FullInstanceData::new(data, instance));
+        }
+        (batches, staging)
+    }
+
+    fn groups(batches: &[TransparentBatch]) -> Vec<(usize, u32, u32)> {
+        batches.iter().map(|batch| (batch.group, batch.first_instance, batch.instance_count)).collect()
+    }
+
+    #[test]
+    fn blended_instances_interleave_groups_back_to_front() {
+        let mut sort_keys = [(1.0, 0, 0), (9.0, 0, 1), (5.0, 1, 0), (4.0, 1, 1), (7.0, 0, 2)];
+        let (batches, staging) = batch_sorted(&mut sort_keys);
+        assert_eq!(groups(&batches), [(0, 0, 2), (1, 2, 2), (0, 4, 1)]);
+        let instances: Vec<_> = staging.iter().map(|data| data.index).collect();
+        assert_eq!(instances, [1, 2, 0, 1, 0]);
+    }
+
+    #[test]
+    fn ties_keep_group_order() {
+        let mut sort_keys = [(3.0, 0, 0), (3.0, 1, 0), (3.0, 0, 1)];
+        let (batches, _) = batch_sorted(&mut sort_keys);
+        assert_eq!(groups(&batches), [(0, 0, 1), (1, 1, 1), (0, 2, 1)]);
+    }
+
+    #[test]
+    fn batches_of_earlier_views_are_not_extended() {
+        let mut batches = vec![TransparentBatch { group: 2, first_instance: 0, instance_count: 1 }];
+        let data = tvk::FullInstanceData::new(tvk::InstanceData { model: Mat4::IDENTITY, color: Vec3::ZERO }, 0);
+        let mut staging = vec![data];
+        push_instance(&mut batches, &mut staging, 1, 2, data);
+        push_instance(&mut batches, &mut staging, 1, 2, data);
+        assert_eq!(groups(&batches), [(2, 0, 1), (2, 1, 2)]);
+    }
+}
@@ -0,0 +1,202 @@
+use ash::vk as avk;
+
+use crate::*;
+
+/// Rectangle of the window in normalized coordinates, with the origin at the top left.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+impl Viewport {
+    pub const FULL: Self = Self::new(0.0, 0.0, 1.0, 1.0);
+
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Pixel rectangle covered inside a target of size `extent`.
+    pub fn to_rect(&self, extent: avk::Extent2D) -> avk::Rect2D {
+        let (width, height) = (extent.width as f32, extent.height as f32);
+        let x = (self.x * width).round().clamp(0.0, width);
+        let y = (self.y * height).round().clamp(0.0, height);
+        let right = ((self.x + self.width) * width).round().clamp(x, width);
+        let bottom = ((self.y + self.height) * height).round().clamp(y, height);
+        avk::Rect2D {
+            offset: avk::Offset2D { x: x as i32, y: y as i32 },
+            extent: avk::Extent2D { width: (right - x) as u32, height: (bottom - y) as u32 },
+        }
+    }
+
+    pub fn aspect_ratio(&self, extent: avk::Extent2D) -> f32 {
+        let rect = self.to_rect(extent);
+        rect.extent.width as f32 / rect.extent.height.max(1) as f32
+    }
+}
+
+/// Extra camera drawn after the main one, e.g. the other half of a split screen or a
+/// minimap in a corner.
+pub struct View {
+    pub camera: Camera,
+    /// Part of the window, or of `target` when set.
+    pub viewport: Viewport,
+    /// Render target to draw into instead of the window.
+    pub target: Option<RenderTargetId>,
+    /// Color to fill the viewport with before drawing. `None` keeps whatever was
+    /// drawn underneath and adds the renderer's background behind the geometry; depth
+    /// is cleared either way.
+    pub clear_color: Option<[f32; 4]>,
+}
+
+impl View {
+    pub fn new(camera: Camera, viewport: Viewport) -> Self {
+        Self {
+            camera,
+            viewport,
+            target: None,
+            clear_color: None,
+        }
+    }
+
+    pub fn with_target(mut self, target: RenderTargetId) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn with_clear_color(mut self, clear_color: [f32; 4]) -> Self {
+        self.clear_color = Some(clear_color);
+        self
+    }
+}
+
+/// One camera to draw in `Renderer::render`.
+#[derive(Clone, Copy)]
+pub struct RenderView<'a> {
+    pub camera: &'a Camera,
+    pub viewport: Viewport,
+    pub target: Option<RenderTargetId>,
+    pub clear_color: Option<[f32; 4]>,
+}
+
+impl<'a> From<&'a View> for RenderView<'a> {
+    fn from(view: &'a View) -> Self {
+        Self {
+            camera: &view.camera,
+            viewport: view.viewport,
+            target: view.target,
+            clear_color: view.clear_color,
+        }
+    }
+}
+
+/// Per-view state resolved for the frame being recorded.
+#[derive(Clone, Copy)]
+pub(crate) struct ViewRecord {
+    /// Position among the views passed to `Renderer::render`.
+    pub index: u32,
+    pub rect: avk::Rect2D,
+    pub uniform_offset: u32,
+    /// Unprojects the view's clip space into world space.
+    pub inverse_view_projection: glam::Mat4,
+    pub target: Option<RenderTargetId>,
+    pub clear_color: Option<[f32; 4]>,
+    /// Earlier views may have drawn into this rectangle of the same target, so its
+    /// depth must be cleared.
+    pub clear_depth: bool,
+}
+
+impl ViewRecord {
+    /// Restricts drawing to the view and clears it as needed. `clear_color` is the
+    /// attachment value used when the view has a clear color.
+    pub fn begin(&self, command_buffer: &tvk::CommandBuffer, reverse_z: bool, clear_color: Option<avk::ClearColorValue>) {
+        self.restrict(command_buffer);
+        if self.clear_depth || clear_color.is_some() {
+            command_buffer.clear_attachments(self.rect, clear_color, if reverse_z { 0.0 } else { 1.0 });
+        }
+    }
+
+    /// Restricts drawing to the view without clearing anything.
+    pub fn restrict(&self, command_buffer: &tvk::CommandBuffer) {
+        command_buffer.set_scissor(self.rect);
+        command_buffer.set_viewport(avk::Viewport::default()
+            .x(self.rect.offset.x as f32)
+            .y(self.rect.offset.y as f32)
+            .width(self.rect.extent.width as f32)
+            .height(self.rect.extent.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0));
+    }
+}
+
+/// Whether the views' cameras use reverse-Z depth, or `None` without views. The scene
+/// pipelines and depth clears are shared by every view of a frame, so mixing regular
+/// and reverse-Z projections is an error.
+pub(crate) fn views_reverse_z(views: &[RenderView]) -> AnyResult<Option<bool>> {
+    let Some(first) = views.first() else {
+        return Ok(None);
+    };
+    let reverse_z = first.camera.projection.is_reverse_z();
+    if let Some(index) = views.iter().position(|view| view.camera.projection.is_reverse_z() != reverse_z) {
+        return Err(format!("view {} disagrees with view 0 on reverse-Z depth; every view of a frame must use the same", index).into());
+    }
+    Ok(Some(reverse_z))
+}
+
+/// The main camera in `viewport`, followed by `views`.
+pub(crate) fn collect_render_views<'a>(camera: &'a Camera, viewport: Viewport, views: &'a [View]) -> Vec<RenderView<'a>> {
+    std::iter::once(RenderView {
+        camera,
+        viewport,
+        target: None,
+        clear_color: None,
+    }).chain(views.iter().map(RenderView::from)).collect()
+}
+
+impl AppData {
+    /// Every camera in drawing order: the main camera, then `views`.
+    pub fn render_views(&self) -> Vec<RenderView<'_>> {
+        collect_render_views(&self.camera, self.viewport, &self.views)
+    }
+
+    /// Topmost camera drawn into the window at `pixel`, with its pixel rectangle.
+    pub fn view_at(&self, pixel: glam::Vec2) -> Option<(&Camera, avk::Rect2D)> {
+        let extent = self.renderer.swapchain.extent;
+        self.render_views().into_iter().rev().filter(|view| view.target.is_none()).map(|view| (view.camera, view.viewport.to_rect(extent))).find(|(_, rect)| {
+            let offset = glam::vec2(rect.offset.x as f32, rect.offset.y as f32);
+            let size = glam::vec2(rect.extent.width as f32, rect.extent.height as f32);
+            pixel.cmpge(offset).all() && pixel.cmplt(offset + size).all()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera(reverse_z: bool) -> Camera {
+        Camera {
+            projection: Projection::Perspective { fov: 45.0, near: 0.1, far: None, reverse_z },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn views_must_agree_on_reverse_z() {
+        let main = camera(true);
+        let agreeing = [View::new(camera(true), Viewport::new(0.5, 0.0, 0.5, 1.0))];
+        assert_eq!(views_reverse_z(&collect_render_views(&main, Viewport::FULL, &agreeing)).unwrap(), Some(true));
+        assert_eq!(views_reverse_z(&[]).unwrap(), None);
+
+        let mixed = [View::new(camera(true), Viewport::FULL), View::new(camera(false), Viewport::FULL)];
+        assert!(views_reverse_z(&collect_render_views(&main, Viewport::FULL, &mixed)).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use ash::vk as avk;
+use crate::*;
+
+/// How the renderer shades the opaque and transparent instance groups. Every mode
+/// but `Solid` draws all groups as opaque, without lighting, background or
+/// post-processing, so the colors show unchanged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ViewMode {
+    #[default]
+    Solid,
+    /// Triangle edges in the instance colors.
+    Wireframe,
+    /// World-space normals mapped to colors.
+    Normals,
+    /// View depth, white at the camera fading logarithmically to black.
+    Depth,
+    /// Checkerboard of the texture coordinates.
+    UvChecker,
+    /// A color per instance, kept when other instances are hidden.
+    InstanceId,
+    /// Heat map of how many fragments cover each pixel, ignoring depth.
+    Overdraw,
+}
+
+impl ViewMode {
+    pub const ALL: [ViewMode; 7] = [
+        ViewMode::Solid,
+        ViewMode::Wireframe,
+        ViewMode::Normals,
+        ViewMode::Depth,
+        ViewMode::UvChecker,
+        ViewMode::InstanceId,
+        ViewMode::Overdraw,
+    ];
+
+    /// The mode after this one, wrapping back to `Solid`.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&mode| mode == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Whether wireframes are drawn from barycentric coordinates, for devices that
+    /// cannot rasterize lines. Instances are then drawn without indices.
+    pub(crate) fn barycentric_wireframe(self, context: &tvk::Context) -> bool {
+        self == ViewMode::Wireframe && !context.physical_device.supports_wireframe()
+    }
+
+    /// Value selecting the mode in the view mode fragment shader.
+    pub(crate) fn shader_mode(self, context: &tvk::Context) -> u32 {
+        if self.barycentric_wireframe(context) {
+            return 7;
+        }
+        self as u32
+    }
+
+    /// Variant of the scene pipeline `state` drawing this mode, and its fragment shader.
+    pub(crate) fn pipeline_variant(self, context: &tvk::Context, state: tvk::PipelineState) -> (tvk::PipelineState, &'static str) {
+        let state = match self {
+            ViewMode::Solid => return (state, "shader.frag.spv"),
+            ViewMode::Wireframe if !self.barycentric_wireframe(context) => tvk::PipelineState {
+                polygon_mode: avk::PolygonMode::LINE,
+                ..state
+            },
+            ViewMode::Overdraw => tvk::PipelineState {
+                additive_blend: true,
+                depth_test: false,
+                ..state
+            },
+            _ => state,
+        };
+        (state, "view_mode.frag.spv")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_cycles_through_every_mode() {
+        let mut mode = ViewMode::Solid;
+        let mut visited = Vec::new();
+        for _ in 0..ViewMode::ALL.len() {
+            visited.push(mode);
+            mode = mode.next();
+        }
+        assert_eq!(visited, ViewMode::ALL);
+        assert_eq!(mode, ViewMode::Solid);
+        assert_eq!(ViewMode::Overdraw.next(), ViewMode::Solid);
+    }
+
+    #[test]
+    fn modes_are_listed_in_shader_order() {
+        // The view mode fragment shader selects modes by these values.
+        for (index, mode) in ViewMode::ALL.into_iter().enumerate() {
+            assert_eq!(mode as usize, index);
+        }
+    }
+}
@@ -0,0 +1,362 @@
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+use crate::*;
+
+pub mod serialization;
+pub use serialization::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn to_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+/// Slot of an `InstanceGroup` whose model matrix is driven by a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstanceBinding {
+    pub group: usize,
+    pub instance: usize,
+}
+
+pub struct Node {
+    pub name: String,
+    transform: Transform,
+    world: Mat4,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    binding: Option<InstanceBinding>,
+    dirty: bool,
+}
+
+impl Node {
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn world_matrix(&self) -> Mat4 {
+        self.world
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    pub fn binding(&self) -> Option<InstanceBinding> {
+        self.binding
+    }
+}
+
+/// Hierarchy of nodes with local transforms.
+///
+/// Changing a node only marks it dirty; `update` recomputes world matrices for the
+/// dirty subtrees and writes them into the bound instances.
+#[derive(Default)]
+pub struct Scene {
+    nodes: Vec<Option<Node>>,
+    free: Vec<usize>,
+    roots: Vec<NodeId>,
+    dirty_nodes: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_node(&mut self, name: &str, parent: Option<NodeId>, transform: Transform) -> NodeId {
+        let node = Node {
+            name: name.to_string(),
+            transform,
+            world: Mat4::IDENTITY,
+            parent,
+            children: Vec::new(),
+            binding: None,
+            dirty: false,
+        };
+        let id = match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = Some(node);
+                NodeId(index)
+            }
+            None => {
+                self.nodes.push(Some(node));
+                NodeId(self.nodes.len() - 1)
+            }
+        };
+
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        self.mark_dirty(id);
+        id
+    }
+
+    /// Adds an instance to `instance_groups[group]` and a node that drives its model matrix.
+    pub fn spawn_instance(
+        &mut self,
+        instance_groups: &mut [InstanceGroup],
+        name: &str,
+        parent: Option<NodeId>,
+        transform: Transform,
+        group: usize,
+        color: Vec3,
+    ) -> NodeId {
+        let instance = instance_groups[group].add_instance(tvk::InstanceData {
+            model: Mat4::IDENTITY,
+            color
+        }, true);
+        let id = self.add_node(name, parent, transform);
+        self.bind_instance(id, InstanceBinding { group, instance });
+        id
+    }
+
+    /// Removes a node and its whole subtree, removing every bound instance from its group.
+    pub fn remove_node(&mut self, id: NodeId, instance_groups: &mut [InstanceGroup]) {
+        match self.node(id).parent {
+            Some(parent) => self.node_mut(parent).children.retain(|&c| c != id),
+            None => self.roots.retain(|&r| r != id),
+        }
+
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop() {
+            let node = self.nodes[current.0].take().expect("node was already removed");
+            if let Some(binding) = node.binding {
+                instance_groups[binding.group].remove_instance(binding.instance);
+            }
+            stack.extend(node.children);
+            self.free.push(current.0);
+        }
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(id.0).and_then(|n| n.as_ref())
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        self.get(id).expect("invalid node id")
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.nodes.get_mut(id.0).and_then(|n| n.as_mut()).expect("invalid node id")
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        self.nodes.iter().enumerate().filter_map(|(i, n)| n.as_ref().map(|n| (NodeId(i), n)))
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.iter().find(|(_, n)| n.name == name).map(|(id, _)| id)
+    }
+
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) {
+        self.node_mut(id).transform = transform;
+        self.mark_dirty(id);
+    }
+
+    pub fn set_translation(&mut self, id: NodeId, translation: Vec3) {
+        self.node_mut(id).transform.translation = translation;
+        self.mark_dirty(id);
+    }
+
+    pub fn set_rotation(&mut self, id: NodeId, rotation: Quat) {
+        self.node_mut(id).transform.rotation = rotation;
+        self.mark_dirty(id);
+    }
+
+    pub fn set_scale(&mut self, id: NodeId, scale: Vec3) {
+        self.node_mut(id).transform.scale = scale;
+        self.mark_dirty(id);
+    }
+
+    pub fn bind_instance(&mut self, id: NodeId, binding: InstanceBinding) {
+        self.node_mut(id).binding = Some(binding);
+        self.mark_dirty(id);
+    }
+
+    pub fn unbind_instance(&mut self, id: NodeId) -> Option<InstanceBinding> {
+        self.node_mut(id).binding.take()
+    }
+
+    /// Moves a node under a new parent, keeping its local transform.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> AnyResult<()> {
+        let mut ancestor = parent;
+        while let Some(current) = ancestor {
+            if current == id {
+                return Err(String::from("cannot parent a node to itself or one of its descendants").into());
+            }
+            ancestor = self.node(current).parent;
+        }
+
+        match self.node(id).parent {
+            Some(old) => self.node_mut(old).children.retain(|&c| c != id),
+            None => self.roots.retain(|&r| r != id),
+        }
+        match parent {
+            Some(new) => self.node_mut(new).children.push(id),
+            None => self.roots.push(id),
+        }
+        self.node_mut(id).parent = parent;
+        self.mark_dirty(id);
+        Ok(())
+    }
+
+    fn mark_dirty(&mut self, id: NodeId) {
+        let node = self.node_mut(id);
+        if !node.dirty {
+            node.dirty = true;
+            self.dirty_nodes.push(id);
+        }
+    }
+
+    /// Propagates world matrices through the dirty subtrees and writes them into the
+    /// bound instances, which `Renderer::render` uploads.
+    pub fn update(&mut self, instance_groups: &mut [InstanceGroup]) {
+        let dirty_nodes = std::mem::take(&mut self.dirty_nodes);
+        for id in dirty_nodes {
+            if !self.get(id).is_some_and(|n| n.dirty) {
+                continue;
+            }
+
+            // Start from the highest dirty ancestor so each subtree is visited once.
+            let mut top = id;
+            while let Some(parent) = self.node(top).parent && self.node(parent).dirty {
+                top = parent;
+            }
+
+            let parent_world = self.node(top).parent.map_or(Mat4::IDENTITY, |p| self.node(p).world);
+            let mut stack = vec![(top, parent_world)];
+            while let Some((current, parent_world)) = stack.pop() {
+                let node = self.node_mut(current);
+                node.world = parent_world * node.transform.to_matrix();
+                node.dirty = false;
+                let world = node.world;
+                if let Some(binding) = node.binding {
+                    let group = &mut instance_groups[binding.group];
+                    let mut data = group.instances()[binding.instance];
+                    data.model = world;
+                    group.set_instance(binding.instance, data);
+                }
+                stack.extend(self.node(current).children.iter().map(|&c| (c, world)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance_groups() -> Vec<InstanceGroup> {
+        vec![InstanceGroup::from(Handle::loading("cube.obj".into(), None))]
+    }
+
+    fn translation(matrix: Mat4) -> Vec3 {
+        matrix.w_axis.truncate()
+    }
+
+    #[test]
+    fn propagates_from_a_dirty_grandparent() {
+        let mut groups = instance_groups();
+        let mut scene = Scene::new();
+        let root = scene.add_node("root", None, Transform::from_translation(Vec3::X));
+        let child = scene.add_node("child", Some(root), Transform {
+            scale: Vec3::splat(2.0),
+            ..Transform::from_translation(Vec3::Y)
+        });
+        let grandchild = scene.spawn_instance(&mut groups, "grandchild", Some(child), Transform::from_translation(Vec3::Z), 0, Vec3::ONE);
+        scene.update(&mut groups);
+        assert_eq!(translation(scene.node(grandchild).world_matrix()), Vec3::new(1.0, 1.0, 2.0));
+
+        // Only the grandparent changes; its descendants must still follow.
+        scene.set_translation(root, Vec3::new(10.0, 0.0, 0.0));
+        scene.update(&mut groups);
+        assert_eq!(translation(scene.node(child).world_matrix()), Vec3::new(10.0, 1.0, 0.0));
+        assert_eq!(translation(scene.node(grandchild).world_matrix()), Vec3::new(10.0, 1.0, 2.0));
+        let instance = scene.node(grandchild).binding().unwrap().instance;
+        assert_eq!(groups[0].instances()[instance].model, scene.node(grandchild).world_matrix());
+    }
+
+    #[test]
+    fn reparenting_keeps_the_local_transform() {
+        let mut groups = instance_groups();
+        let mut scene = Scene::new();
+        let a = scene.add_node("a", None, Transform::from_translation(Vec3::X));
+        let b = scene.add_node("b", None, Transform::from_translation(Vec3::new(0.0, 5.0, 0.0)));
+        let node = scene.add_node("node", Some(a), Transform::from_translation(Vec3::Z));
+        scene.update(&mut groups);
+        assert_eq!(translation(scene.node(node).world_matrix()), Vec3::new(1.0, 0.0, 1.0));
+
+        scene.set_parent(node, Some(b)).unwrap();
+        scene.update(&mut groups);
+        assert_eq!(translation(scene.node(node).world_matrix()), Vec3::new(0.0, 5.0, 1.0));
+        assert!(scene.node(a).children().is_empty());
+        assert_eq!(scene.node(b).children(), [node]);
+
+        scene.set_parent(node, None).unwrap();
+        scene.update(&mut groups);
+        assert_eq!(translation(scene.node(node).world_matrix()), Vec3::Z);
+        assert!(scene.roots().contains(&node));
+        assert!(scene.set_parent(a, Some(a)).is_err());
+    }
+
+    #[test]
+    fn removed_nodes_release_their_instances() {
+        let mut groups = instance_groups();
+        let mut scene = Scene::new();
+        for _ in 0..100 {
+            let parent = scene.spawn_instance(&mut groups, "parent", None, Transform::default(), 0, Vec3::ONE);
+            scene.spawn_instance(&mut groups, "child", Some(parent), Transform::default(), 0, Vec3::ONE);
+            scene.update(&mut groups);
+            scene.remove_node(parent, &mut groups);
+        }
+        assert_eq!(groups[0].instances().len(), 2);
+        assert!(groups[0].visible_indices().is_empty());
+        assert_eq!(scene.iter().count(), 0);
+    }
+}
@@ -0,0 +1,452 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use glam::{Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+use crate::*;
+
+/// Bumped whenever the layout of `SceneFile` changes in a way older readers cannot handle.
+pub const SCENE_FORMAT_VERSION: u32 = 4;
+
+/// Human-readable (RON) snapshot of everything needed to rebuild a scene.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneFile {
+    pub version: u32,
+    #[serde(default = "default_clear_color")]
+    pub clear_color: [f32; 4],
+    #[serde(default)]
+    pub background: BackgroundDescription,
+    #[serde(default)]
+    pub camera: CameraDescription,
+    #[serde(default)]
+    pub light: DirectionalLight,
+    /// `None` turns shadows off.
+    #[serde(default = "default_shadows")]
+    pub shadows: Option<Shadows>,
+    /// Point and spot lights.
+    #[serde(default)]
+    pub lights: Vec<Light>,
+    #[serde(default)]
+    pub instance_groups: Vec<InstanceGroupDescription>,
+    #[serde(default)]
+    pub nodes: Vec<NodeDescription>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CameraDescription {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    #[serde(default)]
+    pub projection: Projection,
+    /// Field of view written by files from before `projection` existed. Overrides the
+    /// field of view of a perspective projection when present.
+    #[serde(default, skip_serializing)]
+    pub fov: Option<f32>,
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        Self::from(&Camera::default())
+    }
+}
+
+impl From<&Camera> for CameraDescription {
+    fn from(camera: &Camera) -> Self {
+        Self {
+            position: camera.position,
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            projection: camera.projection,
+            fov: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InstanceGroupDescription {
+    pub mesh: MeshSource,
+    #[serde(default)]
+    pub format: InstanceFormat,
+    #[serde(default)]
+    pub material: MaterialDescription,
+    #[serde(default)]
+    pub instances: Vec<InstanceDescription>,
+}
+
+/// `Material` with its textures stored by path. Textures that weren't loaded from a
+/// file are left out.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialDescription {
+    pub base_color: Vec4,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: Vec3,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub alpha_mode: AlphaMode,
+    pub base_color_texture: Option<PathBuf>,
+    pub metallic_roughness_texture: Option<PathBuf>,
+    pub normal_texture: Option<PathBuf>,
+    pub occlusion_texture: Option<PathBuf>,
+    pub emissive_texture: Option<PathBuf>,
+}
+
+impl Default for MaterialDescription {
+    fn default() -> Self {
+        Self::from(&Material::default())
+    }
+}
+
+impl From<&Material> for MaterialDescription {
+    fn from(material: &Material) -> Self {
+        let path = |texture: &Option<Handle<Texture>>| texture.as_ref().and_then(Handle::path).map(Path::to_path_buf);
+        Self {
+            base_color: material.base_color,
+            metallic: material.metallic,
+            roughness: material.roughness,
+            emissive: material.emissive,
+            normal_scale: material.normal_scale,
+            occlusion_strength: material.occlusion_strength,
+            alpha_mode: material.alpha_mode,
+            base_color_texture: path(&material.base_color_texture),
+            metallic_roughness_texture: path(&material.metallic_roughness_texture),
+            normal_texture: path(&material.normal_texture),
+            occlusion_texture: path(&material.occlusion_texture),
+            emissive_texture: path(&material.emissive_texture),
+        }
+    }
+}
+
+impl MaterialDescription {
+    /// Loads the textures through `assets`, the color ones as sRGB.
+    pub fn to_material(&self, assets: &mut Assets) -> Material {
+        let mut load = |path: &Option<PathBuf>, srgb: bool| path.as_ref().map(|path| {
+            if srgb { assets.load_texture(path) } else { assets.load_linear_texture(path) }
+        });
+        Material {
+            base_color: self.base_color,
+            metallic: self.metallic,
+            roughness: self.roughness,
+            emissive: self.emissive,
+            normal_scale: self.normal_scale,
+            occlusion_strength: self.occlusion_strength,
+            alpha_mode: self.alpha_mode,
+            base_color_texture: load(&self.base_color_texture, true),
+            metallic_roughness_texture: load(&self.metallic_roughness_texture, false),
+            normal_texture: load(&self.normal_texture, false),
+            occlusion_texture: load(&self.occlusion_texture, false),
+            emissive_texture: load(&self.emissive_texture, true),
+        }
+    }
+}
+
+/// `Background` with skyboxes stored by the path of their panorama.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum BackgroundDescription {
+    #[default]
+    ClearColor,
+    Skybox { path: PathBuf, intensity: f32 },
+    Sky(ProceduralSky),
+}
+
+impl From<&Background> for BackgroundDescription {
+    fn from(background: &Background) -> Self {
+        match background {
+            Background::ClearColor => Self::ClearColor,
+            Background::Skybox { skybox, intensity } => Self::Skybox { path: skybox.path().to_path_buf(), intensity: *intensity },
+            Background::Sky(sky) => Self::Sky(*sky),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InstanceDescription {
+    #[serde(default)]
+    pub transform: Transform,
+    pub color: Vec3,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+}
+
+/// A scene node. `parent` indexes into `SceneFile::nodes` and must precede the node.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodeDescription {
+    pub name: String,
+    #[serde(default)]
+    pub parent: Option<usize>,
+    #[serde(default)]
+    pub transform: Transform,
+    #[serde(default)]
+    pub instance: Option<InstanceBinding>,
+}
+
+fn default_clear_color() -> [f32; 4] {
+    [0.0, 0.0, 0.08, 1.0]
+}
+
+fn default_shadows() -> Option<Shadows> {
+    Some(Shadows::default())
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+impl SceneFile {
+    pub fn capture(app_data: &AppData) -> Self {
+        let instance_groups = app_data.instance_groups.iter().map(|group| {
+            InstanceGroupDescription {
+                mesh: match (group.mesh.path(), group.mesh.get()) {
+                    (Some(path), _) => MeshSource::File(path.to_path_buf()),
+                    (None, Some(mesh)) => mesh.to_source(),
+                    (None, None) => MeshSource::Cube,
+                },
+                format: group.format(),
+                material: MaterialDescription::from(&group.material),
+                instances: group.instances().iter().enumerate().map(|(i, data)| InstanceDescription {
+                    transform: Transform::from_matrix(data.model),
+                    color: data.color,
+                    visible: group.is_visible(i),
+                }).collect(),
+            }
+        }).collect();
+
+        // Depth-first from the roots so parents always come before their children.
+        let mut nodes = Vec::new();
+        let mut indices = HashMap::new();
+        let mut stack = app_data.scene.roots().iter().rev().copied().collect::<Vec<_>>();
+        while let Some(id) = stack.pop() {
+            let node = app_data.scene.node(id);
+            indices.insert(id, nodes.len());
+            nodes.push(NodeDescription {
+                name: node.name.clone(),
+                parent: node.parent().map(|p| indices[&p]),
+                transform: *node.transform(),
+                instance: node.binding(),
+            });
+            stack.extend(node.children().iter().rev());
+        }
+
+        Self {
+            version: SCENE_FORMAT_VERSION,
+            clear_color: app_data.renderer.clear_color,
+            background: BackgroundDescription::from(&app_data.renderer.background),
+            camera: CameraDescription::from(&app_data.camera),
+            light: app_data.renderer.light,
+            shadows: app_data.renderer.shadows,
+            lights: app_data.renderer.lights.clone(),
+            instance_groups,
+            nodes,
+        }
+    }
+
+    /// Replaces the instance groups and their materials, scene graph, camera pose,
+    /// background and lights of `app_data`.
+    pub fn apply(&self, app_data: &mut AppData) -> AnyResult<()> {
+        self.validate()?;
+        let context = &app_data.renderer.context;
+        let assets = &mut app_data.assets;
+
+        let instance_groups = self.instance_groups.iter().map(|description| {
+            let mesh = match &description.mesh {
+                MeshSource::File(path) => assets.load_mesh(path),
+                source => Handle::ready(context.create_mesh_from_source(source)?),
+            };
+            let mut group = InstanceGroup::from(mesh)
+                .with_format(description.format)
+                .with_material(description.material.to_material(assets));
+            group.create_instance_buffer(context)?;
+            for instance in description.instances.iter() {
+                group.add_instance(tvk::InstanceData {
+                    model: instance.transform.to_matrix(),
+                    color: instance.color,
+                }, instance.visible);
+            }
+            Ok(group)
+        }).collect::<AnyResult<Vec<_>>>()?;
+
+        let mut scene = Scene::new();
+        let mut ids = Vec::with_capacity(self.nodes.len());
+        for node in self.nodes.iter() {
+            let id = scene.add_node(&node.name, node.parent.map(|p| ids[p]), node.transform);
+            if let Some(binding) = node.instance {
+                scene.bind_instance(id, binding);
+            }
+            ids.push(id);
+        }
+
+        let background = match &self.background {
+            BackgroundDescription::ClearColor => Background::ClearColor,
+            BackgroundDescription::Skybox { path, intensity } => {
+                // Reloading the same scene keeps its skybox rather than baking it again.
+                let skybox = match &app_data.renderer.background {
+                    Background::Skybox { skybox, .. } if skybox.path() == path => skybox.clone(),
+                    _ => app_data.renderer.load_skybox(path)?,
+                };
+                Background::Skybox { skybox, intensity: *intensity }
+            }
+            BackgroundDescription::Sky(sky) => Background::Sky(*sky),
+        };
+
+        // The old instance buffers may still be read by frames in flight.
+        context.logical_device.device_wait_idle()?;
+        app_data.instance_groups = instance_groups;
+        app_data.scene = scene;
+        app_data.renderer.clear_color = self.clear_color;
+        app_data.renderer.background = background;
+        app_data.renderer.light = self.light;
+        app_data.renderer.shadows = self.shadows;
+        app_data.renderer.lights = self.lights.clone();
+        app_data.camera.position = self.camera.position;
+        app_data.camera.yaw = self.camera.yaw;
+        app_data.camera.pitch = self.camera.pitch;
+        app_data.camera.projection = self.camera.projection;
+        if let (Some(legacy_fov), Projection::Perspective { fov, .. }) = (self.camera.fov, &mut app_data.camera.projection) {
+            *fov = legacy_fov;
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> AnyResult<()> {
+        if self.version == 0 || self.version > SCENE_FORMAT_VERSION {
+            return Err(format!("unsupported scene format version {} (expected at most {})", self.version, SCENE_FORMAT_VERSION).into());
+        }
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            if node.parent.is_some_and(|p| p >= i) {
+                return Err(format!("node '{}' references a parent that is not declared before it", node.name).into());
+            }
+            if let Some(binding) = node.instance {
+                let valid = self.instance_groups.get(binding.group).is_some_and(|g| binding.instance < g.instances.len());
+                if !valid {
+                    return Err(format!("node '{}' is bound to a missing instance {:?}", node.name, binding).into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn from_ron(source: &str) -> AnyResult<Self> {
+        let file: SceneFile = ron::from_str(source)?;
+        file.validate()?;
+        Ok(file)
+    }
+
+    pub fn to_ron(&self) -> AnyResult<String> {
+        Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+    }
+
+    pub fn load(path: &Path) -> AnyResult<Self> {
+        Self::from_ron(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: &Path) -> AnyResult<()> {
+        std::fs::write(path, self.to_ron()?)?;
+        Ok(())
+    }
+}
+
+impl AppData {
+    pub fn save_scene(&self, path: &Path) -> AnyResult<()> {
+        SceneFile::capture(self).save(path)
+    }
+
+    /// Loads a scene file and remembers it, so it is reloaded when it changes on disk
+    /// while asset hot reloading is enabled.
+    pub fn load_scene(&mut self, path: &Path) -> AnyResult<()> {
+        SceneFile::load(path)?.apply(self)?;
+        self.scene_path = Some(path.to_path_buf());
+        self.assets.watch(path);
+        Ok(())
+    }
+
+    /// Reloads the current scene file if it is among the changed files reported by
+    /// `Assets`, keeping the camera where the user moved it.
+    pub fn reload_changed_scene(&mut self) -> AnyResult<()> {
+        let changed = self.assets.take_changed_files();
+        let Some(scene_path) = &self.scene_path else {
+            return Ok(());
+        };
+        let scene_path = std::fs::canonicalize(scene_path).unwrap_or_else(|_| scene_path.clone());
+        if !changed.contains(&scene_path) {
+            return Ok(());
+        }
+
+        log::info!("reloading scene {}", scene_path.display());
+        let mut file = SceneFile::load(&scene_path)?;
+        file.camera = CameraDescription::from(&self.camera);
+        file.apply(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lights_survive_a_round_trip() {
+        let mut spot = SpotLight::new(Vec3::new(1.0, 4.0, -2.0), Vec3::NEG_Y, Vec3::new(1.0, 0.8, 0.5), 12.0);
+        spot.inner_angle = 15.0;
+        spot.outer_angle = 35.0;
+        let file = SceneFile {
+            version: SCENE_FORMAT_VERSION,
+            clear_color: default_clear_color(),
+            background: BackgroundDescription::default(),
+            camera: CameraDescription::default(),
+            light: DirectionalLight { intensity: 0.1, ..Default::default() },
+            shadows: None,
+            lights: vec![PointLight::new(Vec3::ONE, Vec3::X, 6.5).into(), spot.into()],
+            instance_groups: Vec::new(),
+            nodes: Vec::new(),
+        };
+
+        let loaded = SceneFile::from_ron(&file.to_ron().unwrap()).unwrap();
+        assert_eq!(loaded.light, file.light);
+        assert_eq!(loaded.shadows, None);
+        assert_eq!(loaded.lights, file.lights);
+    }
+
+    #[test]
+    fn older_files_get_the_default_lights() {
+        let loaded = SceneFile::from_ron("(version: 1)").unwrap();
+        assert_eq!(loaded.light, DirectionalLight::default());
+        assert_eq!(loaded.shadows, Some(Shadows::default()));
+        assert!(loaded.lights.is_empty());
+    }
+
+    #[test]
+    fn materials_and_background_survive_a_round_trip() {
+        let mut material = Material::default()
+            .with_base_color(Vec4::new(0.8, 0.2, 0.1, 0.5))
+            .with_metallic_roughness(1.0, 0.3)
+            .with_alpha_mode(AlphaMode::Blend);
+        material.normal_texture = Some(Handle::loading("textures/bricks_normal.png".into(), None));
+        let group = InstanceGroupDescription {
+            mesh: MeshSource::Cube,
+            format: InstanceFormat::Full,
+            material: MaterialDescription::from(&material),
+            instances: Vec::new(),
+        };
+        let sky = ProceduralSky { intensity: 5.0, ..Default::default() };
+        let file = SceneFile {
+            background: BackgroundDescription::Sky(sky),
+            instance_groups: vec![group],
+            ..SceneFile::from_ron(&format!("(version: {})", SCENE_FORMAT_VERSION)).unwrap()
+        };
+
+        let loaded = SceneFile::from_ron(&file.to_ron().unwrap()).unwrap();
+        assert_eq!(loaded.background, BackgroundDescription::Sky(sky));
+        let loaded_material = &loaded.instance_groups[0].material;
+        assert_eq!(loaded_material, &file.instance_groups[0].material);
+        assert_eq!(loaded_material.normal_texture.as_deref(), Some(Path::new("textures/bricks_normal.png")));
+        assert_eq!(loaded_material.base_color_texture, None);
+    }
+
+    #[test]
+    fn older_files_get_default_materials() {
+        let loaded = SceneFile::from_ron("(version: 3, instance_groups: [(mesh: Cube)])").unwrap();
+        assert_eq!(loaded.background, BackgroundDescription::ClearColor);
+        assert_eq!(loaded.instance_groups[0].material, MaterialDescription::default());
+    }
+}
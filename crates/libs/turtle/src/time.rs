@@ -0,0 +1,240 @@
+use std::time::{Duration, Instant};
+
+/// Longest frame the clock will report. Anything longer (a breakpoint, dragging the
+/// window) is clamped so simulations don't take one huge step afterwards.
+const MAX_DELTA: Duration = Duration::from_millis(250);
+
+/// Upper bound on fixed steps per frame, so a slow frame can't snowball into ever
+/// more steps. Time left over after this many steps is dropped.
+const MAX_FIXED_STEPS: u32 = 8;
+
+/// Frame clock shared with the update callbacks through `AppData::time`.
+pub struct Time {
+    start: Instant,
+    last_tick: Instant,
+    unscaled_delta: Duration,
+    delta: Duration,
+    elapsed: Duration,
+    frame_count: u64,
+    time_scale: f32,
+    paused: bool,
+    fixed_delta: Duration,
+    accumulator: Duration,
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        let now = Instant::now();
+        Self {
+            start: now,
+            last_tick: now,
+            unscaled_delta: Duration::ZERO,
+            delta: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            frame_count: 0,
+            time_scale: 1.0,
+            paused: false,
+            fixed_delta: Duration::from_secs_f64(1.0 / 60.0),
+            accumulator: Duration::ZERO,
+        }
+    }
+}
+
+impl Time {
+    /// Scaled time since the previous frame. Zero while paused.
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// Wall-clock time since the previous frame, ignoring scale and pause.
+    pub fn unscaled_delta(&self) -> Duration {
+        self.unscaled_delta
+    }
+
+    /// Scaled time accumulated over all frames so far.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+
+    /// Wall-clock time since the app started.
+    pub fn since_startup(&self) -> Duration {
+        self.last_tick - self.start
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Length of one fixed step.
+    pub fn fixed_delta(&self) -> Duration {
+        self.fixed_delta
+    }
+
+    pub fn fixed_delta_seconds(&self) -> f32 {
+        self.fixed_delta.as_secs_f32()
+    }
+
+    pub fn set_fixed_delta(&mut self, fixed_delta: Duration) {
+        assert!(!fixed_delta.is_zero(), "fixed timestep must be greater than zero");
+        self.fixed_delta = fixed_delta;
+    }
+
+    /// How far the current frame is between the last fixed step and the next one, in
+    /// `0.0..1.0`. Use it to interpolate state written by the fixed update.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator.as_secs_f64() / self.fixed_delta.as_secs_f64()) as f32
+    }
+
+    /// Advances the clock to now. Called once per frame before the update callbacks.
+    pub(crate) fn tick(&mut self) {
+        let now = Instant::now();
+        let unscaled_delta = now - self.last_tick;
+        self.last_tick = now;
+        self.advance(unscaled_delta);
+    }
+
+    /// Starts a frame `unscaled_delta` of wall-clock time after the previous one.
+    fn advance(&mut self, unscaled_delta: Duration) {
+        self.unscaled_delta = unscaled_delta;
+        self.frame_count += 1;
+
+        self.delta = if self.paused {
+            Duration::ZERO
+        } else {
+            self.unscaled_delta.min(MAX_DELTA).mul_f32(self.time_scale)
+        };
+        self.elapsed += self.delta;
+        self.accumulator += self.delta;
+    }
+
+    /// Consumes the accumulated time in whole fixed steps and returns how many to run.
+    pub(crate) fn take_fixed_steps(&mut self) -> u32 {
+        let mut steps = 0;
+        while self.accumulator >= self.fixed_delta {
+            self.accumulator -= self.fixed_delta;
+            steps += 1;
+        }
+        if steps > MAX_FIXED_STEPS {
+            log::warn!("dropping {} fixed steps to catch up", steps - MAX_FIXED_STEPS);
+            steps = MAX_FIXED_STEPS;
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time_with_fixed_delta(millis: u64) -> Time {
+        let mut time = Time::default();
+        time.set_fixed_delta(Duration::from_millis(millis));
+        time
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-5, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn accumulates_fixed_steps_across_frames() {
+        let mut time = time_with_fixed_delta(10);
+        time.advance(Duration::from_millis(25));
+        assert_eq!(time.take_fixed_steps(), 2);
+        assert_near(time.alpha() as f64, 0.5);
+
+        // The leftover 5 ms and this frame's 5 ms make up one more step.
+        time.advance(Duration::from_millis(5));
+        assert_eq!(time.take_fixed_steps(), 1);
+        assert_near(time.alpha() as f64, 0.0);
+
+        time.advance(Duration::from_millis(4));
+        assert_eq!(time.take_fixed_steps(), 0);
+        assert_near(time.alpha() as f64, 0.4);
+        assert_eq!(time.frame_count(), 3);
+        assert_near(time.elapsed().as_secs_f64(), 0.034);
+    }
+
+    #[test]
+    fn clamps_fixed_steps_per_frame() {
+        let mut time = time_with_fixed_delta(10);
+        time.advance(Duration::from_millis(200));
+        assert_eq!(time.take_fixed_steps(), MAX_FIXED_STEPS);
+        // The steps over the limit are dropped rather than run on the next frame.
+        assert!(time.alpha() < 1.0);
+        time.advance(Duration::ZERO);
+        assert_eq!(time.take_fixed_steps(), 0);
+    }
+
+    #[test]
+    fn caps_long_frames() {
+        let mut time = time_with_fixed_delta(10);
+        time.advance(Duration::from_secs(3));
+        assert_eq!(time.unscaled_delta(), Duration::from_secs(3));
+        assert_near(time.delta().as_secs_f64(), MAX_DELTA.as_secs_f64());
+        assert_near(time.elapsed().as_secs_f64(), MAX_DELTA.as_secs_f64());
+    }
+
+    #[test]
+    fn pause_stops_scaled_time() {
+        let mut time = time_with_fixed_delta(10);
+        time.pause();
+        time.advance(Duration::from_millis(100));
+        assert_eq!(time.delta(), Duration::ZERO);
+        assert_eq!(time.elapsed(), Duration::ZERO);
+        assert_eq!(time.unscaled_delta(), Duration::from_millis(100));
+        assert_eq!(time.take_fixed_steps(), 0);
+        assert_eq!(time.frame_count(), 1);
+
+        time.resume();
+        time.advance(Duration::from_millis(20));
+        assert_eq!(time.take_fixed_steps(), 2);
+    }
+
+    #[test]
+    fn time_scale_applies_to_delta_and_fixed_steps() {
+        let mut time = time_with_fixed_delta(10);
+        time.set_time_scale(0.5);
+        time.advance(Duration::from_millis(50));
+        assert_near(time.delta_seconds() as f64, 0.025);
+        assert_eq!(time.take_fixed_steps(), 2);
+        assert_near(time.alpha() as f64, 0.5);
+
+        time.set_time_scale(-1.0);
+        assert_eq!(time.time_scale(), 0.0);
+        time.advance(Duration::from_millis(50));
+        assert_eq!(time.delta(), Duration::ZERO);
+    }
+}
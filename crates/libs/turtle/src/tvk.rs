@@ -68,4 +68,7 @@ pub mod depth_buffer;
 pub use depth_buffer::*;
 
 pub mod image;
-pub use image::*;
\ No newline at end of file
+pub use image::*;
+
+pub mod sampler;
+pub use sampler::*;
\ No newline at end of file
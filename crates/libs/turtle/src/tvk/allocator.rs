@@ -1,4 +1,7 @@
-use gpu_allocator::vulkan as gvk;
+use std::sync::{Arc, Mutex};
+
+use ash::vk as avk;
+use gpu_allocator::{vulkan as gvk, MemoryLocation};
 use crate::{tvk, AnyResult};
 
 #[derive(Debug)]
@@ -28,3 +31,44 @@ impl Allocator {
         })
     }
 }
+
+/// Device memory not tied to a single resource, e.g. shared by images that alias
+/// each other. Freed once the last holder drops it.
+pub struct Memory {
+    allocation: Option<gvk::Allocation>,
+    allocator: Arc<Mutex<Allocator>>,
+}
+
+impl Memory {
+    pub fn new(allocator: Arc<Mutex<Allocator>>, name: &str, requirements: avk::MemoryRequirements) -> AnyResult<Self> {
+        let desc = gvk::AllocationCreateDesc {
+            name,
+            requirements,
+            location: MemoryLocation::GpuOnly,
+            linear: false,
+            allocation_scheme: gvk::AllocationScheme::GpuAllocatorManaged,
+        };
+        let allocation = allocator.lock().unwrap().inner.allocate(&desc)?;
+
+        Ok(Self {
+            allocation: Some(allocation),
+            allocator,
+        })
+    }
+
+    pub(crate) fn allocation(&self) -> &gvk::Allocation {
+        self.allocation.as_ref().unwrap()
+    }
+}
+
+impl tvk::Context {
+    pub fn allocate_memory(&self, name: &str, requirements: avk::MemoryRequirements) -> AnyResult<Memory> {
+        Memory::new(self.allocator.clone(), name, requirements)
+    }
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        self.allocator.lock().unwrap().inner.free(self.allocation.take().unwrap()).unwrap();
+    }
+}
@@ -8,6 +8,9 @@ pub struct Buffer {
     allocation: Option<mvk::Allocation>,
     allocator: Arc<Mutex<tvk::Allocator>>,
     logical_device: Arc<tvk::LogicalDevice>,
+    usage: avk::BufferUsageFlags,
+    location: MemoryLocation,
+    queue_family_indices: Vec<u32>,
     pub size: avk::DeviceSize
 }
 
@@ -19,10 +22,29 @@ impl Buffer {
         usage: avk::BufferUsageFlags,
         location: MemoryLocation,
     ) -> AnyResult<Self> {
+        Self::create_shared(allocator, logical_device, size, usage, location, &[])
+    }
+
+    /// Creates a buffer that can be used from several queue families without ownership
+    /// transfers. An empty `queue_family_indices` gives an exclusive buffer.
+    pub fn create_shared(
+        allocator: Arc<Mutex<tvk::Allocator>>,
+        logical_device: Arc<tvk::LogicalDevice>,
+        size: u64,
+        usage: avk::BufferUsageFlags,
+        location: MemoryLocation,
+        queue_family_indices: &[u32],
+    ) -> AnyResult<Self> {
+        let sharing_mode = if queue_family_indices.len() > 1 {
+            avk::SharingMode::CONCURRENT
+        } else {
+            avk::SharingMode::EXCLUSIVE
+        };
         let buffer_info = avk::BufferCreateInfo::default()
             .size(size)
             .usage(usage)
-            .sharing_mode(avk::SharingMode::EXCLUSIVE);    
+            .sharing_mode(sharing_mode)
+            .queue_family_indices(queue_family_indices);
         let inner = unsafe { logical_device.inner.create_buffer(&buffer_info, None)? };
         let requirements = unsafe { logical_device.inner.get_buffer_memory_requirements(inner)};
         let alloc_desc = mvk::AllocationCreateDesc {
@@ -40,31 +62,70 @@ impl Buffer {
             allocation: Some(allocation),
             allocator,
             logical_device,
+            usage,
+            location,
+            queue_family_indices: queue_family_indices.to_vec(),
             size
         })
     }
-    pub fn copy_memory<T: Copy>(&mut self, data: &[T]) -> AnyResult<()> {
-        let required_size = size_of_val(data) as u64;
 
-        if required_size > self.size {
-            let new_size = ((required_size + 255) / 256) * 256;
-            let mut new_buffer = Buffer::create(self.allocator.clone(), self.logical_device.clone(),
-                new_size,
-                avk::BufferUsageFlags::VERTEX_BUFFER,
-                MemoryLocation::CpuToGpu
-            )?;
+    /// Grows the buffer so it can hold at least `size` bytes. The old contents are
+    /// discarded, so callers must re-upload everything when this returns `true`. The old
+    /// buffer is destroyed right away and must not be in use by the GPU.
+    pub fn reserve(&mut self, size: avk::DeviceSize) -> AnyResult<bool> {
+        if size <= self.size {
+            return Ok(false);
+        }
+
+        let new_size = size.div_ceil(256) * 256;
+        let mut new_buffer = Buffer::create_shared(self.allocator.clone(), self.logical_device.clone(),
+            new_size,
+            self.usage,
+            self.location,
+            &self.queue_family_indices
+        )?;
+
+        std::mem::swap(self, &mut new_buffer);
+        Ok(true)
+    }
 
-            std::mem::swap(self, &mut new_buffer);
+    pub fn copy_memory<T: Copy>(&mut self, data: &[T]) -> AnyResult<()> {
+        self.reserve(size_of_val(data) as u64)?;
+        self.copy_memory_at(0, data)
+    }
+
+    /// Writes `data` into the mapped memory starting at byte `offset` without touching
+    /// the rest of the buffer.
+    pub fn copy_memory_at<T: Copy>(&mut self, offset: avk::DeviceSize, data: &[T]) -> AnyResult<()> {
+        let size = size_of_val(data) as u64;
+        if offset + size > self.size {
+            return Err(format!("write of {} bytes at offset {} exceeds buffer size {}", size, offset, self.size).into());
         }
 
         unsafe {
-            let data_ptr = self.allocation.as_ref().unwrap().mapped_ptr().unwrap().as_ptr();
-            let mut align = ash::util::Align::new(data_ptr, align_of::<T>() as _, size_of_val(data) as _);
+            let data_ptr = self.allocation.as_ref().unwrap().mapped_ptr()
+                .ok_or("buffer memory is not host visible")?
+                .as_ptr()
+                .add(offset as usize);
+            let mut align = ash::util::Align::new(data_ptr, align_of::<T>() as _, size);
             align.copy_from_slice(data);
         };
         Ok(())
     }
 
+    /// Reads `count` values of `T` from the start of the mapped memory.
+    pub fn read_memory<T: Copy>(&self, count: usize) -> AnyResult<Vec<T>> {
+        let size = (count * size_of::<T>()) as u64;
+        if size > self.size {
+            return Err(format!("read of {} bytes exceeds buffer size {}", size, self.size).into());
+        }
+
+        let data_ptr = self.allocation.as_ref().unwrap().mapped_ptr()
+            .ok_or("buffer memory is not host visible")?
+            .as_ptr() as *const T;
+        Ok((0..count).map(|i| unsafe { data_ptr.add(i).read_unaligned() }).collect())
+    }
+
     pub fn copy_buffer(&self, context: &tvk::Context, dst_buffer: &tvk::Buffer) -> AnyResult<()> {
         let command_buffers = context.allocate_command_buffers(avk::CommandBufferLevel::PRIMARY, tvk::QueueType::Graphics, 1)?;
         let command_buffer = &command_buffers[0];
@@ -96,6 +157,17 @@ impl tvk::Context {
         Buffer::create(self.allocator.clone(), self.logical_device.clone(), size, usage, memory_location)
     }
     
+    /// Buffer shared between the graphics and transfer queue families, for resources
+    /// uploaded on the transfer queue and read by the graphics queue.
+    pub fn create_transfer_buffer(
+        &self,
+        usage: avk::BufferUsageFlags,
+        memory_location: MemoryLocation,
+        size: avk::DeviceSize
+    ) -> AnyResult<Buffer> {
+        Buffer::create_shared(self.allocator.clone(), self.logical_device.clone(), size, usage, memory_location, &self.transfer_queue_family_indices())
+    }
+
     pub fn copy_buffer(&self, src_buffer: &tvk::Buffer, dst_buffer: &tvk::Buffer) -> AnyResult<()> {
         src_buffer.copy_buffer(self, dst_buffer)
     }
@@ -40,7 +40,7 @@ impl CommandBuffer {
 
      pub fn begin_render_pass(
         &self,
-        swapchain: &tvk::Swapchain,
+        extent: avk::Extent2D,
         render_pass: &tvk::RenderPass,
         frame_buffer: &tvk::FrameBuffer,
         subpass_contents: avk::SubpassContents,
@@ -51,28 +51,74 @@ impl CommandBuffer {
             .framebuffer(frame_buffer.inner)
             .render_area(avk::Rect2D {
                 offset: avk::Offset2D { x: 0, y: 0 },
-                extent: swapchain.extent,
+                extent,
             })
-            .clear_values(&clear_values);
+            .clear_values(clear_values);
         unsafe {
             self.logical_device.inner.cmd_begin_render_pass(self.inner, begin_info, subpass_contents);
         }
     }
 
-    pub fn bind_descriptor_sets(&self, layout: avk::PipelineLayout, set: avk::DescriptorSet) {
+    pub fn bind_descriptor_sets(&self, layout: avk::PipelineLayout, set: avk::DescriptorSet, dynamic_offsets: &[u32]) {
+        self.bind_descriptor_set_at(layout, 0, set, dynamic_offsets);
+    }
+
+    /// Binds `set` as set number `index` of the pipeline layout.
+    pub fn bind_descriptor_set_at(&self, layout: avk::PipelineLayout, index: u32, set: avk::DescriptorSet, dynamic_offsets: &[u32]) {
         unsafe {
             let sets = [set];
             self.logical_device.inner.cmd_bind_descriptor_sets(
                 self.inner,
                 avk::PipelineBindPoint::GRAPHICS,
                 layout,
-                0,
+                index,
                 &sets,
-                &[]
+                dynamic_offsets
             );
         }
     }
 
+    /// Clears part of the current subpass' attachments: the color attachment if
+    /// `color` is set, and always the depth attachment.
+    pub fn clear_attachments(&self, rect: avk::Rect2D, color: Option<avk::ClearColorValue>, depth: f32) {
+        let mut attachments = vec![avk::ClearAttachment {
+            aspect_mask: avk::ImageAspectFlags::DEPTH,
+            color_attachment: 0,
+            clear_value: avk::ClearValue {
+                depth_stencil: avk::ClearDepthStencilValue { depth, stencil: 0 }
+            },
+        }];
+        if let Some(color) = color {
+            attachments.push(avk::ClearAttachment {
+                aspect_mask: avk::ImageAspectFlags::COLOR,
+                color_attachment: 0,
+                clear_value: avk::ClearValue { color },
+            });
+        }
+        let rects = [avk::ClearRect {
+            rect,
+            base_array_layer: 0,
+            layer_count: 1,
+        }];
+        unsafe {
+            self.logical_device.inner.cmd_clear_attachments(self.inner, &attachments, &rects);
+        }
+    }
+
+    /// Fills the whole color image, which must be in `TRANSFER_DST_OPTIMAL`.
+    pub fn clear_color_image(&self, image: &tvk::Image, color: avk::ClearColorValue) {
+        let range = avk::ImageSubresourceRange {
+            aspect_mask: avk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: avk::REMAINING_MIP_LEVELS,
+            base_array_layer: 0,
+            layer_count: avk::REMAINING_ARRAY_LAYERS,
+        };
+        unsafe {
+            self.logical_device.inner.cmd_clear_color_image(self.inner, image.inner, avk::ImageLayout::TRANSFER_DST_OPTIMAL, &color, &[range]);
+        }
+    }
+
     pub fn bind_index_buffer(&self, buffer: &tvk::Buffer) {
         unsafe {
             self.logical_device.inner.cmd_bind_index_buffer(
@@ -108,6 +154,13 @@ impl CommandBuffer {
         }
     }
 
+    /// Depth offset of pipelines created with `PipelineState::depth_bias`.
+    pub fn set_depth_bias(&self, constant_factor: f32, clamp: f32, slope_factor: f32) {
+        unsafe {
+            self.logical_device.inner.cmd_set_depth_bias(self.inner, constant_factor, clamp, slope_factor);
+        }
+    }
+
     pub fn bind_vertex_buffers(&self, buffers: &[avk::Buffer]) {
         let offsets = vec![0; buffers.len()];
         unsafe {
@@ -133,6 +186,190 @@ impl CommandBuffer {
         }
     }
 
+    pub fn copy_buffer_to_image(&self, src_buffer: &tvk::Buffer, dst_image: &tvk::Image, layout: avk::ImageLayout) {
+        let region = avk::BufferImageCopy::default()
+            .image_subresource(avk::ImageSubresourceLayers {
+                aspect_mask: avk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_extent(avk::Extent3D {
+                width: dst_image.extent.width,
+                height: dst_image.extent.height,
+                depth: 1
+            });
+        unsafe {
+            self.logical_device.inner.cmd_copy_buffer_to_image(
+                self.inner,
+                src_buffer.inner,
+                dst_image.inner,
+                layout,
+                &[region]
+            );
+        }
+    }
+
+    /// Copies a `extent` sized block of the color image starting at `offset` to the
+    /// start of `dst_buffer`.
+    pub fn copy_image_to_buffer(
+        &self,
+        src_image: avk::Image,
+        layout: avk::ImageLayout,
+        dst_buffer: &tvk::Buffer,
+        offset: avk::Offset2D,
+        extent: avk::Extent2D,
+    ) {
+        let region = avk::BufferImageCopy::default()
+            .image_subresource(avk::ImageSubresourceLayers {
+                aspect_mask: avk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_offset(avk::Offset3D { x: offset.x, y: offset.y, z: 0 })
+            .image_extent(avk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1
+            });
+        unsafe {
+            self.logical_device.inner.cmd_copy_image_to_buffer(
+                self.inner,
+                src_image,
+                layout,
+                dst_buffer.inner,
+                &[region]
+            );
+        }
+    }
+
+    pub fn push_constants<T: Copy>(&self, layout: avk::PipelineLayout, data: &T) {
+        let bytes = unsafe { std::slice::from_raw_parts(data as *const T as *const u8, size_of::<T>()) };
+        unsafe {
+            self.logical_device.inner.cmd_push_constants(self.inner, layout, tvk::PUSH_CONSTANT_STAGES, 0, bytes);
+        }
+    }
+
+    pub fn pipeline_barrier(
+        &self,
+        src_stage: avk::PipelineStageFlags,
+        dst_stage: avk::PipelineStageFlags,
+        memory_barriers: &[avk::MemoryBarrier],
+        image_barriers: &[avk::ImageMemoryBarrier],
+    ) {
+        unsafe {
+            self.logical_device.inner.cmd_pipeline_barrier(
+                self.inner,
+                src_stage,
+                dst_stage,
+                avk::DependencyFlags::empty(),
+                memory_barriers,
+                &[],
+                image_barriers
+            );
+        }
+    }
+
+    /// Moves the whole color image between layouts with a single barrier.
+    pub fn transition_image_layout(
+        &self,
+        image: &tvk::Image,
+        old_layout: avk::ImageLayout,
+        new_layout: avk::ImageLayout,
+        src: (avk::PipelineStageFlags, avk::AccessFlags),
+        dst: (avk::PipelineStageFlags, avk::AccessFlags),
+    ) {
+        let barrier = avk::ImageMemoryBarrier::default()
+            .image(image.inner)
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_queue_family_index(avk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(avk::QUEUE_FAMILY_IGNORED)
+            .src_access_mask(src.1)
+            .dst_access_mask(dst.1)
+            .subresource_range(avk::ImageSubresourceRange {
+                aspect_mask: avk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: avk::REMAINING_MIP_LEVELS,
+                base_array_layer: 0,
+                layer_count: avk::REMAINING_ARRAY_LAYERS,
+            });
+        self.pipeline_barrier(src.0, dst.0, &[], &[barrier]);
+    }
+
+    /// Fills every mip level of the color image after the first by downsampling the
+    /// previous one. All levels must be in `TRANSFER_DST_OPTIMAL`; they end up in
+    /// `SHADER_READ_ONLY_OPTIMAL`, visible to fragment shaders.
+    pub fn generate_mipmaps(&self, image: &tvk::Image) {
+        let barrier = |level: u32, old_layout, new_layout, src_access, dst_access| avk::ImageMemoryBarrier::default()
+            .image(image.inner)
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_queue_family_index(avk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(avk::QUEUE_FAMILY_IGNORED)
+            .src_access_mask(src_access)
+            .dst_access_mask(dst_access)
+            .subresource_range(avk::ImageSubresourceRange {
+                aspect_mask: avk::ImageAspectFlags::COLOR,
+                base_mip_level: level,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: image.layers,
+            });
+        let level_size = |level: u32| avk::Offset3D {
+            x: (image.extent.width >> level).max(1) as i32,
+            y: (image.extent.height >> level).max(1) as i32,
+            z: 1,
+        };
+
+        for level in 1..image.mip_levels {
+            self.pipeline_barrier(avk::PipelineStageFlags::TRANSFER, avk::PipelineStageFlags::TRANSFER, &[], &[barrier(
+                level - 1,
+                avk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                avk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                avk::AccessFlags::TRANSFER_WRITE,
+                avk::AccessFlags::TRANSFER_READ,
+            )]);
+            let subresource = |mip_level| avk::ImageSubresourceLayers {
+                aspect_mask: avk::ImageAspectFlags::COLOR,
+                mip_level,
+                base_array_layer: 0,
+                layer_count: image.layers,
+            };
+            let blit = avk::ImageBlit::default()
+                .src_subresource(subresource(level - 1))
+                .src_offsets([avk::Offset3D::default(), level_size(level - 1)])
+                .dst_subresource(subresource(level))
+                .dst_offsets([avk::Offset3D::default(), level_size(level)]);
+            unsafe {
+                self.logical_device.inner.cmd_blit_image(
+                    self.inner,
+                    image.inner,
+                    avk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    image.inner,
+                    avk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[blit],
+                    avk::Filter::LINEAR
+                );
+            }
+            self.pipeline_barrier(avk::PipelineStageFlags::TRANSFER, avk::PipelineStageFlags::FRAGMENT_SHADER, &[], &[barrier(
+                level - 1,
+                avk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                avk::AccessFlags::TRANSFER_READ,
+                avk::AccessFlags::SHADER_READ,
+            )]);
+        }
+        self.pipeline_barrier(avk::PipelineStageFlags::TRANSFER, avk::PipelineStageFlags::FRAGMENT_SHADER, &[], &[barrier(
+            image.mip_levels - 1,
+            avk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            avk::AccessFlags::TRANSFER_WRITE,
+            avk::AccessFlags::SHADER_READ,
+        )]);
+    }
+
     pub fn draw(&self, vertex_count: u32, instance_count: u32, first_vertex: u32, first_instance: u32) {
         unsafe {
             self.logical_device.inner.cmd_draw(
@@ -187,4 +424,21 @@ impl Drop for CommandBuffer {
     fn drop(&mut self) {
         unsafe { self.logical_device.inner.free_command_buffers(self.command_pool.inner, &[self.inner]); }
     }
-}
\ No newline at end of file
+}
+
+impl tvk::Context {
+    /// Records commands with `record` into a one-time graphics command buffer, submits
+    /// it and waits for it to finish. For work done while loading.
+    pub fn submit_and_wait(&self, record: impl FnOnce(&tvk::CommandBuffer)) -> AnyResult<()> {
+        let command_buffer = self.allocate_command_buffers(avk::CommandBufferLevel::PRIMARY, tvk::QueueType::Graphics, 1)?.remove(0);
+        command_buffer.begin(avk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)?;
+        record(&command_buffer);
+        command_buffer.end()?;
+
+        let fence = self.create_fence(false)?;
+        let command_buffers = [command_buffer.inner];
+        let submits = [avk::SubmitInfo::default().command_buffers(&command_buffers)];
+        self.queues.get(&tvk::QueueType::Graphics).unwrap().submit(&submits, fence.inner)?;
+        fence.wait(u64::MAX)
+    }
+}
@@ -3,18 +3,18 @@ use std::{collections::HashMap, sync::{Arc, Mutex}};
 use ash::vk as avk;
 use winit::window::Window;
 
-const INSTANCE_EXTENSION_NAMES: [&'static std::ffi::CStr; 4] = [
+const INSTANCE_EXTENSION_NAMES: [&std::ffi::CStr; 4] = [
     avk::KHR_PORTABILITY_ENUMERATION_NAME,
     ash::ext::debug_utils::NAME,
     avk::KHR_GET_PHYSICAL_DEVICE_PROPERTIES2_NAME,
     avk::KHR_SURFACE_NAME,
 ];
 
-const INSTANCE_LAYER_NAMES: [&'static std::ffi::CStr; 1] = [
-    unsafe { std::ffi::CStr::from_bytes_with_nul_unchecked(b"VK_LAYER_KHRONOS_validation\0") }
+const INSTANCE_LAYER_NAMES: [&std::ffi::CStr; 1] = [
+    c"VK_LAYER_KHRONOS_validation"
 ];
 
-const DEVICE_EXTENSION_NAMES: [&'static std::ffi::CStr; 2] = [
+const DEVICE_EXTENSION_NAMES: [&std::ffi::CStr; 2] = [
     avk::KHR_PORTABILITY_SUBSET_NAME,
     avk::KHR_SWAPCHAIN_NAME,
 ];
@@ -56,6 +56,7 @@ impl Context {
         queues.insert(QueueType::Transfer, tvk::Queue::new(transfer.index, logical_device.clone()));
         queues.insert(QueueType::Present, tvk::Queue::new(present.index, logical_device.clone()));
         command_pools.insert(QueueType::Graphics, Arc::new(tvk::CommandPool::new(logical_device.clone(), graphics.index)?));
+        command_pools.insert(QueueType::Transfer, Arc::new(tvk::CommandPool::new(logical_device.clone(), transfer.index)?));
         let allocator = Arc::new(Mutex::new(tvk::Allocator::new(&instance, &logical_device, &physical_device)?));
         
         Ok(Self {
@@ -70,6 +71,18 @@ impl Context {
             allocator,
         })
     }
+
+    /// Queue families that share resources uploaded on the transfer queue. Empty when
+    /// graphics and transfer use the same family, so resources stay exclusive.
+    pub fn transfer_queue_family_indices(&self) -> Vec<u32> {
+        let graphics = self.queue_families.get(&QueueType::Graphics).unwrap().index;
+        let transfer = self.queue_families.get(&QueueType::Transfer).unwrap().index;
+        if graphics == transfer {
+            Vec::new()
+        } else {
+            vec![graphics, transfer]
+        }
+    }
 }
 
 pub fn select_physical_device(physical_devices: &[tvk::PhysicalDevice]) -> AnyResult<(tvk::PhysicalDevice, tvk::QueueFamily, tvk::QueueFamily, tvk::QueueFamily)> {
@@ -8,9 +8,9 @@ pub struct DepthBuffer {
 }
 
 impl DepthBuffer {
-    pub fn new(context: &tvk::Context, swapchain: &tvk::Swapchain) -> AnyResult<Self> {
+    pub fn new(context: &tvk::Context, extent: avk::Extent2D, samples: avk::SampleCountFlags) -> AnyResult<Self> {
         let format = context.physical_device.depth_format;
-        let image = context.create_image(swapchain.extent, format, avk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT)?;
+        let image = context.create_multisampled_image(extent, format, avk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT, samples)?;
         let image_view = context.create_image_view(&image, format, avk::ImageAspectFlags::DEPTH)?;
 
 
@@ -22,7 +22,7 @@ impl DepthBuffer {
 }
 
 impl tvk::Context {
-    pub fn create_depth_buffer(&self, swapchain: &tvk::Swapchain) -> AnyResult<DepthBuffer> {
-        DepthBuffer::new(self, swapchain)
+    pub fn create_depth_buffer(&self, extent: avk::Extent2D, samples: avk::SampleCountFlags) -> AnyResult<DepthBuffer> {
+        DepthBuffer::new(self, extent, samples)
     }
 }
\ No newline at end of file
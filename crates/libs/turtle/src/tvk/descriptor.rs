@@ -14,16 +14,16 @@ impl Descriptor {
     pub fn new(logical_device: Arc<tvk::LogicalDevice>, count: u32) -> AnyResult<Self> {
         let layout_bindings = [avk::DescriptorSetLayoutBinding::default()
             .binding(0)
-            .descriptor_type(avk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_type(avk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
             .descriptor_count(1)
-            .stage_flags(avk::ShaderStageFlags::VERTEX)];
+            .stage_flags(avk::ShaderStageFlags::VERTEX | avk::ShaderStageFlags::FRAGMENT)];
         let layout_create_info = avk::DescriptorSetLayoutCreateInfo::default()
             .bindings(&layout_bindings);
 
         let layout = unsafe { logical_device.inner.create_descriptor_set_layout(&layout_create_info, None)? };
 
         let pool_sizes = [avk::DescriptorPoolSize::default()
-            .ty(avk::DescriptorType::UNIFORM_BUFFER)
+            .ty(avk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
             .descriptor_count(count)];
 
         let pool_create_info = avk::DescriptorPoolCreateInfo::default()
@@ -53,25 +53,30 @@ impl Descriptor {
         Ok(())
     }
 
-    pub fn update(&self, buffers: &Vec<tvk::Buffer>) -> AnyResult<()> {
-        self.sets.iter().zip(buffers.iter()).for_each(|(&set, buffer)| {
-            let buffer_info = [avk::DescriptorBufferInfo::default()
-                .buffer(buffer.inner)
-                .offset(0)
-                .range(avk::WHOLE_SIZE)];
+    /// Points each set at the matching buffer. The binding is a dynamic uniform buffer,
+    /// so `range` is the size of one element and the offset is given when binding.
+    pub fn update(&self, buffers: &[tvk::Buffer], range: avk::DeviceSize) -> AnyResult<()> {
+        for (index, buffer) in buffers.iter().enumerate().take(self.sets.len()) {
+            self.update_set(index, buffer, range);
+        }
+        Ok(())
+    }
 
-            let writes = [avk::WriteDescriptorSet::default()
-                .dst_set(set)
-                .dst_binding(0)
-                .dst_array_element(0)
-                .descriptor_type(avk::DescriptorType::UNIFORM_BUFFER)
-                .descriptor_count(1)
-                .buffer_info(&buffer_info)];
+    pub fn update_set(&self, index: usize, buffer: &tvk::Buffer, range: avk::DeviceSize) {
+        let buffer_info = [avk::DescriptorBufferInfo::default()
+            .buffer(buffer.inner)
+            .offset(0)
+            .range(range)];
 
-            unsafe { self.logical_device.inner.update_descriptor_sets(&writes, &[]);}
-        });
+        let writes = [avk::WriteDescriptorSet::default()
+            .dst_set(self.sets[index])
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(avk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+            .descriptor_count(1)
+            .buffer_info(&buffer_info)];
 
-        Ok(())
+        unsafe { self.logical_device.inner.update_descriptor_sets(&writes, &[]);}
     }
 }
 
@@ -79,6 +84,198 @@ impl tvk::Context {
     pub fn create_descriptor_dependecies(&self, count: u32) -> AnyResult<Descriptor> {
         Descriptor::new(self.logical_device.clone(), count)
     }
+
+    pub fn create_texture_descriptor(&self, count: u32) -> AnyResult<TextureDescriptor> {
+        TextureDescriptor::new(self.logical_device.clone(), count, 1)
+    }
+
+    pub fn create_texture_descriptor_with_bindings(&self, count: u32, bindings: u32) -> AnyResult<TextureDescriptor> {
+        TextureDescriptor::new(self.logical_device.clone(), count, bindings)
+    }
+
+    pub fn create_resource_descriptor(&self, count: u32, bindings: &[(avk::DescriptorType, avk::ShaderStageFlags)]) -> AnyResult<ResourceDescriptor> {
+        ResourceDescriptor::new(self.logical_device.clone(), count, bindings)
+    }
+}
+
+/// Sets of combined image samplers read by the fragment stage, at bindings
+/// `0..bindings`.
+pub struct TextureDescriptor {
+    pub sets: Vec<avk::DescriptorSet>,
+    pub pool: avk::DescriptorPool,
+    pub layout: avk::DescriptorSetLayout,
+    logical_device: Arc<tvk::LogicalDevice>
+}
+
+impl TextureDescriptor {
+    pub fn new(logical_device: Arc<tvk::LogicalDevice>, count: u32, bindings: u32) -> AnyResult<Self> {
+        let layout_bindings = (0..bindings).map(|binding| avk::DescriptorSetLayoutBinding::default()
+            .binding(binding)
+            .descriptor_type(avk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(avk::ShaderStageFlags::FRAGMENT)
+        ).collect::<Vec<_>>();
+        let layout_create_info = avk::DescriptorSetLayoutCreateInfo::default()
+            .bindings(&layout_bindings);
+
+        let layout = unsafe { logical_device.inner.create_descriptor_set_layout(&layout_create_info, None)? };
+
+        let pool_sizes = [avk::DescriptorPoolSize::default()
+            .ty(avk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(count * bindings)];
+
+        let pool_create_info = avk::DescriptorPoolCreateInfo::default()
+            .pool_sizes(&pool_sizes)
+            .max_sets(count);
+
+        let pool = unsafe { logical_device.inner.create_descriptor_pool(&pool_create_info, None)? };
+
+        let layouts = vec![layout; count as usize];
+        let allocate_info = avk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(pool)
+            .set_layouts(&layouts);
+        let sets = unsafe { logical_device.inner.allocate_descriptor_sets(&allocate_info)? };
+
+        Ok(Self {
+            sets,
+            pool,
+            layout,
+            logical_device
+        })
+    }
+
+    /// Points set `index` at `image_view`, which must be in `SHADER_READ_ONLY_OPTIMAL`
+    /// when the set is used.
+    pub fn update_set(&self, index: usize, image_view: &tvk::ImageView, sampler: &tvk::Sampler) {
+        self.update_binding(index, 0, image_view.inner, sampler);
+    }
+
+    /// Like `update_set`, for any binding of the set.
+    pub fn update_binding(&self, index: usize, binding: u32, image_view: avk::ImageView, sampler: &tvk::Sampler) {
+        let image_info = [avk::DescriptorImageInfo::default()
+            .image_view(image_view)
+            .sampler(sampler.inner)
+            .image_layout(avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)];
+
+        let writes = [avk::WriteDescriptorSet::default()
+            .dst_set(self.sets[index])
+            .dst_binding(binding)
+            .dst_array_element(0)
+            .descriptor_type(avk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .image_info(&image_info)];
+
+        unsafe { self.logical_device.inner.update_descriptor_sets(&writes, &[]);}
+    }
+}
+
+/// Sets with arbitrary bindings, given as descriptor type and the stages reading it.
+/// Bindings are numbered in order from 0.
+pub struct ResourceDescriptor {
+    pub sets: Vec<avk::DescriptorSet>,
+    pub pool: avk::DescriptorPool,
+    pub layout: avk::DescriptorSetLayout,
+    types: Vec<avk::DescriptorType>,
+    logical_device: Arc<tvk::LogicalDevice>
+}
+
+impl ResourceDescriptor {
+    pub fn new(logical_device: Arc<tvk::LogicalDevice>, count: u32, bindings: &[(avk::DescriptorType, avk::ShaderStageFlags)]) -> AnyResult<Self> {
+        let layout_bindings = bindings.iter().enumerate().map(|(binding, &(ty, stages))| avk::DescriptorSetLayoutBinding::default()
+            .binding(binding as u32)
+            .descriptor_type(ty)
+            .descriptor_count(1)
+            .stage_flags(stages)
+        ).collect::<Vec<_>>();
+        let layout_create_info = avk::DescriptorSetLayoutCreateInfo::default()
+            .bindings(&layout_bindings);
+
+        let layout = unsafe { logical_device.inner.create_descriptor_set_layout(&layout_create_info, None)? };
+
+        let mut pool_sizes: Vec<avk::DescriptorPoolSize> = Vec::new();
+        for &(ty, _) in bindings.iter() {
+            match pool_sizes.iter_mut().find(|size| size.ty == ty) {
+                Some(size) => size.descriptor_count += count,
+                None => pool_sizes.push(avk::DescriptorPoolSize::default().ty(ty).descriptor_count(count)),
+            }
+        }
+
+        let pool_create_info = avk::DescriptorPoolCreateInfo::default()
+            .pool_sizes(&pool_sizes)
+            .max_sets(count);
+
+        let pool = unsafe { logical_device.inner.create_descriptor_pool(&pool_create_info, None)? };
+
+        let layouts = vec![layout; count as usize];
+        let allocate_info = avk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(pool)
+            .set_layouts(&layouts);
+        let sets = unsafe { logical_device.inner.allocate_descriptor_sets(&allocate_info)? };
+
+        Ok(Self {
+            sets,
+            pool,
+            layout,
+            types: bindings.iter().map(|&(ty, _)| ty).collect(),
+            logical_device
+        })
+    }
+
+    /// Points a uniform or storage buffer binding of set `index` at the first `range`
+    /// bytes of `buffer`.
+    pub fn write_buffer(&self, index: usize, binding: u32, buffer: &tvk::Buffer, range: avk::DeviceSize) {
+        let buffer_info = [avk::DescriptorBufferInfo::default()
+            .buffer(buffer.inner)
+            .offset(0)
+            .range(range)];
+
+        let writes = [avk::WriteDescriptorSet::default()
+            .dst_set(self.sets[index])
+            .dst_binding(binding)
+            .dst_array_element(0)
+            .descriptor_type(self.types[binding as usize])
+            .descriptor_count(1)
+            .buffer_info(&buffer_info)];
+
+        unsafe { self.logical_device.inner.update_descriptor_sets(&writes, &[]);}
+    }
+
+    /// Points a combined image sampler binding of set `index` at `image_view`, which
+    /// must be in `SHADER_READ_ONLY_OPTIMAL` when the set is used.
+    pub fn write_image(&self, index: usize, binding: u32, image_view: avk::ImageView, sampler: &tvk::Sampler) {
+        let image_info = [avk::DescriptorImageInfo::default()
+            .image_view(image_view)
+            .sampler(sampler.inner)
+            .image_layout(avk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)];
+
+        let writes = [avk::WriteDescriptorSet::default()
+            .dst_set(self.sets[index])
+            .dst_binding(binding)
+            .dst_array_element(0)
+            .descriptor_type(avk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .image_info(&image_info)];
+
+        unsafe { self.logical_device.inner.update_descriptor_sets(&writes, &[]);}
+    }
+}
+
+impl Drop for ResourceDescriptor {
+    fn drop(&mut self) {
+        unsafe {
+            self.logical_device.inner.destroy_descriptor_pool(self.pool, None);
+            self.logical_device.inner.destroy_descriptor_set_layout(self.layout, None);
+        }
+    }
+}
+
+impl Drop for TextureDescriptor {
+    fn drop(&mut self) {
+        unsafe {
+            self.logical_device.inner.destroy_descriptor_pool(self.pool, None);
+            self.logical_device.inner.destroy_descriptor_set_layout(self.layout, None);
+        }
+    }
 }
 
 impl Drop for Descriptor {
@@ -38,6 +38,10 @@ impl Fence {
         Ok(())
     }
 
+    pub fn is_signaled(&self) -> AnyResult<bool> {
+        Ok(unsafe { self.logical_device.inner.get_fence_status(self.inner)? })
+    }
+
     pub fn reset(&self) -> AnyResult<()> {
         unsafe {
             self.logical_device.inner.reset_fences(&[self.inner])?;
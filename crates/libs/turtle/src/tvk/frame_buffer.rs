@@ -10,17 +10,25 @@ pub struct FrameBuffer {
 impl FrameBuffer {
     pub fn new(
         logical_device: Arc<tvk::LogicalDevice>,
-        swapchain: &tvk::Swapchain,
+        extent: avk::Extent2D,
         render_pass: &tvk::RenderPass,
         image_view: &tvk::ImageView,
         depth_image_view: &tvk::ImageView,
         ) -> AnyResult<Self> {
-        let attachments = &[image_view.inner, depth_image_view.inner];
+        Self::with_attachments(logical_device, extent, render_pass, &[image_view.inner, depth_image_view.inner])
+    }
+
+    pub fn with_attachments(
+        logical_device: Arc<tvk::LogicalDevice>,
+        extent: avk::Extent2D,
+        render_pass: &tvk::RenderPass,
+        attachments: &[avk::ImageView],
+        ) -> AnyResult<Self> {
         let create_info = avk::FramebufferCreateInfo::default()
             .render_pass(render_pass.inner)
             .attachments(attachments)
-            .width(swapchain.extent.width)
-            .height(swapchain.extent.height)
+            .width(extent.width)
+            .height(extent.height)
             .layers(1);
 
         let inner = unsafe { logical_device.inner.create_framebuffer(&create_info, None)? };
@@ -35,7 +43,7 @@ impl FrameBuffer {
 impl tvk::Context {
     pub fn create_frame_buffers(&self, swapchain:&tvk::Swapchain, render_pass: &tvk::RenderPass, depth_image_view: &tvk::ImageView) -> AnyResult<Vec<FrameBuffer>> {
         swapchain.image_views.iter().map(|image_view| {
-            tvk::FrameBuffer::new(self.logical_device.clone(), swapchain, render_pass, image_view, depth_image_view)
+            tvk::FrameBuffer::new(self.logical_device.clone(), swapchain.extent, render_pass, image_view, depth_image_view)
         }).collect()
     }
 }
@@ -6,7 +6,13 @@ use gpu_allocator::{vulkan as mvk, MemoryLocation};
 
 pub struct Image {
     pub(crate) inner: avk::Image,
+    pub extent: avk::Extent2D,
+    pub format: avk::Format,
+    pub layers: u32,
+    pub mip_levels: u32,
     allocation: Option<mvk::Allocation>,
+    /// Memory shared with other images, when bound through `bind_memory`.
+    memory: Option<Arc<tvk::Memory>>,
     allocator: Arc<Mutex<tvk::Allocator>>,
     logical_device: Arc<tvk::LogicalDevice>
 }
@@ -19,62 +25,193 @@ impl Image {
         format: avk::Format,
         usage: avk::ImageUsageFlags
     ) -> AnyResult<Self> {
-        let image_info = avk::ImageCreateInfo::default()
-            .image_type(avk::ImageType::TYPE_2D)
-            .format(format)
-            .tiling(avk::ImageTiling::OPTIMAL)
-            .initial_layout(avk::ImageLayout::UNDEFINED)
-            .usage(usage)
-            .sharing_mode(avk::SharingMode::EXCLUSIVE)
-            .samples(avk::SampleCountFlags::TYPE_1)
-            .extent(avk::Extent3D {
-                width: extent.width,
-                height: extent.height,
-                depth: 1
-            })
-            .mip_levels(1)
-            .array_layers(1);
+        Self::new_shared(logical_device, allocator, extent, format, usage, &[])
+    }
 
-        let inner = unsafe {
-            logical_device.inner.create_image(&image_info, None)?
-        };
+    /// Like `new`, but usable from every family in `queue_family_indices` without
+    /// ownership transfers.
+    pub fn new_shared(
+        logical_device: Arc<tvk::LogicalDevice>,
+        allocator: Arc<Mutex<tvk::Allocator>>,
+        extent: avk::Extent2D,
+        format: avk::Format,
+        usage: avk::ImageUsageFlags,
+        queue_family_indices: &[u32]
+    ) -> AnyResult<Self> {
+        let image = Self::new_unbound(logical_device, allocator, extent, format, usage, avk::SampleCountFlags::TYPE_1, 1, queue_family_indices)?;
+        image.allocate()
+    }
 
-        let requirements = unsafe {
-            logical_device.inner.get_image_memory_requirements(inner)
-        };
+    /// Like `new`, with `samples` samples per pixel. Only usable as an attachment.
+    pub fn new_multisampled(
+        logical_device: Arc<tvk::LogicalDevice>,
+        allocator: Arc<Mutex<tvk::Allocator>>,
+        extent: avk::Extent2D,
+        format: avk::Format,
+        usage: avk::ImageUsageFlags,
+        samples: avk::SampleCountFlags,
+    ) -> AnyResult<Self> {
+        Self::new_unbound(logical_device, allocator, extent, format, usage, samples, 1, &[])?.allocate()
+    }
 
+    /// Like `new`, with room for a full mip chain down to 1x1.
+    pub fn new_mipmapped(
+        logical_device: Arc<tvk::LogicalDevice>,
+        allocator: Arc<Mutex<tvk::Allocator>>,
+        extent: avk::Extent2D,
+        format: avk::Format,
+        usage: avk::ImageUsageFlags,
+    ) -> AnyResult<Self> {
+        let mip_levels = u32::BITS - extent.width.max(extent.height).max(1).leading_zeros();
+        let image_info = image_info(extent, format, usage, 1)
+            .mip_levels(mip_levels);
+        Self::from_info(logical_device, allocator, &image_info)?.allocate()
+    }
+
+    /// Six square layers of `size` texels that can be viewed as a cube map, each with
+    /// `mip_levels` levels.
+    pub fn new_cube(
+        logical_device: Arc<tvk::LogicalDevice>,
+        allocator: Arc<Mutex<tvk::Allocator>>,
+        size: u32,
+        format: avk::Format,
+        usage: avk::ImageUsageFlags,
+        mip_levels: u32,
+    ) -> AnyResult<Self> {
+        let image_info = image_info(avk::Extent2D { width: size, height: size }, format, usage, 6)
+            .mip_levels(mip_levels)
+            .flags(avk::ImageCreateFlags::CUBE_COMPATIBLE);
+        Self::from_info(logical_device, allocator, &image_info)?.allocate()
+    }
+
+    fn allocate(mut self) -> AnyResult<Self> {
         let alloc_desc = mvk::AllocationCreateDesc {
             name: "Image",
-            requirements,
+            requirements: self.memory_requirements(),
             location: MemoryLocation::GpuOnly,
             linear: false,
             allocation_scheme: mvk::AllocationScheme::GpuAllocatorManaged,
         };
 
-        let allocation = allocator.lock().unwrap().inner.allocate(&alloc_desc)?;
+        let allocation = self.allocator.lock().unwrap().inner.allocate(&alloc_desc)?;
         
-        unsafe { logical_device.inner.bind_image_memory(inner, allocation.memory(), allocation.offset())? };
+        unsafe { self.logical_device.inner.bind_image_memory(self.inner, allocation.memory(), allocation.offset())? };
+        self.allocation = Some(allocation);
+        Ok(self)
+    }
+
+    /// Creates the image without memory. Bind some with `bind_memory` before use.
+    /// `layers` above 1 makes an image array.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_unbound(
+        logical_device: Arc<tvk::LogicalDevice>,
+        allocator: Arc<Mutex<tvk::Allocator>>,
+        extent: avk::Extent2D,
+        format: avk::Format,
+        usage: avk::ImageUsageFlags,
+        samples: avk::SampleCountFlags,
+        layers: u32,
+        queue_family_indices: &[u32]
+    ) -> AnyResult<Self> {
+        let sharing_mode = if queue_family_indices.len() > 1 {
+            avk::SharingMode::CONCURRENT
+        } else {
+            avk::SharingMode::EXCLUSIVE
+        };
+        let image_info = image_info(extent, format, usage, layers)
+            .sharing_mode(sharing_mode)
+            .queue_family_indices(queue_family_indices)
+            .samples(samples);
+        Self::from_info(logical_device, allocator, &image_info)
+    }
+
+    fn from_info(
+        logical_device: Arc<tvk::LogicalDevice>,
+        allocator: Arc<Mutex<tvk::Allocator>>,
+        image_info: &avk::ImageCreateInfo,
+    ) -> AnyResult<Self> {
+        let inner = unsafe {
+            logical_device.inner.create_image(image_info, None)?
+        };
 
         Ok(Self {
             inner,
+            extent: avk::Extent2D { width: image_info.extent.width, height: image_info.extent.height },
+            format: image_info.format,
+            layers: image_info.array_layers,
+            mip_levels: image_info.mip_levels,
             logical_device,
-            allocation: Some(allocation),
+            allocation: None,
+            memory: None,
             allocator
         })
     }
+
+    pub fn memory_requirements(&self) -> avk::MemoryRequirements {
+        unsafe { self.logical_device.inner.get_image_memory_requirements(self.inner) }
+    }
+
+    /// Binds an image created with `new_unbound` to the start of `memory`, which other
+    /// images may share.
+    pub fn bind_memory(&mut self, memory: Arc<tvk::Memory>) -> AnyResult<()> {
+        let allocation = memory.allocation();
+        unsafe { self.logical_device.inner.bind_image_memory(self.inner, allocation.memory(), allocation.offset())? };
+        self.memory = Some(memory);
+        Ok(())
+    }
+}
+
+fn image_info<'a>(extent: avk::Extent2D, format: avk::Format, usage: avk::ImageUsageFlags, layers: u32) -> avk::ImageCreateInfo<'a> {
+    avk::ImageCreateInfo::default()
+        .image_type(avk::ImageType::TYPE_2D)
+        .format(format)
+        .tiling(avk::ImageTiling::OPTIMAL)
+        .initial_layout(avk::ImageLayout::UNDEFINED)
+        .usage(usage)
+        .sharing_mode(avk::SharingMode::EXCLUSIVE)
+        .samples(avk::SampleCountFlags::TYPE_1)
+        .extent(avk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1
+        })
+        .mip_levels(1)
+        .array_layers(layers)
 }
 
 impl tvk::Context {
     pub fn create_image(&self, extent: avk::Extent2D, format: avk::Format, usage: avk::ImageUsageFlags) -> AnyResult<Image> {
         Image::new(self.logical_device.clone(), self.allocator.clone(), extent, format, usage)
     }
+
+    pub fn create_multisampled_image(&self, extent: avk::Extent2D, format: avk::Format, usage: avk::ImageUsageFlags, samples: avk::SampleCountFlags) -> AnyResult<Image> {
+        Image::new_multisampled(self.logical_device.clone(), self.allocator.clone(), extent, format, usage, samples)
+    }
+
+    pub fn create_unbound_image(&self, extent: avk::Extent2D, format: avk::Format, usage: avk::ImageUsageFlags, samples: avk::SampleCountFlags, layers: u32) -> AnyResult<Image> {
+        Image::new_unbound(self.logical_device.clone(), self.allocator.clone(), extent, format, usage, samples, layers, &[])
+    }
+
+    pub fn create_mipmapped_image(&self, extent: avk::Extent2D, format: avk::Format, usage: avk::ImageUsageFlags) -> AnyResult<Image> {
+        Image::new_mipmapped(self.logical_device.clone(), self.allocator.clone(), extent, format, usage)
+    }
+
+    pub fn create_cube_image(&self, size: u32, format: avk::Format, usage: avk::ImageUsageFlags, mip_levels: u32) -> AnyResult<Image> {
+        Image::new_cube(self.logical_device.clone(), self.allocator.clone(), size, format, usage, mip_levels)
+    }
+
+    pub fn create_transfer_image(&self, extent: avk::Extent2D, format: avk::Format, usage: avk::ImageUsageFlags) -> AnyResult<Image> {
+        Image::new_shared(self.logical_device.clone(), self.allocator.clone(), extent, format, usage, &self.transfer_queue_family_indices())
+    }
 }
 
 impl Drop for Image {
     fn drop(&mut self) {
         unsafe {
             self.logical_device.inner.destroy_image(self.inner, None);
-            self.allocator.lock().unwrap().inner.free(self.allocation.take().unwrap()).unwrap();
+            if let Some(allocation) = self.allocation.take() {
+                self.allocator.lock().unwrap().inner.free(allocation).unwrap();
+            }
         }
     }
 }
\ No newline at end of file
@@ -14,10 +14,40 @@ impl ImageView {
         image: avk::Image,
         format: avk::Format,
         aspect_flags: avk::ImageAspectFlags
+    ) -> AnyResult<Self> {
+        Self::with_layers(logical_device, image, format, aspect_flags, avk::ImageViewType::TYPE_2D, 0, 1)
+    }
+
+    /// View of `layer_count` layers of an image array starting at `base_layer`.
+    pub fn with_layers(
+        logical_device: Arc<tvk::LogicalDevice>,
+        image: avk::Image,
+        format: avk::Format,
+        aspect_flags: avk::ImageAspectFlags,
+        view_type: avk::ImageViewType,
+        base_layer: u32,
+        layer_count: u32,
+    ) -> AnyResult<Self> {
+        Self::with_subresources(logical_device, image, format, view_type, avk::ImageSubresourceRange {
+            aspect_mask: aspect_flags,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: base_layer,
+            layer_count,
+        })
+    }
+
+    /// View of any range of mip levels and layers, such as a cube map or one of its faces.
+    pub fn with_subresources(
+        logical_device: Arc<tvk::LogicalDevice>,
+        image: avk::Image,
+        format: avk::Format,
+        view_type: avk::ImageViewType,
+        subresource_range: avk::ImageSubresourceRange,
     ) -> AnyResult<Self> {
         let create_info = avk::ImageViewCreateInfo::default()
             .image(image)
-            .view_type(avk::ImageViewType::TYPE_2D)
+            .view_type(view_type)
             .format(format)
             .components(avk::ComponentMapping {
                 r: avk::ComponentSwizzle::IDENTITY,
@@ -25,13 +55,7 @@ impl ImageView {
                 b: avk::ComponentSwizzle::IDENTITY,
                 a: avk::ComponentSwizzle::IDENTITY,
             })
-            .subresource_range(avk::ImageSubresourceRange {
-                aspect_mask: aspect_flags,
-                base_mip_level: 0,
-                level_count: 1,
-                base_array_layer: 0,
-                layer_count: 1,
-            });
+            .subresource_range(subresource_range);
 
         let inner = unsafe { logical_device.inner.create_image_view(&create_info, None)? };
 
@@ -46,6 +70,27 @@ impl tvk::Context {
     pub fn create_image_view(&self, image: &tvk::Image, format: avk::Format, aspect_flags: avk::ImageAspectFlags) -> AnyResult<ImageView> {
         ImageView::new(self.logical_device.clone(), image.inner, format, aspect_flags)
     }
+
+    pub fn create_layered_image_view(
+        &self,
+        image: &tvk::Image,
+        format: avk::Format,
+        aspect_flags: avk::ImageAspectFlags,
+        view_type: avk::ImageViewType,
+        base_layer: u32,
+        layer_count: u32,
+    ) -> AnyResult<ImageView> {
+        ImageView::with_layers(self.logical_device.clone(), image.inner, format, aspect_flags, view_type, base_layer, layer_count)
+    }
+
+    pub fn create_subresource_image_view(
+        &self,
+        image: &tvk::Image,
+        view_type: avk::ImageViewType,
+        subresource_range: avk::ImageSubresourceRange,
+    ) -> AnyResult<ImageView> {
+        ImageView::with_subresources(self.logical_device.clone(), image.inner, image.format, view_type, subresource_range)
+    }
 }
 
 impl Drop for ImageView {
@@ -51,7 +51,7 @@ impl Instance {
                 .push_next(&mut debug_create_info);
 
             let inner = unsafe { entry.create_instance(&create_info, None)? };  
-            let debug_utils = ash::ext::debug_utils::Instance::new(&entry, &inner);
+            let debug_utils = ash::ext::debug_utils::Instance::new(entry, &inner);
 
             let debug_utils_messenger = unsafe { debug_utils.create_debug_utils_messenger(&debug_create_info, None)? };
 
@@ -70,7 +70,7 @@ impl Instance {
             self.physical_devices = unsafe {
             self.inner.enumerate_physical_devices()?
                 .iter()
-                .map(|pd| tvk::PhysicalDevice::new(surface, *pd, &self))
+                .map(|pd| tvk::PhysicalDevice::new(surface, *pd, self))
                 .collect::<AnyResult<Vec<_>>>()?
             };
 
@@ -33,6 +33,7 @@ impl LogicalDevice {
 
         let pf = avk::PhysicalDeviceFeatures {
             shader_int64: avk::TRUE,
+            fill_mode_non_solid: physical_device.features.fill_mode_non_solid,
             ..Default::default()
         };
         
@@ -12,6 +12,7 @@ pub struct PhysicalDevice {
     pub present_modes: Vec<avk::PresentModeKHR>,
     pub(crate) queue_families: Vec<tvk::QueueFamily>,
     pub(crate) properties: avk::PhysicalDeviceProperties,
+    pub(crate) features: avk::PhysicalDeviceFeatures,
     pub(crate) inner: avk::PhysicalDevice,
 }
 
@@ -22,6 +23,7 @@ impl PhysicalDevice {
         instance: &tvk::Instance,
     ) -> AnyResult<Self> {
         let properties = unsafe { instance.inner.get_physical_device_properties(inner)};
+        let features = unsafe { instance.inner.get_physical_device_features(inner) };
 
         let queue_families = unsafe {
             instance.inner.get_physical_device_queue_family_properties(inner)
@@ -46,6 +48,7 @@ impl PhysicalDevice {
         Ok(Self {
             inner,
             properties,
+            features,
             queue_families,
             surface_capabilities,
             formats,
@@ -54,6 +57,25 @@ impl PhysicalDevice {
         })
     }
 
+    /// Highest sample count usable for both color and depth attachments.
+    pub fn max_sample_count(&self) -> avk::SampleCountFlags {
+        let limits = &self.properties.limits;
+        let supported = limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+        [
+            avk::SampleCountFlags::TYPE_64,
+            avk::SampleCountFlags::TYPE_32,
+            avk::SampleCountFlags::TYPE_16,
+            avk::SampleCountFlags::TYPE_8,
+            avk::SampleCountFlags::TYPE_4,
+            avk::SampleCountFlags::TYPE_2,
+        ].into_iter().find(|&samples| supported.contains(samples)).unwrap_or(avk::SampleCountFlags::TYPE_1)
+    }
+
+    /// Whether pipelines can rasterize polygons as lines.
+    pub fn supports_wireframe(&self) -> bool {
+        self.features.fill_mode_non_solid == avk::TRUE
+    }
+
     fn find_supported_format(
     physical_device: avk::PhysicalDevice,
     instance: &tvk::Instance,
@@ -65,9 +87,8 @@ impl PhysicalDevice {
             unsafe {
                 let props = instance.inner.get_physical_device_format_properties(physical_device, format);
 
-                if tiling == avk::ImageTiling::LINEAR && (props.linear_tiling_features & features == features) {
-                    return Ok(format);
-                } else if tiling == avk::ImageTiling::OPTIMAL && (props.optimal_tiling_features & features) == features {
+                if (tiling == avk::ImageTiling::LINEAR && (props.linear_tiling_features & features) == features)
+                    || (tiling == avk::ImageTiling::OPTIMAL && (props.optimal_tiling_features & features) == features) {
                     return Ok(format);
                 }
             }
@@ -1,4 +1,4 @@
-use std::{path::Path, sync::Arc};
+use std::sync::Arc;
 use ash::vk as avk;
 use crate::{tvk::{self, VertexDescription}, AnyResult};
 pub struct Pipeline {
@@ -8,49 +8,120 @@ pub struct Pipeline {
     
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Clone, Copy)]
 pub struct PipelineShaderCreateInfo<'a> {
-    pub path: &'a Path,
+    pub module: &'a tvk::ShaderModule,
     pub stage: avk::ShaderStageFlags
 }
 
+/// Fixed-function state that varies between the pipelines of a renderer.
+#[derive(Debug, Clone, Copy)]
+pub struct PipelineState {
+    pub depth_compare_op: avk::CompareOp,
+    /// Alpha blending on the color attachment. Must be off for integer formats.
+    pub blend: bool,
+    /// Adds the color onto the attachment instead of alpha blending it. Only with `blend`.
+    pub additive_blend: bool,
+    /// Size of the push constant block visible to the vertex and fragment stages.
+    pub push_constant_size: u32,
+    /// Reads mesh vertices alongside the instance attributes. Off for passes that
+    /// generate their vertices in the shader, like full-screen quads.
+    pub vertex_input: bool,
+    /// Reads `tvk::Vertex` at binding 0 ahead of the attributes of the pipeline's
+    /// vertex description. Off when that description covers every attribute.
+    pub mesh_vertices: bool,
+    pub topology: avk::PrimitiveTopology,
+    /// Anything but `FILL` needs `PhysicalDevice::supports_wireframe`.
+    pub polygon_mode: avk::PolygonMode,
+    pub depth_test: bool,
+    /// Writes the depth of what passes the depth test. Off for backgrounds drawn
+    /// behind everything else.
+    pub depth_write: bool,
+    /// Must match the sample count of the render pass attachments.
+    pub samples: avk::SampleCountFlags,
+    /// Writes one color attachment. Off for depth-only passes.
+    pub color_output: bool,
+    /// Offsets depth by the values set with `CommandBuffer::set_depth_bias`.
+    pub depth_bias: bool,
+    /// Writes the two attachments of weighted blended order-independent transparency
+    /// instead of one: premultiplied color summed into the first, and the product of
+    /// the remaining transparency into the red channel of the second.
+    pub order_independent: bool,
+}
+
+impl Default for PipelineState {
+    fn default() -> Self {
+        Self {
+            depth_compare_op: avk::CompareOp::LESS,
+            blend: true,
+            additive_blend: false,
+            push_constant_size: 0,
+            vertex_input: true,
+            mesh_vertices: true,
+            topology: avk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: avk::PolygonMode::FILL,
+            depth_test: true,
+            depth_write: true,
+            samples: avk::SampleCountFlags::TYPE_1,
+            color_output: true,
+            depth_bias: false,
+            order_independent: false,
+        }
+    }
+}
+
+/// Stages that see the push constants declared through `PipelineState`.
+pub const PUSH_CONSTANT_STAGES: avk::ShaderStageFlags = avk::ShaderStageFlags::from_raw(
+    avk::ShaderStageFlags::VERTEX.as_raw() | avk::ShaderStageFlags::FRAGMENT.as_raw()
+);
+
 impl Pipeline {
-    pub fn new(
+    pub fn new<I: VertexDescription>(
         logical_device: Arc<tvk::LogicalDevice>,
         render_pass: &tvk::RenderPass,
-        descriptor_layout: avk::DescriptorSetLayout,
+        descriptor_layouts: &[avk::DescriptorSetLayout],
         shaders: &[tvk::PipelineShaderCreateInfo],
+        state: &PipelineState,
     ) -> AnyResult<Self> {
-        let set_layouts = [descriptor_layout];
+        let push_constant_ranges = [avk::PushConstantRange::default()
+            .stage_flags(PUSH_CONSTANT_STAGES)
+            .offset(0)
+            .size(state.push_constant_size)];
+        let push_constant_ranges = if state.push_constant_size > 0 { &push_constant_ranges[..] } else { &[] };
         let layout_info = avk::PipelineLayoutCreateInfo::default()
-            .set_layouts(&set_layouts);
+            .set_layouts(descriptor_layouts)
+            .push_constant_ranges(push_constant_ranges);
         let layout = unsafe { logical_device.inner.create_pipeline_layout(&layout_info, None)? };
 
-        let (_modules, stages) = shaders.into_iter()
+        let stages = shaders.iter()
             .map(|shader| {
-            let shader_module = tvk::ShaderModule::create(logical_device.clone(), shader.path).unwrap();
-
-            let stages = avk::PipelineShaderStageCreateInfo::default()
+            avk::PipelineShaderStageCreateInfo::default()
             .stage(shader.stage)
-            .module(shader_module.inner)
-            .name(c"main");
-            (shader_module, stages)
-        }).collect::<(Vec<_>, Vec<_>)>();
+            .module(shader.module.inner)
+            .name(c"main")
+        }).collect::<Vec<_>>();
 
-        let dynamic_states = &[
+        let mut dynamic_states = vec![
             avk::DynamicState::VIEWPORT,
             avk::DynamicState::SCISSOR
         ];
+        if state.depth_bias {
+            dynamic_states.push(avk::DynamicState::DEPTH_BIAS);
+        }
 
         let dynamic_state = avk::PipelineDynamicStateCreateInfo::default()
-            .dynamic_states(dynamic_states);
+            .dynamic_states(&dynamic_states);
 
-        let (vertex_binding_descriptions, vertex_attribute_descriptions) = tvk::Pipeline::pipeline_vertex_input_state();
+        let (vertex_binding_descriptions, vertex_attribute_descriptions) = if state.vertex_input {
+            tvk::Pipeline::pipeline_vertex_input_state::<I>(state.mesh_vertices)
+        } else {
+            (Vec::new(), Vec::new())
+        };
         let vertex_input_state = avk::PipelineVertexInputStateCreateInfo::default()
             .vertex_binding_descriptions(&vertex_binding_descriptions)
             .vertex_attribute_descriptions(&vertex_attribute_descriptions);
         let input_assembly_state = avk::PipelineInputAssemblyStateCreateInfo::default()
-            .topology(avk::PrimitiveTopology::TRIANGLE_LIST)
+            .topology(state.topology)
             .primitive_restart_enable(false);
 
         let viewport_state = avk::PipelineViewportStateCreateInfo::default()
@@ -60,27 +131,50 @@ impl Pipeline {
         let rasterization_state = avk::PipelineRasterizationStateCreateInfo::default()
             .depth_clamp_enable(false)
             .rasterizer_discard_enable(false)
-            .polygon_mode(avk::PolygonMode::FILL)
+            .polygon_mode(state.polygon_mode)
             .line_width(1.0)
             .cull_mode(avk::CullModeFlags::BACK)
-            .front_face(avk::FrontFace::CLOCKWISE)
-            .depth_bias_enable(false);
+            .front_face(avk::FrontFace::COUNTER_CLOCKWISE)
+            .depth_bias_enable(state.depth_bias);
 
         let multisample_state = avk::PipelineMultisampleStateCreateInfo::default()
             .sample_shading_enable(false)
-            .rasterization_samples(avk::SampleCountFlags::TYPE_1);
+            .rasterization_samples(state.samples);
         
         let attachment = avk::PipelineColorBlendAttachmentState::default()
             .color_write_mask(avk::ColorComponentFlags::RGBA)
-            .blend_enable(true)
-            .src_color_blend_factor(avk::BlendFactor::SRC_ALPHA)
-            .dst_color_blend_factor(avk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .blend_enable(state.blend)
+            .src_color_blend_factor(if state.additive_blend { avk::BlendFactor::ONE } else { avk::BlendFactor::SRC_ALPHA })
+            .dst_color_blend_factor(if state.additive_blend { avk::BlendFactor::ONE } else { avk::BlendFactor::ONE_MINUS_SRC_ALPHA })
             .color_blend_op(avk::BlendOp::ADD)
             .src_alpha_blend_factor(avk::BlendFactor::ONE)
             .dst_alpha_blend_factor(avk::BlendFactor::ZERO)
             .alpha_blend_op(avk::BlendOp::ADD);   
 
-        let attachments = &[attachment];
+        let accumulation = avk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(avk::ColorComponentFlags::RGBA)
+            .blend_enable(true)
+            .src_color_blend_factor(avk::BlendFactor::ONE)
+            .dst_color_blend_factor(avk::BlendFactor::ONE)
+            .color_blend_op(avk::BlendOp::ADD)
+            .src_alpha_blend_factor(avk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(avk::BlendFactor::ONE)
+            .alpha_blend_op(avk::BlendOp::ADD);
+        let revealage = avk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(avk::ColorComponentFlags::R)
+            .blend_enable(true)
+            .src_color_blend_factor(avk::BlendFactor::ZERO)
+            .dst_color_blend_factor(avk::BlendFactor::ONE_MINUS_SRC_COLOR)
+            .color_blend_op(avk::BlendOp::ADD)
+            .src_alpha_blend_factor(avk::BlendFactor::ZERO)
+            .dst_alpha_blend_factor(avk::BlendFactor::ONE)
+            .alpha_blend_op(avk::BlendOp::ADD);
+        let order_independent_attachments = [accumulation, revealage];
+        let attachments = match (state.color_output, state.order_independent) {
+            (false, _) => &[][..],
+            (true, false) => &[attachment][..],
+            (true, true) => &order_independent_attachments[..],
+        };
         let color_blend_state = avk::PipelineColorBlendStateCreateInfo::default()
             .logic_op_enable(false)
             .logic_op(avk::LogicOp::COPY)
@@ -88,9 +182,9 @@ impl Pipeline {
             .blend_constants([0.0, 0.0, 0.0, 0.0]);
 
         let depth_stencil = avk::PipelineDepthStencilStateCreateInfo::default()
-            .depth_test_enable(true)
-            .depth_write_enable(true)
-            .depth_compare_op(avk::CompareOp::LESS)
+            .depth_test_enable(state.depth_test)
+            .depth_write_enable(state.depth_test && state.depth_write)
+            .depth_compare_op(state.depth_compare_op)
             .depth_bounds_test_enable(false)
             .stencil_test_enable(false);
 
@@ -119,28 +213,30 @@ impl Pipeline {
         })
     }
 
-    fn pipeline_vertex_input_state() -> (Vec<avk::VertexInputBindingDescription>, Vec<avk::VertexInputAttributeDescription>) {
+    fn pipeline_vertex_input_state<I: VertexDescription>(mesh_vertices: bool) -> (Vec<avk::VertexInputBindingDescription>, Vec<avk::VertexInputAttributeDescription>) {
         let mut bindings = Vec::new();
         let mut attributes = Vec::new();
 
-        bindings.extend(tvk::Vertex::get_binding_descriptions());
-        bindings.extend(tvk::InstanceData::get_binding_descriptions());
-
-        attributes.extend(tvk::Vertex::get_attribute_descriptions());
-        attributes.extend(tvk::InstanceData::get_attribute_descriptions());
+        if mesh_vertices {
+            bindings.extend(tvk::Vertex::get_binding_descriptions());
+            attributes.extend(tvk::Vertex::get_attribute_descriptions());
+        }
+        bindings.extend(I::get_binding_descriptions());
+        attributes.extend(I::get_attribute_descriptions());
 
         (bindings, attributes)
     }
 }
 
 impl tvk::Context {
-    pub fn create_pipeline(
+    pub fn create_pipeline<I: VertexDescription>(
         &self,
         render_pass: &tvk::RenderPass,
-        descriptor_set: avk::DescriptorSetLayout,
-        shaders: &[tvk::PipelineShaderCreateInfo]
+        descriptor_layouts: &[avk::DescriptorSetLayout],
+        shaders: &[tvk::PipelineShaderCreateInfo],
+        state: &PipelineState,
     ) -> AnyResult<tvk::Pipeline> {
-        tvk::Pipeline::new(self.logical_device.clone(), render_pass, descriptor_set, shaders)
+        tvk::Pipeline::new::<I>(self.logical_device.clone(), render_pass, descriptor_layouts, shaders, state)
     }
 }
 
@@ -10,7 +10,52 @@ impl RenderPass {
     pub fn new(
         logical_device: Arc<tvk::LogicalDevice>,
         physical_device: &tvk::PhysicalDevice,
-        swapchain: &tvk::Swapchain
+        swapchain: &tvk::Swapchain,
+        samples: avk::SampleCountFlags,
+    ) -> AnyResult<Self> {
+        Self::multisampled(logical_device, swapchain.format, physical_device.depth_format, samples, avk::ImageLayout::PRESENT_SRC_KHR)
+    }
+
+    /// Color and depth attachments with `samples` samples. With more than one, the
+    /// color is resolved into a third, single-sampled attachment, which is the one left
+    /// in `color_final_layout`.
+    pub fn multisampled(
+        logical_device: Arc<tvk::LogicalDevice>,
+        color_format: avk::Format,
+        depth_format: avk::Format,
+        samples: avk::SampleCountFlags,
+        color_final_layout: avk::ImageLayout,
+    ) -> AnyResult<Self> {
+        if samples == avk::SampleCountFlags::TYPE_1 {
+            return Self::with_formats(logical_device, color_format, depth_format, color_final_layout);
+        }
+
+        // Multisampled color and depth, with the color resolved into the swapchain image.
+        let attachment = |format, samples, store_op, final_layout| avk::AttachmentDescription::default()
+            .format(format)
+            .samples(samples)
+            .load_op(avk::AttachmentLoadOp::CLEAR)
+            .store_op(store_op)
+            .stencil_load_op(avk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(avk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(avk::ImageLayout::UNDEFINED)
+            .final_layout(final_layout);
+        Self::with_attachments(
+            logical_device,
+            &[attachment(color_format, samples, avk::AttachmentStoreOp::DONT_CARE, avk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)],
+            Some(attachment(depth_format, samples, avk::AttachmentStoreOp::DONT_CARE, avk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)),
+            &[attachment(color_format, avk::SampleCountFlags::TYPE_1, avk::AttachmentStoreOp::STORE, color_final_layout)
+                .load_op(avk::AttachmentLoadOp::DONT_CARE)],
+        )
+    }
+
+    /// Single-subpass pass with one color and one depth attachment, leaving the color
+    /// attachment in `color_final_layout`.
+    pub fn with_formats(
+        logical_device: Arc<tvk::LogicalDevice>,
+        color_format: avk::Format,
+        depth_format: avk::Format,
+        color_final_layout: avk::ImageLayout
     ) -> AnyResult<Self> {
         let dependency = avk::SubpassDependency::default()
             .src_subpass(avk::SUBPASS_EXTERNAL)
@@ -21,14 +66,14 @@ impl RenderPass {
             .dst_access_mask(avk::AccessFlags::COLOR_ATTACHMENT_WRITE | avk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE);    
     
         let color_attachment = avk::AttachmentDescription::default()
-            .format(swapchain.format)
+            .format(color_format)
             .samples(avk::SampleCountFlags::TYPE_1)
             .load_op(avk::AttachmentLoadOp::CLEAR)
             .store_op(avk::AttachmentStoreOp::STORE)
             .stencil_load_op(avk::AttachmentLoadOp::DONT_CARE)
             .stencil_store_op(avk::AttachmentStoreOp::DONT_CARE)
             .initial_layout(avk::ImageLayout::UNDEFINED)
-            .final_layout(avk::ImageLayout::PRESENT_SRC_KHR)
+            .final_layout(color_final_layout)
             .samples(avk::SampleCountFlags::TYPE_1);
     
         let color_attachment_ref = avk::AttachmentReference::default()
@@ -36,7 +81,7 @@ impl RenderPass {
             .layout(avk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
     
         let depth_attachment = avk::AttachmentDescription::default()
-            .format(physical_device.depth_format)
+            .format(depth_format)
             .samples(avk::SampleCountFlags::TYPE_1)
             .load_op(avk::AttachmentLoadOp::CLEAR)
             .store_op(avk::AttachmentStoreOp::DONT_CARE)
@@ -72,9 +117,98 @@ impl RenderPass {
     }
 }
 
+impl RenderPass {
+    /// Single-subpass pass over `color_attachments` and an optional depth attachment,
+    /// each used in its optimal attachment layout. Callers synchronize access and
+    /// layout transitions outside the pass. `resolve_attachments` is empty or has one
+    /// single-sampled attachment per color attachment; they come after the depth
+    /// attachment in the framebuffer.
+    pub fn with_attachments(
+        logical_device: Arc<tvk::LogicalDevice>,
+        color_attachments: &[avk::AttachmentDescription],
+        depth_attachment: Option<avk::AttachmentDescription>,
+        resolve_attachments: &[avk::AttachmentDescription],
+    ) -> AnyResult<Self> {
+        let color_attachment_refs = (0..color_attachments.len()).map(|i| avk::AttachmentReference::default()
+            .attachment(i as u32)
+            .layout(avk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+        ).collect::<Vec<_>>();
+        let depth_attachment_ref = avk::AttachmentReference::default()
+            .attachment(color_attachments.len() as u32)
+            .layout(avk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        let first_resolve = color_attachments.len() + depth_attachment.iter().len();
+        let resolve_attachment_refs = (0..resolve_attachments.len()).map(|i| avk::AttachmentReference::default()
+            .attachment((first_resolve + i) as u32)
+            .layout(avk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+        ).collect::<Vec<_>>();
+
+        let mut subpass = avk::SubpassDescription::default()
+            .pipeline_bind_point(avk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_refs);
+        if depth_attachment.is_some() {
+            subpass = subpass.depth_stencil_attachment(&depth_attachment_ref);
+        }
+        if !resolve_attachments.is_empty() {
+            subpass = subpass.resolve_attachments(&resolve_attachment_refs);
+        }
+
+        let attachments = color_attachments.iter().copied()
+            .chain(depth_attachment)
+            .chain(resolve_attachments.iter().copied())
+            .collect::<Vec<_>>();
+        let subpasses = &[subpass];
+        let create_info = avk::RenderPassCreateInfo::default()
+            .attachments(&attachments)
+            .subpasses(subpasses);
+
+        let inner = unsafe { logical_device.inner.create_render_pass(&create_info, None)? };
+
+        Ok(Self {
+            inner,
+            logical_device
+        })
+    }
+}
+
 impl tvk::Context {
-    pub fn create_render_pass(&self, swapchain: &tvk::Swapchain) -> AnyResult<tvk::RenderPass> {
-        tvk::RenderPass::new(self.logical_device.clone(), &self.physical_device, swapchain)
+    pub fn create_render_pass(&self, swapchain: &tvk::Swapchain, samples: avk::SampleCountFlags) -> AnyResult<tvk::RenderPass> {
+        tvk::RenderPass::new(self.logical_device.clone(), &self.physical_device, swapchain, samples)
+    }
+
+    pub fn create_multisampled_render_pass(&self, color_format: avk::Format, samples: avk::SampleCountFlags, color_final_layout: avk::ImageLayout) -> AnyResult<tvk::RenderPass> {
+        tvk::RenderPass::multisampled(self.logical_device.clone(), color_format, self.physical_device.depth_format, samples, color_final_layout)
+    }
+
+    /// Pass with a single color attachment and no depth, for full-screen passes.
+    pub fn create_color_render_pass(&self, color_format: avk::Format, color_final_layout: avk::ImageLayout) -> AnyResult<tvk::RenderPass> {
+        let color_attachment = avk::AttachmentDescription::default()
+            .format(color_format)
+            .samples(avk::SampleCountFlags::TYPE_1)
+            .load_op(avk::AttachmentLoadOp::DONT_CARE)
+            .store_op(avk::AttachmentStoreOp::STORE)
+            .stencil_load_op(avk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(avk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(avk::ImageLayout::UNDEFINED)
+            .final_layout(color_final_layout);
+        tvk::RenderPass::with_attachments(self.logical_device.clone(), &[color_attachment], None, &[])
+    }
+
+    /// Pass with only a depth attachment in the device's depth format, for shadow maps.
+    pub fn create_depth_render_pass(&self) -> AnyResult<tvk::RenderPass> {
+        let depth_attachment = avk::AttachmentDescription::default()
+            .format(self.physical_device.depth_format)
+            .samples(avk::SampleCountFlags::TYPE_1)
+            .load_op(avk::AttachmentLoadOp::CLEAR)
+            .store_op(avk::AttachmentStoreOp::STORE)
+            .stencil_load_op(avk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(avk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(avk::ImageLayout::UNDEFINED)
+            .final_layout(avk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        tvk::RenderPass::with_attachments(self.logical_device.clone(), &[], Some(depth_attachment), &[])
+    }
+
+    pub fn create_offscreen_render_pass(&self, color_format: avk::Format, color_final_layout: avk::ImageLayout) -> AnyResult<tvk::RenderPass> {
+        tvk::RenderPass::with_formats(self.logical_device.clone(), color_format, self.physical_device.depth_format, color_final_layout)
     }
 }
 
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+use ash::vk as avk;
+use crate::{tvk, AnyResult};
+
+pub struct Sampler {
+    pub(crate) inner: avk::Sampler,
+    logical_device: Arc<tvk::LogicalDevice>,
+}
+
+impl Sampler {
+    pub fn new(
+        logical_device: Arc<tvk::LogicalDevice>,
+        filter: avk::Filter,
+        address_mode: avk::SamplerAddressMode
+    ) -> AnyResult<Self> {
+        let create_info = avk::SamplerCreateInfo::default()
+            .mag_filter(filter)
+            .min_filter(filter)
+            .mipmap_mode(avk::SamplerMipmapMode::LINEAR)
+            .address_mode_u(address_mode)
+            .address_mode_v(address_mode)
+            .address_mode_w(address_mode)
+            .max_lod(avk::LOD_CLAMP_NONE)
+            .border_color(avk::BorderColor::FLOAT_OPAQUE_BLACK);
+
+        let inner = unsafe { logical_device.inner.create_sampler(&create_info, None)? };
+
+        Ok(Self {
+            inner,
+            logical_device
+        })
+    }
+
+    /// Linear sampler returning the result of comparing the reference value against
+    /// the texels with `compare_op`, for shadow maps. Reads outside the image pass.
+    pub fn comparison(logical_device: Arc<tvk::LogicalDevice>, compare_op: avk::CompareOp) -> AnyResult<Self> {
+        let create_info = avk::SamplerCreateInfo::default()
+            .mag_filter(avk::Filter::LINEAR)
+            .min_filter(avk::Filter::LINEAR)
+            .mipmap_mode(avk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(avk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_v(avk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .address_mode_w(avk::SamplerAddressMode::CLAMP_TO_BORDER)
+            .compare_enable(true)
+            .compare_op(compare_op)
+            .border_color(avk::BorderColor::FLOAT_OPAQUE_WHITE);
+
+        let inner = unsafe { logical_device.inner.create_sampler(&create_info, None)? };
+
+        Ok(Self {
+            inner,
+            logical_device
+        })
+    }
+}
+
+impl tvk::Context {
+    pub fn create_sampler(&self, filter: avk::Filter, address_mode: avk::SamplerAddressMode) -> AnyResult<Sampler> {
+        Sampler::new(self.logical_device.clone(), filter, address_mode)
+    }
+
+    pub fn create_comparison_sampler(&self, compare_op: avk::CompareOp) -> AnyResult<Sampler> {
+        Sampler::comparison(self.logical_device.clone(), compare_op)
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe { self.logical_device.inner.destroy_sampler(self.inner, None); }
+    }
+}
@@ -15,7 +15,14 @@ impl ShaderModule {
         let file = File::open(path)?;
         let mut reader = std::io::BufReader::new(file);
         let code = ash::util::read_spv(&mut reader)?;
-        let create_info = avk::ShaderModuleCreateInfo::default().code(&code);
+        Self::from_code(logical_device, &code)
+    }
+
+    pub fn from_code(
+        logical_device: Arc<tvk::LogicalDevice>,
+        code: &[u32]
+    ) -> AnyResult<Self> {
+        let create_info = avk::ShaderModuleCreateInfo::default().code(code);
 
         let inner = unsafe { logical_device.inner.create_shader_module(&create_info, None)? };
     
@@ -1,48 +1,70 @@
 use ash::vk as avk;
-use glam::{vec3, Mat4, Vec3, Vec4};
+use glam::{vec2, vec3, vec4, Mat4, Quat, Vec2, Vec3, Vec4};
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct Vertex {
     pub position: glam::Vec3,
+    pub normal: glam::Vec3,
+    pub uv: glam::Vec2,
+    /// Direction of increasing `uv.x`, with the handedness of the bitangent
+    /// `cross(normal, tangent.xyz) * tangent.w` in `w`, as in glTF.
+    pub tangent: glam::Vec4,
+}
+
+impl Vertex {
+    /// Vertex at `position` with placeholder attributes, for meshes that only have
+    /// positions. See `generate_normals` and `generate_tangents`.
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            normal: Vec3::Y,
+            uv: Vec2::ZERO,
+            tangent: Vec4::X.with_w(1.0),
+        }
+    }
+}
+
+const fn cube_vertex(position: Vec3, normal: Vec3, uv: Vec2, tangent: Vec4) -> Vertex {
+    Vertex { position, normal, uv, tangent }
 }
 
 pub const CUBE_VERTICES: [Vertex; 24] = [
     // Front face (Z+)
-    Vertex { position: vec3(-0.5, -0.5,  0.5) },
-    Vertex { position: vec3( 0.5, -0.5,  0.5) },
-    Vertex { position: vec3( 0.5,  0.5,  0.5) },
-    Vertex { position: vec3(-0.5,  0.5,  0.5) },
+    cube_vertex(vec3(-0.5, -0.5,  0.5), vec3( 0.0,  0.0,  1.0), vec2(0.0, 1.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5, -0.5,  0.5), vec3( 0.0,  0.0,  1.0), vec2(1.0, 1.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5,  0.5,  0.5), vec3( 0.0,  0.0,  1.0), vec2(1.0, 0.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3(-0.5,  0.5,  0.5), vec3( 0.0,  0.0,  1.0), vec2(0.0, 0.0), vec4( 1.0,  0.0,  0.0, 1.0)),
 
     // Back face (Z-)
-    Vertex { position: vec3( 0.5, -0.5, -0.5) },
-    Vertex { position: vec3(-0.5, -0.5, -0.5) },
-    Vertex { position: vec3(-0.5,  0.5, -0.5) },
-    Vertex { position: vec3( 0.5,  0.5, -0.5) },
+    cube_vertex(vec3( 0.5, -0.5, -0.5), vec3( 0.0,  0.0, -1.0), vec2(0.0, 1.0), vec4(-1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3(-0.5, -0.5, -0.5), vec3( 0.0,  0.0, -1.0), vec2(1.0, 1.0), vec4(-1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3(-0.5,  0.5, -0.5), vec3( 0.0,  0.0, -1.0), vec2(1.0, 0.0), vec4(-1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5,  0.5, -0.5), vec3( 0.0,  0.0, -1.0), vec2(0.0, 0.0), vec4(-1.0,  0.0,  0.0, 1.0)),
 
     // Left face (X-)
-    Vertex { position: vec3(-0.5, -0.5, -0.5) },
-    Vertex { position: vec3(-0.5, -0.5,  0.5) },
-    Vertex { position: vec3(-0.5,  0.5,  0.5) },
-    Vertex { position: vec3(-0.5,  0.5, -0.5) },
+    cube_vertex(vec3(-0.5, -0.5, -0.5), vec3(-1.0,  0.0,  0.0), vec2(0.0, 1.0), vec4( 0.0,  0.0,  1.0, 1.0)),
+    cube_vertex(vec3(-0.5, -0.5,  0.5), vec3(-1.0,  0.0,  0.0), vec2(1.0, 1.0), vec4( 0.0,  0.0,  1.0, 1.0)),
+    cube_vertex(vec3(-0.5,  0.5,  0.5), vec3(-1.0,  0.0,  0.0), vec2(1.0, 0.0), vec4( 0.0,  0.0,  1.0, 1.0)),
+    cube_vertex(vec3(-0.5,  0.5, -0.5), vec3(-1.0,  0.0,  0.0), vec2(0.0, 0.0), vec4( 0.0,  0.0,  1.0, 1.0)),
 
     // Right face (X+)
-    Vertex { position: vec3( 0.5, -0.5,  0.5) },
-    Vertex { position: vec3( 0.5, -0.5, -0.5) },
-    Vertex { position: vec3( 0.5,  0.5, -0.5) },
-    Vertex { position: vec3( 0.5,  0.5,  0.5) },
+    cube_vertex(vec3( 0.5, -0.5,  0.5), vec3( 1.0,  0.0,  0.0), vec2(0.0, 1.0), vec4( 0.0,  0.0, -1.0, 1.0)),
+    cube_vertex(vec3( 0.5, -0.5, -0.5), vec3( 1.0,  0.0,  0.0), vec2(1.0, 1.0), vec4( 0.0,  0.0, -1.0, 1.0)),
+    cube_vertex(vec3( 0.5,  0.5, -0.5), vec3( 1.0,  0.0,  0.0), vec2(1.0, 0.0), vec4( 0.0,  0.0, -1.0, 1.0)),
+    cube_vertex(vec3( 0.5,  0.5,  0.5), vec3( 1.0,  0.0,  0.0), vec2(0.0, 0.0), vec4( 0.0,  0.0, -1.0, 1.0)),
 
     // Top face (Y+)
-    Vertex { position: vec3(-0.5,  0.5,  0.5) },
-    Vertex { position: vec3( 0.5,  0.5,  0.5) },
-    Vertex { position: vec3( 0.5,  0.5, -0.5) },
-    Vertex { position: vec3(-0.5,  0.5, -0.5) },
+    cube_vertex(vec3(-0.5,  0.5,  0.5), vec3( 0.0,  1.0,  0.0), vec2(0.0, 1.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5,  0.5,  0.5), vec3( 0.0,  1.0,  0.0), vec2(1.0, 1.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5,  0.5, -0.5), vec3( 0.0,  1.0,  0.0), vec2(1.0, 0.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3(-0.5,  0.5, -0.5), vec3( 0.0,  1.0,  0.0), vec2(0.0, 0.0), vec4( 1.0,  0.0,  0.0, 1.0)),
 
     // Bottom face (Y-)
-    Vertex { position: vec3(-0.5, -0.5, -0.5) },
-    Vertex { position: vec3( 0.5, -0.5, -0.5) },
-    Vertex { position: vec3( 0.5, -0.5,  0.5) },
-    Vertex { position: vec3(-0.5, -0.5,  0.5) },
+    cube_vertex(vec3(-0.5, -0.5, -0.5), vec3( 0.0, -1.0,  0.0), vec2(0.0, 1.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5, -0.5, -0.5), vec3( 0.0, -1.0,  0.0), vec2(1.0, 1.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3( 0.5, -0.5,  0.5), vec3( 0.0, -1.0,  0.0), vec2(1.0, 0.0), vec4( 1.0,  0.0,  0.0, 1.0)),
+    cube_vertex(vec3(-0.5, -0.5,  0.5), vec3( 0.0, -1.0,  0.0), vec2(0.0, 0.0), vec4( 1.0,  0.0,  0.0, 1.0)),
 ];
 
 pub const CUBE_INDICES: [u32; 36] = [
@@ -77,6 +99,22 @@ impl VertexDescription for Vertex {
                 .location(0)
                 .format(avk::Format::R32G32B32_SFLOAT)
                 .offset(0),
+            // Locations 1 to 5 are taken by the instance attributes.
+            avk::VertexInputAttributeDescription::default()
+                .binding(0)
+                .location(6)
+                .format(avk::Format::R32G32B32_SFLOAT)
+                .offset(std::mem::offset_of!(Vertex, normal) as u32),
+            avk::VertexInputAttributeDescription::default()
+                .binding(0)
+                .location(7)
+                .format(avk::Format::R32G32_SFLOAT)
+                .offset(std::mem::offset_of!(Vertex, uv) as u32),
+            avk::VertexInputAttributeDescription::default()
+                .binding(0)
+                .location(8)
+                .format(avk::Format::R32G32B32A32_SFLOAT)
+                .offset(std::mem::offset_of!(Vertex, tangent) as u32),
         ]
     }
 }
@@ -88,7 +126,28 @@ pub struct InstanceData {
     pub color: Vec3,
 }
 
-impl VertexDescription for InstanceData {
+/// `InstanceData` as uploaded, with the index of the instance in its group where the
+/// padding would be. Unlike its slot in the buffer, the index doesn't change when other
+/// instances are hidden.
+#[repr(C, align(16))]
+#[derive(Clone, Copy, Debug)]
+pub struct FullInstanceData {
+    pub model: Mat4,
+    pub color: Vec3,
+    pub index: u32,
+}
+
+impl FullInstanceData {
+    pub fn new(data: InstanceData, index: usize) -> Self {
+        Self {
+            model: data.model,
+            color: data.color,
+            index: index as u32,
+        }
+    }
+}
+
+impl VertexDescription for FullInstanceData {
     fn get_attribute_descriptions() -> Vec<avk::VertexInputAttributeDescription> {
         let mut vec = (0..4).map(|i| avk::VertexInputAttributeDescription {
             binding: 1,
@@ -100,16 +159,106 @@ impl VertexDescription for InstanceData {
         vec.push(avk::VertexInputAttributeDescription {
             binding: 1,
             location: 5, 
-            format: avk::Format::R32G32B32A32_SFLOAT,
+            format: avk::Format::R32G32B32_SFLOAT,
             offset: std::mem::size_of::<Mat4>() as u32,
         });
+        vec.push(avk::VertexInputAttributeDescription {
+            binding: 1,
+            location: 9,
+            format: avk::Format::R32_UINT,
+            offset: std::mem::offset_of!(FullInstanceData, index) as u32,
+        });
         vec
     }
 
     fn get_binding_descriptions() -> Vec<avk::VertexInputBindingDescription> {
         vec![avk::VertexInputBindingDescription {
             binding: 1,
-            stride: std::mem::size_of::<InstanceData>() as u32,
+            stride: std::mem::size_of::<FullInstanceData>() as u32,
+            input_rate: avk::VertexInputRate::INSTANCE,
+        }]
+    }
+}
+
+/// 40-byte instance layout: translation, non-uniform scale, a snorm16 rotation
+/// quaternion, an RGBA8 color and the instance index of `FullInstanceData`. Built
+/// from `InstanceData` at upload time, so models with shear cannot be represented.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct CompactInstanceData {
+    pub position: Vec3,
+    pub scale: Vec3,
+    pub rotation: [i16; 4],
+    pub color: [u8; 4],
+    pub index: u32,
+}
+
+impl From<InstanceData> for CompactInstanceData {
+    fn from(value: InstanceData) -> Self {
+        let (scale, rotation, position) = value.model.to_scale_rotation_translation();
+        Self::new(position, rotation, scale, value.color.extend(1.0))
+    }
+}
+
+impl CompactInstanceData {
+    pub fn new(position: Vec3, rotation: Quat, scale: Vec3, color: Vec4) -> Self {
+        let rotation = rotation.normalize().to_array().map(|c| (c.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16);
+        let color = color.to_array().map(|c| (c.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8);
+        Self {
+            position,
+            scale,
+            rotation,
+            color,
+            index: 0,
+        }
+    }
+
+    pub fn with_index(mut self, index: usize) -> Self {
+        self.index = index as u32;
+        self
+    }
+}
+
+impl VertexDescription for CompactInstanceData {
+    fn get_attribute_descriptions() -> Vec<avk::VertexInputAttributeDescription> {
+        vec![
+            avk::VertexInputAttributeDescription {
+                binding: 1,
+                location: 1,
+                format: avk::Format::R32G32B32_SFLOAT,
+                offset: std::mem::offset_of!(CompactInstanceData, position) as u32,
+            },
+            avk::VertexInputAttributeDescription {
+                binding: 1,
+                location: 2,
+                format: avk::Format::R32G32B32_SFLOAT,
+                offset: std::mem::offset_of!(CompactInstanceData, scale) as u32,
+            },
+            avk::VertexInputAttributeDescription {
+                binding: 1,
+                location: 3,
+                format: avk::Format::R16G16B16A16_SNORM,
+                offset: std::mem::offset_of!(CompactInstanceData, rotation) as u32,
+            },
+            avk::VertexInputAttributeDescription {
+                binding: 1,
+                location: 4,
+                format: avk::Format::R8G8B8A8_UNORM,
+                offset: std::mem::offset_of!(CompactInstanceData, color) as u32,
+            },
+            avk::VertexInputAttributeDescription {
+                binding: 1,
+                location: 9,
+                format: avk::Format::R32_UINT,
+                offset: std::mem::offset_of!(CompactInstanceData, index) as u32,
+            },
+        ]
+    }
+
+    fn get_binding_descriptions() -> Vec<avk::VertexInputBindingDescription> {
+        vec![avk::VertexInputBindingDescription {
+            binding: 1,
+            stride: std::mem::size_of::<CompactInstanceData>() as u32,
             input_rate: avk::VertexInputRate::INSTANCE,
         }]
     }
@@ -118,4 +267,16 @@ impl VertexDescription for InstanceData {
 pub trait VertexDescription {
     fn get_binding_descriptions() -> Vec<avk::VertexInputBindingDescription>;
     fn get_attribute_descriptions() -> Vec<avk::VertexInputAttributeDescription>;
+}
+
+/// No per-instance attributes. Also the input type of pipelines that set
+/// `PipelineState::vertex_input` to false.
+impl VertexDescription for () {
+    fn get_binding_descriptions() -> Vec<avk::VertexInputBindingDescription> {
+        Vec::new()
+    }
+
+    fn get_attribute_descriptions() -> Vec<avk::VertexInputAttributeDescription> {
+        Vec::new()
+    }
 }
\ No newline at end of file